@@ -46,10 +46,12 @@ use darkfi::{
             ErrorCode::{InvalidParams, MethodNotFound},
             JsonError, JsonRequest, JsonResult,
         },
+        metrics,
         server::{listen_and_serve, RequestHandler},
     },
     util::path::expand_path,
     wallet::{WalletDb, WalletPtr},
+    zk::{key_store::DEFAULT_KEY_STORE_PATH, KeyStore},
     Error, Result,
 };
 
@@ -91,10 +93,18 @@ struct Args {
     /// Path to blockchain database
     database: String,
 
+    #[structopt(long)]
+    /// Directory where zk verifying keys are stored (default: shared key store)
+    zk_keys: Option<String>,
+
     #[structopt(long, default_value = "tcp://127.0.0.1:8340")]
     /// JSON-RPC listen URL
     rpc_listen: Url,
 
+    #[structopt(long)]
+    /// Prometheus metrics listen URL
+    metrics_listen: Option<Url>,
+
     #[structopt(long)]
     /// P2P accept addresses for the consensus protocol (repeatable flag)
     consensus_p2p_accept: Vec<Url>,
@@ -127,6 +137,10 @@ struct Args {
     /// Prefered transports of outbound connections for the consensus protocol (repeatable flag)
     consensus_p2p_transports: Vec<String>,
 
+    #[structopt(long, default_value = "darkfid_consensus")]
+    /// Network identifier of the consensus protocol P2P network
+    consensus_network_id: String,
+
     #[structopt(long)]
     /// P2P accept addresses for the syncing protocol (repeatable flag)
     sync_p2p_accept: Vec<Url>,
@@ -151,6 +165,10 @@ struct Args {
     /// Prefered transports of outbound connections for the syncing protocol (repeatable flag)
     sync_p2p_transports: Vec<String>,
 
+    #[structopt(long, default_value = "darkfid_sync")]
+    /// Network identifier of the syncing protocol P2P network
+    sync_network_id: String,
+
     #[structopt(long)]
     /// Enable localnet hosts
     localnet: bool,
@@ -341,6 +359,8 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     }
 
     // Initialize validator state
+    let key_store =
+        KeyStore::new(&expand_path(args.zk_keys.as_deref().unwrap_or(DEFAULT_KEY_STORE_PATH))?)?;
     let state = ValidatorState::new(
         &sled_db,
         bootstrap_ts,
@@ -348,6 +368,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         genesis_data,
         initial_distribution,
         wallet.clone(),
+        &key_store,
         faucet_pubkeys,
         args.consensus,
         args.single_node,
@@ -364,6 +385,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
             seeds: args.sync_p2p_seed.clone(),
             allowed_transports: args.sync_p2p_transports,
             localnet: args.localnet,
+            network_id: args.sync_network_id,
             ..Default::default()
         };
 
@@ -407,6 +429,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
                 seeds: args.consensus_p2p_seed.clone(),
                 allowed_transports: args.consensus_p2p_transports,
                 localnet: args.localnet,
+                network_id: args.consensus_network_id,
                 ..Default::default()
             };
             let p2p = net::P2p::new(consensus_network_settings).await;
@@ -442,6 +465,12 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     let _ex = ex.clone();
     ex.spawn(listen_and_serve(args.rpc_listen, darkfid.clone(), _ex)).detach();
 
+    if let Some(metrics_listen) = args.metrics_listen.clone() {
+        info!("Starting metrics server on {}", metrics_listen);
+        let _ex = ex.clone();
+        ex.spawn(metrics::listen_and_serve(metrics_listen, _ex)).detach();
+    }
+
     info!("Starting sync P2P network");
     sync_p2p.clone().unwrap().start(ex.clone()).await?;
     let _ex = ex.clone();
@@ -24,8 +24,9 @@ use darkfi::{
     async_daemonize,
     blockchain::BlockInfo,
     cli_desc,
-    util::time::TimeKeeper,
+    util::{path::expand_path, time::TimeKeeper},
     validator::{Validator, ValidatorConfig, ValidatorPtr},
+    zk::{key_store::DEFAULT_KEY_STORE_PATH, KeyStore},
     Result,
 };
 
@@ -82,7 +83,14 @@ async fn realmain(args: Args, _ex: Arc<smol::Executor<'_>>) -> Result<()> {
     // Initialize validator configuration
     let genesis_block = BlockInfo::default();
     let time_keeper = TimeKeeper::new(genesis_block.header.timestamp, 10, 90, 0);
-    let config = ValidatorConfig::new(time_keeper, genesis_block, vec![], args.testing_node);
+    let key_store = KeyStore::new(&expand_path(DEFAULT_KEY_STORE_PATH)?)?;
+    let config = ValidatorConfig::new(
+        time_keeper,
+        genesis_block,
+        vec![],
+        args.testing_node,
+        Some(key_store),
+    );
 
     if args.testing_node {
         info!("Node is configured to run in testing mode!");
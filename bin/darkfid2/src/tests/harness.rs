@@ -44,7 +44,13 @@ impl Harness {
         // NOTE: we are not using consensus constants here so we
         // don't get circular dependencies.
         let time_keeper = TimeKeeper::new(genesis_block.header.timestamp, 10, 90, 0);
-        let config = ValidatorConfig::new(time_keeper, genesis_block, vec![], testing_node);
+        let config = ValidatorConfig::new(
+            time_keeper,
+            genesis_block,
+            vec![],
+            testing_node,
+            Some(vks::key_store()?),
+        );
 
         // Generate validators using pregenerated vks
         let sled_db = sled::Config::new().temporary(true).open()?;
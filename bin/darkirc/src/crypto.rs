@@ -41,13 +41,29 @@ impl fmt::Display for KeyPair {
     }
 }
 
-/// The format we're using is nonce+ciphertext, where nonce is 24 bytes.
+/// Build a NaCl box from a 32 byte symmetric key
+pub fn salt_box_from_key(key: [u8; 32]) -> SalsaBox {
+    let secret = crypto_box::SecretKey::from(key);
+    SalsaBox::new(&secret.public_key(), &secret)
+}
+
 fn try_decrypt(salt_box: &SalsaBox, ciphertext: &str) -> Option<String> {
+    let plaintext = try_decrypt_bytes(salt_box, ciphertext)?;
+    Some(String::from_utf8_lossy(&plaintext).to_string())
+}
+
+/// The format we're using is nonce+ciphertext, where nonce is 24 bytes.
+pub fn try_decrypt_bytes(salt_box: &SalsaBox, ciphertext: &str) -> Option<Vec<u8>> {
     let bytes = match bs58::decode(ciphertext).into_vec() {
         Ok(v) => v,
         Err(_) => return None,
     };
 
+    decrypt_bytes(salt_box, &bytes)
+}
+
+/// Decrypt raw nonce+ciphertext bytes, where nonce is 24 bytes.
+pub fn decrypt_bytes(salt_box: &SalsaBox, bytes: &[u8]) -> Option<Vec<u8>> {
     if bytes.len() < 25 {
         return None
     }
@@ -62,14 +78,20 @@ fn try_decrypt(salt_box: &SalsaBox, ciphertext: &str) -> Option<String> {
     let message = &bytes[24..];
 
     // Try decrypting the message
-    match salt_box.decrypt(nonce, message) {
-        Ok(v) => Some(String::from_utf8_lossy(&v).to_string()),
-        Err(_) => None,
-    }
+    salt_box.decrypt(nonce, message).ok()
 }
 
 /// The format we're using is nonce+ciphertext, where nonce is 24 bytes.
 pub fn encrypt(salt_box: &SalsaBox, plaintext: &[u8]) -> String {
+    bs58::encode(encrypt_bytes(salt_box, plaintext)).into_string()
+}
+
+/// Bytes [`encrypt_bytes`] adds to the plaintext: the 24 byte nonce and
+/// the 16 byte authentication tag.
+pub const ENCRYPTION_OVERHEAD: usize = 40;
+
+/// Encrypt to raw nonce+ciphertext bytes, where nonce is 24 bytes.
+pub fn encrypt_bytes(salt_box: &SalsaBox, plaintext: &[u8]) -> Vec<u8> {
     let nonce = SalsaBox::generate_nonce(&mut OsRng);
     let mut ciphertext = salt_box.encrypt(&nonce, plaintext).unwrap();
 
@@ -77,7 +99,7 @@ pub fn encrypt(salt_box: &SalsaBox, plaintext: &[u8]) -> String {
     concat.append(&mut nonce.as_slice().to_vec());
     concat.append(&mut ciphertext);
 
-    bs58::encode(concat).into_string()
+    concat
 }
 
 pub fn decrypt_target(
@@ -139,6 +161,8 @@ pub fn decrypt_privmsg(salt_box: &SalsaBox, privmsg: &mut PrivMsgEvent) {
 
     privmsg.nick = String::from_utf8_lossy(&unpad(decrypted_nick.unwrap().into())).to_string();
     privmsg.msg = decrypted_msg.unwrap();
+    // A signature we can't decrypt can't be verified either
+    privmsg.signature = try_decrypt(salt_box, &privmsg.signature).unwrap_or_default();
 }
 
 /// Encrypt PrivMsg
@@ -146,6 +170,9 @@ pub fn encrypt_privmsg(salt_box: &SalsaBox, privmsg: &mut PrivMsgEvent) {
     privmsg.nick = encrypt(salt_box, &pad(privmsg.nick.clone().into()));
     privmsg.target = encrypt(salt_box, &pad(privmsg.target.clone().into()));
     privmsg.msg = encrypt(salt_box, privmsg.msg.as_bytes());
+    if !privmsg.signature.is_empty() {
+        privmsg.signature = encrypt(salt_box, privmsg.signature.as_bytes());
+    }
 }
 
 fn pad(data: Vec<u8>) -> Vec<u8> {
@@ -186,4 +213,41 @@ mod tests {
         assert_eq!(nick, String::from_utf8_lossy(&padded));
         assert_eq!(nick, String::from_utf8_lossy(&unpad(padded)));
     }
+
+    #[test]
+    fn test_signed_privmsg_encryption() {
+        use darkfi::{event_graph::EventMsg, util::time::Timestamp};
+        use darkfi_sdk::crypto::{PublicKey, SecretKey};
+
+        let channel_secret = crypto_box::SecretKey::generate(&mut OsRng);
+        let salt_box = SalsaBox::new(&channel_secret.public_key(), &channel_secret);
+
+        let secret = SecretKey::random(&mut OsRng);
+        let public = PublicKey::from_secret(secret);
+
+        let mut privmsg = PrivMsgEvent::new();
+        privmsg.nick = "terry-davis".to_string();
+        privmsg.target = "#dev".to_string();
+        privmsg.msg = "hello".to_string();
+        privmsg.sign(&secret);
+        let now = Timestamp::current_time();
+        assert!(privmsg.verify(&public, now));
+
+        // The signature is only readable with the channel secret
+        let signature = privmsg.signature.clone();
+        encrypt_privmsg(&salt_box, &mut privmsg);
+        assert_ne!(privmsg.signature, signature);
+        assert_eq!(privmsg.sender(now), None);
+
+        decrypt_privmsg(&salt_box, &mut privmsg);
+        assert_eq!(privmsg.sender(now), Some(public));
+
+        // Replaying the message in a later event invalidates the signature
+        assert_eq!(privmsg.sender(Timestamp(now.0 + 3600)), None);
+
+        // Spoofing the nick invalidates the signature
+        privmsg.nick = "glowie".to_string();
+        assert_eq!(privmsg.sender(now), None);
+        assert!(!privmsg.verify(&public, now));
+    }
 }
@@ -0,0 +1,386 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Files shared in channels and with contacts.
+//!
+//! Shared files are encrypted with a random key and inserted in the
+//! [`Dht`]. The key travels inside a [`FileOffer`] message, which is sent
+//! like any other msg, so the file is readable by exactly those who can
+//! read the channel or the direct msg it was shared in.
+//!
+//! Files are only requested from our direct peers, since the DHT can't
+//! look them up through the network yet. Peers don't forward requests,
+//! so a file is found only if one of our peers holds it.
+
+use std::{fmt, path::Path, str::FromStr, time::Duration};
+
+use async_std::{
+    future::timeout,
+    sync::{Arc, Mutex},
+};
+use log::{debug, info, warn};
+use rand::{rngs::OsRng, RngCore};
+
+use darkfi::{
+    dht2::{Dht, MAX_CHUNK_SIZE},
+    net::{Message, P2pPtr},
+    system::{Subscriber, SubscriberPtr},
+    Error, Result,
+};
+
+use crate::crypto::{decrypt_bytes, encrypt_bytes, salt_box_from_key, ENCRYPTION_OVERHEAD};
+
+mod protocol;
+pub use protocol::ProtocolFiles;
+use protocol::{ChunkRequest, FileRequest};
+
+/// Marker of the msgs carrying a file offer, sent like a CTCP request
+const OFFER_PREFIX: &str = "\x01DARKFILE ";
+const OFFER_SUFFIX: &str = "\x01";
+
+/// Maximum size of a shared file (32 MiB)
+pub const MAXIMUM_FILE_SIZE: u64 = 33_554_432;
+
+/// How long to wait for a peer to answer a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A file shared in a msg: everything needed to fetch and decrypt it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileOffer {
+    /// Hash of the encrypted file in the DHT
+    pub hash: blake3::Hash,
+    /// Size of the decrypted file
+    pub size: u64,
+    /// Key the file is encrypted with
+    pub key: [u8; 32],
+    /// Name of the file
+    pub name: String,
+}
+
+impl FileOffer {
+    /// Short identifier of the file used in IRC commands
+    pub fn id(&self) -> String {
+        self.hash.to_hex()[..16].to_string()
+    }
+
+    /// Name of the file without any path, safe to save it with
+    pub fn file_name(&self) -> String {
+        let name = Path::new(&self.name).file_name().and_then(|x| x.to_str()).unwrap_or_default();
+
+        if name.is_empty() || name.starts_with('.') {
+            return self.id()
+        }
+
+        name.to_string()
+    }
+
+    /// Size of the encrypted file in the DHT
+    fn encrypted_size(&self) -> u64 {
+        self.size + ENCRYPTION_OVERHEAD as u64
+    }
+
+    /// Number of chunks the encrypted file is split in
+    fn chunk_count(&self) -> usize {
+        let chunk_size = MAX_CHUNK_SIZE as u64;
+        ((self.encrypted_size() + chunk_size - 1) / chunk_size) as usize
+    }
+}
+
+impl fmt::Display for FileOffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{} {} {} {}{}",
+            OFFER_PREFIX,
+            self.hash.to_hex(),
+            self.size,
+            bs58::encode(self.key).into_string(),
+            self.name,
+            OFFER_SUFFIX
+        )
+    }
+}
+
+impl FromStr for FileOffer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || Error::ParseFailed("Invalid file offer");
+
+        let offer = s.strip_prefix(OFFER_PREFIX).and_then(|x| x.strip_suffix(OFFER_SUFFIX));
+        let parts: Vec<&str> = offer.ok_or_else(err)?.splitn(4, ' ').collect();
+        let [hash, size, key, name] = parts[..] else { return Err(err()) };
+
+        let hash = blake3::Hash::from_hex(hash).map_err(|_| err())?;
+        let size = size.parse().map_err(|_| err())?;
+        if size > MAXIMUM_FILE_SIZE {
+            return Err(Error::ParseFailed("File offer is too large"))
+        }
+
+        let key = bs58::decode(key).into_vec().map_err(|_| err())?;
+        let key = key.try_into().map_err(|_| err())?;
+
+        Ok(Self { hash, size, key, name: name.to_string() })
+    }
+}
+
+/// Replies of our peers to the requests of [`FileStore::fetch`]
+#[derive(Clone, Debug)]
+pub enum FileReply {
+    /// Chunk hashes of a file
+    Chunks { hash: blake3::Hash, chunks: Vec<blake3::Hash> },
+    /// Contents of a chunk
+    Chunk { hash: blake3::Hash, data: Vec<u8> },
+}
+
+pub type FileStorePtr = Arc<FileStore>;
+
+/// Encrypted files stored in the DHT, served to our peers by
+/// [`ProtocolFiles`] and fetched from them when needed.
+pub struct FileStore {
+    dht: Mutex<Dht>,
+    p2p: P2pPtr,
+    replies: SubscriberPtr<FileReply>,
+}
+
+impl FileStore {
+    pub async fn new(path: &Path, p2p: P2pPtr) -> Result<FileStorePtr> {
+        let mut dht = Dht::new(&path.to_path_buf().into(), p2p.clone()).await?;
+        dht.garbage_collect().await?;
+
+        Ok(Arc::new(Self { dht: Mutex::new(dht), p2p, replies: Subscriber::new() }))
+    }
+
+    /// Encrypt a file with a new key and insert it in the DHT, returning
+    /// the offer to send to those who should get it
+    pub async fn share(&self, path: &Path) -> Result<FileOffer> {
+        let metadata = async_std::fs::metadata(path).await?;
+        if !metadata.is_file() {
+            return Err(Error::Custom(format!("{} is not a file", path.display())))
+        }
+
+        if metadata.len() > MAXIMUM_FILE_SIZE {
+            return Err(Error::Custom(format!(
+                "{} is larger than {} bytes",
+                path.display(),
+                MAXIMUM_FILE_SIZE
+            )))
+        }
+
+        let plaintext = async_std::fs::read(path).await?;
+
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        let ciphertext = encrypt_bytes(&salt_box_from_key(key), &plaintext);
+
+        let (hash, _) = self.dht.lock().await.insert(&ciphertext).await?;
+
+        let name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default().to_string();
+        info!("[FILES] Shared {} as {}", name, hash.to_hex());
+
+        Ok(FileOffer { hash, size: plaintext.len() as u64, key, name })
+    }
+
+    /// Get the encrypted file of an offer, locally or from our peers,
+    /// and return its decrypted contents. Fetched files are kept in the
+    /// DHT, so we serve them to others too.
+    pub async fn fetch(&self, offer: &FileOffer) -> Result<Vec<u8>> {
+        // Peers can send us any chunk list, so we keep asking for other
+        // ones until we get the file or nobody has one left to offer.
+        let mut chunk_hashes = self.get_chunk_hashes(&offer.hash).await;
+        let mut tried = vec![];
+        let ciphertext = loop {
+            let chunks = match chunk_hashes.take() {
+                Some(v) => v,
+                None => {
+                    let request = FileRequest { hash: offer.hash };
+                    self.request(&request, |reply| match reply {
+                        FileReply::Chunks { hash, chunks }
+                            if *hash == offer.hash &&
+                                chunks.len() <= offer.chunk_count() &&
+                                !tried.contains(chunks) =>
+                        {
+                            Some(chunks.clone())
+                        }
+                        _ => None,
+                    })
+                    .await?
+                }
+            };
+
+            match self.fetch_chunks(offer, &chunks).await {
+                Ok(v) => break v,
+                Err(e) => {
+                    warn!("[FILES] {}, asking our peers for other chunks", e);
+                    tried.push(chunks);
+                }
+            }
+        };
+
+        self.dht.lock().await.insert(&ciphertext).await?;
+
+        let Some(plaintext) = decrypt_bytes(&salt_box_from_key(offer.key), &ciphertext) else {
+            return Err(Error::Custom(format!("Failed decrypting file {}", offer.id())))
+        };
+
+        if plaintext.len() as u64 != offer.size {
+            return Err(Error::Custom(format!("File {} doesn't match its size", offer.id())))
+        }
+
+        info!("[FILES] Fetched {}", offer.hash.to_hex());
+        Ok(plaintext)
+    }
+
+    /// Get the given chunks of the file of an offer, locally or from our
+    /// peers, and return the encrypted file if they match its hash
+    async fn fetch_chunks(
+        &self,
+        offer: &FileOffer,
+        chunk_hashes: &[blake3::Hash],
+    ) -> Result<Vec<u8>> {
+        let mut ciphertext = vec![];
+        for chunk_hash in chunk_hashes {
+            let chunk = match self.get_chunk(chunk_hash).await {
+                Some(v) => v,
+                None => {
+                    let request = ChunkRequest { hash: *chunk_hash };
+                    self.request(&request, |reply| match reply {
+                        FileReply::Chunk { hash, data }
+                            if hash == chunk_hash && blake3::hash(data) == *chunk_hash =>
+                        {
+                            Some(data.clone())
+                        }
+                        _ => None,
+                    })
+                    .await?
+                }
+            };
+
+            ciphertext.extend_from_slice(&chunk);
+            if ciphertext.len() as u64 > offer.encrypted_size() {
+                return Err(Error::Custom(format!("File {} is larger than offered", offer.id())))
+            }
+        }
+
+        if blake3::hash(&ciphertext) != offer.hash {
+            return Err(Error::Custom(format!("File {} doesn't match its hash", offer.id())))
+        }
+
+        Ok(ciphertext)
+    }
+
+    /// Chunk hashes of a file we hold
+    async fn get_chunk_hashes(&self, hash: &blake3::Hash) -> Option<Vec<blake3::Hash>> {
+        self.dht.lock().await.get_file_chunk_hashes_local(hash).await.ok()
+    }
+
+    /// Contents of a chunk we hold
+    async fn get_chunk(&self, hash: &blake3::Hash) -> Option<Vec<u8>> {
+        let path = self.dht.lock().await.get_chunk_local(hash).await.ok()?;
+        let data = async_std::fs::read(path).await.ok()?;
+
+        // Don't serve chunks corrupted on disk
+        if blake3::hash(&data) != *hash {
+            return None
+        }
+
+        Some(data)
+    }
+
+    /// Broadcast a request to our direct peers and wait for the first
+    /// reply accepted by `matches`
+    async fn request<M: Message, T>(
+        &self,
+        request: &M,
+        matches: impl Fn(&FileReply) -> Option<T>,
+    ) -> Result<T> {
+        debug!("[FILES] Requesting {}", M::NAME);
+        let subscription = self.replies.clone().subscribe().await;
+        self.p2p.broadcast(request).await;
+
+        let reply = timeout(REPLY_TIMEOUT, async {
+            loop {
+                if let Some(v) = matches(&subscription.receive().await) {
+                    break v
+                }
+            }
+        })
+        .await;
+
+        subscription.unsubscribe().await;
+        reply.map_err(|_| Error::Custom("None of our peers has the file".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_offer() {
+        let offer = FileOffer {
+            hash: blake3::hash(b"darkirc"),
+            size: 1024,
+            key: [7u8; 32],
+            name: "dank memes.png".to_string(),
+        };
+
+        let msg = offer.to_string();
+        assert!(msg.starts_with(OFFER_PREFIX));
+        assert_eq!(msg.parse::<FileOffer>().unwrap(), offer);
+
+        assert!("DARKFILE foo".parse::<FileOffer>().is_err());
+        assert!(format!("{}{} 12 key name\x01", OFFER_PREFIX, offer.hash.to_hex())
+            .parse::<FileOffer>()
+            .is_err());
+
+        let large = FileOffer { size: MAXIMUM_FILE_SIZE + 1, ..offer };
+        assert!(large.to_string().parse::<FileOffer>().is_err());
+    }
+
+    #[test]
+    fn test_chunk_count() {
+        let mut offer =
+            FileOffer { hash: blake3::hash(b"x"), size: 0, key: [0u8; 32], name: String::new() };
+        assert_eq!(offer.chunk_count(), 1);
+
+        offer.size = (MAX_CHUNK_SIZE - ENCRYPTION_OVERHEAD) as u64;
+        assert_eq!(offer.chunk_count(), 1);
+
+        offer.size += 1;
+        assert_eq!(offer.chunk_count(), 2);
+
+        offer.size = MAXIMUM_FILE_SIZE;
+        assert_eq!(offer.chunk_count(), 17);
+    }
+
+    #[test]
+    fn test_file_name() {
+        let mut offer =
+            FileOffer { hash: blake3::hash(b"x"), size: 1, key: [0u8; 32], name: String::new() };
+
+        offer.name = "../../.bashrc".to_string();
+        assert_eq!(offer.file_name(), offer.id());
+
+        offer.name = "/etc/passwd".to_string();
+        assert_eq!(offer.file_name(), "passwd");
+
+        offer.name = "notes.txt".to_string();
+        assert_eq!(offer.file_name(), "notes.txt");
+    }
+}
@@ -0,0 +1,162 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use log::debug;
+use smol::Executor;
+
+use darkfi::{
+    impl_p2p_message,
+    net::{
+        ChannelPtr, Message, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
+        ProtocolJobsManager, ProtocolJobsManagerPtr,
+    },
+    Result,
+};
+use darkfi_serial::{SerialDecodable, SerialEncodable};
+
+use super::{FileReply, FileStorePtr};
+
+/// Request for the chunk hashes of a file
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct FileRequest {
+    pub hash: blake3::Hash,
+}
+
+impl_p2p_message!(FileRequest, "darkirc_filerequest");
+
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct FileChunks {
+    pub hash: blake3::Hash,
+    pub chunks: Vec<blake3::Hash>,
+}
+
+impl_p2p_message!(FileChunks, "darkirc_filechunks");
+
+/// Request for the contents of a chunk
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct ChunkRequest {
+    pub hash: blake3::Hash,
+}
+
+impl_p2p_message!(ChunkRequest, "darkirc_chunkrequest");
+
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct ChunkData {
+    pub hash: blake3::Hash,
+    pub data: Vec<u8>,
+}
+
+impl_p2p_message!(ChunkData, "darkirc_chunkdata");
+
+/// Serves the files of our [`super::FileStore`] to the peer of a channel,
+/// and passes its replies to our own requests on to the store.
+pub struct ProtocolFiles {
+    jobsman: ProtocolJobsManagerPtr,
+    channel: ChannelPtr,
+    store: FileStorePtr,
+    file_request_sub: MessageSubscription<FileRequest>,
+    file_chunks_sub: MessageSubscription<FileChunks>,
+    chunk_request_sub: MessageSubscription<ChunkRequest>,
+    chunk_data_sub: MessageSubscription<ChunkData>,
+}
+
+impl ProtocolFiles {
+    pub async fn init(
+        channel: ChannelPtr,
+        _p2p: P2pPtr,
+        store: FileStorePtr,
+    ) -> Result<ProtocolBasePtr> {
+        let msg_subsystem = channel.message_subsystem();
+        msg_subsystem.add_dispatch::<FileRequest>().await;
+        msg_subsystem.add_dispatch::<FileChunks>().await;
+        msg_subsystem.add_dispatch::<ChunkRequest>().await;
+        msg_subsystem.add_dispatch::<ChunkData>().await;
+
+        let file_request_sub = channel.subscribe_msg().await?;
+        let file_chunks_sub = channel.subscribe_msg().await?;
+        let chunk_request_sub = channel.subscribe_msg().await?;
+        let chunk_data_sub = channel.subscribe_msg().await?;
+
+        Ok(Arc::new(Self {
+            jobsman: ProtocolJobsManager::new("ProtocolFiles", channel.clone()),
+            channel,
+            store,
+            file_request_sub,
+            file_chunks_sub,
+            chunk_request_sub,
+            chunk_data_sub,
+        }))
+    }
+
+    async fn handle_file_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolFiles::handle_file_request START");
+        loop {
+            let msg = self.file_request_sub.receive().await?;
+
+            let Some(chunks) = self.store.get_chunk_hashes(&msg.hash).await else { continue };
+            self.channel.send(&FileChunks { hash: msg.hash, chunks }).await?;
+        }
+    }
+
+    async fn handle_file_chunks(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolFiles::handle_file_chunks START");
+        loop {
+            let msg = self.file_chunks_sub.receive().await?;
+            let reply = FileReply::Chunks { hash: msg.hash, chunks: msg.chunks.clone() };
+            self.store.replies.notify(reply).await;
+        }
+    }
+
+    async fn handle_chunk_request(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolFiles::handle_chunk_request START");
+        loop {
+            let msg = self.chunk_request_sub.receive().await?;
+
+            let Some(data) = self.store.get_chunk(&msg.hash).await else { continue };
+            self.channel.send(&ChunkData { hash: msg.hash, data }).await?;
+        }
+    }
+
+    async fn handle_chunk_data(self: Arc<Self>) -> Result<()> {
+        debug!("ProtocolFiles::handle_chunk_data START");
+        loop {
+            let msg = self.chunk_data_sub.receive().await?;
+            let reply = FileReply::Chunk { hash: msg.hash, data: msg.data.clone() };
+            self.store.replies.notify(reply).await;
+        }
+    }
+}
+
+#[async_trait]
+impl ProtocolBase for ProtocolFiles {
+    async fn start(self: Arc<Self>, ex: Arc<Executor<'_>>) -> Result<()> {
+        debug!("ProtocolFiles::start()");
+        self.jobsman.clone().start(ex.clone());
+        self.jobsman.clone().spawn(self.clone().handle_file_request(), ex.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_file_chunks(), ex.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_chunk_request(), ex.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_chunk_data(), ex.clone()).await;
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolFiles"
+    }
+}
@@ -0,0 +1,468 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::HashMap, time::Duration};
+
+use async_std::{future::timeout, net::TcpStream};
+use futures::{
+    io::{BufReader, WriteHalf},
+    AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, FutureExt, StreamExt,
+};
+use log::{debug, error, info};
+
+use darkfi::{
+    event_graph::{
+        model::{Event, EventId},
+        protocol_event::{Seen, SeenPtr},
+    },
+    system::Subscription,
+    Error, Result,
+};
+
+use crate::{
+    crypto::{decrypt_privmsg, decrypt_target, encrypt_privmsg},
+    settings::{BridgeInfo, MAXIMUM_LENGTH_OF_NICK_CHAN_CNT},
+    ChannelInfo, PrivMsgEvent,
+};
+
+use super::{ClientSubMsg, IrcConfig, NotifierMsg};
+
+/// Delay before reconnecting to the upstream server
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Maximum length of the text sent in one upstream PRIVMSG, to stay
+/// within the 512 bytes limit of IRC lines
+const MAXIMUM_UPSTREAM_TEXT: usize = 400;
+/// CTCP marker of `/me` actions
+const CTCP_ACTION: &str = "\x01ACTION ";
+
+/// A bridge connects to an upstream IRC network as a regular client and
+/// relays msgs both ways between darkirc channels and upstream channels.
+///
+/// Nicks relayed from upstream get the configured prefix. The msgs are
+/// marked as relayed, and marked msgs are never sent upstream, so bridges
+/// on the same channels don't loop. Every darkirc event is relayed at
+/// most once, using a [`Seen`] buffer of their ids.
+pub struct Bridge {
+    name: String,
+    info: BridgeInfo,
+    /// The bridged darkirc channels
+    channels: HashMap<String, ChannelInfo>,
+    server_notifier: smol::channel::Sender<(NotifierMsg, u64)>,
+    subscription: Subscription<ClientSubMsg>,
+    /// darkirc events already relayed upstream
+    relayed: SeenPtr<EventId>,
+    /// Our current nick upstream
+    nick: String,
+}
+
+impl Bridge {
+    pub fn new(
+        name: String,
+        info: BridgeInfo,
+        channels: HashMap<String, ChannelInfo>,
+        server_notifier: smol::channel::Sender<(NotifierMsg, u64)>,
+        subscription: Subscription<ClientSubMsg>,
+    ) -> Self {
+        let nick = info.nick.clone();
+        let mut bridge = Self {
+            name,
+            info,
+            channels: HashMap::new(),
+            server_notifier,
+            subscription,
+            relayed: Seen::new(),
+            nick,
+        };
+        bridge.set_channels(channels);
+        bridge
+    }
+
+    /// Keep the configured darkirc channels this bridge relays
+    fn set_channels(&mut self, channels: HashMap<String, ChannelInfo>) {
+        self.channels = channels
+            .into_iter()
+            .filter(|(name, _)| self.info.channels.contains_key(name))
+            .map(|(name, mut info)| {
+                // Nobody joins the channels of the bridge, but it still
+                // needs to read them
+                info.joined = true;
+                (name, info)
+            })
+            .collect();
+
+        for name in self.info.channels.keys() {
+            if !self.channels.contains_key(name) {
+                self.channels
+                    .insert(name.clone(), ChannelInfo { joined: true, ..Default::default() });
+            }
+        }
+    }
+
+    /// Stay connected to the upstream server, reconnecting when needed
+    pub async fn run(mut self) {
+        loop {
+            if let Err(e) = self.session().await {
+                error!("[BRIDGE {}] Upstream connection failed: {}", self.name, e);
+            }
+
+            // Whatever happens on darkirc meanwhile isn't relayed later
+            let _ = timeout(RECONNECT_DELAY, async {
+                loop {
+                    if let ClientSubMsg::Config(config) = self.subscription.receive().await {
+                        self.update_config(config);
+                    }
+                }
+            })
+            .await;
+        }
+    }
+
+    fn update_config(&mut self, config: IrcConfig) {
+        // Bridges relay the channels of the default profile
+        if config.profile.is_none() {
+            self.set_channels(config.channels);
+        }
+    }
+
+    async fn session(&mut self) -> Result<()> {
+        if self.info.upstream.scheme() != "tcp" {
+            return Err(Error::Custom("Only tcp:// upstream servers are supported".to_string()))
+        }
+
+        let addr = self.info.upstream.socket_addrs(|| Some(6667))?;
+        let stream = TcpStream::connect(&*addr).await?;
+        info!("[BRIDGE {}] Connected to {}", self.name, self.info.upstream);
+
+        // The lines stream keeps partial lines buffered across the
+        // iterations of the select below
+        let (reader, mut writer) = stream.split();
+        let mut lines = BufReader::new(reader).lines();
+
+        self.nick = self.info.nick.clone();
+        if let Some(password) = &self.info.password {
+            send(&mut writer, &format!("PASS {}", password)).await?;
+        }
+        send(&mut writer, &format!("NICK {}", self.nick)).await?;
+        send(&mut writer, &format!("USER {} 0 * :darkirc bridge", self.nick)).await?;
+
+        loop {
+            futures::select! {
+                msg = self.subscription.receive().fuse() => {
+                    match msg {
+                        ClientSubMsg::Privmsg(event) => self.on_event(&mut writer, &event).await?,
+                        ClientSubMsg::Config(config) => self.update_config(config),
+                    }
+                }
+
+                line = lines.next().fuse() => {
+                    let Some(line) = line else { return Err(Error::ChannelStopped) };
+                    self.on_upstream_line(&mut writer, line?.trim_end()).await?;
+                }
+            }
+        }
+    }
+
+    /// Handle a line sent by the upstream server
+    async fn on_upstream_line(
+        &mut self,
+        writer: &mut WriteHalf<TcpStream>,
+        line: &str,
+    ) -> Result<()> {
+        debug!("[BRIDGE {}] Upstream: {}", self.name, line);
+        let Some((source, command, params)) = parse_line(line) else { return Ok(()) };
+
+        match (command, params.as_slice()) {
+            ("PING", [token, ..]) => send(writer, &format!("PONG :{}", token)).await?,
+
+            // Welcome, we're registered
+            ("001", _) => {
+                let channels: Vec<String> = self.info.channels.values().cloned().collect();
+                if !channels.is_empty() {
+                    send(writer, &format!("JOIN {}", channels.join(","))).await?;
+                }
+            }
+
+            // Nick in use
+            ("433", _) => {
+                self.nick.push('_');
+                send(writer, &format!("NICK {}", self.nick)).await?;
+            }
+
+            ("PRIVMSG", [target, text]) => {
+                let nick = source.and_then(|x| x.split('!').next()).unwrap_or_default();
+                if nick.is_empty() || nick.eq_ignore_ascii_case(&self.nick) {
+                    return Ok(())
+                }
+
+                let channel = self
+                    .info
+                    .channels
+                    .iter()
+                    .find(|(_, upstream)| upstream.eq_ignore_ascii_case(target))
+                    .map(|(channel, _)| channel.clone());
+
+                if let Some(channel) = channel {
+                    self.relay_to_darkirc(&channel, nick, text).await?;
+                }
+            }
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Publish a msg from upstream in a darkirc channel
+    async fn relay_to_darkirc(&mut self, channel: &str, nick: &str, text: &str) -> Result<()> {
+        // Other CTCP requests make no sense on darkirc
+        if text.starts_with('\x01') && !text.starts_with(CTCP_ACTION) {
+            return Ok(())
+        }
+
+        let mut msg = PrivMsgEvent::new();
+        msg.nick = relayed_nick(&self.info.nick_prefix, nick);
+        msg.target = channel.to_string();
+        msg.msg = text.to_string();
+        msg.relayed = true;
+
+        if let Some(salt_box) = self.channels.get(channel).and_then(|x| x.salt_box.as_ref()) {
+            encrypt_privmsg(salt_box, &mut msg);
+        }
+
+        self.server_notifier.send((NotifierMsg::Privmsg(msg), self.subscription.get_id())).await?;
+        Ok(())
+    }
+
+    /// Relay a darkirc event upstream if it belongs to a bridged channel
+    async fn on_event(
+        &mut self,
+        writer: &mut WriteHalf<TcpStream>,
+        event: &Event<PrivMsgEvent>,
+    ) -> Result<()> {
+        // Msgs relayed by a bridge aren't sent back, this avoids loops
+        if event.action.relayed || !self.relayed.push(&event.hash()).await {
+            return Ok(())
+        }
+
+        let mut msg = event.action.clone();
+        let mut contact = String::new();
+        decrypt_target(&mut contact, &mut msg, self.channels.clone(), HashMap::new());
+
+        let Some(upstream) = self.info.channels.get(&msg.target) else { return Ok(()) };
+        if let Some(salt_box) = self.channels.get(&msg.target).and_then(|x| x.salt_box.as_ref()) {
+            decrypt_privmsg(salt_box, &mut msg);
+        }
+
+        // Only the text is relayed, never things like file offers
+        let text = match msg.msg.strip_prefix(CTCP_ACTION) {
+            Some(action) => format!("* {} {}", msg.nick, action.trim_end_matches('\x01')),
+            None if msg.msg.starts_with('\x01') => return Ok(()),
+            None => format!("<{}> {}", msg.nick, msg.msg),
+        };
+
+        let text = text.replace(['\r', '\n'], " ");
+        for part in split_text(&text, MAXIMUM_UPSTREAM_TEXT) {
+            send(writer, &format!("PRIVMSG {} :{}", upstream, part)).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn send(writer: &mut WriteHalf<TcpStream>, line: &str) -> Result<()> {
+    debug!("[BRIDGE] Send: {}", line);
+    writer.write_all(format!("{}\r\n", line).as_bytes()).await?;
+    Ok(())
+}
+
+/// Parse an IRC line into its source, command and params
+fn parse_line(line: &str) -> Option<(Option<&str>, &str, Vec<&str>)> {
+    let (source, rest) = match line.strip_prefix(':') {
+        Some(rest) => {
+            let (source, rest) = rest.split_once(' ')?;
+            (Some(source), rest)
+        }
+        None => (None, line),
+    };
+
+    let (rest, trailing) = match rest.split_once(" :") {
+        Some((rest, trailing)) => (rest, Some(trailing)),
+        None => (rest, None),
+    };
+
+    let mut words = rest.split_whitespace();
+    let command = words.next()?;
+    let mut params: Vec<&str> = words.collect();
+    params.extend(trailing);
+
+    Some((source, command, params))
+}
+
+/// Nick used on darkirc for a nick relayed from upstream
+fn relayed_nick(prefix: &str, nick: &str) -> String {
+    let mut relayed = format!("{}{}", prefix, nick);
+    while relayed.len() >= MAXIMUM_LENGTH_OF_NICK_CHAN_CNT {
+        relayed.pop();
+    }
+    relayed
+}
+
+/// Split a text in parts of at most `max` bytes, on char boundaries
+fn split_text(text: &str, max: usize) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = text;
+
+    while rest.len() > max {
+        let mut idx = max;
+        while !rest.is_char_boundary(idx) {
+            idx -= 1;
+        }
+        parts.push(&rest[..idx]);
+        rest = &rest[idx..];
+    }
+
+    parts.push(rest);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_std::net::TcpListener;
+    use darkfi::{system::Subscriber, util::time::Timestamp};
+    use url::Url;
+
+    async fn expect_line(reader: &mut BufReader<TcpStream>, expected: &str) {
+        let mut line = String::new();
+        timeout(Duration::from_secs(10), reader.read_line(&mut line)).await.unwrap().unwrap();
+        assert_eq!(line.trim_end(), expected);
+    }
+
+    fn event(nick: &str, target: &str, msg: &str) -> Event<PrivMsgEvent> {
+        let mut action = PrivMsgEvent::new();
+        action.nick = nick.to_string();
+        action.target = target.to_string();
+        action.msg = msg.to_string();
+        Event { previous_event_hash: blake3::hash(b""), action, timestamp: Timestamp(1) }
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let (source, command, params) =
+            parse_line(":bob!bob@host PRIVMSG #darkfi :hello there").unwrap();
+        assert_eq!(source, Some("bob!bob@host"));
+        assert_eq!(command, "PRIVMSG");
+        assert_eq!(params, vec!["#darkfi", "hello there"]);
+
+        let (source, command, params) = parse_line("PING :irc.local").unwrap();
+        assert_eq!(source, None);
+        assert_eq!(command, "PING");
+        assert_eq!(params, vec!["irc.local"]);
+
+        assert!(parse_line(":lonely").is_none());
+    }
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_text("hello", 400), vec!["hello"]);
+        assert_eq!(split_text("aaaabbbbc", 4), vec!["aaaa", "bbbb", "c"]);
+        assert_eq!(split_text("ééé", 3), vec!["é", "é", "é"]);
+        assert_eq!(relayed_nick("libera|", "bob"), "libera|bob");
+        assert_eq!(relayed_nick("libera|", &"x".repeat(40)).len(), 31);
+    }
+
+    /// Run a bridge against a local stand-in of an IRC server
+    #[test]
+    fn test_bridge_relay() -> Result<()> {
+        smol::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let port = listener.local_addr()?.port();
+
+            let info = BridgeInfo {
+                upstream: Url::parse(&format!("tcp://127.0.0.1:{}", port))?,
+                nick: "bridge".to_string(),
+                password: None,
+                nick_prefix: "up|".to_string(),
+                channels: HashMap::from([("#dev".to_string(), "#darkfi".to_string())]),
+            };
+
+            let subscriber = Subscriber::new();
+            let subscription = subscriber.clone().subscribe().await;
+            let (notifier, recv) = smol::channel::unbounded();
+            let bridge =
+                Bridge::new("test".to_string(), info, HashMap::new(), notifier, subscription);
+            let task = smol::spawn(bridge.run());
+
+            let (stream, _) = listener.accept().await?;
+            let mut reader = BufReader::new(stream.clone());
+            let mut writer = stream;
+
+            // Registration
+            expect_line(&mut reader, "NICK bridge").await;
+            expect_line(&mut reader, "USER bridge 0 * :darkirc bridge").await;
+            writer.write_all(b":irc.local 433 * bridge :Nickname is already in use\r\n").await?;
+            expect_line(&mut reader, "NICK bridge_").await;
+            writer.write_all(b":irc.local 001 bridge_ :Welcome\r\n").await?;
+            expect_line(&mut reader, "JOIN #darkfi").await;
+            writer.write_all(b"PING :irc.local\r\n").await?;
+            expect_line(&mut reader, "PONG :irc.local").await;
+
+            // Upstream to darkirc, ignoring our own msgs and other channels
+            writer.write_all(b":bridge_!b@host PRIVMSG #darkfi :<alice> echo\r\n").await?;
+            writer.write_all(b":carol!c@host PRIVMSG #other :elsewhere\r\n").await?;
+            writer.write_all(b":bob!bob@host PRIVMSG #darkfi :hello darkirc\r\n").await?;
+            let (msg, _) = timeout(Duration::from_secs(10), recv.recv()).await.unwrap()?;
+            let NotifierMsg::Privmsg(msg) = msg else { panic!("Expected a privmsg") };
+            assert_eq!(msg.nick, "up|bob");
+            assert_eq!(msg.target, "#dev");
+            assert_eq!(msg.msg, "hello darkirc");
+            assert!(msg.relayed);
+
+            // darkirc to upstream, each event once, and never the msgs
+            // relayed by a bridge, file offers or other channels
+            let hello = event("alice", "#dev", "hi libera");
+            subscriber.notify(ClientSubMsg::Privmsg(hello.clone())).await;
+            expect_line(&mut reader, "PRIVMSG #darkfi :<alice> hi libera").await;
+            subscriber.notify(ClientSubMsg::Privmsg(hello)).await;
+            let mut relayed = event("other|bob", "#dev", "loop");
+            relayed.action.relayed = true;
+            subscriber.notify(ClientSubMsg::Privmsg(relayed)).await;
+            subscriber
+                .notify(ClientSubMsg::Privmsg(event("alice", "#dev", "\x01DARKFILE x\x01")))
+                .await;
+            subscriber.notify(ClientSubMsg::Privmsg(event("alice", "#random", "nope"))).await;
+            subscriber
+                .notify(ClientSubMsg::Privmsg(event("alice", "#dev", "\x01ACTION waves\x01")))
+                .await;
+            expect_line(&mut reader, "PRIVMSG #darkfi :* alice waves").await;
+
+            // Lines split across reads are kept whole
+            writer.write_all(b":carol!c@host PRIVMSG #darkfi :split ").await?;
+            subscriber.notify(ClientSubMsg::Privmsg(event("alice", "#dev", "meanwhile"))).await;
+            expect_line(&mut reader, "PRIVMSG #darkfi :<alice> meanwhile").await;
+            writer.write_all(b"line\r\n").await?;
+            let (msg, _) = timeout(Duration::from_secs(10), recv.recv()).await.unwrap()?;
+            let NotifierMsg::Privmsg(msg) = msg else { panic!("Expected a privmsg") };
+            assert_eq!(msg.msg, "split line");
+
+            task.cancel().await;
+            Ok(())
+        })
+    }
+}
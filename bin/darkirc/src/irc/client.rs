@@ -16,30 +16,68 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::net::SocketAddr;
+use std::{collections::HashMap, net::SocketAddr, ops::Range};
 
-use async_std::sync::{Arc, Mutex};
 use futures::{
     io::{BufReader, ReadHalf, WriteHalf},
     AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, FutureExt,
 };
 
+use chrono::{DateTime, NaiveDateTime};
+use darkfi_sdk::crypto::PublicKey;
 use log::{debug, error, info, warn};
 
 use darkfi::{
-    event_graph::{model::Event, EventMsg},
+    event_graph::{
+        model::{Event, EventId},
+        EventMsg,
+    },
     system::Subscription,
+    util::time::Timestamp,
     Error, Result,
 };
 
 use crate::{
     crypto::{decrypt_privmsg, decrypt_target, encrypt_privmsg},
+    files::FileOffer,
+    privmsg::fingerprint,
     settings,
     settings::RPL,
     ChannelInfo, PrivMsgEvent,
 };
 
-use super::{ClientSubMsg, IrcConfig, NotifierMsg};
+use super::{
+    is_listed, ChanServ, ClientSubMsg, ConfServ, FileServ, HistoryPtr, IrcConfig, Moderation,
+    NickServ, NotifierMsg, Profile, Profiles, NICK_CHANSERV, NICK_CONFSERV, NICK_FILESERV,
+    NICK_NICKSERV,
+};
+
+/// Prefix added to messages using a registered nick without a valid signature
+const UNVERIFIED_PREFIX: &str = "[UNVERIFIED]";
+/// Host shown for senders of messages
+const HOST: &str = "dark.fi";
+/// Host shown for senders signing with the key their nick is registered to
+const VERIFIED_HOST: &str = "verified.dark.fi";
+
+/// A message decrypted and verified for the client
+struct RenderedMsg {
+    /// Buffer the message belongs to, the channel or the contact
+    buffer: String,
+    /// Nick of the sender
+    nick: String,
+    /// PRIVMSG line to send to the client
+    line: String,
+    /// Moderation of the channel carried by the message
+    moderation: Option<Moderation>,
+    /// File shared in the message
+    offer: Option<FileOffer>,
+}
+
+/// Reference to a message in a `CHATHISTORY` request
+enum MsgRef {
+    Id(EventId),
+    Time(Timestamp),
+}
 
 pub struct IrcClient<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> {
     // network stream
@@ -53,27 +91,45 @@ pub struct IrcClient<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> {
     server_notifier: smol::channel::Sender<(NotifierMsg, u64)>,
     subscription: Subscription<ClientSubMsg>,
 
-    missed_events: Arc<Mutex<Vec<Event<PrivMsgEvent>>>>,
+    history: HistoryPtr,
+
+    // services of the profile the client authenticated as
+    profiles: Profiles,
+    nickserv: NickServ,
+    confserv: ConfServ,
+    fileserv: FileServ,
+    chanserv: ChanServ,
+
+    // files shared with the client, by their id
+    file_offers: HashMap<String, FileOffer>,
 }
 
 impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         write_stream: WriteHalf<C>,
         read_stream: BufReader<ReadHalf<C>>,
         address: SocketAddr,
-        irc_config: IrcConfig,
+        profile: Profile,
         server_notifier: smol::channel::Sender<(NotifierMsg, u64)>,
         subscription: Subscription<ClientSubMsg>,
-        missed_events: Arc<Mutex<Vec<Event<PrivMsgEvent>>>>,
+        history: HistoryPtr,
+        profiles: Profiles,
     ) -> Self {
         Self {
             write_stream,
             read_stream,
             address,
-            irc_config,
+            irc_config: profile.config,
             subscription,
             server_notifier,
-            missed_events,
+            history,
+            profiles,
+            nickserv: profile.nickserv,
+            confserv: profile.confserv,
+            fileserv: profile.fileserv,
+            chanserv: profile.chanserv,
+            file_offers: HashMap::new(),
         }
     }
 
@@ -86,8 +142,8 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
                 // Process msg from View or other client connnected to the same irc server
                 msg = self.subscription.receive().fuse() => {
                     match msg {
-                        ClientSubMsg::Privmsg(mut m) => {
-                            if let Err(e) = self.process_msg(&mut m).await {
+                        ClientSubMsg::Privmsg(event) => {
+                            if let Err(e) = self.process_msg(&event).await {
                                 error!("[CLIENT {}] Process msg: {}",  self.address, e);
                                 break
                             }
@@ -115,10 +171,24 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
     }
 
     pub async fn update_config(&mut self, new_config: IrcConfig) {
+        // Configs are sent for every profile, only ours is of interest
+        if new_config.profile != self.irc_config.profile {
+            return
+        }
+
         info!("[CLIENT {}] Updating config...", self.address);
 
-        self.irc_config.channels.extend(new_config.channels);
+        // Mutes come from the moderation seen so far, not from the config
+        let mut channels = new_config.channels;
+        for (name, chan_info) in channels.iter_mut() {
+            if let Some(old) = self.irc_config.channels.get(name) {
+                chan_info.muted = old.muted.clone();
+            }
+        }
+
+        self.irc_config.channels.extend(channels);
         self.irc_config.contacts.extend(new_config.contacts);
+        self.irc_config.ignored = new_config.ignored;
         self.irc_config.password = new_config.password;
 
         if self.on_receive_join(self.irc_config.channels.keys().cloned().collect()).await.is_err() {
@@ -128,10 +198,23 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
         }
     }
 
-    pub async fn process_msg(&mut self, msg: &mut PrivMsgEvent) -> Result<()> {
-        debug!("[CLIENT {}] msg from View: {:?}", self.address, msg.to_string());
+    pub async fn process_msg(&mut self, event: &Event<PrivMsgEvent>) -> Result<()> {
+        debug!("[CLIENT {}] msg from View: {:?}", self.address, event.action.to_string());
 
-        let mut msg = msg.clone();
+        if let Some(rendered) = self.render_msg(event) {
+            self.apply_msg(&rendered);
+            let tags = self.message_tags(event, None);
+            self.reply(&format!("{}{}", tags, rendered.line)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Decrypt and verify a message, returning what to show the client.
+    /// Returns `None` if the message isn't meant for us. Rendering doesn't
+    /// change the client state, see [`Self::apply_msg`].
+    fn render_msg(&self, event: &Event<PrivMsgEvent>) -> Option<RenderedMsg> {
+        let mut msg = event.action.clone();
         let mut contact = String::new();
 
         decrypt_target(
@@ -143,13 +226,9 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
 
         if msg.target.starts_with('#') {
             // Try to potentially decrypt the incoming message.
-            if !self.irc_config.channels.contains_key(&msg.target) {
-                return Ok(())
-            }
-
-            let chan_info = self.irc_config.channels.get_mut(&msg.target).unwrap();
+            let chan_info = self.irc_config.channels.get(&msg.target)?;
             if !chan_info.joined {
-                return Ok(())
+                return None
             }
 
             if let Some(salt_box) = &chan_info.salt_box {
@@ -157,29 +236,146 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
                 debug!("[P2P] Decrypted received message: {:?}", msg);
             }
 
-            // add the nickname to the channel's names
-            if !chan_info.names.contains(&msg.nick) {
-                chan_info.names.push(msg.nick.clone());
+            let sender = msg.sender(event.timestamp);
+            if is_listed(&self.irc_config.ignored, &msg.nick, sender.as_ref()) {
+                return None
             }
 
-            self.reply(&msg.to_string()).await?;
-        } else if self.irc_config.is_cap_end && self.irc_config.is_nick_init {
-            if !self.irc_config.contacts.contains_key(&contact) {
-                return Ok(())
+            // Moderation is only respected from the operators we trust
+            let mut moderation = None;
+            if let Ok(action) = msg.msg.parse::<Moderation>() {
+                if !sender.map_or(false, |x| chan_info.operators.contains(&x)) {
+                    warn!(
+                        "[CLIENT {}] Moderation of {} from non operator {}",
+                        self.address, msg.target, msg.nick
+                    );
+                    return None
+                }
+
+                msg.msg = action.describe();
+                moderation = Some(action);
+            } else if is_listed(&chan_info.muted, &msg.nick, sender.as_ref()) {
+                return None
             }
 
-            let contact_info = self.irc_config.contacts.get(&contact).unwrap();
+            let offer = msg.msg.parse::<FileOffer>().ok();
+            let (user, host) = self.check_signature(&mut msg, sender);
+            if let Some(offer) = &offer {
+                describe_file_offer(&mut msg, offer);
+            }
+
+            return Some(RenderedMsg {
+                buffer: msg.target.clone(),
+                nick: msg.nick.clone(),
+                line: msg.to_irc_string(&user, host),
+                moderation,
+                offer,
+            })
+        } else if self.irc_config.is_cap_end && self.irc_config.is_nick_init {
+            let contact_info = self.irc_config.contacts.get(&contact)?;
+            let (mut user, mut host) = ("anon".to_string(), HOST);
+            let mut offer = None;
             if let Some(salt_box) = &contact_info.salt_box {
                 decrypt_privmsg(salt_box, &mut msg);
-                // This is for /query
-                msg.nick = contact;
+                let sender = msg.sender(event.timestamp);
+                if is_listed(&self.irc_config.ignored, &contact, sender.as_ref()) {
+                    return None
+                }
+                offer = msg.msg.parse::<FileOffer>().ok();
+                (user, host) = self.check_signature(&mut msg, sender);
+                if let Some(offer) = &offer {
+                    describe_file_offer(&mut msg, offer);
+                }
+                // This is for /query, our own messages keep our nick
+                if msg.nick != self.irc_config.nickname {
+                    msg.nick = contact.clone();
+                }
                 debug!("[P2P] Decrypted received message: {:?}", msg);
             }
 
-            self.reply(&msg.to_string()).await?;
+            return Some(RenderedMsg {
+                buffer: contact,
+                nick: msg.nick.clone(),
+                line: msg.to_irc_string(&user, host),
+                moderation: None,
+                offer,
+            })
         }
 
-        Ok(())
+        None
+    }
+
+    /// Update the client state with a message shown live: apply the
+    /// channel moderation, add the sender to the channel names, and
+    /// remember the file it shares.
+    fn apply_msg(&mut self, rendered: &RenderedMsg) {
+        if let Some(chan_info) = self.irc_config.channels.get_mut(&rendered.buffer) {
+            if let Some(moderation) = &rendered.moderation {
+                moderation.apply(&mut chan_info.muted);
+            }
+
+            if !chan_info.names.contains(&rendered.nick) {
+                chan_info.names.push(rendered.nick.clone());
+            }
+        }
+
+        if let Some(offer) = &rendered.offer {
+            self.file_offers.insert(offer.id(), offer.clone());
+        }
+    }
+
+    /// Build the IRCv3 message tags prefix for the message of an event,
+    /// according to the capabilities the client enabled.
+    fn message_tags(&self, event: &Event<PrivMsgEvent>, batch: Option<&str>) -> String {
+        let mut tags = vec![];
+
+        if let Some(batch) = batch {
+            tags.push(format!("batch={}", batch));
+        }
+
+        if self.is_cap_enabled("message-tags") {
+            tags.push(format!("msgid={}", event.hash()));
+        }
+
+        if self.is_cap_enabled("server-time") {
+            tags.push(format!("time={}", server_time(event.timestamp)));
+        }
+
+        if tags.is_empty() {
+            return String::new()
+        }
+
+        format!("@{} ", tags.join(";"))
+    }
+
+    fn is_cap_enabled(&self, cap: &str) -> bool {
+        *self.irc_config.capabilities.get(cap).unwrap_or(&false)
+    }
+
+    /// Return the user and host to show the verified `sender` of a message
+    /// with. Signed messages show the signing key fingerprint as the user,
+    /// and the verified host when the nick is registered to that key with
+    /// NickServ. Messages claiming a registered nick without being signed
+    /// by its key get flagged.
+    fn check_signature(
+        &self,
+        msg: &mut PrivMsgEvent,
+        sender: Option<PublicKey>,
+    ) -> (String, &'static str) {
+        let user = sender.as_ref().map(fingerprint).unwrap_or_else(|| "anon".to_string());
+
+        match self.nickserv.registered_key(&msg.nick) {
+            Some(public) if sender == Some(public) => (user, VERIFIED_HOST),
+            Some(_) => {
+                warn!(
+                    "[CLIENT {}] Unverified message from registered nick {}",
+                    self.address, msg.nick
+                );
+                msg.msg = format!("{} {}", UNVERIFIED_PREFIX, msg.msg);
+                (user, HOST)
+            }
+            None => (user, HOST),
+        }
     }
 
     pub async fn process_line(&mut self, line: String) -> Result<()> {
@@ -219,6 +415,7 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
             "PING" => self.on_ping(value).await?,
             "PRIVMSG" => self.on_receive_privmsg(&line, value).await?,
             "CAP" => self.on_receive_cap(&line, &value.to_uppercase()).await?,
+            "CHATHISTORY" => self.on_receive_chathistory(&line).await?,
             "QUIT" => self.on_quit()?,
             _ => warn!("[CLIENT {}] Unimplemented `{}` command", self.address, command),
         }
@@ -241,6 +438,12 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
             let register_reply =
                 format!(":darkfi 001 {} :Let there be dark\r\n", self.irc_config.nickname);
             self.reply(&register_reply).await?;
+            let isupport_reply = format!(
+                ":darkfi 005 {} CHATHISTORY={} MSGREFTYPES=msgid,timestamp :are supported by this server\r\n",
+                self.irc_config.nickname,
+                settings::MAXIMUM_CHATHISTORY_LIMIT
+            );
+            self.reply(&isupport_reply).await?;
             self.irc_config.is_registered = true;
 
             // join all channels
@@ -275,16 +478,49 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
     }
 
     async fn on_receive_pass(&mut self, password: &str) -> Result<()> {
-        if self.irc_config.password == password {
-            self.irc_config.is_pass_init = true
-        } else {
-            // Close the connection
-            warn!("[CLIENT {}] Password is not correct!", self.address);
-            return self.on_quit()
+        if self.irc_config.is_registered {
+            warn!("[CLIENT {}] Ignoring PASS after registration", self.address);
+            return Ok(())
+        }
+
+        match self.profiles.authenticate(password)? {
+            Some(profile) => self.switch_profile(profile),
+            None => {
+                // Close the connection
+                warn!("[CLIENT {}] Password is not correct!", self.address);
+                return self.on_quit()
+            }
         }
+
+        self.irc_config.is_pass_init = true;
         Ok(())
     }
 
+    /// Use the channels, contacts and services of the profile the client
+    /// authenticated as, keeping the state of the connection
+    fn switch_profile(&mut self, profile: Profile) {
+        if profile.config.profile != self.irc_config.profile {
+            info!(
+                "[CLIENT {}] Using profile {}",
+                self.address,
+                profile.config.profile.as_deref().unwrap_or("default")
+            );
+        }
+
+        let config = profile.config;
+        self.irc_config.password = config.password;
+        self.irc_config.auto_channels = config.auto_channels;
+        self.irc_config.channels = config.channels;
+        self.irc_config.contacts = config.contacts;
+        self.irc_config.ignored = config.ignored;
+        self.irc_config.profile = config.profile;
+        self.irc_config.identity = None;
+        self.nickserv = profile.nickserv;
+        self.confserv = profile.confserv;
+        self.fileserv = profile.fileserv;
+        self.chanserv = profile.chanserv;
+    }
+
     async fn on_receive_nick(&mut self, nickname: &str) -> Result<()> {
         if nickname.len() >= settings::MAXIMUM_LENGTH_OF_NICK_CHAN_CNT {
             return Ok(())
@@ -293,6 +529,13 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
         self.irc_config.is_nick_init = true;
         let old_nick = std::mem::replace(&mut self.irc_config.nickname, nickname.to_string());
 
+        // The NickServ identity only signs for the nick it was registered with
+        if let Some((nick, _)) = &self.irc_config.identity {
+            if !nick.eq_ignore_ascii_case(nickname) {
+                self.irc_config.identity = None;
+            }
+        }
+
         let nick_reply =
             format!(":{}!anon@dark.fi NICK {}\r\n", old_nick, self.irc_config.nickname);
         self.reply(&nick_reply).await
@@ -482,11 +725,42 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
 
         debug!("[CLIENT {}] (Plain) PRIVMSG {} :{}", self.address, target, message,);
 
+        if target.to_lowercase() == NICK_NICKSERV {
+            return self.on_receive_nickserv(self.new_privmsg(target, message)).await
+        }
+
+        if target.to_lowercase() == NICK_CONFSERV {
+            return self.on_receive_confserv(self.new_privmsg(target, message)).await
+        }
+
+        if target.to_lowercase() == NICK_FILESERV {
+            return self.on_receive_fileserv(self.new_privmsg(target, message)).await
+        }
+
+        if target.to_lowercase() == NICK_CHANSERV {
+            return self.on_receive_chanserv(self.new_privmsg(target, message)).await
+        }
+
+        self.send_privmsg(target, message).await
+    }
+
+    fn new_privmsg(&self, target: &str, message: String) -> PrivMsgEvent {
         let mut privmsg = PrivMsgEvent::new();
 
         privmsg.nick = self.irc_config.nickname.clone();
         privmsg.target = target.to_string();
-        privmsg.msg = message.clone();
+        privmsg.msg = message;
+        privmsg
+    }
+
+    /// Sign and encrypt a msg to one of our channels or contacts, and send
+    /// it to the server
+    async fn send_privmsg(&mut self, target: &str, message: String) -> Result<()> {
+        let mut privmsg = self.new_privmsg(target, message);
+
+        if let Some((_, secret)) = &self.irc_config.identity {
+            privmsg.sign(secret);
+        }
 
         if target.starts_with('#') {
             if !self.irc_config.channels.contains_key(target) {
@@ -508,10 +782,17 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
                 return Ok(())
             }
 
+            // Direct msgs are sealed by the server, which keeps the
+            // ratcheting sessions shared by all the clients of a profile.
             let contact_info = self.irc_config.contacts.get(target).unwrap();
-            if let Some(salt_box) = &contact_info.salt_box {
-                encrypt_privmsg(salt_box, &mut privmsg);
-                debug!("[CLIENT {}] (Encrypted) PRIVMSG: {:?}", self.address, privmsg);
+            if contact_info.salt_box.is_some() {
+                let msg = NotifierMsg::DirectMsg {
+                    profile: self.irc_config.profile.clone(),
+                    contact: target.to_string(),
+                    msg: privmsg,
+                };
+                self.server_notifier.send((msg, self.subscription.get_id())).await?;
+                return Ok(())
             }
         }
 
@@ -522,6 +803,108 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
         Ok(())
     }
 
+    async fn on_receive_nickserv(&mut self, privmsg: PrivMsgEvent) -> Result<()> {
+        let lines = match self.nickserv.act(privmsg) {
+            Ok(reply) => {
+                if let Some((nick, secret)) = reply.identity {
+                    self.irc_config.identity = Some((nick.clone(), secret));
+                    if self.irc_config.nickname != nick {
+                        let old_nick = std::mem::replace(&mut self.irc_config.nickname, nick);
+                        let nick_reply = format!(
+                            ":{}!anon@dark.fi NICK {}\r\n",
+                            old_nick, self.irc_config.nickname
+                        );
+                        self.reply(&nick_reply).await?;
+                    }
+                }
+
+                if let Some(registration) = reply.registration {
+                    self.server_notifier
+                        .send((NotifierMsg::Privmsg(registration), self.subscription.get_id()))
+                        .await?;
+                }
+
+                reply.lines
+            }
+            Err(lines) => lines,
+        };
+
+        for line in lines {
+            let notice = format!(
+                ":NickServ!nickserv@dark.fi NOTICE {} :{}\r\n",
+                self.irc_config.nickname, line
+            );
+            self.reply(&notice).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn on_receive_confserv(&mut self, privmsg: PrivMsgEvent) -> Result<()> {
+        for line in self.confserv.act(&privmsg.msg).await {
+            let notice = format!(
+                ":ConfServ!confserv@dark.fi NOTICE {} :{}\r\n",
+                self.irc_config.nickname, line
+            );
+            self.reply(&notice).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn on_receive_fileserv(&mut self, privmsg: PrivMsgEvent) -> Result<()> {
+        let reply = self.fileserv.act(&privmsg.msg, &self.file_offers).await;
+
+        let mut lines = reply.lines;
+        if let Some((target, offer)) = reply.share {
+            let joined = self.irc_config.channels.get(&target).map_or(false, |x| x.joined);
+            if joined || self.irc_config.contacts.contains_key(&target) {
+                self.send_privmsg(&target, offer.to_string()).await?;
+                self.file_offers.insert(offer.id(), offer);
+            } else {
+                lines = vec![format!("Unknown channel or contact {}", target)];
+            }
+        }
+
+        for line in lines {
+            let notice = format!(
+                ":FileServ!fileserv@dark.fi NOTICE {} :{}\r\n",
+                self.irc_config.nickname, line
+            );
+            self.reply(&notice).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn on_receive_chanserv(&mut self, privmsg: PrivMsgEvent) -> Result<()> {
+        let reply = self.chanserv.act(&privmsg.msg, &self.irc_config).await;
+
+        if let Some((channel, moderation)) = reply.moderation {
+            self.send_privmsg(&channel, moderation.to_string()).await?;
+            // Our own msgs don't come back to us
+            if let Some(chan_info) = self.irc_config.channels.get_mut(&channel) {
+                moderation.apply(&mut chan_info.muted);
+            }
+        }
+
+        for rotation in reply.rotations {
+            self.server_notifier
+                .send((NotifierMsg::Privmsg(rotation), self.subscription.get_id()))
+                .await?;
+        }
+
+        for line in reply.lines {
+            let notice = format!(
+                ":ChanServ!chanserv@dark.fi NOTICE {} :{}\r\n",
+                self.irc_config.nickname, line
+            );
+            self.reply(&notice).await?;
+        }
+
+        Ok(())
+    }
+
     async fn on_receive_join(&mut self, channels: Vec<String>) -> Result<()> {
         for chan in channels.iter() {
             if !chan.starts_with('#') {
@@ -551,28 +934,196 @@ impl<C: AsyncRead + AsyncWrite + Send + Unpin + 'static> IrcClient<C> {
             }
         }
 
-        if *self.irc_config.capabilities.get("no-history").unwrap() {
+        if self.is_cap_enabled("no-history") {
             return Ok(())
         }
 
-        // Process missed messages if any (sorted by event's timestamp)
-        let mut hash_vec = self.missed_events.lock().await.clone();
-        hash_vec.sort_by(|a, b| a.timestamp.0.cmp(&b.timestamp.0));
-
-        for event in hash_vec {
-            let mut action = event.action.clone();
-            if let Err(e) = self.process_msg(&mut action).await {
+        // Process missed messages if any
+        let events = self.history.lock().await.events().await;
+        for event in events {
+            if let Err(e) = self.process_msg(&event).await {
                 error!("[CLIENT {}] Process msg: {}", self.address, e);
                 continue
             }
         }
         Ok(())
     }
+
+    async fn on_receive_chathistory(&mut self, line: &str) -> Result<()> {
+        let args: Vec<&str> = line.split_ascii_whitespace().skip(1).collect();
+
+        let subcommand = args.first().map(|x| x.to_uppercase()).unwrap_or_default();
+        if !["BEFORE", "AFTER", "LATEST", "BETWEEN"].contains(&subcommand.as_str()) {
+            return self.chathistory_fail("UNKNOWN_COMMAND", &subcommand, "Unknown command").await
+        }
+
+        // BETWEEN takes two message references, the rest take one
+        let refs = if subcommand == "BETWEEN" { 2 } else { 1 };
+        if args.len() < 3 + refs {
+            return self
+                .chathistory_fail("NEED_MORE_PARAMS", &subcommand, "Missing parameters")
+                .await
+        }
+
+        let target = args[1];
+
+        let mut references = vec![];
+        for r in &args[2..2 + refs] {
+            let reference = match (subcommand.as_str(), *r) {
+                ("LATEST", "*") => None,
+                (_, r) => match parse_msgref(r) {
+                    Some(r) => Some(r),
+                    None => {
+                        return self
+                            .chathistory_fail(
+                                "INVALID_PARAMS",
+                                &subcommand,
+                                "Invalid message reference",
+                            )
+                            .await
+                    }
+                },
+            };
+            references.push(reference);
+        }
+
+        let limit = match args[2 + refs].parse::<usize>() {
+            Ok(v) => v.min(settings::MAXIMUM_CHATHISTORY_LIMIT),
+            Err(_) => {
+                return self.chathistory_fail("INVALID_PARAMS", &subcommand, "Invalid limit").await
+            }
+        };
+
+        // Collect the messages of the requested buffer, without applying
+        // them again. Offers are remembered so they can still be fetched.
+        let events = self.history.lock().await.events().await;
+        let mut history = vec![];
+        for event in events {
+            let Some(rendered) = self.render_msg(&event) else { continue };
+            if rendered.buffer.eq_ignore_ascii_case(target) {
+                if let Some(offer) = rendered.offer {
+                    self.file_offers.insert(offer.id(), offer);
+                }
+                history.push((event, rendered.line));
+            }
+        }
+
+        let history = &history[history_range(&history, &subcommand, &references, limit)];
+
+        // Replies are wrapped in a batch if the client supports it
+        let batch = if self.is_cap_enabled("batch") {
+            let batch = format!("{:x}", rand::random::<u32>());
+            self.reply(&format!("BATCH +{} chathistory {}\r\n", batch, target)).await?;
+            Some(batch)
+        } else {
+            None
+        };
+
+        for (event, line) in history {
+            let tags = self.message_tags(event, batch.as_deref());
+            self.reply(&format!("{}{}", tags, line)).await?;
+        }
+
+        if let Some(batch) = batch {
+            self.reply(&format!("BATCH -{}\r\n", batch)).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn chathistory_fail(&mut self, code: &str, subcommand: &str, reason: &str) -> Result<()> {
+        let fail_reply = format!("FAIL CHATHISTORY {} {} :{}\r\n", code, subcommand, reason);
+        self.reply(&fail_reply).await
+    }
 }
 
 //
 // Helper functions
 //
+
+/// Format a timestamp as an IRCv3 `server-time` value
+fn server_time(timestamp: Timestamp) -> String {
+    let datetime = NaiveDateTime::from_timestamp_opt(timestamp.0 as i64, 0).unwrap_or_default();
+    datetime.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Parse an IRCv3 `server-time` value into a timestamp
+fn parse_server_time(time: &str) -> Option<Timestamp> {
+    let datetime = DateTime::parse_from_rfc3339(time).ok()?;
+    Some(Timestamp(datetime.timestamp().try_into().ok()?))
+}
+
+/// Parse a `CHATHISTORY` message reference
+fn parse_msgref(msgref: &str) -> Option<MsgRef> {
+    if let Some(id) = msgref.strip_prefix("msgid=") {
+        return blake3::Hash::from_hex(id).ok().map(MsgRef::Id)
+    }
+
+    msgref.strip_prefix("timestamp=").and_then(parse_server_time).map(MsgRef::Time)
+}
+
+/// Locate a message reference in the history of a buffer, returning the
+/// end of the messages before it and the start of the ones after it.
+/// `None` stands for `*`, which has the whole history around it, and
+/// unknown msgids have nothing around them.
+fn locate_msgref<T>(
+    history: &[(Event<PrivMsgEvent>, T)],
+    reference: Option<&MsgRef>,
+) -> (usize, usize) {
+    match reference {
+        None => (history.len(), 0),
+        Some(MsgRef::Id(id)) => match history.iter().position(|(e, _)| e.hash() == *id) {
+            Some(i) => (i, i + 1),
+            None => (0, history.len()),
+        },
+        Some(MsgRef::Time(t)) => (
+            history.partition_point(|(e, _)| e.timestamp.0 < t.0),
+            history.partition_point(|(e, _)| e.timestamp.0 <= t.0),
+        ),
+    }
+}
+
+/// Range of the history of a buffer answering a `CHATHISTORY` request.
+/// Messages are always returned in order, and when there are more than
+/// `limit` of them, the ones closest to the first reference are kept.
+fn history_range<T>(
+    history: &[(Event<PrivMsgEvent>, T)],
+    subcommand: &str,
+    references: &[Option<MsgRef>],
+    limit: usize,
+) -> Range<usize> {
+    let (before, after) = locate_msgref(history, references[0].as_ref());
+
+    match subcommand {
+        "BEFORE" => before.saturating_sub(limit)..before,
+        "AFTER" => after..history.len().min(after + limit),
+        "BETWEEN" => {
+            let (end_before, end_after) = locate_msgref(history, references[1].as_ref());
+            if after <= end_before {
+                after..end_before.min(after + limit)
+            } else if end_after <= before {
+                // The references are given newest first
+                before.saturating_sub(limit).max(end_after)..before
+            } else {
+                0..0
+            }
+        }
+        _ => history.len().saturating_sub(limit).max(after)..history.len(),
+    }
+}
+
+/// Describe a file shared in a msg in place of the raw offer
+fn describe_file_offer(msg: &mut PrivMsgEvent, offer: &FileOffer) {
+    let description = format!(
+        "shared {} ({} bytes), download it with: /msg FileServ GET {}",
+        offer.file_name(),
+        offer.size,
+        offer.id()
+    );
+
+    msg.msg = msg.msg.replace(&offer.to_string(), &description);
+}
+
 fn clean_input_line(mut line: String) -> Result<String> {
     if line.is_empty() {
         return Err(Error::ChannelStopped)
@@ -603,3 +1154,99 @@ fn parse_line(line: &str) -> Result<(String, String)> {
     let value = tokens.next().ok_or(Error::MalformedPacket)?;
     Ok((command, value.to_owned()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_time() {
+        assert_eq!(server_time(Timestamp(0)), "1970-01-01T00:00:00.000Z");
+        assert_eq!(server_time(Timestamp(1682942400)), "2023-05-01T12:00:00.000Z");
+
+        let timestamp = Some(Timestamp(1682942400));
+        assert_eq!(parse_server_time("2023-05-01T12:00:00.000Z"), timestamp);
+        assert_eq!(parse_server_time("2023-05-01T13:00:00.999+01:00"), timestamp);
+        assert_eq!(parse_server_time(&server_time(Timestamp(1682942400))), timestamp);
+
+        // Times before the epoch can't be represented
+        assert_eq!(parse_server_time("1969-12-31T23:59:59Z"), None);
+        assert_eq!(parse_server_time("2023-05-01"), None);
+        assert_eq!(parse_server_time("yesterday"), None);
+    }
+
+    #[test]
+    fn test_parse_msgref() {
+        let id = blake3::hash(b"foo");
+        assert!(matches!(
+            parse_msgref(&format!("msgid={}", id.to_hex())),
+            Some(MsgRef::Id(x)) if x == id
+        ));
+        assert!(matches!(
+            parse_msgref("timestamp=2023-05-01T12:00:00.000Z"),
+            Some(MsgRef::Time(Timestamp(1682942400)))
+        ));
+
+        assert!(parse_msgref("msgid=foo").is_none());
+        assert!(parse_msgref("timestamp=foo").is_none());
+        assert!(parse_msgref(&id.to_hex()).is_none());
+        assert!(parse_msgref("*").is_none());
+    }
+
+    #[test]
+    fn test_history_range() {
+        let history: Vec<_> = [10, 20, 20, 30, 40]
+            .into_iter()
+            .enumerate()
+            .map(|(i, timestamp)| {
+                let action = PrivMsgEvent {
+                    nick: "alice".to_string(),
+                    msg: format!("message {}", i),
+                    target: "#dev".to_string(),
+                    signature: String::new(),
+                    relayed: false,
+                };
+                let event = Event {
+                    previous_event_hash: blake3::hash(b""),
+                    action,
+                    timestamp: Timestamp(timestamp),
+                };
+                (event, ())
+            })
+            .collect();
+
+        let id = |i: usize| Some(MsgRef::Id(history[i].0.hash()));
+        let time = |t: u64| Some(MsgRef::Time(Timestamp(t)));
+        let unknown = || Some(MsgRef::Id(blake3::hash(b"unknown")));
+        let range = |subcommand: &str, references: &[Option<MsgRef>], limit: usize| {
+            history_range(&history, subcommand, references, limit)
+        };
+
+        // LATEST keeps the newest messages, after the reference if any
+        assert_eq!(range("LATEST", &[None], 10), 0..5);
+        assert_eq!(range("LATEST", &[None], 2), 3..5);
+        assert_eq!(range("LATEST", &[id(1)], 10), 2..5);
+        assert_eq!(range("LATEST", &[time(20)], 10), 3..5);
+
+        // BEFORE and AFTER exclude the reference, and keep the messages
+        // closest to it
+        assert_eq!(range("BEFORE", &[id(3)], 10), 0..3);
+        assert_eq!(range("BEFORE", &[id(3)], 2), 1..3);
+        assert_eq!(range("BEFORE", &[time(20)], 10), 0..1);
+        assert_eq!(range("AFTER", &[id(0)], 10), 1..5);
+        assert_eq!(range("AFTER", &[time(20)], 10), 3..5);
+        assert_eq!(range("AFTER", &[time(20)], 1), 3..4);
+
+        // Unknown msgids have nothing around them
+        assert_eq!(range("BEFORE", &[unknown()], 10), 0..0);
+        assert_eq!(range("AFTER", &[unknown()], 10), 5..5);
+
+        // BETWEEN excludes both references, in either order
+        assert_eq!(range("BETWEEN", &[id(0), id(4)], 10), 1..4);
+        assert_eq!(range("BETWEEN", &[id(0), id(4)], 2), 1..3);
+        assert_eq!(range("BETWEEN", &[id(4), id(0)], 10), 1..4);
+        assert_eq!(range("BETWEEN", &[id(4), id(0)], 2), 2..4);
+        assert_eq!(range("BETWEEN", &[time(10), time(30)], 10), 1..3);
+        assert_eq!(range("BETWEEN", &[id(2), id(2)], 10), 0..0);
+    }
+}
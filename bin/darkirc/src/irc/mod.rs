@@ -16,23 +16,36 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use darkfi::{util::path::get_config_path, Result};
+use async_std::sync::{Arc, Mutex};
+use darkfi::{
+    event_graph::model::{Event, ModelPtr},
+    util::path::get_config_path,
+    Error, Result,
+};
+use darkfi_sdk::crypto::SecretKey;
 
 use crate::{
     settings::{
-        parse_configured_channels, parse_configured_contacts, Args, ChannelInfo, ContactInfo,
-        CONFIG_FILE, MAXIMUM_LENGTH_OF_NICK_CHAN_CNT,
+        parse_configured_channels, parse_configured_contacts, parse_configured_ignores,
+        parse_configured_profiles, Args, ChannelInfo, ContactInfo, CONFIG_FILE,
+        MAXIMUM_LENGTH_OF_NICK_CHAN_CNT,
     },
     PrivMsgEvent,
 };
 
+mod bridge;
+pub use bridge::Bridge;
+
 mod client;
 pub use client::IrcClient;
 
 mod server;
-pub use server::IrcServer;
+pub use server::{
+    is_listed, ChanServ, ConfServ, FileServ, IrcServer, Moderation, NickServ, Profile, Profiles,
+    NICK_CHANSERV, NICK_CONFSERV, NICK_FILESERV, NICK_NICKSERV,
+};
 
 #[derive(Clone)]
 pub struct IrcConfig {
@@ -47,28 +60,49 @@ pub struct IrcConfig {
     pub nickname: String,
     pub password: String,
     pub capabilities: HashMap<String, bool>,
+    /// Nick and key set through NickServ `IDENTIFY`, used to sign
+    /// outgoing messages
+    pub identity: Option<(String, SecretKey)>,
+
+    /// Profile the client authenticated as, `None` for the default one
+    pub profile: Option<String>,
 
     // channels and contacts
     pub auto_channels: Vec<String>,
     pub channels: HashMap<String, ChannelInfo>,
     pub contacts: HashMap<String, ContactInfo>,
+    /// Nicks and key fingerprints whose messages are never shown
+    pub ignored: Vec<String>,
 }
 
 impl IrcConfig {
-    pub fn new(settings: &Args) -> Result<Self> {
-        let password = settings.password.as_ref().unwrap_or(&String::new()).clone();
+    /// Load the configuration of a profile, or the default one when no
+    /// profile is given
+    pub fn new(settings: &Args, profile: Option<&str>) -> Result<Self> {
+        let cfg_path = get_config_path(settings.config.clone(), CONFIG_FILE)?;
+        let toml_contents = std::fs::read_to_string(cfg_path)?;
 
-        let mut auto_channels = settings.autojoin.clone();
+        let (password, mut auto_channels) = match profile {
+            Some(name) => {
+                let profiles = parse_configured_profiles(&toml_contents)?;
+                let info = profiles.get(name).ok_or(Error::ConfigInvalid)?;
+                (info.password.clone(), info.autojoin.clone())
+            }
+            None => (settings.password.clone().unwrap_or_default(), settings.autojoin.clone()),
+        };
         auto_channels.retain(|chan| chan.len() <= MAXIMUM_LENGTH_OF_NICK_CHAN_CNT);
 
         // Pick up channel settings from the TOML configuration
-        let cfg_path = get_config_path(settings.config.clone(), CONFIG_FILE)?;
-        let toml_contents = std::fs::read_to_string(cfg_path)?;
-        let channels = parse_configured_channels(&toml_contents)?;
-        let contacts = parse_configured_contacts(&toml_contents)?;
+        let channels = parse_configured_channels(&toml_contents, profile)?;
+        let contacts = parse_configured_contacts(&toml_contents, profile)?;
+        let ignored = parse_configured_ignores(&toml_contents, profile)?;
 
         let mut capabilities = HashMap::new();
         capabilities.insert("no-history".to_string(), false);
+        capabilities.insert("server-time".to_string(), false);
+        capabilities.insert("batch".to_string(), false);
+        capabilities.insert("draft/chathistory".to_string(), false);
+        capabilities.insert("message-tags".to_string(), false);
 
         Ok(Self {
             is_nick_init: false,
@@ -81,18 +115,104 @@ impl IrcConfig {
             auto_channels,
             channels,
             contacts,
+            ignored,
             capabilities,
+            identity: None,
+            profile: profile.map(String::from),
         })
     }
+
+    /// Load the configuration of the default profile and of every
+    /// configured one
+    pub fn load_all(settings: &Args) -> Result<Vec<Self>> {
+        let cfg_path = get_config_path(settings.config.clone(), CONFIG_FILE)?;
+        let profiles = parse_configured_profiles(&std::fs::read_to_string(cfg_path)?)?;
+
+        // The top level password may also be given on the command line
+        for (name, info) in profiles.iter() {
+            if settings.password.as_deref() == Some(info.password.as_str()) {
+                return Err(Error::Custom(format!(
+                    "Profile {} has the same password as the top level configuration",
+                    name
+                )))
+            }
+        }
+
+        let mut configs = vec![Self::new(settings, None)?];
+        for name in profiles.keys() {
+            configs.push(Self::new(settings, Some(name))?);
+        }
+
+        Ok(configs)
+    }
+}
+
+pub type HistoryPtr = Arc<Mutex<History>>;
+
+/// Messages shown to the clients, kept for the ones connecting later and
+/// for `CHATHISTORY`. Direct messages are kept the way they were opened.
+/// Messages older than the root of the event graph are dropped, so the
+/// history follows the retention of the model instead of growing forever.
+///
+/// History isn't read back from the event graph itself: direct messages
+/// can only be opened once, since their ratchet moves on, and the graph
+/// doesn't keep what was received before darkirc started. So it only
+/// covers the messages shown since then.
+pub struct History {
+    model: ModelPtr<PrivMsgEvent>,
+    events: VecDeque<Event<PrivMsgEvent>>,
+}
+
+impl History {
+    pub fn new(model: ModelPtr<PrivMsgEvent>) -> HistoryPtr {
+        Arc::new(Mutex::new(Self { model, events: VecDeque::new() }))
+    }
+
+    /// Add a message shown to the clients
+    pub async fn push(&mut self, event: Event<PrivMsgEvent>) {
+        self.events.push_back(event);
+        self.prune().await;
+    }
+
+    /// Return the kept messages, in event graph order
+    pub async fn events(&mut self) -> Vec<Event<PrivMsgEvent>> {
+        self.prune().await;
+        let mut events: Vec<_> = self.events.iter().cloned().collect();
+        events.sort_by_cached_key(|event| (event.timestamp.0, *event.hash().as_bytes()));
+        events
+    }
+
+    /// Drop the messages older than the root of the model. Messages mostly
+    /// arrive in order, so only the oldest ones are checked.
+    async fn prune(&mut self) {
+        let cutoff = {
+            let model = self.model.lock().await;
+            let root = model.get_root_hash();
+            match model.get_event(&root) {
+                Some(event) if root != model.get_genesis_hash() => event.timestamp.0,
+                _ => return,
+            }
+        };
+
+        while self.events.front().map_or(false, |event| event.timestamp.0 < cutoff) {
+            self.events.pop_front();
+        }
+    }
 }
 
 #[derive(Clone)]
 pub enum ClientSubMsg {
-    Privmsg(PrivMsgEvent),
+    Privmsg(Event<PrivMsgEvent>),
     Config(IrcConfig),
 }
 #[derive(Clone)]
 pub enum NotifierMsg {
     Privmsg(PrivMsgEvent),
+    /// A msg to one of our contacts, to be sealed by its direct message session
+    DirectMsg {
+        profile: Option<String>,
+        contact: String,
+        msg: PrivMsgEvent,
+    },
     UpdateConfig,
 }
@@ -16,7 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{fs::File, net::SocketAddr};
+use std::{collections::HashMap, fs::File, net::SocketAddr};
 
 use async_rustls::{rustls, TlsAcceptor};
 use async_std::{
@@ -24,28 +24,68 @@ use async_std::{
     sync::{Arc, Mutex},
 };
 use futures::{io::BufReader, AsyncRead, AsyncReadExt, AsyncWrite};
-use log::{error, info};
+use log::{error, info, warn};
 
 use darkfi::{
     event_graph::{
         model::{Event, EventId, ModelPtr},
-        protocol_event::{Seen, SeenPtr},
+        protocol_event::{broadcast, Seen, SeenPtr},
         view::ViewPtr,
     },
     net::P2pPtr,
     system::SubscriberPtr,
-    util::{path::expand_path, time::Timestamp},
+    util::{
+        path::{expand_path, get_config_path},
+        time::Timestamp,
+    },
     Error, Result,
 };
 
-use super::{ClientSubMsg, IrcClient, IrcConfig, NotifierMsg};
+use super::{Bridge, ClientSubMsg, History, HistoryPtr, IrcClient, IrcConfig, NotifierMsg};
 
-use crate::{settings::Args, PrivMsgEvent};
+use crate::{
+    files::FileStorePtr,
+    settings::{parse_configured_bridges, Args, CONFIG_FILE},
+    PrivMsgEvent,
+};
 
-mod nickserv;
-use nickserv::NickServ;
+mod chanserv;
+pub use chanserv::{is_listed, ChanServ, Moderation};
+use chanserv::{open_rotation, ROTATION_TARGET};
+
+mod confserv;
+pub use confserv::ConfServ;
+
+mod fileserv;
+pub use fileserv::FileServ;
 
-const NICK_NICKSERV: &str = "nickserv";
+mod dm;
+use dm::{DirectMessages, DirectMessagesPtr, DmEvent};
+
+mod nickserv;
+pub use nickserv::NickServ;
+
+mod profiles;
+pub use profiles::{Profile, Profiles};
+
+pub const NICK_NICKSERV: &str = "nickserv";
+pub const NICK_CONFSERV: &str = "confserv";
+pub const NICK_FILESERV: &str = "fileserv";
+pub const NICK_CHANSERV: &str = "chanserv";
+
+/// Direct message sessions of every profile, `None` being the default one
+type ProfileDms = Arc<Mutex<HashMap<Option<String>, DirectMessagesPtr>>>;
+
+/// Name of the sled tree holding the data of a profile. The default
+/// profile keeps using the tree name as is.
+pub fn profile_tree(tree: &[u8], profile: Option<&str>) -> Vec<u8> {
+    let mut name = tree.to_vec();
+    if let Some(profile) = profile {
+        name.push(b':');
+        name.extend_from_slice(profile.as_bytes());
+    }
+    name
+}
 
 pub struct IrcServer {
     settings: Args,
@@ -54,9 +94,16 @@ pub struct IrcServer {
     view: ViewPtr<PrivMsgEvent>,
     clients_subscriptions: SubscriberPtr<ClientSubMsg>,
     seen: SeenPtr<EventId>,
-    missed_events: Arc<Mutex<Vec<Event<PrivMsgEvent>>>>,
+    history: HistoryPtr,
     /// nickserv service
     pub nickserv: NickServ,
+    /// confserv service
+    pub confserv: ConfServ,
+    /// Profiles served to the irc clients
+    profiles: Profiles,
+    /// Direct message sessions with the contacts of every profile
+    dms: ProfileDms,
+    sled_db: sled::Db,
 }
 
 impl IrcServer {
@@ -66,9 +113,24 @@ impl IrcServer {
         model: ModelPtr<PrivMsgEvent>,
         view: ViewPtr<PrivMsgEvent>,
         clients_subscriptions: SubscriberPtr<ClientSubMsg>,
+        files: FileStorePtr,
     ) -> Result<Self> {
         let seen = Seen::new();
-        let missed_events = Arc::new(Mutex::new(vec![]));
+        let history = History::new(model.clone());
+        let sled_db = sled::open(expand_path(&settings.datastore)?)?;
+        let nickserv = NickServ::new(&sled_db, None)?;
+
+        let mut dms = HashMap::new();
+        for config in IrcConfig::load_all(&settings)? {
+            let dm = DirectMessages::new(&sled_db, config.contacts, config.profile.as_deref())?;
+            dms.insert(config.profile, dm);
+        }
+        let dms = Arc::new(Mutex::new(dms));
+
+        let confserv = ConfServ::new(settings.clone(), clients_subscriptions.clone());
+        let fileserv = FileServ::new(files, expand_path(&settings.files_path)?);
+        let profiles = Profiles::new(settings.clone(), sled_db.clone(), confserv.clone(), fileserv);
+
         Ok(Self {
             settings,
             p2p,
@@ -76,8 +138,12 @@ impl IrcServer {
             view,
             clients_subscriptions,
             seen,
-            missed_events,
-            nickserv: NickServ::default(),
+            history,
+            nickserv,
+            confserv,
+            profiles,
+            dms,
+            sled_db,
         })
     }
 
@@ -88,11 +154,16 @@ impl IrcServer {
         executor
             .clone()
             .spawn(Self::listen_to_msgs(
+                self.settings.clone(),
+                self.confserv.clone(),
                 self.p2p.clone(),
                 self.model.clone(),
                 self.seen.clone(),
+                self.history.clone(),
                 msg_recv,
                 self.clients_subscriptions.clone(),
+                self.nickserv.clone(),
+                self.dms.clone(),
             ))
             .detach();
 
@@ -100,24 +171,65 @@ impl IrcServer {
         executor
             .clone()
             .spawn(Self::listen_to_view(
+                self.settings.clone(),
+                self.confserv.clone(),
+                self.p2p.clone(),
+                self.model.clone(),
                 self.view.clone(),
                 self.seen.clone(),
-                self.missed_events.clone(),
+                self.history.clone(),
+                self.clients_subscriptions.clone(),
+                self.nickserv.clone(),
+                self.dms.clone(),
+            ))
+            .detach();
+
+        // Keep the contacts of direct messages in sync with the config
+        executor
+            .clone()
+            .spawn(Self::listen_to_config(
                 self.clients_subscriptions.clone(),
+                self.sled_db.clone(),
+                self.dms.clone(),
             ))
             .detach();
 
+        // Relay the bridged channels to their upstream IRC networks
+        let cfg_path = get_config_path(self.settings.config.clone(), CONFIG_FILE)?;
+        let bridges = parse_configured_bridges(&std::fs::read_to_string(cfg_path)?)?;
+        if !bridges.is_empty() {
+            let config = IrcConfig::new(&self.settings, None)?;
+            for (name, info) in bridges {
+                let subscription = self.clients_subscriptions.clone().subscribe().await;
+                let bridge = Bridge::new(
+                    name,
+                    info,
+                    config.channels.clone(),
+                    msg_notifier.clone(),
+                    subscription,
+                );
+                executor.clone().spawn(bridge.run()).detach();
+            }
+        }
+
         // Start listening for new connections
         self.listen(msg_notifier, executor.clone()).await?;
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn listen_to_view(
+        settings: Args,
+        confserv: ConfServ,
+        p2p: P2pPtr,
+        model: ModelPtr<PrivMsgEvent>,
         view: ViewPtr<PrivMsgEvent>,
         seen: SeenPtr<EventId>,
-        missed_events: Arc<Mutex<Vec<Event<PrivMsgEvent>>>>,
+        history: HistoryPtr,
         clients_subscriptions: SubscriberPtr<ClientSubMsg>,
+        nickserv: NickServ,
+        dms: ProfileDms,
     ) -> Result<()> {
         loop {
             let event = view.lock().await.process().await?;
@@ -125,59 +237,191 @@ impl IrcServer {
                 continue
             }
 
-            missed_events.lock().await.push(event.clone());
+            // Nick registrations are consumed by NickServ and never
+            // shown to the clients.
+            if event.action.target.to_lowercase() == NICK_NICKSERV {
+                nickserv.process_registration(&event);
+                continue
+            }
+
+            // New channel secrets are saved to the configuration of the
+            // profiles trusting their sender, and never shown either.
+            if event.action.target == ROTATION_TARGET {
+                Self::process_rotation(&settings, &confserv, &event).await;
+                continue
+            }
 
-            let msg = event.action.clone();
+            // Direct messages are decrypted here, since their sessions
+            // are shared by all the clients of a profile. At most one of
+            // the profiles can open a direct message.
+            let mut outcome = DmEvent::Pass;
+            let mut responses = vec![];
+            for dm in dms.lock().await.values() {
+                let (profile_outcome, profile_responses) = dm.lock().await.open(&event.action);
+                responses.extend(profile_responses);
+                match (&outcome, profile_outcome) {
+                    (DmEvent::Message(_), _) | (_, DmEvent::Pass) => {}
+                    (_, profile_outcome) => outcome = profile_outcome,
+                }
+            }
+
+            for msg in responses {
+                Self::broadcast_msg(&p2p, &model, &seen, msg).await;
+            }
 
-            clients_subscriptions.notify(ClientSubMsg::Privmsg(msg)).await;
+            let event = match outcome {
+                DmEvent::Pass => event,
+                DmEvent::Drop => continue,
+                DmEvent::Message(action) => Event { action, ..event },
+            };
+
+            history.lock().await.push(event.clone()).await;
+
+            clients_subscriptions.notify(ClientSubMsg::Privmsg(event)).await;
+        }
+    }
+
+    /// Replace the channel secret in every profile able to open the rotation
+    async fn process_rotation(settings: &Args, confserv: &ConfServ, event: &Event<PrivMsgEvent>) {
+        let configs = match IrcConfig::load_all(settings) {
+            Ok(configs) => configs,
+            Err(e) => {
+                error!("[IRC SERVER] Failed loading the configuration: {}", e);
+                return
+            }
+        };
+
+        for config in configs {
+            let Some(rotation) = open_rotation(&config, event) else { continue };
+            let channel = &rotation.channel;
+
+            // Rotations replayed or sent from an older secret are rejected
+            let confserv = confserv.with_profile(config.profile.as_deref());
+            match confserv
+                .set_channel_secret(channel, &rotation.secret, Some(&rotation.previous))
+                .await
+            {
+                Ok(_) => info!("[IRC SERVER] Received a new secret for channel {}", channel),
+                Err(e) => warn!("[IRC SERVER] Rejected the new secret of {}: {}", channel, e),
+            }
         }
     }
 
+    async fn listen_to_config(
+        clients_subscriptions: SubscriberPtr<ClientSubMsg>,
+        sled_db: sled::Db,
+        dms: ProfileDms,
+    ) -> Result<()> {
+        let subscription = clients_subscriptions.subscribe().await;
+        loop {
+            let ClientSubMsg::Config(config) = subscription.receive().await else { continue };
+
+            let mut dms = dms.lock().await;
+            match dms.get(&config.profile) {
+                Some(dm) => dm.lock().await.set_contacts(config.contacts),
+                None => {
+                    // A profile added since we started
+                    let profile = config.profile.as_deref();
+                    match DirectMessages::new(&sled_db, config.contacts, profile) {
+                        Ok(dm) => {
+                            dms.insert(config.profile, dm);
+                        }
+                        Err(e) => error!("[IRC SERVER] Failed loading direct messages: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create an event with the given msg on top of the DAG and broadcast it
+    async fn broadcast_msg(
+        p2p: &P2pPtr,
+        model: &ModelPtr<PrivMsgEvent>,
+        seen: &SeenPtr<EventId>,
+        msg: PrivMsgEvent,
+    ) -> Event<PrivMsgEvent> {
+        let event = Event {
+            previous_event_hash: model.lock().await.get_head_hash(),
+            action: msg,
+            timestamp: Timestamp::current_time(),
+        };
+
+        if seen.push(&event.hash()).await {
+            broadcast::<PrivMsgEvent, _>(p2p, &event, &[]).await;
+        }
+
+        event
+    }
+
     /// Start listening to msgs from irc clients
+    #[allow(clippy::too_many_arguments)]
     pub async fn listen_to_msgs(
+        settings: Args,
+        confserv: ConfServ,
         p2p: P2pPtr,
         model: ModelPtr<PrivMsgEvent>,
         seen: SeenPtr<EventId>,
+        history: HistoryPtr,
         recv: smol::channel::Receiver<(NotifierMsg, u64)>,
         clients_subscriptions: SubscriberPtr<ClientSubMsg>,
+        nickserv: NickServ,
+        dms: ProfileDms,
     ) -> Result<()> {
         loop {
             let (msg, subscription_id) = recv.recv().await?;
 
             match msg {
                 NotifierMsg::Privmsg(msg) => {
-                    // First check if we're communicating with any services.
-                    // If not, then we proceed with behaving like it's a normal
-                    // message.
-                    // TODO: This needs to be protected from adversaries doing
-                    //       remote execution.
-                    #[allow(clippy::single_match)]
-                    match msg.target.to_lowercase().as_str() {
-                        NICK_NICKSERV => {
-                            //self.nickserv.act(msg);
-                            continue
-                        }
+                    // NickServ commands are handled by the clients themselves,
+                    // so anything targeting it here is a nick registration to
+                    // be published on the network.
+                    if msg.target.to_lowercase() == NICK_NICKSERV {
+                        let event = Self::broadcast_msg(&p2p, &model, &seen, msg).await;
+                        nickserv.process_registration(&event);
+                        continue
+                    }
 
-                        _ => {} // pass
+                    // Rotations may be for other profiles of this darkirc too
+                    if msg.target == ROTATION_TARGET {
+                        let event = Self::broadcast_msg(&p2p, &model, &seen, msg).await;
+                        Self::process_rotation(&settings, &confserv, &event).await;
+                        continue
                     }
 
-                    let event = Event {
-                        previous_event_hash: model.lock().await.get_head_hash(),
-                        action: msg.clone(),
-                        timestamp: Timestamp::current_time(),
-                    };
+                    let event = Self::broadcast_msg(&p2p, &model, &seen, msg).await;
 
                     // Since this will be added to the View directly, other clients connected to irc
-                    // server must get informed about this new msg
+                    // server must get informed about this new msg. It is also kept so it shows up
+                    // in the history of clients connecting later.
+                    history.lock().await.push(event.clone()).await;
                     clients_subscriptions
-                        .notify_with_exclude(ClientSubMsg::Privmsg(msg), &[subscription_id])
+                        .notify_with_exclude(ClientSubMsg::Privmsg(event), &[subscription_id])
                         .await;
+                }
 
-                    if !seen.push(&event.hash()).await {
+                NotifierMsg::DirectMsg { profile, contact, msg } => {
+                    let Some(dm) = dms.lock().await.get(&profile).cloned() else {
+                        warn!("[IRC SERVER] Direct msg from unknown profile {:?}", profile);
                         continue
+                    };
+
+                    let (msgs, local) = dm.lock().await.seal(&contact, msg);
+                    for msg in msgs {
+                        Self::broadcast_msg(&p2p, &model, &seen, msg).await;
                     }
 
-                    p2p.broadcast(&event).await;
+                    // Other clients get the msg sealed the way they expect direct msgs
+                    if let Some(local) = local {
+                        let event = Event {
+                            previous_event_hash: model.lock().await.get_head_hash(),
+                            action: local,
+                            timestamp: Timestamp::current_time(),
+                        };
+                        history.lock().await.push(event.clone()).await;
+                        clients_subscriptions
+                            .notify_with_exclude(ClientSubMsg::Privmsg(event), &[subscription_id])
+                            .await;
+                    }
                 }
 
                 NotifierMsg::UpdateConfig => {
@@ -248,18 +492,20 @@ impl IrcServer {
         // Subscription for the new client
         let client_subscription = self.clients_subscriptions.clone().subscribe().await;
 
-        // new irc configuration
-        let irc_config = IrcConfig::new(&self.settings)?;
+        // The client starts with the default profile, until its PASS
+        // picks another one
+        let profile = self.profiles.load(None)?;
 
         // New irc client
         let mut client = IrcClient::new(
             writer,
             reader,
             peer_addr,
-            irc_config,
+            profile,
             notifier,
             client_subscription,
-            self.missed_events.clone(),
+            self.history.clone(),
+            self.profiles.clone(),
         );
 
         // Start listening and detach
@@ -0,0 +1,490 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use darkfi_sdk::crypto::PublicKey;
+use darkfi_serial::{deserialize, serialize};
+use log::{debug, warn};
+
+use darkfi::{event_graph::model::Event, Error, Result};
+
+use crate::{
+    crypto::{encrypt, try_decrypt_bytes},
+    privmsg::{fingerprint, is_fingerprint},
+    PrivMsgEvent,
+};
+
+use super::{super::IrcConfig, ConfServ};
+
+/// Target of the events carrying a new channel secret to a contact
+pub const ROTATION_TARGET: &str = "%";
+
+/// A moderation action, sent to the channel as a signed message by one of
+/// its operators. Clients apply the ones signed by the operators they
+/// trust, in the order they receive them, and show them as actions of the
+/// operator. Only key fingerprints can be muted, as nicks are free to
+/// change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Moderation {
+    /// Hide the messages signed by a key fingerprint
+    Mute(String),
+    /// Show the messages of a muted key fingerprint again
+    Unmute(String),
+}
+
+impl Moderation {
+    /// Apply the action to the muted fingerprints of a channel
+    pub fn apply(&self, muted: &mut HashSet<String>) {
+        match self {
+            Self::Mute(target) => muted.insert(target.clone()),
+            Self::Unmute(target) => muted.remove(target),
+        };
+    }
+
+    /// Text shown to the clients in place of the action
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Mute(target) => format!("\x01ACTION muted {}\x01", target),
+            Self::Unmute(target) => format!("\x01ACTION unmuted {}\x01", target),
+        }
+    }
+}
+
+impl fmt::Display for Moderation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mute(target) => write!(f, "\x01MODERATE MUTE {}\x01", target),
+            Self::Unmute(target) => write!(f, "\x01MODERATE UNMUTE {}\x01", target),
+        }
+    }
+}
+
+impl FromStr for Moderation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(s) = s.strip_prefix("\x01MODERATE ").and_then(|x| x.strip_suffix('\x01')) else {
+            return Err(Error::ParseFailed("Not a moderation message"))
+        };
+
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["MUTE", target] if is_fingerprint(target) => Ok(Self::Mute(target.to_string())),
+            ["UNMUTE", target] if is_fingerprint(target) => Ok(Self::Unmute(target.to_string())),
+            _ => Err(Error::ParseFailed("Invalid moderation message")),
+        }
+    }
+}
+
+/// Check if a message sender is in a list of nicks and key fingerprints
+pub fn is_listed<'a>(
+    list: impl IntoIterator<Item = &'a String>,
+    nick: &str,
+    sender: Option<&PublicKey>,
+) -> bool {
+    let sender = sender.map(fingerprint);
+    list.into_iter().any(|x| x.eq_ignore_ascii_case(nick) || Some(x) == sender.as_ref())
+}
+
+/// A new channel secret, sent by one of the channel operators to each
+/// remaining member
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rotation {
+    /// Channel the secret is for
+    pub channel: String,
+    /// Hash of the secret it replaces, so it only applies once
+    pub previous: String,
+    /// The new secret
+    pub secret: String,
+}
+
+/// Open a secret rotation event sent to one of the contacts in the config.
+/// Returns the rotation if it is signed by one of the channel operators,
+/// and delivered by the contact the operator sent it from.
+pub fn open_rotation(config: &IrcConfig, event: &Event<PrivMsgEvent>) -> Option<Rotation> {
+    let msg = &event.action;
+    if msg.target != ROTATION_TARGET {
+        return None
+    }
+
+    for (name, contact) in &config.contacts {
+        let Some(salt_box) = &contact.salt_box else { continue };
+        let Some(bytes) = try_decrypt_bytes(salt_box, &msg.msg) else { continue };
+        let Ok(rotation) = deserialize::<PrivMsgEvent>(&bytes) else { continue };
+
+        let Some(channel) = config.channels.get(&rotation.target) else {
+            debug!("[CHANSERV] Rotation from {} of unknown channel {}", name, rotation.target);
+            return None
+        };
+
+        let Some(sender) = rotation.sender(event.timestamp) else { return None };
+        if !channel.operators.contains(&sender) {
+            warn!(
+                "[CHANSERV] Rotation of {} from {} not signed by an operator",
+                rotation.target, name
+            );
+            return None
+        }
+
+        // The signed payload names the contact key of the operator, so a
+        // rotation can't be forwarded by the members who received it
+        let parts: Vec<&str> = rotation.msg.split(' ').collect();
+        let [previous, from, secret] = parts[..] else {
+            warn!("[CHANSERV] Malformed rotation of {} from {}", rotation.target, name);
+            return None
+        };
+
+        let contact_key = contact.public.as_ref().map(|x| bs58::encode(x.as_bytes()).into_string());
+        if contact_key.as_deref() != Some(from) {
+            // Our own rotations open with the contacts we sent them to
+            debug!("[CHANSERV] Rotation of {} not sent by {}", rotation.target, name);
+            return None
+        }
+
+        return Some(Rotation {
+            channel: rotation.target,
+            previous: previous.to_string(),
+            secret: secret.to_string(),
+        })
+    }
+
+    None
+}
+
+/// Outcome of a ChanServ command
+#[derive(Default)]
+pub struct ChanServReply {
+    /// Lines to send back to the client
+    pub lines: Vec<String>,
+    /// Moderation the client should sign and send to a channel
+    pub moderation: Option<(String, Moderation)>,
+    /// Events carrying a new channel secret to publish as they are
+    pub rotations: Vec<PrivMsgEvent>,
+}
+
+impl ChanServReply {
+    fn new(lines: Vec<String>) -> Self {
+        Self { lines, ..Default::default() }
+    }
+}
+
+/// ChanServ lets the operators of a channel moderate it and remove its
+/// members. Operators are the NickServ keys listed in the `operators` of
+/// a channel, so the client has to `IDENTIFY` with one of them first.
+///
+/// Removing members is done by rotating the channel secret: a new one is
+/// generated and sent to each remaining member, encrypted to their contact
+/// key. Members trusting the operator replace the secret on their own.
+#[derive(Clone)]
+pub struct ChanServ {
+    confserv: ConfServ,
+}
+
+impl ChanServ {
+    pub fn new(confserv: ConfServ) -> Self {
+        Self { confserv }
+    }
+
+    fn usage() -> Vec<String> {
+        let r = vec![
+            "***** chanserv help *****",
+            "",
+            "chanserv lets channel operators moderate their channels. You",
+            "must be identified with NickServ using an operator key.",
+            "",
+            "The following commands are available:",
+            "",
+            "    MUTE <#channel> <fingerprint>           Mute a key",
+            "    UNMUTE <#channel> <fingerprint>         Unmute a key",
+            "    ROTATE <#channel> <contact>...          Send a new secret to the",
+            "                                            given contacts only",
+            "",
+            "***** end of help *****",
+        ];
+
+        r.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn reply(line: &str) -> ChanServReply {
+        ChanServReply::new(vec![line.to_string()])
+    }
+
+    /// Parse an incoming chanserv message from a client with the given config
+    pub async fn act(&self, msg: &str, config: &IrcConfig) -> ChanServReply {
+        let parts: Vec<&str> = msg.split_whitespace().collect();
+        if parts.is_empty() {
+            return ChanServReply::new(Self::usage())
+        }
+
+        let command = parts[0].to_uppercase();
+        let args = &parts[1..];
+
+        let result = match (command.as_str(), args) {
+            ("MUTE", [channel, target]) => {
+                self.moderate(config, channel, Moderation::Mute(target.to_string()))
+            }
+
+            ("UNMUTE", [channel, target]) => {
+                self.moderate(config, channel, Moderation::Unmute(target.to_string()))
+            }
+
+            ("ROTATE", [channel, contacts @ ..]) if !contacts.is_empty() => {
+                self.rotate(config, channel, contacts).await
+            }
+
+            ("HELP", _) => Ok(ChanServReply::new(Self::usage())),
+
+            ("MUTE", _) => Ok(Self::reply("Usage: MUTE <#channel> <fingerprint>")),
+            ("UNMUTE", _) => Ok(Self::reply("Usage: UNMUTE <#channel> <fingerprint>")),
+            ("ROTATE", _) => Ok(Self::reply("Usage: ROTATE <#channel> <contact>...")),
+
+            (c, _) => Ok(ChanServReply::new(vec![
+                format!("Invalid command {}", c),
+                "Type HELP to get help".to_string(),
+            ])),
+        };
+
+        result.unwrap_or_else(|e| ChanServReply::new(vec![e.to_string()]))
+    }
+
+    fn moderate(
+        &self,
+        config: &IrcConfig,
+        channel: &str,
+        moderation: Moderation,
+    ) -> Result<ChanServReply> {
+        Self::check_operator(config, channel)?;
+
+        let (Moderation::Mute(target) | Moderation::Unmute(target)) = &moderation;
+        if !is_fingerprint(target) {
+            return Err(Error::Custom(format!("Invalid fingerprint {}", target)))
+        }
+
+        let line = match &moderation {
+            Moderation::Mute(target) => format!("Muted {} in {}", target, channel),
+            Moderation::Unmute(target) => format!("Unmuted {} in {}", target, channel),
+        };
+
+        Ok(ChanServReply {
+            lines: vec![line],
+            moderation: Some((channel.to_string(), moderation)),
+            ..Default::default()
+        })
+    }
+
+    /// Replace the secret of a channel, and seal the new one for each of
+    /// the given contacts
+    async fn rotate(
+        &self,
+        config: &IrcConfig,
+        channel: &str,
+        contacts: &[&str],
+    ) -> Result<ChanServReply> {
+        Self::check_operator(config, channel)?;
+
+        if config.channels[channel].salt_box.is_none() {
+            return Err(Error::Custom(format!("Channel {} has no secret", channel)))
+        }
+
+        let mut salt_boxes = vec![];
+        for contact in contacts {
+            match config.contacts.get(*contact).and_then(|x| x.salt_box.as_ref()) {
+                Some(salt_box) => salt_boxes.push(salt_box),
+                None => return Err(Error::Custom(format!("Unknown contact {}", contact))),
+            }
+        }
+
+        let from = self.confserv.public_key().await?;
+        let (previous, secret) = self.confserv.rotate_channel(channel).await?;
+
+        // Checked above
+        let (nick, key) = config.identity.as_ref().unwrap();
+        let mut rotation = PrivMsgEvent {
+            nick: nick.clone(),
+            msg: format!("{} {} {}", previous, from, secret),
+            target: channel.to_string(),
+            signature: String::new(),
+            relayed: false,
+        };
+        rotation.sign(key);
+        let rotation = serialize(&rotation);
+
+        let rotations = salt_boxes
+            .into_iter()
+            .map(|salt_box| PrivMsgEvent {
+                nick: String::new(),
+                msg: encrypt(salt_box, &rotation),
+                target: ROTATION_TARGET.to_string(),
+                signature: String::new(),
+                relayed: false,
+            })
+            .collect();
+
+        Ok(ChanServReply {
+            lines: vec![format!("Sent a new secret of {} to {}", channel, contacts.join(", "))],
+            rotations,
+            ..Default::default()
+        })
+    }
+
+    /// Check the client is identified with an operator key of the channel
+    fn check_operator(config: &IrcConfig, channel: &str) -> Result<()> {
+        let Some(info) = config.channels.get(channel) else {
+            return Err(Error::Custom(format!("Unknown channel {}", channel)))
+        };
+
+        let Some((_, key)) = &config.identity else {
+            return Err(Error::Custom("Identify with NickServ first".to_string()))
+        };
+
+        if !info.operators.contains(&PublicKey::from_secret(*key)) {
+            return Err(Error::Custom(format!("You are not an operator of {}", channel)))
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use darkfi::{system::Subscriber, util::time::Timestamp};
+    use darkfi_sdk::crypto::{Keypair, SecretKey};
+    use rand::rngs::OsRng;
+    use structopt::StructOpt;
+
+    use crate::settings::Args;
+
+    const SECRET: &str = "GvH4kno3kUu6dqPrZ8zjMhqxTUDZ2ev16EdprZiZJgj1";
+
+    /// ConfServ and settings of a member of #dev trusting the given operators
+    fn member(name: &str, operators: &[PublicKey]) -> (ConfServ, Args) {
+        let operators: Vec<String> = operators.iter().map(|x| format!("\"{}\"", x)).collect();
+        let contents = format!(
+            "[channel.\"#dev\"]\nsecret = \"{}\"\noperators = [{}]\n",
+            SECRET,
+            operators.join(", ")
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "darkirc_chanserv_{}_{}.toml",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        let settings = Args::from_iter(["darkirc", "--config", path.to_str().unwrap()]);
+        (ConfServ::new(settings.clone(), Subscriber::new()), settings)
+    }
+
+    /// Add two members to the contacts of each other
+    async fn befriend(a: (&str, &ConfServ), b: (&str, &ConfServ)) {
+        a.1.add_contact(b.0, &b.1.public_key().await.unwrap()).await.unwrap();
+        b.1.add_contact(a.0, &a.1.public_key().await.unwrap()).await.unwrap();
+    }
+
+    fn event(action: PrivMsgEvent) -> Event<PrivMsgEvent> {
+        Event {
+            previous_event_hash: blake3::hash(b""),
+            action,
+            timestamp: Timestamp::current_time(),
+        }
+    }
+
+    #[test]
+    fn test_moderation() {
+        let mute = Moderation::Mute("5hmnVQmUeXZc".to_string());
+        assert_eq!(mute.to_string().parse::<Moderation>().unwrap(), mute);
+        assert_eq!(
+            "\x01MODERATE UNMUTE 5hmnVQmUeXZc\x01".parse::<Moderation>().unwrap(),
+            Moderation::Unmute("5hmnVQmUeXZc".to_string())
+        );
+        assert!("MODERATE MUTE 5hmnVQmUeXZc".parse::<Moderation>().is_err());
+        assert!("\x01MODERATE BAN 5hmnVQmUeXZc\x01".parse::<Moderation>().is_err());
+        assert!("\x01MODERATE MUTE\x01".parse::<Moderation>().is_err());
+
+        // Nicks can't be muted
+        assert!("\x01MODERATE MUTE spammer\x01".parse::<Moderation>().is_err());
+        assert!("\x01MODERATE MUTE 0OIl0OIl0OIl\x01".parse::<Moderation>().is_err());
+
+        // Muting a key hides its messages whatever the nick
+        let public = PublicKey::from_secret(SecretKey::random(&mut OsRng));
+        let mut muted = HashSet::new();
+        Moderation::Mute(fingerprint(&public)).apply(&mut muted);
+        assert!(is_listed(&muted, "newnick", Some(&public)));
+        Moderation::Unmute(fingerprint(&public)).apply(&mut muted);
+        assert!(!is_listed(&muted, "newnick", Some(&public)));
+    }
+
+    #[test]
+    fn test_open_rotation() {
+        smol::block_on(async {
+            let operator = Keypair::random(&mut OsRng);
+            let (alice, alice_settings) = member("alice", &[operator.public]);
+            let (bob, bob_settings) = member("bob", &[operator.public]);
+            let (carol, carol_settings) = member("carol", &[operator.public]);
+            befriend(("alice", &alice), ("bob", &bob)).await;
+            befriend(("bob", &bob), ("carol", &carol)).await;
+
+            // The operator sends two new secrets in a row to bob
+            let mut config = IrcConfig::new(&alice_settings, None).unwrap();
+            config.identity = Some(("alice".to_string(), operator.secret));
+            let chanserv = ChanServ::new(alice.clone());
+            let first = chanserv.act("ROTATE #dev bob", &config).await;
+            assert_eq!(first.rotations.len(), 1, "{:?}", first.lines);
+            let first = event(first.rotations[0].clone());
+            let second = event(chanserv.act("ROTATE #dev bob", &config).await.rotations[0].clone());
+
+            let bob_config = IrcConfig::new(&bob_settings, None).unwrap();
+            let rotation = open_rotation(&bob_config, &first).unwrap();
+            assert_eq!(rotation.channel, "#dev");
+            assert_ne!(rotation.secret, SECRET);
+
+            // Only the contact it was sealed for can open it
+            let carol_config = IrcConfig::new(&carol_settings, None).unwrap();
+            assert!(open_rotation(&carol_config, &first).is_none());
+
+            // Forwarded by bob, it still names the contact key of alice
+            let bob_alice = bob_config.contacts["alice"].salt_box.as_ref().unwrap();
+            let bob_carol = bob_config.contacts["carol"].salt_box.as_ref().unwrap();
+            let payload = try_decrypt_bytes(bob_alice, &first.action.msg).unwrap();
+            let mut forwarded = first.action.clone();
+            forwarded.msg = encrypt(bob_carol, &payload);
+            assert!(open_rotation(&carol_config, &event(forwarded)).is_none());
+
+            // Rotations not signed by an operator are dropped
+            let mut untrusting = bob_config.clone();
+            untrusting.channels.get_mut("#dev").unwrap().operators.clear();
+            assert!(open_rotation(&untrusting, &first).is_none());
+
+            // Each rotation only replaces the secret it was made from
+            let later = open_rotation(&bob_config, &second).unwrap();
+            assert!(bob
+                .set_channel_secret("#dev", &later.secret, Some(&later.previous))
+                .await
+                .is_err());
+            bob.set_channel_secret("#dev", &rotation.secret, Some(&rotation.previous))
+                .await
+                .unwrap();
+            assert!(bob
+                .set_channel_secret("#dev", &rotation.secret, Some(&rotation.previous))
+                .await
+                .is_err());
+            bob.set_channel_secret("#dev", &later.secret, Some(&later.previous)).await.unwrap();
+        });
+    }
+}
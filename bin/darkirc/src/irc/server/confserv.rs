@@ -0,0 +1,633 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{path::PathBuf, str::FromStr};
+
+use async_std::sync::{Arc, Mutex};
+use darkfi_sdk::crypto::PublicKey;
+use log::info;
+use rand::rngs::OsRng;
+use toml_edit::{table, value, Array, Document, Item, Table};
+
+use darkfi::{system::SubscriberPtr, util::path::get_config_path, Error, Result};
+
+use crate::settings::{Args, CONFIG_FILE, MAXIMUM_LENGTH_OF_NICK_CHAN_CNT};
+
+use super::{
+    super::{ClientSubMsg, IrcConfig},
+    NICK_CHANSERV, NICK_CONFSERV, NICK_FILESERV, NICK_NICKSERV,
+};
+
+/// Prefix of the invite strings used to share a channel and its secret
+const INVITE_PREFIX: &str = "darkirc:";
+
+/// ConfServ manages the channels and contacts in the configuration file
+/// at runtime, so they don't need to be edited by hand. It is used both
+/// by the JSON-RPC interface and by irc clients through the `confserv`
+/// service nick.
+///
+/// Every change is written back to the configuration file, keeping its
+/// comments and formatting, and the new configuration is then sent to
+/// the connected clients the same way a SIGHUP would. With a profile,
+/// changes go to the `[profile."name"]` section instead.
+#[derive(Clone)]
+pub struct ConfServ {
+    settings: Args,
+    clients_subscriptions: SubscriberPtr<ClientSubMsg>,
+    profile: Option<String>,
+    /// Serializes the changes to the configuration file
+    lock: Arc<Mutex<()>>,
+}
+
+impl ConfServ {
+    pub fn new(settings: Args, clients_subscriptions: SubscriberPtr<ClientSubMsg>) -> Self {
+        Self { settings, clients_subscriptions, profile: None, lock: Arc::new(Mutex::new(())) }
+    }
+
+    /// Return a ConfServ managing the configuration of the given profile
+    pub fn with_profile(&self, profile: Option<&str>) -> Self {
+        Self { profile: profile.map(String::from), ..self.clone() }
+    }
+
+    fn usage() -> Vec<String> {
+        let r = vec![
+            "***** confserv help *****",
+            "",
+            "confserv manages the channels and contacts of this darkirc",
+            "and saves them to its configuration file.",
+            "",
+            "The following commands are available:",
+            "",
+            "    CREATE <#channel> [topic]     Create a channel with a new secret",
+            "    INVITE <#channel>             Show the invite of a channel",
+            "    JOIN <invite>                 Add a channel from an invite",
+            "    CONTACT <name> <pubkey>       Add a contact for direct messages",
+            "    PUBKEY                        Show the public key to give to contacts",
+            "    OPERATOR <#channel> <pubkey>  Trust a NickServ key to moderate a channel",
+            "    IGNORE <nick|fingerprint>     Hide the messages of a nick or key",
+            "    UNIGNORE <nick|fingerprint>   Show the messages of a nick or key again",
+            "",
+            "***** end of help *****",
+        ];
+
+        r.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Parse an incoming confserv message and return the lines to send
+    /// back to the client
+    pub async fn act(&self, msg: &str) -> Vec<String> {
+        let parts: Vec<&str> = msg.split_whitespace().collect();
+        if parts.is_empty() {
+            return Self::usage()
+        }
+
+        let command = parts[0].to_uppercase();
+        let args = &parts[1..];
+
+        let result = match (command.as_str(), args) {
+            ("CREATE", [name, topic @ ..]) => {
+                let topic = if topic.is_empty() { None } else { Some(topic.join(" ")) };
+                self.create_channel(name, topic).await.map(|invite| {
+                    vec![
+                        format!("Created channel {}. Share this invite to let others join:", name),
+                        invite,
+                    ]
+                })
+            }
+
+            ("INVITE", [name]) => self.export_invite(name).await.map(|invite| vec![invite]),
+
+            ("JOIN", [invite]) => {
+                self.join_channel(invite).await.map(|name| vec![format!("Added channel {}", name)])
+            }
+
+            ("CONTACT", [name, pubkey]) => self.add_contact(name, pubkey).await.map(|public| {
+                vec![format!("Added contact {}. Give them your public key:", name), public]
+            }),
+
+            ("PUBKEY", []) => self.public_key().await.map(|public| vec![public]),
+
+            ("OPERATOR", [name, pubkey]) => self
+                .add_operator(name, pubkey)
+                .await
+                .map(|_| vec![format!("Added operator {} to channel {}", pubkey, name)]),
+
+            ("IGNORE", [target]) => {
+                self.ignore(target).await.map(|_| vec![format!("Ignoring {}", target)])
+            }
+
+            ("UNIGNORE", [target]) => {
+                self.unignore(target).await.map(|_| vec![format!("No longer ignoring {}", target)])
+            }
+
+            ("HELP", _) => Ok(Self::usage()),
+
+            ("CREATE", _) => Ok(vec!["Usage: CREATE <#channel> [topic]".to_string()]),
+            ("INVITE", _) => Ok(vec!["Usage: INVITE <#channel>".to_string()]),
+            ("JOIN", _) => Ok(vec!["Usage: JOIN <invite>".to_string()]),
+            ("CONTACT", _) => Ok(vec!["Usage: CONTACT <name> <pubkey>".to_string()]),
+            ("PUBKEY", _) => Ok(vec!["Usage: PUBKEY".to_string()]),
+            ("OPERATOR", _) => Ok(vec!["Usage: OPERATOR <#channel> <pubkey>".to_string()]),
+            ("IGNORE", _) => Ok(vec!["Usage: IGNORE <nick|fingerprint>".to_string()]),
+            ("UNIGNORE", _) => Ok(vec!["Usage: UNIGNORE <nick|fingerprint>".to_string()]),
+
+            (c, _) => {
+                Ok(vec![format!("Invalid command {}", c), "Type HELP to get help".to_string()])
+            }
+        };
+
+        result.unwrap_or_else(|e| vec![e.to_string()])
+    }
+
+    /// Create a channel with a freshly generated secret, returning its invite
+    pub async fn create_channel(&self, name: &str, topic: Option<String>) -> Result<String> {
+        check_channel_name(name)?;
+        let secret = new_secret();
+
+        self.edit(|doc| {
+            let channels = section(self.root(doc)?, "channel")?;
+            if channels.contains_key(name) {
+                return Err(Error::Custom(format!("Channel {} already exists", name)))
+            }
+
+            let channel = entry(channels, name)?;
+            channel["secret"] = value(&secret);
+            if let Some(topic) = topic {
+                channel["topic"] = value(topic);
+            }
+            Ok(())
+        })
+        .await?;
+
+        info!("[CONFSERV] Created channel {}", name);
+        Ok(invite(name, &secret))
+    }
+
+    /// Add the channel from an invite, returning its name. The secret of
+    /// an already configured channel is replaced.
+    pub async fn join_channel(&self, invite: &str) -> Result<String> {
+        let (name, secret) = invite
+            .strip_prefix(INVITE_PREFIX)
+            .and_then(|x| x.rsplit_once(':'))
+            .ok_or_else(|| Error::Custom(format!("Invalid invite {}", invite)))?;
+
+        check_channel_name(name)?;
+        check_key(secret)?;
+
+        self.edit(|doc| {
+            let channel = entry(section(self.root(doc)?, "channel")?, name)?;
+            channel["secret"] = value(secret);
+            Ok(())
+        })
+        .await?;
+
+        info!("[CONFSERV] Joined channel {}", name);
+        Ok(name.to_string())
+    }
+
+    /// Replace the secret of a configured channel with a freshly generated
+    /// one, returning the hash of the replaced secret and the new one
+    pub async fn rotate_channel(&self, name: &str) -> Result<(String, String)> {
+        let secret = new_secret();
+        let previous = self.set_channel_secret(name, &secret, None).await?;
+        Ok((previous, secret))
+    }
+
+    /// Replace the secret of a configured channel, returning the hash of
+    /// the replaced one. When `previous` is given, it has to be the hash
+    /// of the current secret, so each replacement only applies once.
+    pub async fn set_channel_secret(
+        &self,
+        name: &str,
+        secret: &str,
+        previous: Option<&str>,
+    ) -> Result<String> {
+        check_key(secret)?;
+
+        let replaced = self
+            .edit(|doc| {
+                let channel = channel(self.root(doc)?, name)?;
+                let Some(current) = channel.get("secret").and_then(|x| x.as_str()) else {
+                    return Err(Error::Custom(format!("Channel {} has no secret", name)))
+                };
+
+                let current = secret_hash(current);
+                if previous.map_or(false, |x| x != current) {
+                    return Err(Error::Custom(format!(
+                        "New secret of {} doesn't replace the current one",
+                        name
+                    )))
+                }
+
+                channel["secret"] = value(secret);
+                Ok(current)
+            })
+            .await?;
+
+        info!("[CONFSERV] Set a new secret for channel {}", name);
+        Ok(replaced)
+    }
+
+    /// Trust the given NickServ public key to moderate a configured channel
+    /// and rotate its secret
+    pub async fn add_operator(&self, name: &str, pubkey: &str) -> Result<()> {
+        if PublicKey::from_str(pubkey).is_err() {
+            return Err(Error::Custom(format!("Invalid key {}", pubkey)))
+        }
+
+        self.edit(|doc| {
+            let operators = array(channel(self.root(doc)?, name)?, "operators")?;
+            if !operators.iter().any(|x| x.as_str() == Some(pubkey)) {
+                operators.push(pubkey);
+            }
+            Ok(())
+        })
+        .await?;
+
+        info!("[CONFSERV] Added operator {} to channel {}", pubkey, name);
+        Ok(())
+    }
+
+    /// Hide the messages of a nick or key fingerprint
+    pub async fn ignore(&self, target: &str) -> Result<()> {
+        check_ignore_target(target)?;
+
+        self.edit(|doc| {
+            let ignored = array(self.root(doc)?, "ignore")?;
+            if !ignored.iter().any(|x| x.as_str() == Some(target)) {
+                ignored.push(target);
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Show the messages of an ignored nick or key fingerprint again
+    pub async fn unignore(&self, target: &str) -> Result<()> {
+        self.edit(|doc| {
+            let ignored = array(self.root(doc)?, "ignore")?;
+            let len = ignored.len();
+            ignored.retain(|x| x.as_str() != Some(target));
+            if ignored.len() == len {
+                return Err(Error::Custom(format!("{} is not ignored", target)))
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Return the invite of a configured channel
+    pub async fn export_invite(&self, name: &str) -> Result<String> {
+        let _guard = self.lock.lock().await;
+        let doc = self.load()?;
+
+        let root = match &self.profile {
+            Some(profile) => doc.get("profile").and_then(|x| x.get(profile.as_str())),
+            None => Some(doc.as_item()),
+        };
+
+        let Some(secret) = root
+            .and_then(|x| x.get("channel"))
+            .and_then(|x| x.get(name))
+            .and_then(|x| x.get("secret"))
+            .and_then(|x| x.as_str())
+        else {
+            return Err(Error::Custom(format!("Channel {} has no secret", name)))
+        };
+
+        Ok(invite(name, secret))
+    }
+
+    /// Add a contact for direct messages, returning our public key to
+    /// give to the contact. A private key is generated if we don't have
+    /// one yet.
+    pub async fn add_contact(&self, name: &str, pubkey: &str) -> Result<String> {
+        check_contact_name(name)?;
+        check_key(pubkey)?;
+
+        let public = self
+            .edit(|doc| {
+                let root = self.root(doc)?;
+                let public = private_key(root)?;
+                let contact = entry(section(root, "contact")?, name)?;
+                contact["contact_pubkey"] = value(pubkey);
+                Ok(public)
+            })
+            .await?;
+
+        info!("[CONFSERV] Added contact {}", name);
+        Ok(public)
+    }
+
+    /// Return our public key to give to contacts, generating a private
+    /// key if we don't have one yet.
+    pub async fn public_key(&self) -> Result<String> {
+        self.edit(|doc| private_key(self.root(doc)?)).await
+    }
+
+    /// Table holding the channels and contacts we manage
+    fn root<'a>(&self, doc: &'a mut Document) -> Result<&'a mut Table> {
+        match &self.profile {
+            Some(profile) => entry(section(doc.as_table_mut(), "profile")?, profile),
+            None => Ok(doc.as_table_mut()),
+        }
+    }
+
+    fn config_path(&self) -> Result<PathBuf> {
+        get_config_path(self.settings.config.clone(), CONFIG_FILE)
+    }
+
+    fn load(&self) -> Result<Document> {
+        let contents = std::fs::read_to_string(self.config_path()?)?;
+        contents.parse().map_err(|_| Error::ParseFailed("Failed parsing the configuration file"))
+    }
+
+    /// Apply a change to the configuration file, save it and send the new
+    /// configuration to the clients
+    async fn edit<T>(&self, change: impl FnOnce(&mut Document) -> Result<T>) -> Result<T> {
+        let _guard = self.lock.lock().await;
+
+        let mut doc = self.load()?;
+        let contents = doc.to_string();
+        let ret = change(&mut doc)?;
+
+        if doc.to_string() != contents {
+            std::fs::write(self.config_path()?, doc.to_string())?;
+            let config = IrcConfig::new(&self.settings, self.profile.as_deref())?;
+            self.clients_subscriptions.notify(ClientSubMsg::Config(config)).await;
+        }
+
+        Ok(ret)
+    }
+}
+
+fn new_secret() -> String {
+    bs58::encode(crypto_box::SecretKey::generate(&mut OsRng).as_bytes()).into_string()
+}
+
+/// Hash identifying a channel secret without revealing it
+pub fn secret_hash(secret: &str) -> String {
+    blake3::hash(secret.as_bytes()).to_hex().to_string()
+}
+
+fn invite(name: &str, secret: &str) -> String {
+    format!("{}{}:{}", INVITE_PREFIX, name, secret)
+}
+
+fn check_channel_name(name: &str) -> Result<()> {
+    if !name.starts_with('#') ||
+        name.len() < 2 ||
+        name.len() > MAXIMUM_LENGTH_OF_NICK_CHAN_CNT ||
+        name.contains(|c: char| c.is_whitespace() || c == ',')
+    {
+        return Err(Error::Custom(format!("Invalid channel name {}", name)))
+    }
+
+    Ok(())
+}
+
+fn check_contact_name(name: &str) -> Result<()> {
+    let lowercase = name.to_lowercase();
+    if name.is_empty() ||
+        name.starts_with('#') ||
+        name.len() > MAXIMUM_LENGTH_OF_NICK_CHAN_CNT ||
+        name.contains(|c: char| c.is_whitespace() || c == ',') ||
+        lowercase == NICK_NICKSERV ||
+        lowercase == NICK_CONFSERV ||
+        lowercase == NICK_FILESERV ||
+        lowercase == NICK_CHANSERV
+    {
+        return Err(Error::Custom(format!("Invalid contact name {}", name)))
+    }
+
+    Ok(())
+}
+
+fn check_ignore_target(target: &str) -> Result<()> {
+    if target.is_empty() ||
+        target.starts_with('#') ||
+        target.len() > MAXIMUM_LENGTH_OF_NICK_CHAN_CNT ||
+        target.contains(char::is_whitespace)
+    {
+        return Err(Error::Custom(format!("Invalid nick or fingerprint {}", target)))
+    }
+
+    Ok(())
+}
+
+/// Check a key is a base58 encoded 32 byte string
+fn check_key(key: &str) -> Result<()> {
+    match bs58::decode(key).into_vec() {
+        Ok(v) if v.len() == 32 => Ok(()),
+        _ => Err(Error::Custom(format!("Invalid key {}", key))),
+    }
+}
+
+/// Get a table like `channel` holding `[channel."#foo"]` tables, creating
+/// it if needed
+fn section<'a>(root: &'a mut Table, name: &str) -> Result<&'a mut Table> {
+    let item = root.entry(name).or_insert_with(|| {
+        let mut t = Table::new();
+        t.set_implicit(true);
+        Item::Table(t)
+    });
+
+    item.as_table_mut().ok_or(Error::ParseFailed("Configuration section is not a table"))
+}
+
+/// Get the table of an entry inside a section, creating it if needed
+fn entry<'a>(section: &'a mut Table, name: &str) -> Result<&'a mut Table> {
+    let item = section.entry(name).or_insert(table());
+    item.as_table_mut().ok_or(Error::ParseFailed("Configuration entry is not a table"))
+}
+
+/// Get the table of a configured channel
+fn channel<'a>(root: &'a mut Table, name: &str) -> Result<&'a mut Table> {
+    section(root, "channel")?
+        .get_mut(name)
+        .and_then(|x| x.as_table_mut())
+        .ok_or_else(|| Error::Custom(format!("Unknown channel {}", name)))
+}
+
+/// Get an array like `ignore` inside a table, creating it if needed
+fn array<'a>(table: &'a mut Table, name: &str) -> Result<&'a mut Array> {
+    let item = table.entry(name).or_insert(value(Array::new()));
+    item.as_array_mut().ok_or(Error::ParseFailed("Configuration entry is not an array"))
+}
+
+/// Return the public key matching the configured private key, generating
+/// a private key if there is none
+fn private_key(root: &mut Table) -> Result<String> {
+    let keys = section(root, "private_key")?;
+
+    let secret = match keys.iter().next() {
+        Some((key, _)) => {
+            let bytes: [u8; 32] = bs58::decode(key)
+                .into_vec()?
+                .try_into()
+                .map_err(|_| Error::ParseFailed("Configured private key is not 32 bytes"))?;
+            crypto_box::SecretKey::from(bytes)
+        }
+        None => {
+            let secret = crypto_box::SecretKey::generate(&mut OsRng);
+            entry(keys, &bs58::encode(secret.as_bytes()).into_string())?;
+            info!("[CONFSERV] Generated a new private key");
+            secret
+        }
+    };
+
+    Ok(bs58::encode(secret.public_key().as_bytes()).into_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_box::SalsaBox;
+    use darkfi::system::Subscriber;
+    use darkfi_sdk::crypto::SecretKey;
+    use structopt::StructOpt;
+
+    use super::*;
+    use crate::{
+        crypto::{encrypt, try_decrypt_bytes},
+        settings::{parse_configured_channels, parse_configured_contacts},
+    };
+
+    const CONFIG: &str = r##"## darkirc configuration file
+## Comments and settings ConfServ doesn't manage are kept as they are
+
+autojoin = ["#dev"] # joined on start
+
+# The dev channel
+[channel."#dev"]
+secret = "GvH4kno3kUu6dqPrZ8zjMhqxTUDZ2ev16EdprZiZJgj1"
+topic = "DarkFi Development Channel"
+
+[profile."work"]
+password = "work-password"
+"##;
+
+    /// ConfServ managing a fresh configuration file with the given contents
+    fn confserv(name: &str, contents: &str) -> (ConfServ, PathBuf) {
+        let path =
+            std::env::temp_dir().join(format!("darkirc_{}_{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let settings = Args::from_iter(["darkirc", "--config", path.to_str().unwrap()]);
+        (ConfServ::new(settings, Subscriber::new()), path)
+    }
+
+    #[test]
+    fn test_invite_round_trip() {
+        smol::block_on(async {
+            let (alice, _) = confserv("invite_alice", CONFIG);
+            let (bob, bob_path) = confserv("invite_bob", "");
+
+            let invite = alice.create_channel("#foo", Some("Foo stuff".to_string())).await.unwrap();
+            assert!(invite.starts_with("darkirc:#foo:"));
+            assert_eq!(alice.export_invite("#foo").await.unwrap(), invite);
+            assert!(alice.create_channel("#foo", None).await.is_err());
+
+            // Joining from the invite gives the same channel secret
+            assert_eq!(bob.join_channel(&invite).await.unwrap(), "#foo");
+            assert_eq!(bob.export_invite("#foo").await.unwrap(), invite);
+            let channels =
+                parse_configured_channels(&std::fs::read_to_string(&bob_path).unwrap(), None)
+                    .unwrap();
+            assert!(channels["#foo"].salt_box.is_some());
+
+            // Malformed invites are refused
+            let secret = invite.rsplit_once(':').unwrap().1;
+            for invalid in [
+                format!("#foo:{}", secret),
+                format!("darkirc:foo:{}", secret),
+                "darkirc:#foo".to_string(),
+                "darkirc:#foo:foo".to_string(),
+            ] {
+                assert!(bob.join_channel(&invalid).await.is_err());
+            }
+
+            assert!(bob.export_invite("#bar").await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_add_contact() {
+        smol::block_on(async {
+            let (confserv, path) = confserv("contact", CONFIG);
+
+            let bob = crypto_box::SecretKey::generate(&mut OsRng);
+            let bob_public = bs58::encode(bob.public_key().as_bytes()).into_string();
+
+            // Our private key is generated on first use only
+            let public = confserv.add_contact("bob", &bob_public).await.unwrap();
+            assert_eq!(confserv.public_key().await.unwrap(), public);
+            assert_eq!(confserv.add_contact("carol", &bob_public).await.unwrap(), public);
+
+            // Both ends derive the same box from each other's key
+            let contacts =
+                parse_configured_contacts(&std::fs::read_to_string(&path).unwrap(), None).unwrap();
+            let ours = contacts["bob"].salt_box.clone().unwrap();
+            let public: [u8; 32] = bs58::decode(&public).into_vec().unwrap().try_into().unwrap();
+            let theirs = SalsaBox::new(&crypto_box::PublicKey::from(public), &bob);
+            assert_eq!(try_decrypt_bytes(&theirs, &encrypt(&ours, b"hi bob")).unwrap(), b"hi bob");
+
+            // Invalid names and keys are refused
+            assert!(confserv.add_contact("nickserv", &bob_public).await.is_err());
+            assert!(confserv.add_contact("#bob", &bob_public).await.is_err());
+            assert!(confserv.add_contact("bob", "foo").await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_keeps_unrelated_config() {
+        smol::block_on(async {
+            let (confserv, path) = confserv("keep", CONFIG);
+            let operator = PublicKey::from_secret(SecretKey::random(&mut OsRng)).to_string();
+
+            confserv.create_channel("#foo", None).await.unwrap();
+            confserv.rotate_channel("#dev").await.unwrap();
+            confserv.add_operator("#dev", &operator).await.unwrap();
+            confserv.ignore("mallory").await.unwrap();
+
+            // Every line is kept, except for the replaced secret
+            let contents = std::fs::read_to_string(&path).unwrap();
+            for line in CONFIG.lines().filter(|x| !x.starts_with("secret")) {
+                assert!(contents.contains(line), "{} is missing from:\n{}", line, contents);
+            }
+            assert!(!contents.contains("GvH4kno3kUu6dqPrZ8zjMhqxTUDZ2ev16EdprZiZJgj1"));
+
+            // Nothing changes when there's nothing to change
+            confserv.ignore("mallory").await.unwrap();
+            confserv.add_operator("#dev", &operator).await.unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+            confserv.unignore("mallory").await.unwrap();
+            assert!(confserv.unignore("mallory").await.is_err());
+
+            // Profiles only change their own section
+            let work = confserv.with_profile(Some("work"));
+            work.create_channel("#bar", None).await.unwrap();
+            assert!(confserv.export_invite("#bar").await.is_err());
+            assert!(work.export_invite("#dev").await.is_err());
+
+            let contents = std::fs::read_to_string(&path).unwrap();
+            let channels = parse_configured_channels(&contents, Some("work")).unwrap();
+            assert!(channels.contains_key("#bar"));
+            assert!(!channels.contains_key("#dev"));
+            assert!(contents.contains("password = \"work-password\""));
+        });
+    }
+}
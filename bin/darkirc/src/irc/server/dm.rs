@@ -0,0 +1,616 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+
+use async_std::sync::{Arc, Mutex};
+use crypto_box::SalsaBox;
+use darkfi_sdk::crypto::{Keypair, PublicKey};
+use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
+use log::{debug, info, warn};
+use rand::rngs::OsRng;
+
+use darkfi::{util::time::Timestamp, Result};
+
+use crate::{
+    crypto::{encrypt, encrypt_privmsg, try_decrypt_bytes},
+    ratchet::{Ratchet, RatchetMessage, Tag},
+    settings::ContactInfo,
+    PrivMsgEvent,
+};
+
+use super::profile_tree;
+
+/// Target of handshake events. Their payload is encrypted with the
+/// static NaCl box of the contact.
+pub const DM_HANDSHAKE_TARGET: &str = "?";
+
+/// Prefix of the target of ratchet messages, followed by their bs58 tag
+pub const DM_TAG_PREFIX: &str = "~";
+
+/// sled tree holding the DM sessions, `contact => Session`
+const SLED_DM_SESSIONS_TREE: &[u8] = b"_dm_sessions";
+/// sled tree holding the handshake keys we already answered, along with
+/// the time after which their handshake expires
+const SLED_DM_HANDSHAKES_TREE: &[u8] = b"_dm_handshakes";
+
+/// Seconds a handshake can be answered for after it was made. Older ones
+/// are refused as replays, so the answered ones are only kept that long.
+const HANDSHAKE_EXPIRY: u64 = 60 * 60 * 24;
+
+pub type DirectMessagesPtr = Arc<Mutex<DirectMessages>>;
+
+/// Handshake message. The initiator sends its handshake public key in
+/// `init`, and the responder answers with the same `init` along with
+/// its first ratchet public key in `reply`.
+#[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
+struct Handshake {
+    init: PublicKey,
+    reply: Option<PublicKey>,
+    /// Time the handshake was made at
+    timestamp: u64,
+}
+
+/// DM session state with a contact
+#[derive(SerialEncodable, SerialDecodable, Clone, Debug, Default)]
+struct Session {
+    /// Our handshake keypair, while waiting for the contact to reply
+    pending: Option<Keypair>,
+    /// The reply we sent to the contact's handshake, kept until their
+    /// first message gets through so it can be sent again
+    reply: Option<Handshake>,
+    /// Established ratchet
+    ratchet: Option<Ratchet>,
+}
+
+/// Outcome of processing an event from the event graph
+pub enum DmEvent {
+    /// Not a direct message, process it as usual
+    Pass,
+    /// Nothing to show to the clients
+    Drop,
+    /// Decrypted direct message, sealed with the static box of the
+    /// contact the same way clients expect direct messages
+    Message(PrivMsgEvent),
+}
+
+/// Forward-secret direct messages with the configured contacts.
+///
+/// Sessions are established with a handshake encrypted with the static
+/// box of the contact, and messages are then encrypted with a double
+/// ratchet. Until a session can be used, messages fall back to the
+/// static box. Ratchet messages are addressed with tags, so we only
+/// need a lookup to know which ones are ours.
+pub struct DirectMessages {
+    contacts: HashMap<String, ContactInfo>,
+    sessions: HashMap<String, Session>,
+    /// Tags of the messages we can receive, mapped to their contact
+    tags: HashMap<Tag, String>,
+    sessions_tree: sled::Tree,
+    handshakes_tree: sled::Tree,
+}
+
+impl DirectMessages {
+    pub fn new(
+        db: &sled::Db,
+        contacts: HashMap<String, ContactInfo>,
+        profile: Option<&str>,
+    ) -> Result<DirectMessagesPtr> {
+        let sessions_tree = db.open_tree(profile_tree(SLED_DM_SESSIONS_TREE, profile))?;
+        let handshakes_tree = db.open_tree(profile_tree(SLED_DM_HANDSHAKES_TREE, profile))?;
+
+        let mut sessions = HashMap::new();
+        for item in sessions_tree.iter() {
+            let (contact, session) = item?;
+            let contact = String::from_utf8_lossy(&contact).to_string();
+            let session: Session = deserialize(&session)?;
+            sessions.insert(contact, session);
+        }
+
+        let mut dm =
+            Self { contacts, sessions, tags: HashMap::new(), sessions_tree, handshakes_tree };
+
+        let names: Vec<String> = dm.sessions.keys().cloned().collect();
+        for contact in names {
+            dm.index_tags(&contact);
+        }
+
+        Ok(Arc::new(Mutex::new(dm)))
+    }
+
+    /// Replace the contacts after the configuration changed. Sessions
+    /// with removed contacts are kept, in case they get added back.
+    pub fn set_contacts(&mut self, contacts: HashMap<String, ContactInfo>) {
+        self.contacts = contacts;
+    }
+
+    fn salt_box(&self, contact: &str) -> Option<SalsaBox> {
+        self.contacts.get(contact).and_then(|x| x.salt_box.clone())
+    }
+
+    /// Refresh the tags we expect from a contact
+    fn index_tags(&mut self, contact: &str) {
+        self.tags.retain(|_, v| v != contact);
+
+        let Some(ratchet) = self.sessions.get(contact).and_then(|x| x.ratchet.as_ref()) else {
+            return
+        };
+
+        for tag in ratchet.expected_tags() {
+            self.tags.insert(tag, contact.to_string());
+        }
+    }
+
+    /// Persist the session with a contact after it changed
+    fn save(&mut self, contact: &str) {
+        self.index_tags(contact);
+
+        let Some(session) = self.sessions.get(contact) else { return };
+        if let Err(e) = self.sessions_tree.insert(contact.as_bytes(), serialize(session)) {
+            warn!("[DM] Failed saving session with {}: {}", contact, e);
+        }
+    }
+
+    /// Seal a plaintext direct message for a contact. Returns the events
+    /// to publish, along with a copy sealed with the static box of the
+    /// contact to show to the local clients.
+    pub fn seal(
+        &mut self,
+        contact: &str,
+        msg: PrivMsgEvent,
+    ) -> (Vec<PrivMsgEvent>, Option<PrivMsgEvent>) {
+        let Some(salt_box) = self.salt_box(contact) else { return (vec![], None) };
+
+        let mut local = msg.clone();
+        encrypt_privmsg(&salt_box, &mut local);
+
+        let session = self.sessions.entry(contact.to_string()).or_default();
+
+        if let Some(ratchet) = session.ratchet.as_mut() {
+            if let Some(message) = ratchet.encrypt(&serialize(&msg)) {
+                self.save(contact);
+                return (vec![ratchet_event(message)], Some(local))
+            }
+        }
+
+        // Without a usable session, fall back to the static box and
+        // (re)send our handshake until the contact replies.
+        let mut events = vec![];
+        if session.ratchet.is_none() {
+            let keypair = *session.pending.get_or_insert_with(|| Keypair::random(&mut OsRng));
+            let handshake = Handshake {
+                init: keypair.public,
+                reply: None,
+                timestamp: Timestamp::current_time().0,
+            };
+            events.push(handshake_event(&salt_box, &handshake));
+            self.save(contact);
+        }
+        events.push(local.clone());
+
+        (events, Some(local))
+    }
+
+    /// Process an event coming from the event graph. Returns what to do
+    /// with it, along with any events to publish in response.
+    pub fn open(&mut self, msg: &PrivMsgEvent) -> (DmEvent, Vec<PrivMsgEvent>) {
+        if msg.target == DM_HANDSHAKE_TARGET {
+            return (DmEvent::Drop, self.open_handshake(msg))
+        }
+
+        if let Some(tag) = msg.target.strip_prefix(DM_TAG_PREFIX) {
+            return self.open_ratchet(tag, msg)
+        }
+
+        (DmEvent::Pass, vec![])
+    }
+
+    fn open_handshake(&mut self, msg: &PrivMsgEvent) -> Vec<PrivMsgEvent> {
+        // Handshakes are rare, so here we do try all of our contacts
+        let found = self.contacts.iter().find_map(|(name, info)| {
+            let plaintext = try_decrypt_bytes(info.salt_box.as_ref()?, &msg.msg)?;
+            let handshake: Handshake = deserialize(&plaintext).ok()?;
+            Some((name.clone(), handshake))
+        });
+
+        let Some((contact, handshake)) = found else { return vec![] };
+
+        match handshake.reply {
+            None => self.on_handshake_init(&contact, handshake.init, handshake.timestamp),
+            Some(reply) => self.on_handshake_reply(&contact, handshake.init, reply),
+        }
+    }
+
+    fn on_handshake_init(
+        &mut self,
+        contact: &str,
+        init: PublicKey,
+        timestamp: u64,
+    ) -> Vec<PrivMsgEvent> {
+        let salt_box = self.salt_box(contact).unwrap();
+        let session = self.sessions.entry(contact.to_string()).or_default();
+
+        // The initiator didn't get our reply yet
+        if let Some(reply) = session.reply.as_ref().filter(|x| x.init == init) {
+            return vec![handshake_event(&salt_box, reply)]
+        }
+
+        // Handshakes too far from our clock could be replays we already
+        // forgot about
+        let now = Timestamp::current_time().0;
+        if timestamp.abs_diff(now) > HANDSHAKE_EXPIRY {
+            warn!("[DM] Ignoring expired handshake from {}", contact);
+            return vec![]
+        }
+
+        // Replayed handshake we already answered
+        let seen = self.handshakes_tree.contains_key(init.to_bytes()).unwrap_or(true);
+        if seen {
+            return vec![]
+        }
+
+        // When both sides start a handshake at the same time, the one
+        // with the lowest key wins. Our own handshake could also be
+        // reflected back to us.
+        if let Some(pending) = &session.pending {
+            if pending.public.to_bytes() <= init.to_bytes() {
+                return vec![]
+            }
+        }
+
+        info!("[DM] Answering handshake from {}", contact);
+        let keypair = Keypair::random(&mut OsRng);
+        let reply = Handshake { init, reply: Some(keypair.public), timestamp: now };
+
+        *session = Session {
+            pending: None,
+            reply: Some(reply.clone()),
+            ratchet: Some(Ratchet::init_responder(keypair, &init)),
+        };
+
+        // The handshake is remembered until it expires for us, which may be
+        // later than by its timestamp if our clock is behind.
+        self.prune_handshakes(now);
+        let expiry = serialize(&(now.max(timestamp) + HANDSHAKE_EXPIRY));
+        if let Err(e) = self.handshakes_tree.insert(init.to_bytes(), expiry) {
+            warn!("[DM] Failed recording handshake from {}: {}", contact, e);
+        }
+        self.save(contact);
+
+        vec![handshake_event(&salt_box, &reply)]
+    }
+
+    /// Forget the answered handshakes that expired, since replaying them
+    /// gets them refused anyway
+    fn prune_handshakes(&self, now: u64) {
+        for item in self.handshakes_tree.iter() {
+            let Ok((key, value)) = item else { continue };
+            if deserialize::<u64>(&value).map_or(true, |expiry| expiry < now) {
+                if let Err(e) = self.handshakes_tree.remove(key) {
+                    warn!("[DM] Failed removing expired handshake: {}", e);
+                }
+            }
+        }
+    }
+
+    fn on_handshake_reply(
+        &mut self,
+        contact: &str,
+        init: PublicKey,
+        reply: PublicKey,
+    ) -> Vec<PrivMsgEvent> {
+        let Some(session) = self.sessions.get_mut(contact) else { return vec![] };
+        let Some(pending) = session.pending.filter(|x| x.public == init) else { return vec![] };
+
+        info!("[DM] Established session with {}", contact);
+        let mut ratchet = Ratchet::init_initiator(&pending, &reply);
+
+        // Send an empty message right away, so the contact can start
+        // using the session as well.
+        let ack = PrivMsgEvent {
+            nick: String::new(),
+            msg: String::new(),
+            target: String::new(),
+            signature: String::new(),
+            relayed: false,
+        };
+        let message = ratchet.encrypt(&serialize(&ack)).unwrap();
+
+        *session = Session { pending: None, reply: None, ratchet: Some(ratchet) };
+        self.save(contact);
+
+        vec![ratchet_event(message)]
+    }
+
+    fn open_ratchet(&mut self, tag: &str, msg: &PrivMsgEvent) -> (DmEvent, Vec<PrivMsgEvent>) {
+        let tag: Option<Tag> = bs58::decode(tag).into_vec().ok().and_then(|x| x.try_into().ok());
+        let Some(contact) = tag.and_then(|x| self.tags.get(&x)).cloned() else {
+            // Not for us
+            return (DmEvent::Drop, vec![])
+        };
+
+        let message = RatchetMessage {
+            tag: tag.unwrap(),
+            header: msg.nick.clone(),
+            ciphertext: msg.msg.clone(),
+        };
+
+        let session = self.sessions.get_mut(&contact).unwrap();
+        let Some(plaintext) = session.ratchet.as_mut().and_then(|x| x.decrypt(&message)) else {
+            warn!("[DM] Failed decrypting message from {}", contact);
+            return (DmEvent::Drop, vec![])
+        };
+
+        // The initiator got our reply, no need to keep it around
+        session.reply = None;
+        self.save(&contact);
+
+        let Ok(mut inner) = deserialize::<PrivMsgEvent>(&plaintext) else {
+            warn!("[DM] Received malformed message from {}", contact);
+            return (DmEvent::Drop, vec![])
+        };
+
+        // Empty messages are only used to acknowledge the handshake
+        if inner.msg.is_empty() {
+            return (DmEvent::Drop, vec![])
+        }
+
+        debug!("[DM] Decrypted message from {}", contact);
+        encrypt_privmsg(&self.salt_box(&contact).unwrap(), &mut inner);
+        (DmEvent::Message(inner), vec![])
+    }
+}
+
+fn handshake_event(salt_box: &SalsaBox, handshake: &Handshake) -> PrivMsgEvent {
+    PrivMsgEvent {
+        nick: String::new(),
+        msg: encrypt(salt_box, &serialize(handshake)),
+        target: DM_HANDSHAKE_TARGET.to_string(),
+        signature: String::new(),
+        relayed: false,
+    }
+}
+
+fn ratchet_event(message: RatchetMessage) -> PrivMsgEvent {
+    PrivMsgEvent {
+        nick: message.header,
+        msg: message.ciphertext,
+        target: format!("{}{}", DM_TAG_PREFIX, bs58::encode(message.tag).into_string()),
+        signature: String::new(),
+        relayed: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::decrypt_privmsg;
+
+    use super::*;
+
+    /// Direct messages of someone having `name` as their only contact
+    fn direct_messages(
+        name: &str,
+        secret: &crypto_box::SecretKey,
+        public: &crypto_box::PublicKey,
+    ) -> DirectMessages {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let info = ContactInfo {
+            salt_box: Some(SalsaBox::new(public, secret)),
+            public: Some(public.clone()),
+        };
+        let contacts = HashMap::from([(name.to_string(), info)]);
+        let dm = DirectMessages::new(&db, contacts, None).unwrap();
+        Arc::try_unwrap(dm).ok().unwrap().into_inner()
+    }
+
+    /// Alice and Bob, having each other as contacts
+    fn pair() -> (DirectMessages, DirectMessages) {
+        let alice = crypto_box::SecretKey::generate(&mut OsRng);
+        let bob = crypto_box::SecretKey::generate(&mut OsRng);
+        (
+            direct_messages("bob", &alice, &bob.public_key()),
+            direct_messages("alice", &bob, &alice.public_key()),
+        )
+    }
+
+    fn privmsg(nick: &str, target: &str, msg: &str) -> PrivMsgEvent {
+        PrivMsgEvent {
+            nick: nick.to_string(),
+            msg: msg.to_string(),
+            target: target.to_string(),
+            signature: String::new(),
+            relayed: false,
+        }
+    }
+
+    /// Read the handshake carried by an event
+    fn read_handshake(dm: &DirectMessages, contact: &str, event: &PrivMsgEvent) -> Handshake {
+        let plaintext = try_decrypt_bytes(&dm.salt_box(contact).unwrap(), &event.msg).unwrap();
+        deserialize(&plaintext).unwrap()
+    }
+
+    /// Open a ratchet message and return its plaintext
+    fn read_message(dm: &mut DirectMessages, contact: &str, event: &PrivMsgEvent) -> String {
+        let (DmEvent::Message(mut msg), events) = dm.open(event) else { panic!("not a message") };
+        assert!(events.is_empty());
+        decrypt_privmsg(&dm.salt_box(contact).unwrap(), &mut msg);
+        msg.msg
+    }
+
+    /// Run the handshake, returning the established pair
+    fn established() -> (DirectMessages, DirectMessages) {
+        let (mut alice, mut bob) = pair();
+        let (events, _) = alice.seal("bob", privmsg("alice", "bob", "hi bob"));
+        let (_, replies) = bob.open(&events[0]);
+        let (_, acks) = alice.open(&replies[0]);
+        bob.open(&acks[0]);
+        (alice, bob)
+    }
+
+    #[test]
+    fn test_dm_session() {
+        let (mut alice, mut bob) = pair();
+
+        // Without a session, the handshake goes along the message sealed
+        // with the static box
+        let (events, local) = alice.seal("bob", privmsg("alice", "bob", "hi bob"));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].target, DM_HANDSHAKE_TARGET);
+        assert_eq!(events[1].msg, local.unwrap().msg);
+        assert!(matches!(bob.open(&events[1]).0, DmEvent::Pass));
+
+        // Bob answers the handshake, and can't use the session until
+        // Alice does
+        let (event, replies) = bob.open(&events[0]);
+        assert!(matches!(event, DmEvent::Drop));
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].target, DM_HANDSHAKE_TARGET);
+        let (events, _) = bob.seal("alice", privmsg("bob", "alice", "too early"));
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].target.starts_with(DM_TAG_PREFIX));
+
+        // Alice gets the reply and acknowledges it through the ratchet
+        let (event, acks) = alice.open(&replies[0]);
+        assert!(matches!(event, DmEvent::Drop));
+        assert_eq!(acks.len(), 1);
+        assert!(acks[0].target.starts_with(DM_TAG_PREFIX));
+        assert!(alice.sessions["bob"].pending.is_none());
+
+        // The acknowledgement isn't shown, and Bob stops keeping the reply
+        let (event, events) = bob.open(&acks[0]);
+        assert!(matches!(event, DmEvent::Drop));
+        assert!(events.is_empty());
+        assert!(bob.sessions["alice"].reply.is_none());
+
+        // From then on, both use the ratchet
+        let (events, _) = bob.seal("alice", privmsg("bob", "alice", "hi alice"));
+        assert_eq!(events.len(), 1);
+        assert!(events[0].target.starts_with(DM_TAG_PREFIX));
+        assert_eq!(read_message(&mut alice, "bob", &events[0]), "hi alice");
+
+        let (events, _) = alice.seal("bob", privmsg("alice", "bob", "how are you?"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(read_message(&mut bob, "alice", &events[0]), "how are you?");
+    }
+
+    #[test]
+    fn test_open_ratchet() {
+        let (mut alice, mut bob) = established();
+        let (events, _) = alice.seal("bob", privmsg("alice", "bob", "hi bob"));
+
+        // Messages with unknown or malformed tags aren't for us
+        let mut unknown = events[0].clone();
+        unknown.target = format!("{}{}", DM_TAG_PREFIX, bs58::encode([0u8; 32]).into_string());
+        assert!(matches!(bob.open(&unknown).0, DmEvent::Drop));
+        let mut malformed = events[0].clone();
+        malformed.target = format!("{}foo", DM_TAG_PREFIX);
+        assert!(matches!(bob.open(&malformed).0, DmEvent::Drop));
+
+        // Neither are the ones of other sessions
+        let (mut eve, _) = established();
+        let (foreign, _) = eve.seal("bob", privmsg("eve", "bob", "hi bob"));
+        assert!(matches!(bob.open(&foreign[0]).0, DmEvent::Drop));
+
+        // The tag leads to the session, and can't be used twice
+        assert_eq!(read_message(&mut bob, "alice", &events[0]), "hi bob");
+        assert!(matches!(bob.open(&events[0]).0, DmEvent::Drop));
+    }
+
+    #[test]
+    fn test_handshake_init() {
+        let (mut alice, mut bob) = pair();
+        let (events, _) = alice.seal("bob", privmsg("alice", "bob", "hi bob"));
+
+        // Until the session is used, the same reply is sent again
+        let (_, first) = bob.open(&events[0]);
+        let (_, second) = bob.open(&events[0]);
+        let reply = read_handshake(&bob, "alice", &first[0]);
+        assert_eq!(read_handshake(&bob, "alice", &second[0]).reply, reply.reply);
+
+        // Once it is, the handshake is a replay
+        let (_, acks) = alice.open(&first[0]);
+        bob.open(&acks[0]);
+        assert!(bob.open(&events[0]).1.is_empty());
+
+        // Expired handshakes are refused, and forgotten once answered ones
+        // are recorded
+        let salt_box = bob.salt_box("alice").unwrap();
+        let now = Timestamp::current_time().0;
+        let init = Keypair::random(&mut OsRng).public;
+        let expired = Handshake { init, reply: None, timestamp: now - HANDSHAKE_EXPIRY - 1 };
+        assert!(bob.open(&handshake_event(&salt_box, &expired)).1.is_empty());
+
+        bob.handshakes_tree.insert(init.to_bytes(), serialize(&(now - 1))).unwrap();
+        let init = Keypair::random(&mut OsRng).public;
+        let fresh = Handshake { init, reply: None, timestamp: now };
+        assert_eq!(bob.open(&handshake_event(&salt_box, &fresh)).1.len(), 1);
+        assert_eq!(bob.handshakes_tree.len(), 2);
+        assert!(bob.handshakes_tree.contains_key(init.to_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_handshake_tie_break() {
+        let (mut alice, mut bob) = pair();
+
+        // Both start a handshake at the same time
+        let (alice_events, _) = alice.seal("bob", privmsg("alice", "bob", "hi bob"));
+        let (bob_events, _) = bob.seal("alice", privmsg("bob", "alice", "hi alice"));
+
+        // Our own handshake reflected back is ignored
+        assert!(alice.open(&alice_events[0]).1.is_empty());
+
+        // Only the side with the highest key answers
+        let (_, alice_replies) = alice.open(&bob_events[0]);
+        let (_, bob_replies) = bob.open(&alice_events[0]);
+        assert_eq!(alice_replies.len() + bob_replies.len(), 1);
+
+        let (mut winner, mut loser, replies, winner_name, loser_name) = if alice_replies.is_empty()
+        {
+            (alice, bob, bob_replies, "alice", "bob")
+        } else {
+            (bob, alice, alice_replies, "bob", "alice")
+        };
+
+        let (_, acks) = winner.open(&replies[0]);
+        loser.open(&acks[0]);
+
+        let (events, _) = loser.seal(winner_name, privmsg(loser_name, winner_name, "hi"));
+        assert_eq!(read_message(&mut winner, loser_name, &events[0]), "hi");
+    }
+
+    #[test]
+    fn test_handshake_reply() {
+        let (mut alice, mut bob) = pair();
+        let salt_box = alice.salt_box("bob").unwrap();
+        let now = Timestamp::current_time().0;
+
+        // Replies to handshakes we didn't start are ignored
+        let init = Keypair::random(&mut OsRng).public;
+        let reply = Keypair::random(&mut OsRng).public;
+        let unknown = Handshake { init, reply: Some(reply), timestamp: now };
+        assert!(alice.open(&handshake_event(&salt_box, &unknown)).1.is_empty());
+        assert!(!alice.sessions.contains_key("bob"));
+
+        // The reply to ours establishes the session once
+        let (events, _) = alice.seal("bob", privmsg("alice", "bob", "hi bob"));
+        let (_, replies) = bob.open(&events[0]);
+        assert_eq!(alice.open(&replies[0]).1.len(), 1);
+        assert!(alice.sessions["bob"].ratchet.is_some());
+        assert!(alice.open(&replies[0]).1.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::HashMap, path::PathBuf};
+
+use darkfi::{Error, Result};
+
+use crate::files::{FileOffer, FileStorePtr};
+
+/// Outcome of a FileServ command
+#[derive(Default)]
+pub struct FileServReply {
+    /// Lines to send back to the client
+    pub lines: Vec<String>,
+    /// File offer the client should send to a channel or contact
+    pub share: Option<(String, FileOffer)>,
+}
+
+impl FileServReply {
+    fn new(lines: Vec<String>) -> Self {
+        Self { lines, ..Default::default() }
+    }
+}
+
+/// FileServ lets irc clients share files in their channels and with
+/// their contacts, and download the files shared with them.
+///
+/// Files are read from and downloaded to the filesystem of the darkirc
+/// node. Only the files in the `shared` directory can be shared, and
+/// profiles have their own `shared` and downloads directories.
+#[derive(Clone)]
+pub struct FileServ {
+    store: FileStorePtr,
+    files_path: PathBuf,
+    downloads_path: PathBuf,
+    /// Directory the shared files must be in
+    shared_path: PathBuf,
+}
+
+impl FileServ {
+    pub fn new(store: FileStorePtr, files_path: PathBuf) -> Self {
+        let downloads_path = files_path.join("downloads");
+        let shared_path = files_path.join("shared");
+        Self { store, files_path, downloads_path, shared_path }
+    }
+
+    /// Return a FileServ for the given profile
+    pub fn with_profile(&self, profile: Option<&str>) -> Self {
+        match profile {
+            Some(profile) => Self {
+                store: self.store.clone(),
+                files_path: self.files_path.clone(),
+                downloads_path: self.files_path.join("downloads").join(profile),
+                shared_path: self.files_path.join("shared").join(profile),
+            },
+            None => self.clone(),
+        }
+    }
+
+    fn usage() -> Vec<String> {
+        let r = vec![
+            "***** fileserv help *****",
+            "",
+            "fileserv shares files through the DHT. Files are encrypted, and",
+            "the key is sent along with the offer, so only those reading the",
+            "channel or direct msg can open them.",
+            "",
+            "The following commands are available:",
+            "",
+            "    SHARE <#channel|contact> <path>    Share a file of your shared",
+            "                                       directory on this node",
+            "    LIST                               List the files shared with you",
+            "    GET <id>                           Download a shared file",
+            "",
+            "***** end of help *****",
+        ];
+
+        r.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Parse an incoming fileserv message. `offers` are the files shared
+    /// with the client, by their id.
+    pub async fn act(&self, msg: &str, offers: &HashMap<String, FileOffer>) -> FileServReply {
+        let parts: Vec<&str> = msg.split_whitespace().collect();
+        if parts.is_empty() {
+            return FileServReply::new(Self::usage())
+        }
+
+        let command = parts[0].to_uppercase();
+        let args = &parts[1..];
+
+        let result = match (command.as_str(), args) {
+            ("SHARE", [target, path @ ..]) if !path.is_empty() => {
+                self.share(&path.join(" ")).await.map(|offer| FileServReply {
+                    lines: vec![format!(
+                        "Shared {} with {} as {}",
+                        offer.file_name(),
+                        target,
+                        offer.id()
+                    )],
+                    share: Some((target.to_string(), offer)),
+                })
+            }
+
+            ("LIST", []) => Ok(FileServReply::new(Self::list(offers))),
+
+            ("GET", [id]) => match offers.get(*id) {
+                Some(offer) => self.download(offer).await.map(|path| {
+                    FileServReply::new(vec![format!("Saved {} to {}", offer.id(), path.display())])
+                }),
+                None => Err(Error::Custom(format!("Unknown file {}", id))),
+            },
+
+            ("HELP", _) => Ok(FileServReply::new(Self::usage())),
+
+            ("SHARE", _) => {
+                Ok(FileServReply::new(vec!["Usage: SHARE <#channel|contact> <path>".to_string()]))
+            }
+            ("LIST", _) => Ok(FileServReply::new(vec!["Usage: LIST".to_string()])),
+            ("GET", _) => Ok(FileServReply::new(vec!["Usage: GET <id>".to_string()])),
+
+            (c, _) => Ok(FileServReply::new(vec![
+                format!("Invalid command {}", c),
+                "Type HELP to get help".to_string(),
+            ])),
+        };
+
+        result.unwrap_or_else(|e| FileServReply::new(vec![e.to_string()]))
+    }
+
+    fn list(offers: &HashMap<String, FileOffer>) -> Vec<String> {
+        if offers.is_empty() {
+            return vec!["No files were shared with you".to_string()]
+        }
+
+        let mut lines: Vec<String> = offers
+            .values()
+            .map(|x| format!("{}  {} ({} bytes)", x.id(), x.file_name(), x.size))
+            .collect();
+        lines.sort();
+        lines
+    }
+
+    async fn share(&self, path: &str) -> Result<FileOffer> {
+        async_std::fs::create_dir_all(&self.shared_path).await?;
+
+        // Resolve links and `..` so the file can't be outside
+        let path = self.shared_path.join(path).canonicalize()?;
+        if !path.starts_with(self.shared_path.canonicalize()?) {
+            return Err(Error::Custom(format!(
+                "Only files in {} can be shared",
+                self.shared_path.display()
+            )))
+        }
+
+        self.store.share(&path).await
+    }
+
+    /// Fetch and decrypt a file into the downloads directory, returning
+    /// the path it was saved to
+    async fn download(&self, offer: &FileOffer) -> Result<PathBuf> {
+        let contents = self.store.fetch(offer).await?;
+
+        async_std::fs::create_dir_all(&self.downloads_path).await?;
+
+        // Never overwrite a file already there
+        let mut path = self.downloads_path.join(offer.file_name());
+        if path.exists() {
+            path = self.downloads_path.join(format!("{}-{}", offer.id(), offer.file_name()));
+        }
+
+        async_std::fs::write(&path, contents).await?;
+        Ok(path)
+    }
+}
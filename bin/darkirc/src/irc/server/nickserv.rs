@@ -16,32 +16,99 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::collections::BTreeMap;
+use darkfi::{event_graph::model::Event, util::time::Timestamp};
+use darkfi_sdk::crypto::{Keypair, PublicKey, SecretKey};
+use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
+use log::{debug, warn};
+use rand::rngs::OsRng;
 
-use sled::IVec;
+use crate::{settings::MAXIMUM_LENGTH_OF_NICK_CHAN_CNT, PrivMsgEvent};
 
-use crate::PrivMsgEvent;
+use super::{profile_tree, NICK_NICKSERV};
 
-#[derive(Debug, Clone, Default)]
+/// sled tree holding the locally created accounts, `nick => Keypair`.
+/// Every profile has its own.
+const SLED_NICKSERV_ACCOUNTS_TREE: &[u8] = b"_nickserv_accounts";
+/// sled tree holding the nicks registered on the network, `nick => Registration`
+const SLED_NICKSERV_REGISTRY_TREE: &[u8] = b"_nickserv_registry";
+
+/// Seconds after which a registration that wasn't announced again expires
+const REGISTRATION_EXPIRY: u64 = 60 * 60 * 24 * 30;
+/// Maximum number of registrations kept in the registry
+const MAX_REGISTRATIONS: usize = 10000;
+
+/// Registration of a nick, along with the local time it was last announced
+#[derive(SerialEncodable, SerialDecodable)]
+struct Registration {
+    public: PublicKey,
+    last_seen: u64,
+}
+
+impl Registration {
+    fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_seen) > REGISTRATION_EXPIRY
+    }
+}
+
+/// Outcome of a successful NickServ command
+#[derive(Default)]
+pub struct NickServReply {
+    /// Lines to send back to the client
+    pub lines: Vec<String>,
+    /// Nick and key the client should sign its outgoing messages with
+    pub identity: Option<(String, SecretKey)>,
+    /// Registration event to publish in the event graph
+    pub registration: Option<PrivMsgEvent>,
+}
+
+impl NickServReply {
+    fn new(lines: Vec<String>) -> Self {
+        Self { lines, ..Default::default() }
+    }
+}
+
+/// NickServ keeps a signing keypair per local account, and the public
+/// registry of `nick => public key` bindings it has seen on the network.
+///
+/// Registrations are published as signed events targeting `nickserv`
+/// through the event graph. The first valid registration of a nick this
+/// node sees binds it, and afterwards only re-announcements with the same
+/// key are accepted. Event timestamps are picked by their author, so they
+/// aren't trusted to order registrations, and a backdated registration
+/// can't take a nick already bound here. In exchange, nodes may disagree on
+/// the owner of a nick two keys registered at around the same time.
+///
+/// Registrations are announced again on every IDENTIFY. The ones that
+/// weren't for `REGISTRATION_EXPIRY` expire and free their nick, and at
+/// most `MAX_REGISTRATIONS` are kept in the registry.
+#[derive(Debug, Clone)]
 pub struct NickServ {
-    _db: BTreeMap<IVec, IVec>,
+    accounts: sled::Tree,
+    registry: sled::Tree,
+    max_registrations: usize,
 }
 
 impl NickServ {
+    pub fn new(db: &sled::Db, profile: Option<&str>) -> darkfi::Result<Self> {
+        let accounts = db.open_tree(profile_tree(SLED_NICKSERV_ACCOUNTS_TREE, profile))?;
+        let registry = db.open_tree(SLED_NICKSERV_REGISTRY_TREE)?;
+        Ok(Self { accounts, registry, max_registrations: MAX_REGISTRATIONS })
+    }
+
     fn usage() -> Vec<String> {
         let r = vec![
             "***** nickserv help *****",
             "",
-            "nickserv allows clients to 'register' an account. An account",
-            "registration is necessary to be able to join and send messages",
-            "to the p2p network.",
+            "nickserv allows clients to claim ownership of a nick. Every",
+            "account holds a signing keypair, and once registered, messages",
+            "using the nick without a valid signature are flagged.",
             "",
             "The following commands are available:",
             "",
-            "    CREATE      Create a new account",
-            "    LIST        List available accounts",
-            "    REGISTER    Register a new account",
-            "    IDENTIFY    Identify and pick an account to use",
+            "    CREATE <nick>      Create a new account",
+            "    LIST               List available accounts",
+            "    REGISTER <nick>    Register an account's nick on the network",
+            "    IDENTIFY <nick>    Identify and sign messages with an account",
             "",
             "***** end of help *****",
         ];
@@ -57,43 +124,339 @@ impl NickServ {
     }
 
     /// Parse an incoming nickserv message
-    pub fn act(&mut self, ev: PrivMsgEvent) -> Result<Vec<String>, Vec<String>> {
-        assert_eq!(ev.target.to_lowercase().as_str(), super::NICK_NICKSERV);
+    pub fn act(&mut self, ev: PrivMsgEvent) -> Result<NickServReply, Vec<String>> {
+        assert_eq!(ev.target.to_lowercase().as_str(), NICK_NICKSERV);
 
-        let parts: Vec<String> = ev.msg.split(' ').map(|x| x.to_string()).collect();
+        let parts: Vec<String> = ev.msg.split_whitespace().map(|x| x.to_string()).collect();
+        if parts.is_empty() {
+            return Ok(NickServReply::new(Self::usage()))
+        }
 
         match parts[0].to_uppercase().as_str() {
-            "CREATE" => self.create(),
+            "CREATE" => self.create(Self::nick_arg(&parts)?),
 
             "LIST" => self.list(),
 
-            "REGISTER" => self.register(),
+            "REGISTER" => self.register(Self::nick_arg(&parts)?),
 
-            "IDENTIFY" => self.identify(),
+            "IDENTIFY" => self.identify(Self::nick_arg(&parts)?),
 
-            "HELP" => Ok(Self::usage()),
+            "HELP" => Ok(NickServReply::new(Self::usage())),
 
             c => Err(vec![format!("Invalid command {}", c), "Type HELP to get help".to_string()]),
         }
     }
 
+    /// Grab and validate the nick argument of a command
+    fn nick_arg(parts: &[String]) -> Result<&str, Vec<String>> {
+        let Some(nick) = parts.get(1) else {
+            return Err(Self::reply(format!("Usage: {} <nick>", parts[0].to_uppercase())))
+        };
+
+        if !Self::is_valid_nick(nick) {
+            return Err(Self::reply(format!("Invalid nick {}", nick)))
+        }
+
+        Ok(nick.as_str())
+    }
+
+    fn is_valid_nick(nick: &str) -> bool {
+        !nick.is_empty() &&
+            nick.len() < MAXIMUM_LENGTH_OF_NICK_CHAN_CNT &&
+            !nick.starts_with('#') &&
+            nick.to_lowercase() != NICK_NICKSERV
+    }
+
     /// Create a new account
-    fn create(&mut self) -> Result<Vec<String>, Vec<String>> {
-        Ok(Self::reply("Account created successfully.".to_string()))
+    fn create(&mut self, nick: &str) -> Result<NickServReply, Vec<String>> {
+        let keypair = Keypair::random(&mut OsRng);
+        let key = nick.to_lowercase();
+
+        match self.accounts.compare_and_swap(
+            key.as_bytes(),
+            None as Option<&[u8]>,
+            Some(serialize(&keypair)),
+        ) {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return Err(Self::reply(format!("Account {} already exists.", nick))),
+            Err(e) => return Err(Self::reply(format!("Failed creating account: {}", e))),
+        }
+
+        if let Err(e) = self.accounts.flush() {
+            return Err(Self::reply(format!("Failed creating account: {}", e)))
+        }
+
+        Ok(NickServReply::new(vec![
+            format!("Account {} created with public key {}.", nick, keypair.public),
+            format!("Type REGISTER {} to claim the nick on the network.", nick),
+        ]))
     }
 
     /// List available accounts
-    fn list(&self) -> Result<Vec<String>, Vec<String>> {
-        Ok(Self::reply("Accounts: ...".to_string()))
+    fn list(&self) -> Result<NickServReply, Vec<String>> {
+        let mut lines = vec![];
+
+        for account in self.accounts.iter() {
+            let (nick, keypair) = match account {
+                Ok(v) => v,
+                Err(e) => return Err(Self::reply(format!("Failed listing accounts: {}", e))),
+            };
+
+            let nick = String::from_utf8_lossy(&nick).to_string();
+            let keypair: Keypair = deserialize(&keypair).unwrap();
+
+            let status = match self.registered_key(&nick) {
+                Some(public) if public == keypair.public => "registered",
+                Some(_) => "registered to another key",
+                None => "unregistered",
+            };
+
+            lines.push(format!("{} {} ({})", nick, keypair.public, status));
+        }
+
+        if lines.is_empty() {
+            lines.push("No accounts. Type CREATE <nick> to create one.".to_string());
+        }
+
+        Ok(NickServReply::new(lines))
     }
 
     /// Register a created but unregistered account
-    fn register(&mut self) -> Result<Vec<String>, Vec<String>> {
-        Ok(Self::reply("Account created successfully.".to_string()))
+    fn register(&mut self, nick: &str) -> Result<NickServReply, Vec<String>> {
+        let keypair = self.account(nick)?;
+        let registration = self.registration(nick, &keypair)?;
+
+        let mut reply = NickServReply::new(Self::reply(format!(
+            "Registration of {} published to the network.",
+            nick
+        )));
+        reply.registration = Some(registration);
+        Ok(reply)
     }
 
     /// Pick an account to use
-    fn identify(&mut self) -> Result<Vec<String>, Vec<String>> {
-        Ok(Self::reply("Using account 0.".to_string()))
+    fn identify(&mut self, nick: &str) -> Result<NickServReply, Vec<String>> {
+        let keypair = self.account(nick)?;
+        // Registrations aren't persisted in the event graph forever, so
+        // we announce ours again for peers that might have missed it.
+        let registration = self.registration(nick, &keypair)?;
+
+        let mut reply =
+            NickServReply::new(Self::reply(format!("You are now identified as {}.", nick)));
+        reply.identity = Some((nick.to_string(), keypair.secret));
+        reply.registration = Some(registration);
+        Ok(reply)
+    }
+
+    /// Fetch a locally created account
+    fn account(&self, nick: &str) -> Result<Keypair, Vec<String>> {
+        match self.accounts.get(nick.to_lowercase().as_bytes()) {
+            Ok(Some(keypair)) => Ok(deserialize(&keypair).unwrap()),
+            Ok(None) => Err(Self::reply(format!(
+                "Account {} does not exist. Type CREATE {} to create it.",
+                nick, nick
+            ))),
+            Err(e) => Err(Self::reply(format!("Failed reading account: {}", e))),
+        }
+    }
+
+    /// Create a signed registration event for the given account. It gets
+    /// recorded in the registry once it is published in the event graph.
+    fn registration(&self, nick: &str, keypair: &Keypair) -> Result<PrivMsgEvent, Vec<String>> {
+        if self.registered_key(nick).map_or(false, |public| public != keypair.public) {
+            return Err(Self::reply(format!("Nick {} is registered to another key.", nick)))
+        }
+
+        let mut event = PrivMsgEvent {
+            nick: nick.to_string(),
+            msg: format!("REGISTER {}", keypair.public),
+            target: NICK_NICKSERV.to_string(),
+            signature: String::new(),
+            relayed: false,
+        };
+        event.sign(&keypair.secret);
+
+        Ok(event)
+    }
+
+    /// Process a registration event of the event graph. Returns `true`
+    /// if the nick is now registered to the key in the event.
+    pub fn process_registration(&self, event: &Event<PrivMsgEvent>) -> bool {
+        let ev = &event.action;
+        let parts: Vec<&str> = ev.msg.split(' ').collect();
+        if parts.len() != 2 || parts[0] != "REGISTER" || !Self::is_valid_nick(&ev.nick) {
+            warn!("[NICKSERV] Received malformed registration: {:?}", ev);
+            return false
+        }
+
+        let Ok(public) = parts[1].parse::<PublicKey>() else {
+            warn!("[NICKSERV] Received registration with invalid public key: {:?}", ev);
+            return false
+        };
+
+        if !ev.verify(&public, event.timestamp) {
+            warn!("[NICKSERV] Received registration with invalid signature: {:?}", ev);
+            return false
+        }
+
+        self.bind(&ev.nick, public, Timestamp::current_time().0)
+    }
+
+    /// Bind a nick to a public key at the given local time, unless it's
+    /// bound to another key already. Returns `true` if the nick is now
+    /// registered to the key.
+    fn bind(&self, nick: &str, public: PublicKey, now: u64) -> bool {
+        let key = nick.to_lowercase();
+        let registration = serialize(&Registration { public, last_seen: now });
+
+        loop {
+            let current = match self.registry.get(key.as_bytes()) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("[NICKSERV] Failed reading registration: {}", e);
+                    return false
+                }
+            };
+
+            let bound = current
+                .as_ref()
+                .and_then(|v| deserialize::<Registration>(v).ok())
+                .filter(|x| !x.is_expired(now));
+
+            match bound {
+                // Re-announcements refresh the registration
+                Some(bound) if bound.public == public => {}
+                Some(_) => return false,
+                // New nicks only fit if the registry isn't full
+                None if current.is_none() && !self.has_room(now) => {
+                    warn!("[NICKSERV] Registry is full, ignoring registration of {}", nick);
+                    return false
+                }
+                None => {}
+            }
+
+            match self.registry.compare_and_swap(
+                key.as_bytes(),
+                current,
+                Some(registration.clone()),
+            ) {
+                Ok(Ok(())) => {
+                    debug!("[NICKSERV] Registered {} to {}", nick, public);
+                    return true
+                }
+                // Raced with another registration, check again
+                Ok(Err(_)) => continue,
+                Err(e) => {
+                    warn!("[NICKSERV] Failed writing registration: {}", e);
+                    return false
+                }
+            }
+        }
+    }
+
+    /// Check if the registry has room for another registration,
+    /// removing the expired ones if it doesn't.
+    fn has_room(&self, now: u64) -> bool {
+        if self.registry.len() < self.max_registrations {
+            return true
+        }
+
+        for entry in self.registry.iter() {
+            let Ok((key, value)) = entry else { continue };
+            if deserialize::<Registration>(&value).map_or(true, |x| x.is_expired(now)) {
+                // Keep it if it was refreshed in the meantime
+                let _ = self.registry.compare_and_swap(key, Some(value), None as Option<&[u8]>);
+            }
+        }
+
+        self.registry.len() < self.max_registrations
+    }
+
+    /// Return the public key a nick is registered to, if any
+    pub fn registered_key(&self, nick: &str) -> Option<PublicKey> {
+        let now = Timestamp::current_time().0;
+        match self.registry.get(nick.to_lowercase().as_bytes()) {
+            Ok(Some(registration)) => deserialize::<Registration>(&registration)
+                .ok()
+                .filter(|x| !x.is_expired(now))
+                .map(|x| x.public),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(nick: &str, keypair: &Keypair, timestamp: u64) -> Event<PrivMsgEvent> {
+        let mut action = PrivMsgEvent {
+            nick: nick.to_string(),
+            msg: format!("REGISTER {}", keypair.public),
+            target: NICK_NICKSERV.to_string(),
+            signature: String::new(),
+            relayed: false,
+        };
+        action.sign(&keypair.secret);
+
+        Event { previous_event_hash: blake3::hash(b""), action, timestamp: Timestamp(timestamp) }
+    }
+
+    fn now() -> u64 {
+        Timestamp::current_time().0
+    }
+
+    #[test]
+    fn test_registration() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let nickserv = NickServ::new(&db, None).unwrap();
+
+        let alice = Keypair::random(&mut OsRng);
+        let mallory = Keypair::random(&mut OsRng);
+
+        // The first registration seen wins, even over backdated ones
+        assert!(nickserv.process_registration(&registration("Foo", &alice, now())));
+        assert!(!nickserv.process_registration(&registration("foo", &mallory, now() - 30)));
+        assert_eq!(nickserv.registered_key("FOO"), Some(alice.public));
+
+        // Re-announcements keep the original registration
+        assert!(nickserv.process_registration(&registration("foo", &alice, now() + 3)));
+        assert!(!nickserv.process_registration(&registration("foo", &mallory, now() + 4)));
+        assert_eq!(nickserv.registered_key("foo"), Some(alice.public));
+
+        // Registrations must be signed by the registered key
+        let mut forged = registration("bar", &alice, now());
+        forged.action.msg = format!("REGISTER {}", mallory.public);
+        assert!(!nickserv.process_registration(&forged));
+
+        // and published along with it
+        assert!(!nickserv.process_registration(&registration("bar", &alice, now() - 3600)));
+        assert_eq!(nickserv.registered_key("bar"), None);
+    }
+
+    #[test]
+    fn test_registration_expiry() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let mut nickserv = NickServ::new(&db, None).unwrap();
+        nickserv.max_registrations = 2;
+
+        let alice = Keypair::random(&mut OsRng);
+        let bob = Keypair::random(&mut OsRng);
+        let mallory = Keypair::random(&mut OsRng);
+
+        // Registrations hold until they expire, unless announced again
+        assert!(nickserv.bind("foo", alice.public, 0));
+        assert!(nickserv.bind("bar", bob.public, 0));
+        assert!(nickserv.bind("bar", bob.public, REGISTRATION_EXPIRY));
+        assert!(!nickserv.bind("foo", mallory.public, REGISTRATION_EXPIRY));
+
+        // The registry is full, so new nicks are refused
+        assert!(!nickserv.bind("baz", mallory.public, REGISTRATION_EXPIRY));
+
+        // until expired registrations make room for them
+        assert!(!nickserv.bind("bar", mallory.public, REGISTRATION_EXPIRY + 1));
+        assert!(nickserv.bind("baz", mallory.public, REGISTRATION_EXPIRY + 1));
+        assert_eq!(nickserv.registry.len(), 2);
+        assert_eq!(nickserv.registered_key("foo"), None);
     }
 }
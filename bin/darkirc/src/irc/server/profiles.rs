@@ -0,0 +1,177 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi::{util::path::get_config_path, Result};
+
+use crate::settings::{parse_configured_profiles, Args, CONFIG_FILE};
+
+use super::{super::IrcConfig, ChanServ, ConfServ, FileServ, NickServ};
+
+/// State of a client for the profile it authenticated as
+pub struct Profile {
+    pub config: IrcConfig,
+    pub nickserv: NickServ,
+    pub confserv: ConfServ,
+    pub fileserv: FileServ,
+    pub chanserv: ChanServ,
+}
+
+/// Profiles let one darkirc serve several users. Each profile is picked
+/// by the client with its `PASS`, and has its own nick, channels,
+/// contacts and NickServ accounts. The default profile is the top level
+/// configuration, used by clients sending the top level password.
+#[derive(Clone)]
+pub struct Profiles {
+    settings: Args,
+    db: sled::Db,
+    confserv: ConfServ,
+    fileserv: FileServ,
+}
+
+impl Profiles {
+    pub fn new(settings: Args, db: sled::Db, confserv: ConfServ, fileserv: FileServ) -> Self {
+        Self { settings, db, confserv, fileserv }
+    }
+
+    /// Load the state of a profile, or of the default one when no profile
+    /// is given
+    pub fn load(&self, profile: Option<&str>) -> Result<Profile> {
+        Ok(Profile {
+            config: IrcConfig::new(&self.settings, profile)?,
+            nickserv: NickServ::new(&self.db, profile)?,
+            confserv: self.confserv.with_profile(profile),
+            fileserv: self.fileserv.with_profile(profile),
+            chanserv: ChanServ::new(self.confserv.with_profile(profile)),
+        })
+    }
+
+    /// Return the profile matching the password sent by a client, or
+    /// `None` if it matches none of them
+    pub fn authenticate(&self, password: &str) -> Result<Option<Profile>> {
+        let cfg_path = get_config_path(self.settings.config.clone(), CONFIG_FILE)?;
+        let profiles = parse_configured_profiles(&std::fs::read_to_string(cfg_path)?)?;
+
+        if let Some((name, _)) = profiles.iter().find(|(_, info)| info.password == password) {
+            return Ok(Some(self.load(Some(name))?))
+        }
+
+        if self.settings.password.as_deref().unwrap_or_default() == password {
+            return Ok(Some(self.load(None)?))
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi::{net, system::Subscriber};
+    use rand::rngs::OsRng;
+    use structopt_toml::StructOptToml;
+
+    use super::{super::NICK_NICKSERV, *};
+    use crate::{files::FileStore, settings::parse_configured_profiles, PrivMsgEvent};
+
+    const CONFIG: &str = r##"password = "main-password"
+autojoin = ["#dev"]
+
+[profile."work"]
+password = "work-password"
+autojoin = ["#work"]
+"##;
+
+    /// Profiles served from a fresh configuration file and database
+    async fn profiles(name: &str, contents: &str) -> Profiles {
+        let dir =
+            std::env::temp_dir().join(format!("darkirc_profiles_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        std::fs::write(&path, contents).unwrap();
+
+        let settings =
+            Args::from_iter_with_toml(contents, ["darkirc", "--config", path.to_str().unwrap()])
+                .unwrap();
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let p2p = net::P2p::new(net::Settings::default()).await;
+        let files = FileStore::new(&dir.join("dht"), p2p).await.unwrap();
+
+        let confserv = ConfServ::new(settings.clone(), Subscriber::new());
+        Profiles::new(settings, db, confserv, FileServ::new(files, dir))
+    }
+
+    fn nickserv_msg(msg: &str) -> PrivMsgEvent {
+        PrivMsgEvent {
+            nick: "anon".to_string(),
+            msg: msg.to_string(),
+            target: NICK_NICKSERV.to_string(),
+            signature: String::new(),
+            relayed: false,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_passwords() {
+        assert_eq!(parse_configured_profiles(CONFIG).unwrap().len(), 1);
+
+        let shared = format!("{}\n[profile.\"home\"]\npassword = \"work-password\"\n", CONFIG);
+        assert!(parse_configured_profiles(&shared).is_err());
+
+        let top_level = format!("{}\n[profile.\"home\"]\npassword = \"main-password\"\n", CONFIG);
+        assert!(parse_configured_profiles(&top_level).is_err());
+    }
+
+    #[test]
+    fn test_profile_isolation() {
+        smol::block_on(async {
+            let profiles = profiles("isolation", CONFIG).await;
+
+            let mut main = profiles.authenticate("main-password").unwrap().unwrap();
+            let mut work = profiles.authenticate("work-password").unwrap().unwrap();
+            assert!(profiles.authenticate("wrong-password").unwrap().is_none());
+
+            assert_eq!(main.config.profile, None);
+            assert_eq!(main.config.auto_channels, vec!["#dev"]);
+            assert_eq!(work.config.profile.as_deref(), Some("work"));
+            assert_eq!(work.config.auto_channels, vec!["#work"]);
+
+            // Channels and contacts only land in their own profile
+            main.confserv.create_channel("#main", None).await.unwrap();
+            work.confserv.create_channel("#work", None).await.unwrap();
+
+            let bob = crypto_box::SecretKey::generate(&mut OsRng);
+            let bob_public = bs58::encode(bob.public_key().as_bytes()).into_string();
+            work.confserv.add_contact("bob", &bob_public).await.unwrap();
+
+            let main_config = profiles.authenticate("main-password").unwrap().unwrap().config;
+            let work_config = profiles.authenticate("work-password").unwrap().unwrap().config;
+            assert!(main_config.channels.contains_key("#main"));
+            assert!(!main_config.channels.contains_key("#work"));
+            assert!(work_config.channels.contains_key("#work"));
+            assert!(!work_config.channels.contains_key("#main"));
+            assert!(!main_config.contacts.contains_key("bob"));
+            assert!(work_config.contacts.contains_key("bob"));
+
+            // NickServ accounts are kept apart as well
+            main.nickserv.act(nickserv_msg("CREATE alice")).unwrap();
+            let list = work.nickserv.act(nickserv_msg("LIST")).unwrap();
+            assert_eq!(list.lines, vec!["No accounts. Type CREATE <nick> to create one."]);
+            let list = main.nickserv.act(nickserv_msg("LIST")).unwrap();
+            assert!(list.lines[0].starts_with("alice "));
+        });
+    }
+}
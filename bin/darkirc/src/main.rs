@@ -22,7 +22,6 @@ use async_std::{
     task,
 };
 
-use chrono::{Duration, Utc};
 use irc::ClientSubMsg;
 use log::{debug, error, info, warn};
 use rand::rngs::OsRng;
@@ -34,29 +33,32 @@ use darkfi::{
     async_daemonize,
     event_graph::{
         events_queue::EventsQueue,
-        model::{Model, ModelPtr},
+        model::Model,
         protocol_event::{ProtocolEvent, Seen},
         view::View,
     },
     net,
-    rpc::server::listen_and_serve,
+    rpc::{metrics, server::listen_and_serve},
     system::{Subscriber, SubscriberPtr},
-    util::{async_util::sleep, file::save_json_file, path::expand_path, time::Timestamp},
+    util::{file::save_json_file, path::expand_path},
     Result,
 };
 
 pub mod crypto;
+pub mod files;
 pub mod irc;
 pub mod privmsg;
+pub mod ratchet;
 pub mod rpc;
 pub mod settings;
 
 use crate::{
     crypto::KeyPair,
+    files::{FileStore, ProtocolFiles},
     irc::{IrcConfig, IrcServer},
-    privmsg::PrivMsgEvent,
+    privmsg::{PrivMsgEvent, SIGNED_PRIVMSG_FEATURE},
     rpc::JsonRpcInterface,
-    settings::{Args, ChannelInfo, CONFIG_FILE, CONFIG_FILE_CONTENTS},
+    settings::{Args, ChannelInfo, CONFIG_FILE, CONFIG_FILE_CONTENTS, NETWORK_ID},
 };
 
 async_daemonize!(realmain);
@@ -93,10 +95,9 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
     // Initialize the base structures
     ////////////////////
     let events_queue = EventsQueue::<PrivMsgEvent>::new();
-    let model = Arc::new(Mutex::new(Model::new(events_queue.clone())));
+    let model = Arc::new(Mutex::new(Model::new(events_queue.clone(), settings.dag.clone().into())));
     let view = Arc::new(Mutex::new(View::new(events_queue)));
     let model_clone = model.clone();
-    let model_clone2 = model.clone();
 
     ////////////////////
     // P2p setup
@@ -106,7 +107,9 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
     let seen_inv = Seen::new();
 
     // Check the version
-    let net_settings = settings.net.clone();
+    let mut net_settings = settings.net.clone();
+    net_settings.network_id.get_or_insert_with(|| NETWORK_ID.to_string());
+    net_settings.features.push(SIGNED_PRIVMSG_FEATURE.to_string());
 
     // New p2p
     let p2p = net::P2p::new(net_settings.into()).await;
@@ -123,6 +126,17 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
         })
         .await;
 
+    // Files shared through the DHT
+    let files_path = expand_path(&settings.files_path)?.join("dht");
+    let files = FileStore::new(&files_path, p2p.clone()).await?;
+    let _files = files.clone();
+    registry
+        .register(net::SESSION_ALL, move |channel, p2p| {
+            let files = _files.clone();
+            async move { ProtocolFiles::init(channel, p2p, files).await.unwrap() }
+        })
+        .await;
+
     // Start
     p2p.clone().start(executor.clone()).await?;
 
@@ -130,16 +144,11 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
     let executor_cloned = executor.clone();
     executor_cloned.spawn(p2p.clone().run(executor.clone())).detach();
 
-    ////////////////////
-    // RPC interface setup
-    ////////////////////
-    let rpc_listen_addr = settings.rpc_listen.clone();
-    let rpc_interface =
-        Arc::new(JsonRpcInterface { addr: rpc_listen_addr.clone(), p2p: p2p.clone() });
-    let _ex = executor.clone();
-    executor
-        .spawn(async move { listen_and_serve(rpc_listen_addr, rpc_interface, _ex).await })
-        .detach();
+    if let Some(metrics_listen) = settings.metrics_listen.clone() {
+        info!("Starting metrics server on {}", metrics_listen);
+        let _ex = executor.clone();
+        executor.spawn(metrics::listen_and_serve(metrics_listen, _ex)).detach();
+    }
 
     ////////////////////
     // IRC server
@@ -152,16 +161,28 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
         model_clone,
         view.clone(),
         clients_subscriptions,
+        files,
     )
     .await?;
 
+    ////////////////////
+    // RPC interface setup
+    ////////////////////
+    let rpc_listen_addr = settings.rpc_listen.clone();
+    let rpc_interface = Arc::new(JsonRpcInterface {
+        addr: rpc_listen_addr.clone(),
+        p2p: p2p.clone(),
+        confserv: irc_server.confserv.clone(),
+    });
+    let _ex = executor.clone();
+    executor
+        .spawn(async move { listen_and_serve(rpc_listen_addr, rpc_interface, _ex).await })
+        .detach();
+
     // Start the irc server and detach it
     let executor_cloned = executor.clone();
     executor.spawn(async move { irc_server.start(executor_cloned).await }).detach();
 
-    // Reset root task
-    executor.spawn(async move { reset_root(model_clone2).await }).detach();
-
     ////////////////////
     // Wait for termination signal
     ////////////////////
@@ -193,8 +214,10 @@ async fn handle_signals(
                     error!("Error parsing the config file");
                     continue
                 }
-                let new_config = IrcConfig::new(&args.unwrap())?;
-                subscriber.notify(ClientSubMsg::Config(new_config)).await;
+                // Every profile gets its new config, clients pick their own
+                for new_config in IrcConfig::load_all(&args.unwrap())? {
+                    subscriber.notify(ClientSubMsg::Config(new_config)).await;
+                }
             }
             SIGTERM | SIGINT | SIGQUIT => {
                 term_tx.send(()).await?;
@@ -205,25 +228,3 @@ async fn handle_signals(
     }
     Ok(())
 }
-
-async fn reset_root(model: ModelPtr<PrivMsgEvent>) {
-    loop {
-        let now = Utc::now();
-
-        // clocks are valid, safe to unwrap
-        let next_midnight = (now + Duration::days(1)).date_naive().and_hms_opt(0, 0, 0).unwrap();
-
-        let duration = next_midnight.signed_duration_since(now.naive_utc()).to_std().unwrap();
-
-        // make sure the root is the same as everyone else's at
-        // startup by passing today's date 00:00 AM UTC as
-        // timestamp to root_event
-        let now_datetime = now.date_naive().and_hms_opt(0, 0, 0).unwrap();
-        let timestamp = now_datetime.timestamp() as u64;
-
-        model.lock().await.reset_root(Timestamp(timestamp));
-
-        sleep(duration.as_secs()).await;
-        info!("Resetting root");
-    }
-}
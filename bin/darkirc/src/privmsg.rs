@@ -16,19 +16,124 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use darkfi::event_graph::EventMsg;
-use darkfi_serial::{SerialDecodable, SerialEncodable};
+use darkfi::{event_graph::EventMsg, util::time::Timestamp};
+use darkfi_sdk::crypto::{
+    schnorr::{SchnorrPublic, SchnorrSecret, Signature},
+    PublicKey, SecretKey,
+};
+use darkfi_serial::{deserialize, serialize, Encodable, SerialDecodable, SerialEncodable};
+use rand::rngs::OsRng;
+
+/// Length of the key fingerprint shown to IRC clients
+pub const FINGERPRINT_LENGTH: usize = 12;
+
+/// Maximum difference in seconds between the time a message got signed
+/// at and the timestamp of the event carrying it. Signatures outside of
+/// it are treated as replays of older messages.
+pub const MAX_SIGNATURE_SKEW: u64 = 60;
+
+/// Protocol feature of the nodes encoding [`PrivMsgEvent`] with its
+/// `signature` and `relayed` fields. Events are only exchanged with peers
+/// advertising it.
+pub const SIGNED_PRIVMSG_FEATURE: &str = "signed-privmsg";
+
+/// Signature of a message sender, along with the key and the time it
+/// was made with
+#[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
+pub struct SenderSignature {
+    pub public: PublicKey,
+    pub timestamp: u64,
+    pub signature: Signature,
+}
 
 #[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
 pub struct PrivMsgEvent {
     pub nick: String,
     pub msg: String,
     pub target: String,
+    /// Optional bs58 encoded [`SenderSignature`] over the plaintext `nick`,
+    /// `target` and `msg`, and the signing time. Empty when the message is
+    /// unsigned. It gets encrypted along with the other fields, so only the
+    /// ones able to decrypt the message can tell who signed it.
+    pub signature: String,
+    /// Set by bridges on the messages they relay from another network, so
+    /// no bridge sends them back there. It isn't encrypted, so everyone
+    /// can tell a message was relayed.
+    pub relayed: bool,
+}
+
+impl PrivMsgEvent {
+    /// Serialized plaintext fields and signing time covered by the signature
+    fn signing_data(&self, timestamp: u64) -> Vec<u8> {
+        let mut data = vec![];
+        self.nick.encode(&mut data).unwrap();
+        self.target.encode(&mut data).unwrap();
+        self.msg.encode(&mut data).unwrap();
+        timestamp.encode(&mut data).unwrap();
+        data
+    }
+
+    /// Sign the message. Has to be done before the message gets encrypted,
+    /// and right before it gets published in an event.
+    pub fn sign(&mut self, secret: &SecretKey) {
+        let timestamp = Timestamp::current_time().0;
+        let sender = SenderSignature {
+            public: PublicKey::from_secret(*secret),
+            timestamp,
+            signature: secret.sign(&mut OsRng, &self.signing_data(timestamp)),
+        };
+        self.signature = bs58::encode(serialize(&sender)).into_string();
+    }
+
+    /// Return the public key the message is validly signed with, if any,
+    /// given the timestamp of the event carrying it. Has to be done after
+    /// the message got decrypted.
+    pub fn sender(&self, timestamp: Timestamp) -> Option<PublicKey> {
+        if self.signature.is_empty() {
+            return None
+        }
+
+        let bytes = bs58::decode(&self.signature).into_vec().ok()?;
+        let sender: SenderSignature = deserialize(&bytes).ok()?;
+
+        // Copying a signed message into a new event doesn't make it recent
+        if sender.timestamp.abs_diff(timestamp.0) > MAX_SIGNATURE_SKEW {
+            return None
+        }
+
+        if !sender.public.verify(&self.signing_data(sender.timestamp), &sender.signature) {
+            return None
+        }
+
+        Some(sender.public)
+    }
+
+    /// Verify the message is signed with the given public key, given the
+    /// timestamp of the event carrying it. Has to be done after the message
+    /// got decrypted.
+    pub fn verify(&self, public: &PublicKey, timestamp: Timestamp) -> bool {
+        self.sender(timestamp) == Some(*public)
+    }
+
+    /// Render the message as an IRC PRIVMSG coming from `nick!user@host`
+    pub fn to_irc_string(&self, user: &str, host: &str) -> String {
+        format!(":{}!{}@{} PRIVMSG {} :{}\r\n", self.nick, user, host, self.target, self.msg)
+    }
+}
+
+/// Short, human comparable fingerprint of a public key
+pub fn fingerprint(public: &PublicKey) -> String {
+    public.to_string().chars().take(FINGERPRINT_LENGTH).collect()
+}
+
+/// Check a string is shaped like a key fingerprint
+pub fn is_fingerprint(s: &str) -> bool {
+    s.len() == FINGERPRINT_LENGTH && bs58::decode(s).into_vec().is_ok()
 }
 
 impl std::string::ToString for PrivMsgEvent {
     fn to_string(&self) -> String {
-        format!(":{}!anon@dark.fi PRIVMSG {} :{}\r\n", self.nick, self.target, self.msg)
+        self.to_irc_string("anon", "dark.fi")
     }
 }
 
@@ -38,6 +143,12 @@ impl EventMsg for PrivMsgEvent {
             nick: "root".to_string(),
             msg: "Let there be dark".to_string(),
             target: "root".to_string(),
+            signature: String::new(),
+            relayed: false,
         }
     }
+
+    fn feature() -> Option<&'static str> {
+        Some(SIGNED_PRIVMSG_FEATURE)
+    }
 }
@@ -0,0 +1,390 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Double ratchet sessions used for forward-secret direct messages.
+//!
+//! This follows the Signal double ratchet with header encryption, using
+//! Diffie-Hellman over Pallas for the DH ratchet, BLAKE3 as the KDF, and
+//! NaCl boxes for encryption. Instead of trial-decrypting headers with
+//! the current and next header keys, every message carries a tag derived
+//! from its header key and number, so a recipient can find the messages
+//! meant for it with a lookup in the tags it expects.
+
+use darkfi_sdk::crypto::{diffie_hellman::sapling_ka_agree, Keypair, PublicKey};
+use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
+use rand::rngs::OsRng;
+
+use crate::crypto::{encrypt, salt_box_from_key, try_decrypt_bytes};
+
+/// Maximum number of message keys skipped within a single chain
+pub const MAX_SKIP: u64 = 64;
+
+/// Maximum number of skipped message keys kept around for late messages
+const MAX_SKIPPED_KEYS: usize = 1000;
+
+pub type Key = [u8; 32];
+
+/// Tag identifying the session and message key of a message
+pub type Tag = [u8; 32];
+
+/// Ratchet message header, encrypted with the header key of its chain
+#[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
+struct Header {
+    /// Current ratchet public key of the sender
+    public: PublicKey,
+    /// Number of messages in the sender's previous sending chain
+    pn: u64,
+    /// Message number in the sender's current sending chain
+    n: u64,
+}
+
+/// Message key kept for a message that hasn't arrived yet
+#[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
+struct SkippedKey {
+    tag: Tag,
+    mk: Key,
+}
+
+/// An encrypted ratchet message
+#[derive(Clone, Debug)]
+pub struct RatchetMessage {
+    pub tag: Tag,
+    /// bs58 encoded encrypted header
+    pub header: String,
+    /// bs58 encoded encrypted payload
+    pub ciphertext: String,
+}
+
+/// Double ratchet session state with a single contact
+#[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
+pub struct Ratchet {
+    /// Our current ratchet keypair
+    dhs: Keypair,
+    /// Their current ratchet public key
+    dhr: Option<PublicKey>,
+    /// Root key
+    rk: Key,
+    /// Sending and receiving chain keys
+    cks: Option<Key>,
+    ckr: Option<Key>,
+    /// Sending and receiving header keys
+    hks: Option<Key>,
+    hkr: Option<Key>,
+    /// Next sending and receiving header keys
+    nhks: Key,
+    nhkr: Key,
+    /// Message numbers for sending and receiving
+    ns: u64,
+    nr: u64,
+    /// Number of messages in the previous sending chain
+    pn: u64,
+    /// Message keys of skipped messages
+    skipped: Vec<SkippedKey>,
+}
+
+impl Ratchet {
+    /// Initialize the session of the handshake initiator, given its
+    /// handshake keypair and the ratchet public key of the responder.
+    pub fn init_initiator(handshake: &Keypair, responder: &PublicKey) -> Self {
+        let (sk, hka, nhkb) = kdf_handshake(&dh(handshake, responder));
+        let dhs = Keypair::random(&mut OsRng);
+        let (rk, cks, nhks) = kdf_rk(&sk, &dh(&dhs, responder));
+
+        Self {
+            dhs,
+            dhr: Some(*responder),
+            rk,
+            cks: Some(cks),
+            ckr: None,
+            hks: Some(hka),
+            hkr: None,
+            nhks,
+            nhkr: nhkb,
+            ns: 0,
+            nr: 0,
+            pn: 0,
+            skipped: vec![],
+        }
+    }
+
+    /// Initialize the session of the handshake responder, given its
+    /// ratchet keypair and the handshake public key of the initiator.
+    /// The responder can only send once the first message of the
+    /// initiator got through.
+    pub fn init_responder(keypair: Keypair, initiator: &PublicKey) -> Self {
+        let (sk, hka, nhkb) = kdf_handshake(&dh(&keypair, initiator));
+
+        Self {
+            dhs: keypair,
+            dhr: None,
+            rk: sk,
+            cks: None,
+            ckr: None,
+            hks: None,
+            hkr: None,
+            nhks: nhkb,
+            nhkr: hka,
+            ns: 0,
+            nr: 0,
+            pn: 0,
+            skipped: vec![],
+        }
+    }
+
+    /// Whether the session can be used to send messages
+    pub fn can_send(&self) -> bool {
+        self.cks.is_some()
+    }
+
+    /// Encrypt a message, advancing the sending chain
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Option<RatchetMessage> {
+        let (Some(cks), Some(hks)) = (self.cks, self.hks) else { return None };
+
+        let (cks, mk) = kdf_ck(&cks);
+        let header = Header { public: self.dhs.public, pn: self.pn, n: self.ns };
+
+        let message = RatchetMessage {
+            tag: tag(&hks, self.ns),
+            header: encrypt(&salt_box_from_key(header_key(&hks)), &serialize(&header)),
+            ciphertext: encrypt(&salt_box_from_key(mk), plaintext),
+        };
+
+        self.cks = Some(cks);
+        self.ns += 1;
+
+        Some(message)
+    }
+
+    /// Tags of the messages this session can currently receive
+    pub fn expected_tags(&self) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = self.skipped.iter().map(|x| x.tag).collect();
+
+        if let Some(hkr) = &self.hkr {
+            tags.extend((self.nr..self.nr + MAX_SKIP).map(|n| tag(hkr, n)));
+        }
+
+        tags.extend((0..MAX_SKIP).map(|n| tag(&self.nhkr, n)));
+        tags
+    }
+
+    /// Decrypt a message. The session is only updated if decryption
+    /// succeeds, and each message can only be decrypted once.
+    pub fn decrypt(&mut self, message: &RatchetMessage) -> Option<Vec<u8>> {
+        if let Some(idx) = self.skipped.iter().position(|x| x.tag == message.tag) {
+            let mk = self.skipped[idx].mk;
+            let plaintext = try_decrypt_bytes(&salt_box_from_key(mk), &message.ciphertext)?;
+            self.skipped.remove(idx);
+            return Some(plaintext)
+        }
+
+        let mut state = self.clone();
+        let plaintext = state.decrypt_new(message)?;
+        *self = state;
+
+        Some(plaintext)
+    }
+
+    fn decrypt_new(&mut self, message: &RatchetMessage) -> Option<Vec<u8>> {
+        let nr = self.nr;
+        let current =
+            self.hkr.filter(|hkr| (nr..nr + MAX_SKIP).any(|n| tag(hkr, n) == message.tag));
+
+        let hk = match current {
+            Some(hkr) => hkr,
+            None if (0..MAX_SKIP).any(|n| tag(&self.nhkr, n) == message.tag) => self.nhkr,
+            None => return None,
+        };
+
+        let header = try_decrypt_bytes(&salt_box_from_key(header_key(&hk)), &message.header)?;
+        let header: Header = deserialize(&header).ok()?;
+        if tag(&hk, header.n) != message.tag {
+            return None
+        }
+
+        // A message from the next chain means the sender ratcheted forward
+        if current.is_none() {
+            self.skip(header.pn)?;
+            self.dh_ratchet(&header);
+        }
+
+        self.skip(header.n)?;
+        let (ckr, mk) = kdf_ck(&self.ckr?);
+        self.ckr = Some(ckr);
+        self.nr += 1;
+
+        try_decrypt_bytes(&salt_box_from_key(mk), &message.ciphertext)
+    }
+
+    /// Store the message keys of the current receiving chain up to `until`
+    fn skip(&mut self, until: u64) -> Option<()> {
+        if self.nr + MAX_SKIP < until {
+            return None
+        }
+
+        if let (Some(mut ckr), Some(hkr)) = (self.ckr, self.hkr) {
+            while self.nr < until {
+                let (next, mk) = kdf_ck(&ckr);
+                self.skipped.push(SkippedKey { tag: tag(&hkr, self.nr), mk });
+                ckr = next;
+                self.nr += 1;
+            }
+            self.ckr = Some(ckr);
+        }
+
+        if self.skipped.len() > MAX_SKIPPED_KEYS {
+            let excess = self.skipped.len() - MAX_SKIPPED_KEYS;
+            self.skipped.drain(..excess);
+        }
+
+        Some(())
+    }
+
+    fn dh_ratchet(&mut self, header: &Header) {
+        self.pn = self.ns;
+        self.ns = 0;
+        self.nr = 0;
+        self.hks = Some(self.nhks);
+        self.hkr = Some(self.nhkr);
+        self.dhr = Some(header.public);
+
+        let (rk, ckr, nhkr) = kdf_rk(&self.rk, &dh(&self.dhs, &header.public));
+        self.rk = rk;
+        self.ckr = Some(ckr);
+        self.nhkr = nhkr;
+
+        self.dhs = Keypair::random(&mut OsRng);
+        let (rk, cks, nhks) = kdf_rk(&self.rk, &dh(&self.dhs, &header.public));
+        self.rk = rk;
+        self.cks = Some(cks);
+        self.nhks = nhks;
+    }
+}
+
+/// Tag of message number `n` of the chain using header key `hk`
+pub fn tag(hk: &Key, n: u64) -> Tag {
+    *blake3::keyed_hash(hk, &n.to_le_bytes()).as_bytes()
+}
+
+fn dh(keypair: &Keypair, public: &PublicKey) -> Key {
+    sapling_ka_agree(&keypair.secret, public).to_bytes()
+}
+
+/// Derive three keys from the given context and input keying material
+fn kdf3(context: &str, ikm: &[&[u8]]) -> (Key, Key, Key) {
+    let mut hasher = blake3::Hasher::new_derive_key(context);
+    for i in ikm {
+        hasher.update(i);
+    }
+
+    let mut okm = [0u8; 96];
+    hasher.finalize_xof().fill(&mut okm);
+
+    (okm[..32].try_into().unwrap(), okm[32..64].try_into().unwrap(), okm[64..].try_into().unwrap())
+}
+
+/// Shared secret, initiator header key and responder next header key
+/// derived from the handshake
+fn kdf_handshake(dh_out: &Key) -> (Key, Key, Key) {
+    kdf3("darkirc ratchet handshake", &[dh_out])
+}
+
+/// Root key, chain key and next header key derived from a DH ratchet step
+fn kdf_rk(rk: &Key, dh_out: &Key) -> (Key, Key, Key) {
+    kdf3("darkirc ratchet root", &[rk, dh_out])
+}
+
+/// Next chain key and message key derived from a chain key
+fn kdf_ck(ck: &Key) -> (Key, Key) {
+    (*blake3::keyed_hash(ck, &[0x02]).as_bytes(), *blake3::keyed_hash(ck, &[0x01]).as_bytes())
+}
+
+/// Key used to encrypt headers, kept apart from the one deriving tags
+fn header_key(hk: &Key) -> Key {
+    blake3::derive_key("darkirc ratchet header", hk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the handshake, returning the initiator and responder sessions
+    fn handshake() -> (Ratchet, Ratchet) {
+        let init = Keypair::random(&mut OsRng);
+        let reply = Keypair::random(&mut OsRng);
+        let bob = Ratchet::init_responder(reply, &init.public);
+        let alice = Ratchet::init_initiator(&init, &reply.public);
+        (alice, bob)
+    }
+
+    #[test]
+    fn test_ratchet_conversation() {
+        let (mut alice, mut bob) = handshake();
+        assert!(alice.can_send());
+        assert!(!bob.can_send());
+
+        let m0 = alice.encrypt(b"hi bob").unwrap();
+        assert!(bob.expected_tags().contains(&m0.tag));
+        assert_eq!(bob.decrypt(&m0).unwrap(), b"hi bob");
+        assert!(bob.can_send());
+
+        // Messages can't be decrypted twice
+        assert!(bob.decrypt(&m0).is_none());
+
+        let m1 = bob.encrypt(b"hi alice").unwrap();
+        assert_eq!(alice.decrypt(&m1).unwrap(), b"hi alice");
+
+        let m2 = alice.encrypt(b"how are you?").unwrap();
+        assert_eq!(bob.decrypt(&m2).unwrap(), b"how are you?");
+    }
+
+    #[test]
+    fn test_ratchet_out_of_order() {
+        let (mut alice, mut bob) = handshake();
+
+        let m0 = alice.encrypt(b"0").unwrap();
+        let m1 = alice.encrypt(b"1").unwrap();
+        let m2 = alice.encrypt(b"2").unwrap();
+
+        assert_eq!(bob.decrypt(&m2).unwrap(), b"2");
+        let b0 = bob.encrypt(b"b0").unwrap();
+        assert_eq!(alice.decrypt(&b0).unwrap(), b"b0");
+        let m3 = alice.encrypt(b"3").unwrap();
+
+        assert_eq!(bob.decrypt(&m3).unwrap(), b"3");
+        assert_eq!(bob.decrypt(&m0).unwrap(), b"0");
+        assert_eq!(bob.decrypt(&m1).unwrap(), b"1");
+    }
+
+    #[test]
+    fn test_ratchet_rejects_foreign_messages() {
+        let (mut alice, _) = handshake();
+        let (_, mut eve) = handshake();
+
+        let m0 = alice.encrypt(b"hi bob").unwrap();
+        assert!(!eve.expected_tags().contains(&m0.tag));
+        assert!(eve.decrypt(&m0).is_none());
+
+        // A failed decryption leaves the session untouched
+        let (mut alice, mut bob) = handshake();
+        let mut m0 = alice.encrypt(b"hi bob").unwrap();
+        let ciphertext = std::mem::take(&mut m0.ciphertext);
+        assert!(bob.decrypt(&m0).is_none());
+        m0.ciphertext = ciphertext;
+        assert_eq!(bob.decrypt(&m0).unwrap(), b"hi bob");
+    }
+}
@@ -29,9 +29,12 @@ use darkfi::{
     },
 };
 
+use crate::irc::ConfServ;
+
 pub struct JsonRpcInterface {
     pub addr: Url,
     pub p2p: net::P2pPtr,
+    pub confserv: ConfServ,
 }
 
 #[async_trait]
@@ -46,6 +49,11 @@ impl RequestHandler for JsonRpcInterface {
         match req.method.as_str() {
             Some("ping") => self.pong(req.id, req.params).await,
             Some("get_info") => self.get_info(req.id, req.params).await,
+            Some("create_channel") => self.create_channel(req.id, req.params).await,
+            Some("join_channel") => self.join_channel(req.id, req.params).await,
+            Some("get_invite") => self.get_invite(req.id, req.params).await,
+            Some("add_contact") => self.add_contact(req.id, req.params).await,
+            Some("get_pubkey") => self.get_pubkey(req.id, req.params).await,
             Some(_) | None => JsonError::new(ErrorCode::MethodNotFound, None, req.id).into(),
         }
     }
@@ -68,4 +76,164 @@ impl JsonRpcInterface {
         let resp = self.p2p.get_info().await;
         JsonResponse::new(resp, id).into()
     }
+
+    // RPCAPI:
+    // Creates a channel with a freshly generated secret and saves it to the
+    // config. Returns the invite to share with the other members.
+    // --> {"jsonrpc": "2.0", "method": "create_channel", "params": ["#foo", "topic"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "darkirc:#foo:7CkVuFgw...", "id": 1}
+    async fn create_channel(&self, id: Value, params: Value) -> JsonResult {
+        let params = params.as_array().unwrap();
+        if params.is_empty() || params.len() > 2 || !params.iter().all(|x| x.is_string()) {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        }
+
+        let name = params[0].as_str().unwrap();
+        let topic = params.get(1).map(|x| x.as_str().unwrap().to_string());
+
+        match self.confserv.create_channel(name, topic).await {
+            Ok(invite) => JsonResponse::new(json!(invite), id).into(),
+            Err(e) => JsonError::new(ErrorCode::InvalidParams, Some(e.to_string()), id).into(),
+        }
+    }
+
+    // RPCAPI:
+    // Adds the channel from an invite to the config. Returns the channel name.
+    // --> {"jsonrpc": "2.0", "method": "join_channel", "params": ["darkirc:#foo:7CkVuFgw..."], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "#foo", "id": 1}
+    async fn join_channel(&self, id: Value, params: Value) -> JsonResult {
+        let params = params.as_array().unwrap();
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        }
+
+        match self.confserv.join_channel(params[0].as_str().unwrap()).await {
+            Ok(name) => JsonResponse::new(json!(name), id).into(),
+            Err(e) => JsonError::new(ErrorCode::InvalidParams, Some(e.to_string()), id).into(),
+        }
+    }
+
+    // RPCAPI:
+    // Exports the invite of a configured channel.
+    // --> {"jsonrpc": "2.0", "method": "get_invite", "params": ["#foo"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "darkirc:#foo:7CkVuFgw...", "id": 1}
+    async fn get_invite(&self, id: Value, params: Value) -> JsonResult {
+        let params = params.as_array().unwrap();
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        }
+
+        match self.confserv.export_invite(params[0].as_str().unwrap()).await {
+            Ok(invite) => JsonResponse::new(json!(invite), id).into(),
+            Err(e) => JsonError::new(ErrorCode::InvalidParams, Some(e.to_string()), id).into(),
+        }
+    }
+
+    // RPCAPI:
+    // Adds a contact with its public key to the config. Returns our public
+    // key to give to the contact.
+    // --> {"jsonrpc": "2.0", "method": "add_contact", "params": ["bob", "C9vC6HND..."], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "7CkVuFgw...", "id": 1}
+    async fn add_contact(&self, id: Value, params: Value) -> JsonResult {
+        let params = params.as_array().unwrap();
+        if params.len() != 2 || !params[0].is_string() || !params[1].is_string() {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        }
+
+        let name = params[0].as_str().unwrap();
+        let pubkey = params[1].as_str().unwrap();
+        match self.confserv.add_contact(name, pubkey).await {
+            Ok(public) => JsonResponse::new(json!(public), id).into(),
+            Err(e) => JsonError::new(ErrorCode::InvalidParams, Some(e.to_string()), id).into(),
+        }
+    }
+
+    // RPCAPI:
+    // Returns our public key to give to contacts, generating a private key
+    // in the config if there is none.
+    // --> {"jsonrpc": "2.0", "method": "get_pubkey", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "7CkVuFgw...", "id": 1}
+    async fn get_pubkey(&self, id: Value, _params: Value) -> JsonResult {
+        match self.confserv.public_key().await {
+            Ok(public) => JsonResponse::new(json!(public), id).into(),
+            Err(e) => JsonError::new(ErrorCode::InternalError, Some(e.to_string()), id).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi::system::Subscriber;
+    use structopt::StructOpt;
+
+    use super::*;
+    use crate::settings::Args;
+
+    /// RPC interface over a fresh, empty configuration file
+    async fn interface(name: &str) -> JsonRpcInterface {
+        let path =
+            std::env::temp_dir().join(format!("darkirc_rpc_{}_{}.toml", name, std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let settings = Args::from_iter(["darkirc", "--config", path.to_str().unwrap()]);
+
+        JsonRpcInterface {
+            addr: Url::parse("tcp://127.0.0.1:0").unwrap(),
+            p2p: net::P2p::new(net::Settings::default()).await,
+            confserv: ConfServ::new(settings, Subscriber::new()),
+        }
+    }
+
+    /// Call a method, returning its result or error message
+    async fn call(rpc: &JsonRpcInterface, method: &str, params: Value) -> Result<Value, Value> {
+        match rpc.handle_request(JsonRequest::new(method, params)).await {
+            JsonResult::Response(resp) => Ok(resp.result),
+            JsonResult::Error(err) => Err(err.error.message),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_rpc_invites() {
+        smol::block_on(async {
+            let alice = interface("invite_alice").await;
+            let bob = interface("invite_bob").await;
+
+            let invite =
+                call(&alice, "create_channel", json!(["#foo", "Foo stuff"])).await.unwrap();
+            assert_eq!(call(&alice, "get_invite", json!(["#foo"])).await.unwrap(), invite);
+
+            assert_eq!(call(&bob, "join_channel", json!([invite])).await.unwrap(), json!("#foo"));
+            assert_eq!(call(&bob, "get_invite", json!(["#foo"])).await.unwrap(), invite);
+
+            // Errors carry the reason
+            let err = call(&bob, "join_channel", json!(["foo"])).await.unwrap_err();
+            assert_eq!(err, json!("Invalid invite foo"));
+            assert!(call(&bob, "get_invite", json!(["#bar"])).await.is_err());
+            assert!(call(&bob, "create_channel", json!([])).await.is_err());
+            assert!(call(&bob, "create_channel", json!(["#bar", 42])).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_rpc_contacts() {
+        smol::block_on(async {
+            let alice = interface("contact_alice").await;
+            let bob = interface("contact_bob").await;
+
+            // Each side adds the other by the key it got from them
+            let alice_public = call(&alice, "get_pubkey", json!([])).await.unwrap();
+            let bob_public = call(&bob, "get_pubkey", json!([])).await.unwrap();
+            assert_eq!(
+                call(&alice, "add_contact", json!(["bob", bob_public])).await.unwrap(),
+                alice_public
+            );
+            assert_eq!(
+                call(&bob, "add_contact", json!(["alice", alice_public])).await.unwrap(),
+                bob_public
+            );
+
+            assert!(call(&alice, "add_contact", json!(["bob"])).await.is_err());
+            assert!(call(&alice, "add_contact", json!(["bob", "foo"])).await.is_err());
+        });
+    }
 }
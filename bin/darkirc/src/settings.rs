@@ -17,24 +17,34 @@
  */
 
 use crypto_box::SalsaBox;
+use darkfi_sdk::crypto::PublicKey;
 use log::{info, warn};
 use serde::{self, Deserialize};
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 use structopt::StructOpt;
 use structopt_toml::StructOptToml;
 use toml::Value;
 use url::Url;
 
-use darkfi::{net::settings::SettingsOpt, Result};
+use darkfi::{event_graph::retention::RetentionOpt, net::settings::SettingsOpt, Error, Result};
 
 // Location for config file
 pub const CONFIG_FILE: &str = "darkirc_config.toml";
 pub const CONFIG_FILE_CONTENTS: &str = include_str!("../darkirc_config.toml");
 
+// P2P network identifier used unless configured otherwise
+pub const NETWORK_ID: &str = "darkirc";
+
 // Msg config
 pub const MAXIMUM_LENGTH_OF_MESSAGE: usize = 1024;
 pub const MAXIMUM_LENGTH_OF_NICK_CHAN_CNT: usize = 32;
 
+// Maximum number of messages returned by a CHATHISTORY request
+pub const MAXIMUM_CHATHISTORY_LIMIT: usize = 100;
+
 // IRC Client
 pub enum RPL {
     NoTopic = 331,
@@ -56,6 +66,10 @@ pub struct Args {
     #[structopt(long = "rpc", default_value = "tcp://127.0.0.1:26660")]
     pub rpc_listen: Url,
 
+    /// Prometheus metrics listen URL
+    #[structopt(long)]
+    pub metrics_listen: Option<Url>,
+
     /// IRC listen URL
     #[structopt(long = "irc", default_value = "tcp://127.0.0.1:6667")]
     pub irc_listen: Url,
@@ -82,10 +96,22 @@ pub struct Args {
     #[structopt(long)]
     pub password: Option<String>,
 
+    /// Path to the NickServ accounts and registrations database
+    #[structopt(long, default_value = "~/.local/darkfi/darkirc_db")]
+    pub datastore: String,
+
+    /// Path where shared files and downloads are stored
+    #[structopt(long, default_value = "~/.local/darkfi/darkirc_files")]
+    pub files_path: String,
+
     /// Network settings
     #[structopt(flatten)]
     pub net: SettingsOpt,
 
+    /// Event graph retention settings
+    #[structopt(flatten)]
+    pub dag: RetentionOpt,
+
     /// Increase verbosity
     #[structopt(short, parse(from_occurrences))]
     pub verbose: u8,
@@ -102,11 +128,13 @@ pub struct Args {
 pub struct ContactInfo {
     /// Optional NaCl box for the channel, used for {en,de}cryption.
     pub salt_box: Option<SalsaBox>,
+    /// Public key of the contact the box is made with
+    pub public: Option<crypto_box::PublicKey>,
 }
 
 impl ContactInfo {
     pub fn new() -> Result<Self> {
-        Ok(Self { salt_box: None })
+        Ok(Self { salt_box: None, public: None })
     }
 }
 
@@ -116,6 +144,7 @@ impl ContactInfo {
 /// [channel."#dev"]
 /// secret = "GvH4kno3kUu6dqPrZ8zjMhqxTUDZ2ev16EdprZiZJgj1"
 /// topic = "DarkFi Development Channel"
+/// operators = ["5hmnVQmUeXZcEE9dRfQKVdDjnZtvzsz3f7vasGLhZkiN"]
 /// ```
 /// Having a secret will enable a NaCl box that is able to encrypt and
 /// decrypt messages in this channel using this set shared secret.
@@ -123,6 +152,8 @@ impl ContactInfo {
 /// Having a topic set is useful if one wants to have a topic in the
 /// configured channel. It is not shared with others, but it is useful
 /// for personal reference.
+/// Operators are the NickServ public keys whose moderation and secret
+/// rotations we respect in this channel.
 #[derive(Default, Clone)]
 pub struct ChannelInfo {
     /// Optional topic for the channel
@@ -133,23 +164,122 @@ pub struct ChannelInfo {
     pub joined: bool,
     /// All nicknames which are visible on the channel
     pub names: Vec<String>,
+    /// Keys allowed to moderate the channel and rotate its secret
+    pub operators: Vec<PublicKey>,
+    /// Key fingerprints muted by the channel operators
+    pub muted: HashSet<String>,
 }
 
 impl ChannelInfo {
     pub fn new() -> Result<Self> {
-        Ok(Self { topic: None, salt_box: None, joined: false, names: vec![] })
+        Ok(Self {
+            topic: None,
+            salt_box: None,
+            joined: false,
+            names: vec![],
+            operators: vec![],
+            muted: HashSet::new(),
+        })
     }
 }
 
-/// Parse a TOML string for any configured channels and return
-/// a map containing said configurations.
+/// This struct holds the settings of a profile, used to serve several
+/// users with their own channels, contacts and NickServ accounts from
+/// one darkirc. Clients pick their profile with its password.
+/// In the TOML configuration file, we can configure profiles as such:
 ///
 /// ```toml
-/// [channel."#memes"]
+/// [profile."alice"]
+/// password = "CHANGE_ME"
+/// autojoin = ["#dev"]
+///
+/// [profile."alice".channel."#secret"]
 /// secret = "7CkVuFgwTUpJn5Sv67Q3fyEDpa28yrSeL5Hg2GqQ4jfM"
-/// topic = "Dank Memes"
 /// ```
-pub fn parse_configured_channels(data: &str) -> Result<HashMap<String, ChannelInfo>> {
+#[derive(Clone)]
+pub struct ProfileInfo {
+    /// Password the clients of this profile connect with
+    pub password: String,
+    /// Channels to autojoin for the clients of this profile
+    pub autojoin: Vec<String>,
+}
+
+/// Parse a TOML string for any configured profiles and return
+/// a map containing said configurations. Clients are told apart by
+/// their password only, so passwords shared by several profiles, or
+/// with the top level configuration, are refused.
+pub fn parse_configured_profiles(data: &str) -> Result<HashMap<String, ProfileInfo>> {
+    let mut ret = HashMap::new();
+
+    let map = match toml::from_str(data)? {
+        Value::Table(m) => m,
+        _ => return Ok(ret),
+    };
+
+    let Some(profiles) = map.get("profile").and_then(|x| x.as_table()) else { return Ok(ret) };
+
+    let mut owners = HashMap::new();
+    if let Some(password) = map.get("password").and_then(|x| x.as_str()) {
+        owners.insert(password, "the top level configuration".to_string());
+    }
+
+    for (name, profile) in profiles {
+        let Some(password) = profile.get("password").and_then(|x| x.as_str()) else {
+            warn!("Profile {} doesn't have a `password` set, skipping...", name);
+            continue
+        };
+
+        if password.is_empty() {
+            warn!("Profile {} has an empty password, skipping...", name);
+            continue
+        }
+
+        if let Some(owner) = owners.insert(password, format!("profile {}", name)) {
+            return Err(Error::Custom(format!(
+                "Profile {} has the same password as {}",
+                name, owner
+            )))
+        }
+
+        let autojoin = match profile.get("autojoin").and_then(|x| x.as_array()) {
+            Some(chans) => chans.iter().filter_map(|x| x.as_str()).map(String::from).collect(),
+            None => vec![],
+        };
+
+        info!("Found configuration for profile {}", name);
+        ret.insert(name.to_string(), ProfileInfo { password: password.to_string(), autojoin });
+    }
+
+    Ok(ret)
+}
+
+/// This struct holds the settings of a bridge relaying darkirc channels
+/// to the channels of an upstream IRC network, such as Libera.
+/// In the TOML configuration file, we can configure bridges as such:
+///
+/// ```toml
+/// [bridge."libera"]
+/// upstream = "tcp://irc.libera.chat:6667"
+/// nick = "darkirc-bridge"
+/// channels = { "#dev" = "#darkfi-dev" }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BridgeInfo {
+    /// URL of the upstream IRC server
+    pub upstream: Url,
+    /// Nick of the bridge on the upstream network
+    pub nick: String,
+    /// Optional password of the upstream server
+    pub password: Option<String>,
+    /// Prefix added to the nicks relayed from upstream
+    pub nick_prefix: String,
+    /// Bridged channels, `darkirc channel => upstream channel`
+    pub channels: HashMap<String, String>,
+}
+
+/// Parse a TOML string for any configured bridges and return
+/// a map containing said configurations.
+pub fn parse_configured_bridges(data: &str) -> Result<HashMap<String, BridgeInfo>> {
     let mut ret = HashMap::new();
 
     let map = match toml::from_str(data)? {
@@ -157,6 +287,79 @@ pub fn parse_configured_channels(data: &str) -> Result<HashMap<String, ChannelIn
         _ => return Ok(ret),
     };
 
+    let Some(bridges) = map.get("bridge").and_then(|x| x.as_table()) else { return Ok(ret) };
+
+    for (name, bridge) in bridges {
+        let upstream = bridge.get("upstream").and_then(|x| x.as_str()).map(Url::parse);
+        let Some(Ok(upstream)) = upstream else {
+            warn!("Bridge {} doesn't have a valid `upstream` set, skipping...", name);
+            continue
+        };
+
+        let Some(nick) = bridge.get("nick").and_then(|x| x.as_str()) else {
+            warn!("Bridge {} doesn't have a `nick` set, skipping...", name);
+            continue
+        };
+
+        let password = bridge.get("password").and_then(|x| x.as_str()).map(String::from);
+
+        let nick_prefix = match bridge.get("nick_prefix").and_then(|x| x.as_str()) {
+            Some(prefix) => prefix.to_string(),
+            None => format!("{}|", name),
+        };
+
+        let mut channels = HashMap::new();
+        if let Some(chans) = bridge.get("channels").and_then(|x| x.as_table()) {
+            for (chan, upstream_chan) in chans {
+                let Some(upstream_chan) = upstream_chan.as_str() else { continue };
+                if !chan.starts_with('#') || !upstream_chan.starts_with('#') {
+                    warn!("Bridge {} has an invalid channel {}, skipping...", name, chan);
+                    continue
+                }
+                channels.insert(chan.to_string(), upstream_chan.to_string());
+            }
+        }
+
+        info!("Found configuration for bridge {}", name);
+        ret.insert(
+            name.to_string(),
+            BridgeInfo { upstream, nick: nick.to_string(), password, nick_prefix, channels },
+        );
+    }
+
+    Ok(ret)
+}
+
+/// Return the TOML table holding the configuration of a profile, or
+/// the top level table when no profile is given.
+fn profile_table(data: &str, profile: Option<&str>) -> Result<Option<toml::value::Table>> {
+    let map = match toml::from_str(data)? {
+        Value::Table(m) => m,
+        _ => return Ok(None),
+    };
+
+    let Some(profile) = profile else { return Ok(Some(map)) };
+
+    Ok(map.get("profile").and_then(|x| x.get(profile)).and_then(|x| x.as_table()).cloned())
+}
+
+/// Parse a TOML string for any channels configured for the given profile
+/// (or the top level ones without a profile) and return a map containing
+/// said configurations.
+///
+/// ```toml
+/// [channel."#memes"]
+/// secret = "7CkVuFgwTUpJn5Sv67Q3fyEDpa28yrSeL5Hg2GqQ4jfM"
+/// topic = "Dank Memes"
+/// ```
+pub fn parse_configured_channels(
+    data: &str,
+    profile: Option<&str>,
+) -> Result<HashMap<String, ChannelInfo>> {
+    let mut ret = HashMap::new();
+
+    let Some(map) = profile_table(data, profile)? else { return Ok(ret) };
+
     if !map.contains_key("channel") {
         return Ok(ret)
     }
@@ -188,24 +391,51 @@ pub fn parse_configured_channels(data: &str) -> Result<HashMap<String, ChannelIn
             }
         }
 
+        if let Some(operators) = chan.1.get("operators").and_then(|x| x.as_array()) {
+            for operator in operators {
+                match operator.as_str().map(PublicKey::from_str) {
+                    Some(Ok(public)) => channel_info.operators.push(public),
+                    _ => warn!("Invalid operator key for channel {}, skipping...", chan.0),
+                }
+            }
+        }
+
         ret.insert(chan.0.to_string(), channel_info);
     }
 
     Ok(ret)
 }
 
-/// Parse a TOML string for any configured contact list and return
-/// a map containing said configurations.
+/// Parse a TOML string for the nicks and key fingerprints ignored by the
+/// given profile (or the top level ones without a profile).
+///
+/// ```toml
+/// ignore = ["spammer", "5hmnVQmUeXZc"]
+/// ```
+pub fn parse_configured_ignores(data: &str, profile: Option<&str>) -> Result<Vec<String>> {
+    let Some(map) = profile_table(data, profile)? else { return Ok(vec![]) };
+
+    let Some(ignored) = map.get("ignore").and_then(|x| x.as_array()) else { return Ok(vec![]) };
+
+    Ok(ignored.iter().filter_map(|x| x.as_str()).map(String::from).collect())
+}
+
+/// Parse a TOML string for any contact list configured for the given
+/// profile (or the top level one without a profile) and return a map
+/// containing said configurations.
 ///
 /// ```toml
 /// [contact."nick"]
 /// contact_pubkey = "7CkVuFgwTUpJn5Sv67Q3fyEDpa28yrSeL5Hg2GqQ4jfM"
 /// ```
-pub fn parse_configured_contacts(data: &str) -> Result<HashMap<String, ContactInfo>> {
+pub fn parse_configured_contacts(
+    data: &str,
+    profile: Option<&str>,
+) -> Result<HashMap<String, ContactInfo>> {
     let mut ret = HashMap::new();
 
-    let map = match toml::from_str(data) {
-        Ok(Value::Table(m)) => m,
+    let map = match profile_table(data, profile) {
+        Ok(Some(m)) => m,
         _ => {
             warn!("Invalid TOML string passed as argument to parse_configured_contacts()");
             return Ok(ret)
@@ -224,7 +454,7 @@ pub fn parse_configured_contacts(data: &str) -> Result<HashMap<String, ContactIn
     let contacts = map["contact"].as_table().unwrap();
 
     // Our secret key for NaCl boxes.
-    let found_priv = match parse_priv_key(data) {
+    let found_priv = match parse_priv_key(&map) {
         Ok(v) => v,
         Err(_) => {
             info!("Did not find private key in config, skipping contact configuration.");
@@ -293,6 +523,7 @@ pub fn parse_configured_contacts(data: &str) -> Result<HashMap<String, ContactIn
 
         let public = crypto_box::PublicKey::from(bytes);
         contact_info.salt_box = Some(SalsaBox::new(&public, &secret));
+        contact_info.public = Some(public);
         ret.insert(cnt.0.to_string(), contact_info);
         info!("Instantiated NaCl box for contact {}", cnt.0);
     }
@@ -307,14 +538,9 @@ fn salt_box_from_shared_secret(s: &str) -> Result<SalsaBox> {
     Ok(SalsaBox::new(&public, &secret))
 }
 
-fn parse_priv_key(data: &str) -> Result<String> {
+fn parse_priv_key(map: &toml::value::Table) -> Result<String> {
     let mut pk = String::new();
 
-    let map = match toml::from_str(data)? {
-        Value::Table(m) => m,
-        _ => return Ok(pk),
-    };
-
     if !map.contains_key("private_key") {
         return Ok(pk)
     }
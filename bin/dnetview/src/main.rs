@@ -16,12 +16,17 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{fs::File, io, io::Read};
+use std::{
+    fs::File,
+    io,
+    io::Read,
+    time::{Duration, Instant},
+};
 
 use async_std::sync::Arc;
 use clap::Parser;
 use easy_parallel::Parallel;
-use log::info;
+use log::{error, info};
 use simplelog::*;
 use smol::Executor;
 use termion::{async_stdin, event::Key, input::TermRead, raw::IntoRawMode};
@@ -41,6 +46,8 @@ pub mod error;
 pub mod model;
 pub mod options;
 pub mod parser;
+pub mod recorder;
+pub mod replay;
 pub mod rpc;
 pub mod util;
 pub mod view;
@@ -51,17 +58,41 @@ use crate::{
     model::Model,
     options::Args,
     parser::DataParser,
+    recorder::Recorder,
+    replay::Player,
     view::View,
 };
 
 struct DnetView {
     model: Arc<Model>,
     view: View,
+    /// Parser and player used when replaying a recording
+    replay: Option<(Arc<DataParser>, Player)>,
 }
 
 impl DnetView {
-    fn new(model: Arc<Model>, view: View) -> Self {
-        Self { model, view }
+    fn new(model: Arc<Model>, view: View, replay: Option<(Arc<DataParser>, Player)>) -> Self {
+        Self { model, view, replay }
+    }
+
+    /// Apply due replay frames to the model, rebuilding it from scratch
+    /// if we seeked backwards.
+    async fn step_replay(&mut self, elapsed: Duration, seek: i64) -> DnetViewResult<()> {
+        let Some((parser, player)) = &mut self.replay else { return Ok(()) };
+
+        if seek != 0 && player.seek(seek) {
+            self.model.reset().await;
+            self.view.reset();
+        }
+
+        for frame in player.advance(elapsed) {
+            if let Err(e) = parser.apply_frame(&frame).await {
+                error!("Failed applying replayed frame: {:?}", e);
+            }
+        }
+
+        self.view.status = Some(player.status());
+        Ok(())
     }
 
     async fn render_view<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> DnetViewResult<()> {
@@ -72,7 +103,15 @@ impl DnetView {
         self.view.id_menu.state.select(Some(0));
         self.view.msg_list.state.select(Some(0));
 
+        let mut last_tick = Instant::now();
+        let mut seek = 0;
+
         loop {
+            let now = Instant::now();
+            self.step_replay(now - last_tick, seek).await?;
+            last_tick = now;
+            seek = 0;
+
             self.view.update(
                 self.model.msg_map.lock().await.clone(),
                 self.model.selectables.lock().await.clone(),
@@ -117,6 +156,23 @@ impl DnetView {
                         // TODO
                         //view.msg_list.previous();
                     }
+                    Key::Char(' ') => {
+                        if let Some((_, player)) = &mut self.replay {
+                            player.paused = !player.paused;
+                        }
+                    }
+                    Key::Char('l') => seek += 10,
+                    Key::Char('h') => seek -= 10,
+                    Key::Char('+') => {
+                        if let Some((_, player)) = &mut self.replay {
+                            player.speed = (player.speed * 2.0).min(64.0);
+                        }
+                    }
+                    Key::Char('-') => {
+                        if let Some((_, player)) = &mut self.replay {
+                            player.speed = (player.speed / 2.0).max(0.125);
+                        }
+                    }
                     _ => (),
                 }
             }
@@ -147,6 +203,24 @@ async fn main() -> DnetViewResult<()> {
 
     let config = Config::<DnvConfig>::load(config_path)?;
 
+    // Load the recording up front, so headless export never touches the terminal
+    let frames = match &args.replay {
+        Some(path) => Some(recorder::load(&expand_path(path)?)?),
+        None => None,
+    };
+
+    if let Some(export) = &args.export {
+        let frames = frames.as_ref().unwrap();
+        replay::export(frames, &expand_path(export)?).await?;
+        println!("Exported timelines of {} frames to {}", frames.len(), export);
+        return Ok(())
+    }
+
+    let recorder = match &args.record {
+        Some(path) => Some(Recorder::new(&expand_path(path)?)?),
+        None => None,
+    };
+
     let stdout = io::stdout().into_raw_mode()?;
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
@@ -159,8 +233,10 @@ async fn main() -> DnetViewResult<()> {
     let ex = Arc::new(Executor::new());
     let ex2 = ex.clone();
 
-    let mut dnetview = DnetView::new(model.clone(), view);
-    let parser = DataParser::new(model, config);
+    let parser = DataParser::new(model.clone(), config, recorder);
+    let replay = frames.map(|frames| (parser.clone(), Player::new(frames)));
+    let replaying = replay.is_some();
+    let mut dnetview = DnetView::new(model, view, replay);
 
     let nthreads = std::thread::available_parallelism().unwrap().get();
     let (signal, shutdown) = async_channel::unbounded::<()>();
@@ -169,7 +245,9 @@ async fn main() -> DnetViewResult<()> {
         .each(0..nthreads, |_| smol::future::block_on(ex.run(shutdown.recv())))
         .finish(|| {
             smol::future::block_on(async move {
-                parser.start_connect_slots(ex2).await?;
+                if !replaying {
+                    parser.start_connect_slots(ex2).await?;
+                }
                 dnetview.render_view(&mut terminal).await?;
                 drop(signal);
                 Ok(())
@@ -56,6 +56,13 @@ impl Model {
         let msg_log = Mutex::new(Vec::new());
         Arc::new(Model { msg_map, msg_log, selectables })
     }
+
+    /// Drop all parsed data, used when seeking backwards in a replay
+    pub async fn reset(&self) {
+        self.msg_map.lock().await.clear();
+        self.msg_log.lock().await.clear();
+        self.selectables.lock().await.clear();
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Eq)]
@@ -32,4 +32,16 @@ pub struct Args {
     /// Sets a custom config file
     #[clap(short, long)]
     pub config: Option<String>,
+
+    /// Record every polled snapshot to the given file
+    #[clap(long, conflicts_with = "replay")]
+    pub record: Option<String>,
+
+    /// Replay a recording offline instead of polling nodes
+    #[clap(long)]
+    pub replay: Option<String>,
+
+    /// Export per-node timelines of the replayed recording as JSON and exit
+    #[clap(long, requires = "replay")]
+    pub export: Option<String>,
 }
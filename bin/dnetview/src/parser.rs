@@ -33,6 +33,7 @@ use crate::{
         ConnectInfo, LilithInfo, Model, NetworkInfo, NodeInfo, SelectableObject, Session,
         SessionInfo,
     },
+    recorder::{Frame, Recorder},
     rpc::RpcConnect,
     util::{is_empty_session, make_connect_id, make_empty_id, make_node_id, make_session_id},
 };
@@ -40,11 +41,41 @@ use crate::{
 pub struct DataParser {
     model: Arc<Model>,
     config: DnvConfig,
+    recorder: Option<Recorder>,
 }
 
 impl DataParser {
-    pub fn new(model: Arc<Model>, config: DnvConfig) -> Arc<Self> {
-        Arc::new(Self { model, config })
+    pub fn new(model: Arc<Model>, config: DnvConfig, recorder: Option<Recorder>) -> Arc<Self> {
+        Arc::new(Self { model, config, recorder })
+    }
+
+    /// Record the frame if recording is enabled, then parse it into the model
+    async fn handle_frame(&self, frame: Frame) -> DnetViewResult<()> {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record(&frame) {
+                error!("Failed recording frame: {:?}", e);
+            }
+        }
+
+        self.apply_frame(&frame).await
+    }
+
+    /// Parse a polled (or replayed) frame into the model
+    pub async fn apply_frame(&self, frame: &Frame) -> DnetViewResult<()> {
+        let reply = match &frame.reply {
+            Some(reply) => reply,
+            None => return self.parse_offline(frame.node.clone()).await,
+        };
+
+        let reply = match reply.as_object() {
+            Some(reply) => reply,
+            None => return Err(DnetViewError::ValueIsNotObject),
+        };
+
+        match &frame.node_type {
+            NodeType::LILITH => self.parse_lilith_data(reply.clone(), frame.node.clone()).await,
+            _ => self.parse_data(reply, frame.node.clone()).await,
+        }
     }
 
     pub async fn start_connect_slots(self: Arc<Self>, ex: Arc<Executor<'_>>) -> DnetViewResult<()> {
@@ -72,7 +103,13 @@ impl DataParser {
                     error!("RPC client creation error: {:?}", e);
                 }
             }
-            self.parse_offline(node.name.clone()).await?;
+            let frame = Frame {
+                time: NanoTimestamp::current_time(),
+                node: node.name.clone(),
+                node_type: node.node_type.clone(),
+                reply: None,
+            };
+            self.handle_frame(frame).await?;
             async_util::sleep(2000).await;
         }
     }
@@ -98,16 +135,13 @@ impl DataParser {
                         return Err(DnetViewError::EmptyRpcReply)
                     }
 
-                    match &node.node_type {
-                        NodeType::LILITH => {
-                            self.parse_lilith_data(
-                                reply.as_object().unwrap().clone(),
-                                node.name.clone(),
-                            )
-                            .await?
-                        }
-                        _ => self.parse_data(reply.as_object().unwrap(), node.name.clone()).await?,
+                    let frame = Frame {
+                        time: NanoTimestamp::current_time(),
+                        node: node.name.clone(),
+                        node_type: node.node_type.clone(),
+                        reply: Some(reply),
                     };
+                    self.handle_frame(frame).await?;
                 }
                 Err(e) => return Err(e),
             }
@@ -0,0 +1,99 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use darkfi::util::time::NanoTimestamp;
+
+use crate::{config::NodeType, error::DnetViewResult};
+
+/// A single polled snapshot of a node. Recordings are a sequence of
+/// frames stored as JSON lines, in the order they were polled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Frame {
+    /// Time the snapshot was taken
+    pub time: NanoTimestamp,
+    /// Configured node name
+    pub node: String,
+    /// Configured node type, tells the parser how to read the reply
+    pub node_type: NodeType,
+    /// Raw RPC reply, `None` if the node was offline
+    pub reply: Option<Value>,
+}
+
+/// Appends polled frames to a recording file
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    /// Open given recording file for appending, creating it if needed
+    pub fn new(path: &Path) -> DnetViewResult<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    /// Write a frame to the recording
+    pub fn record(&self, frame: &Frame) -> DnetViewResult<()> {
+        let mut line = serde_json::to_string(frame)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Load all frames of a recording, ordered by time.
+/// Malformed lines (e.g. a truncated last line) are skipped.
+pub fn load(path: &Path) -> DnetViewResult<Vec<Frame>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut frames = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue
+        }
+
+        match serde_json::from_str::<Frame>(&line) {
+            Ok(frame) => frames.push(frame),
+            Err(e) => {
+                warn!(target: "dnetview", "Skipping malformed frame on line {}: {}", i + 1, e)
+            }
+        }
+    }
+
+    // Nodes are polled concurrently, so make sure we replay in order.
+    frames.sort_by_key(|frame| frame.time.0);
+    Ok(frames)
+}
@@ -0,0 +1,263 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use serde_json::{json, Value};
+
+use darkfi::util::time::NanoTimestamp;
+
+use crate::{
+    config::DnvConfig,
+    error::DnetViewResult,
+    model::{Model, SelectableObject},
+    parser::DataParser,
+    recorder::Frame,
+    util::make_node_id,
+};
+
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+/// Plays back a recording against a virtual clock
+pub struct Player {
+    frames: Vec<Frame>,
+    /// Index of the next frame to be applied
+    position: usize,
+    /// Current virtual time in nanoseconds
+    clock: u128,
+    /// Playback speed multiplier
+    pub speed: f64,
+    /// Whether playback is paused
+    pub paused: bool,
+}
+
+impl Player {
+    pub fn new(frames: Vec<Frame>) -> Self {
+        let clock = frames.first().map_or(0, |frame| frame.time.0);
+        Self { frames, position: 0, clock, speed: 1.0, paused: false }
+    }
+
+    fn start(&self) -> u128 {
+        self.frames.first().map_or(0, |frame| frame.time.0)
+    }
+
+    fn end(&self) -> u128 {
+        self.frames.last().map_or(0, |frame| frame.time.0)
+    }
+
+    /// Advance the virtual clock by the given wall-clock time, scaled by
+    /// the playback speed. Returns the frames that became due.
+    pub fn advance(&mut self, elapsed: Duration) -> Vec<Frame> {
+        if !self.paused {
+            let delta = (elapsed.as_nanos() as f64 * self.speed) as u128;
+            self.clock = (self.clock + delta).min(self.end());
+        }
+
+        self.due()
+    }
+
+    /// Move the virtual clock by the given amount of seconds. Returns `true`
+    /// when seeking backwards, in which case the caller has to reset its
+    /// state since all frames will be applied again from the start.
+    pub fn seek(&mut self, secs: i64) -> bool {
+        let delta = (secs.unsigned_abs() as f64 * NANOS_PER_SEC) as u128;
+
+        if secs >= 0 {
+            self.clock = (self.clock + delta).min(self.end());
+            return false
+        }
+
+        self.clock = self.clock.saturating_sub(delta).max(self.start());
+        self.position = 0;
+        true
+    }
+
+    /// Returns all not yet applied frames up to the current clock
+    fn due(&mut self) -> Vec<Frame> {
+        let begin = self.position;
+        while self.position < self.frames.len() && self.frames[self.position].time.0 <= self.clock {
+            self.position += 1;
+        }
+
+        self.frames[begin..self.position].to_vec()
+    }
+
+    /// Status line shown in the TUI
+    pub fn status(&self) -> String {
+        let elapsed = (self.clock - self.start()) as f64 / NANOS_PER_SEC;
+        let total = (self.end() - self.start()) as f64 / NANOS_PER_SEC;
+
+        format!(
+            "[REPLAY {}] {} | {:.0}s / {:.0}s | speed {}x | frame {}/{} | \
+             space: pause, h/l: seek -/+10s, -/+: speed",
+            if self.paused { "PAUSED" } else { "PLAYING" },
+            NanoTimestamp(self.clock),
+            elapsed,
+            total,
+            self.speed,
+            self.position,
+            self.frames.len(),
+        )
+    }
+}
+
+/// Build a per-node timeline from the parsed model state after a frame
+async fn timeline_entry(model: &Model, frame: &Frame) -> DnetViewResult<Value> {
+    let id = make_node_id(&frame.node)?;
+
+    let entry = match model.selectables.lock().await.get(&id) {
+        Some(SelectableObject::Node(node)) => {
+            let mut connections = vec![];
+            let mut messages = vec![];
+            for session in node.children.iter().filter(|s| !s.is_empty) {
+                for connect in session.children.iter().filter(|c| !c.is_empty) {
+                    connections.push(json!({
+                        "session": session.name,
+                        "addr": connect.addr,
+                        "remote_node_id": connect.remote_node_id,
+                    }));
+                    for (time, status, msg) in &connect.msg_log {
+                        messages.push(json!([time.0.to_string(), connect.addr, status, msg]));
+                    }
+                }
+            }
+
+            json!({
+                "time": frame.time.0.to_string(),
+                "online": !node.is_offline,
+                "state": node.state,
+                "connections": connections,
+                "messages": messages,
+            })
+        }
+
+        Some(SelectableObject::Lilith(lilith)) => {
+            let networks: BTreeMap<String, usize> =
+                lilith.networks.iter().map(|n| (n.name.clone(), n.nodes.len())).collect();
+
+            json!({
+                "time": frame.time.0.to_string(),
+                "online": true,
+                "networks": networks,
+            })
+        }
+
+        _ => json!({ "time": frame.time.0.to_string(), "online": false }),
+    };
+
+    Ok(entry)
+}
+
+/// Headless export of per-node timelines of a recording into a JSON file
+pub async fn export(frames: &[Frame], path: &Path) -> DnetViewResult<()> {
+    let model = Model::new();
+    let parser = DataParser::new(model.clone(), DnvConfig { nodes: vec![] }, None);
+
+    let mut timelines: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for frame in frames {
+        // Unparsable frames are kept in the timeline so gaps are visible
+        let entry = match parser.apply_frame(frame).await {
+            Ok(()) => timeline_entry(&model, frame).await?,
+            Err(e) => json!({
+                "time": frame.time.0.to_string(),
+                "error": format!("{:?}", e),
+            }),
+        };
+
+        timelines.entry(frame.node.clone()).or_default().push(entry);
+    }
+
+    std::fs::write(path, serde_json::to_string_pretty(&timelines)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NodeType;
+
+    const SEC: u128 = 1_000_000_000;
+
+    /// Player over frames polled at 1, 2, 3 and 4 seconds
+    fn player() -> Player {
+        let frames = (1..=4)
+            .map(|i| Frame {
+                time: NanoTimestamp(i * SEC),
+                node: format!("node{}", i),
+                node_type: NodeType::NORMAL,
+                reply: None,
+            })
+            .collect();
+
+        Player::new(frames)
+    }
+
+    fn nodes(frames: Vec<Frame>) -> Vec<String> {
+        frames.into_iter().map(|frame| frame.node).collect()
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut player = player();
+        assert_eq!(nodes(player.due()), vec!["node1"]);
+        assert!(player.due().is_empty());
+
+        assert_eq!(nodes(player.advance(Duration::from_millis(1500))), vec!["node2"]);
+
+        player.paused = true;
+        assert!(player.advance(Duration::from_secs(10)).is_empty());
+
+        // Speed scales the elapsed time, and the clock stops at the end
+        player.paused = false;
+        player.speed = 4.0;
+        assert_eq!(nodes(player.advance(Duration::from_secs(1))), vec!["node3", "node4"]);
+        assert_eq!(player.clock, 4 * SEC);
+        assert!(player.advance(Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut player = player();
+        assert!(!player.seek(1));
+        assert_eq!(nodes(player.due()), vec!["node1", "node2"]);
+
+        // Seeking past the end stops at the last frame
+        assert!(!player.seek(100));
+        assert_eq!(player.clock, 4 * SEC);
+        assert_eq!(nodes(player.due()), vec!["node3", "node4"]);
+
+        // Seeking back replays all frames up to the new clock
+        assert!(player.seek(-1));
+        assert_eq!(nodes(player.due()), vec!["node1", "node2", "node3"]);
+
+        // Seeking before the start stops at the first frame
+        assert!(player.seek(-100));
+        assert_eq!(player.clock, SEC);
+        assert_eq!(nodes(player.due()), vec!["node1"]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut player = Player::new(vec![]);
+        assert!(player.advance(Duration::from_secs(1)).is_empty());
+        assert!(!player.seek(10));
+        assert!(player.seek(-10));
+        assert!(player.due().is_empty());
+        assert_eq!(player.clock, 0);
+    }
+}
@@ -43,6 +43,8 @@ pub struct View {
     pub msg_list: MsgList,
     pub selectables: HashMap<String, SelectableObject>,
     pub ordered_list: Vec<String>,
+    /// Optional status line rendered above the panes
+    pub status: Option<String>,
 }
 
 impl Default for View {
@@ -59,7 +61,15 @@ impl<'a> View {
         let id_menu = IdMenu::new(Vec::new());
         let ordered_list = Vec::new();
 
-        Self { id_menu, msg_list, selectables, ordered_list }
+        Self { id_menu, msg_list, selectables, ordered_list, status: None }
+    }
+
+    /// Drop all accumulated data, keeping the current selection index
+    pub fn reset(&mut self) {
+        self.msg_list.msg_map.clear();
+        self.selectables.clear();
+        self.id_menu.ids.clear();
+        self.ordered_list.clear();
     }
 
     pub fn update(&mut self, msg_map: MsgMap, selectables: HashMap<String, SelectableObject>) {
@@ -146,11 +156,18 @@ impl<'a> View {
         let direction = Direction::Horizontal;
         let cnstrnts = vec![Constraint::Percentage(50), Constraint::Percentage(50)];
 
-        let slice = Layout::default()
-            .direction(direction)
-            .margin(margin)
-            .constraints(cnstrnts)
-            .split(f.size());
+        let mut area = f.size();
+        if let Some(status) = &self.status {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Length(1), Constraint::Min(0)])
+                .split(area);
+            f.render_widget(Paragraph::new(status.clone()), rows[0]);
+            area = rows[1];
+        }
+
+        let slice =
+            Layout::default().direction(direction).margin(margin).constraints(cnstrnts).split(area);
 
         self.render_ids(f, slice.clone())?;
         if self.ordered_list.is_empty() {
@@ -45,7 +45,9 @@ use darkfi::{
     util::{
         cli::{get_log_config, get_log_level},
         parse::encode_base10,
+        path::expand_path,
     },
+    zk::{key_store::DEFAULT_KEY_STORE_PATH, KeyStore},
 };
 
 /// Airdrop methods
@@ -98,6 +100,10 @@ struct Args {
     /// darkfid JSON-RPC endpoint
     endpoint: Url,
 
+    #[arg(long, default_value = DEFAULT_KEY_STORE_PATH)]
+    /// Directory where zk proving keys are cached
+    zk_keys: String,
+
     #[command(subcommand)]
     command: Subcmd,
 }
@@ -460,12 +466,14 @@ enum SubscribeSubcmd {
 
 pub struct Drk {
     pub rpc_client: RpcClient,
+    pub key_store: KeyStore,
 }
 
 impl Drk {
-    async fn new(endpoint: Url) -> Result<Self> {
+    async fn new(endpoint: Url, zk_keys: &str) -> Result<Self> {
         let rpc_client = RpcClient::new(endpoint).await?;
-        Ok(Self { rpc_client })
+        let key_store = KeyStore::new(&expand_path(zk_keys)?)?;
+        Ok(Self { rpc_client, key_store })
     }
 
     async fn ping(&self) -> Result<()> {
@@ -496,7 +504,7 @@ async fn main() -> Result<()> {
         }
 
         Subcmd::Ping => {
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
             drk.ping().await.with_context(|| "Failed to ping darkfid RPC endpoint")?;
 
             Ok(())
@@ -532,7 +540,7 @@ async fn main() -> Result<()> {
                 exit(2);
             }
 
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
             if initialize {
                 drk.initialize_wallet().await?;
@@ -717,7 +725,7 @@ async fn main() -> Result<()> {
             };
 
             let coin = Coin::from(elem);
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
             drk.unspend_coin(&coin).await.with_context(|| "Failed to mark coin as unspent")?;
 
             Ok(())
@@ -725,7 +733,7 @@ async fn main() -> Result<()> {
 
         Subcmd::Airdrop { faucet_endpoint, amount, address } => {
             let amount = f64::from_str(&amount).with_context(|| "Invalid amount")?;
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
             let address = match address {
                 Some(v) => PublicKey::from_str(v.as_str()).with_context(|| "Invalid address")?,
@@ -747,7 +755,7 @@ async fn main() -> Result<()> {
         Subcmd::Transfer { amount, token, recipient, dao, dao_bulla } => {
             let _ = f64::from_str(&amount).with_context(|| "Invalid amount")?;
             let rcpt = PublicKey::from_str(&recipient).with_context(|| "Invalid recipient")?;
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
             let token_id = drk.get_token(token).await.with_context(|| "Invalid token alias")?;
 
             let tx = drk
@@ -761,7 +769,7 @@ async fn main() -> Result<()> {
         }
 
         Subcmd::Otc(cmd) => {
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
             match cmd {
                 OtcSubcmd::Init { value_pair, token_pair } => {
@@ -833,7 +841,7 @@ async fn main() -> Result<()> {
             let bytes = bs58::decode(&buf.trim()).into_vec()?;
             let tx = deserialize(&bytes)?;
 
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
             let txid =
                 drk.broadcast_tx(&tx).await.with_context(|| "Failed to broadcast transaction")?;
@@ -845,7 +853,7 @@ async fn main() -> Result<()> {
 
         Subcmd::Subscribe(cmd) => match cmd {
             SubscribeSubcmd::Blocks => {
-                let drk = Drk::new(args.endpoint.clone()).await?;
+                let drk = Drk::new(args.endpoint.clone(), &args.zk_keys).await?;
 
                 drk.subscribe_blocks(args.endpoint.clone())
                     .await
@@ -855,7 +863,7 @@ async fn main() -> Result<()> {
             }
 
             SubscribeSubcmd::Transactions => {
-                let drk = Drk::new(args.endpoint.clone()).await?;
+                let drk = Drk::new(args.endpoint.clone(), &args.zk_keys).await?;
 
                 drk.subscribe_err_txs(args.endpoint)
                     .await
@@ -866,7 +874,7 @@ async fn main() -> Result<()> {
         },
 
         Subcmd::Scan { reset, list, checkpoint } => {
-            let drk = Drk::new(args.endpoint).await?;
+            let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
             if reset {
                 eprintln!("Reset requested.");
@@ -911,7 +919,7 @@ async fn main() -> Result<()> {
                 let approval_ratio_base = 100_u64;
                 let approval_ratio_quot = (approval_ratio * approval_ratio_base as f64) as u64;
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let gov_token_id =
                     drk.get_token(gov_token_id).await.with_context(|| "Invalid Token ID")?;
 
@@ -950,7 +958,7 @@ async fn main() -> Result<()> {
                 let bytes = bs58::decode(&buf.trim()).into_vec()?;
                 let dao_params: DaoParams = deserialize(&bytes)?;
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
                 drk.import_dao(dao_name, dao_params)
                     .await
@@ -960,7 +968,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::List { dao_alias } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 // We cannot use .map() since get_dao_id() uses ?
                 let dao_id = match dao_alias {
                     Some(alias) => Some(drk.get_dao_id(&alias).await?),
@@ -973,7 +981,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::Balance { dao_alias } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
 
                 let balmap =
@@ -1008,7 +1016,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::Mint { dao_alias } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
 
                 let tx = drk.dao_mint(dao_id).await.with_context(|| "Failed to mint DAO")?;
@@ -1020,7 +1028,7 @@ async fn main() -> Result<()> {
                 let _ = f64::from_str(&amount).with_context(|| "Invalid amount")?;
                 let amount = decode_base10(&amount, 8, true)?;
                 let rcpt = PublicKey::from_str(&recipient).with_context(|| "Invalid recipient")?;
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
                 let token_id = drk.get_token(token).await.with_context(|| "Invalid token alias")?;
 
@@ -1034,7 +1042,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::Proposals { dao_alias } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
 
                 let proposals = drk.get_dao_proposals(dao_id).await?;
@@ -1047,7 +1055,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::Proposal { dao_alias, proposal_id } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
 
                 let proposals = drk.get_dao_proposals(dao_id).await?;
@@ -1069,7 +1077,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::Vote { dao_alias, proposal_id, vote, vote_weight } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
 
                 let _ = f64::from_str(&vote_weight).with_context(|| "Invalid vote weight")?;
@@ -1094,7 +1102,7 @@ async fn main() -> Result<()> {
             }
 
             DaoSubcmd::Exec { dao_alias, proposal_id } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let dao_id = drk.get_dao_id(&dao_alias).await?;
                 let dao = drk.get_dao_by_id(dao_id).await?;
                 let proposal = drk.get_dao_proposal_by_id(proposal_id).await?;
@@ -1115,7 +1123,7 @@ async fn main() -> Result<()> {
             ExplorerSubcmd::FetchTx { tx_hash, full, encode } => {
                 let tx_hash = blake3::Hash::from_hex(&tx_hash)?;
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
                 let tx = if let Some(tx) =
                     drk.get_tx(&tx_hash).await.with_context(|| "Failed to fetch transaction")?
@@ -1149,7 +1157,7 @@ async fn main() -> Result<()> {
                 let bytes = bs58::decode(&buf.trim()).into_vec()?;
                 let tx = deserialize(&bytes)?;
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
                 let is_valid =
                     drk.simulate_tx(&tx).await.with_context(|| "Failed to simulate tx")?;
@@ -1161,7 +1169,7 @@ async fn main() -> Result<()> {
             }
 
             ExplorerSubcmd::TxsHistory { tx_hash, encode } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
 
                 if let Some(c) = tx_hash {
                     let (tx_hash, status, tx) = drk.get_tx_history_record(&c).await?;
@@ -1207,7 +1215,7 @@ async fn main() -> Result<()> {
 
                 let token_id =
                     TokenId::from_str(token.as_str()).with_context(|| "Invalid Token ID")?;
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 drk.add_alias(alias, token_id).await?;
 
                 Ok(())
@@ -1221,7 +1229,7 @@ async fn main() -> Result<()> {
                     None => None,
                 };
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let map = drk.get_aliases(alias, token_id).await?;
 
                 // Create a prettytable with the new data:
@@ -1242,7 +1250,7 @@ async fn main() -> Result<()> {
             }
 
             AliasSubcmd::Remove { alias } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 drk.remove_alias(alias).await?;
 
                 Ok(())
@@ -1256,7 +1264,7 @@ async fn main() -> Result<()> {
                 let mint_authority =
                     SecretKey::from_str(buf.trim()).with_context(|| "Invalid secret key")?;
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 drk.import_mint_authority(mint_authority).await?;
 
                 let token_id = TokenId::derive(mint_authority);
@@ -1268,7 +1276,7 @@ async fn main() -> Result<()> {
             TokenSubcmd::GenerateMint => {
                 let mint_authority = SecretKey::random(&mut OsRng);
 
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 drk.import_mint_authority(mint_authority).await?;
 
                 let token_id = TokenId::derive(mint_authority);
@@ -1278,7 +1286,7 @@ async fn main() -> Result<()> {
             }
 
             TokenSubcmd::List => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let tokens = drk.list_tokens().await?;
                 let aliases_map = drk
                     .get_aliases_mapped_by_token()
@@ -1309,7 +1317,7 @@ async fn main() -> Result<()> {
 
             // TODO: Mint directly into DAO treasury
             TokenSubcmd::Mint { token, amount, recipient } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let _ = f64::from_str(&amount).with_context(|| "Invalid amount")?;
                 let rcpt = PublicKey::from_str(&recipient).with_context(|| "Invalid recipient")?;
                 let token_id = drk.get_token(token).await.with_context(|| "Invalid Token ID")?;
@@ -1325,7 +1333,7 @@ async fn main() -> Result<()> {
             }
 
             TokenSubcmd::Freeze { token } => {
-                let drk = Drk::new(args.endpoint).await?;
+                let drk = Drk::new(args.endpoint, &args.zk_keys).await?;
                 let token_id = drk.get_token(token).await.with_context(|| "Invalid Token ID")?;
 
                 let tx = drk
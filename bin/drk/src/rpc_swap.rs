@@ -21,7 +21,7 @@ use anyhow::{anyhow, Result};
 use darkfi::{
     tx::Transaction,
     util::parse::encode_base10,
-    zk::{halo2::Field, proof::ProvingKey, vm::ZkCircuit, vm_heap::empty_witnesses, Proof},
+    zk::{halo2::Field, Proof},
     zkas::ZkBinary,
 };
 use darkfi_money_contract::{
@@ -120,12 +120,12 @@ impl Drk {
             return Err(anyhow!("Burn circuit not found"))
         };
 
-        let mint_zkbin = ZkBinary::decode(&mint_zkbin.1)?;
-        let burn_zkbin = ZkBinary::decode(&burn_zkbin.1)?;
+        let mint_bincode = &mint_zkbin.1;
+        let mint_zkbin = ZkBinary::decode(mint_bincode)?;
+        let burn_bincode = &burn_zkbin.1;
+        let burn_zkbin = ZkBinary::decode(burn_bincode)?;
 
         let k = 13;
-        let mint_circuit = ZkCircuit::new(empty_witnesses(&mint_zkbin), mint_zkbin.clone());
-        let burn_circuit = ZkCircuit::new(empty_witnesses(&burn_zkbin), burn_zkbin.clone());
 
         // Since we're creating the first half, we generate the blinds.
         let value_blinds = [pallas::Scalar::random(&mut OsRng), pallas::Scalar::random(&mut OsRng)];
@@ -147,9 +147,9 @@ impl Drk {
             coin: burn_coin,
             tree,
             mint_zkbin,
-            mint_pk: ProvingKey::build(k, &mint_circuit),
+            mint_pk: self.key_store.proving_key(mint_bincode, k)?,
             burn_zkbin,
-            burn_pk: ProvingKey::build(k, &burn_circuit),
+            burn_pk: self.key_store.proving_key(burn_bincode, k)?,
         };
 
         eprintln!("Building first half of the swap transaction");
@@ -212,12 +212,12 @@ impl Drk {
             return Err(anyhow!("Burn circuit not found"))
         };
 
-        let mint_zkbin = ZkBinary::decode(&mint_zkbin.1)?;
-        let burn_zkbin = ZkBinary::decode(&burn_zkbin.1)?;
+        let mint_bincode = &mint_zkbin.1;
+        let mint_zkbin = ZkBinary::decode(mint_bincode)?;
+        let burn_bincode = &burn_zkbin.1;
+        let burn_zkbin = ZkBinary::decode(burn_bincode)?;
 
         let k = 13;
-        let mint_circuit = ZkCircuit::new(empty_witnesses(&mint_zkbin), mint_zkbin.clone());
-        let burn_circuit = ZkCircuit::new(empty_witnesses(&burn_zkbin), burn_zkbin.clone());
 
         // TODO: Maybe some kind of verification at this point
 
@@ -237,9 +237,9 @@ impl Drk {
             coin: burn_coin,
             tree,
             mint_zkbin,
-            mint_pk: ProvingKey::build(k, &mint_circuit),
+            mint_pk: self.key_store.proving_key(mint_bincode, k)?,
             burn_zkbin,
-            burn_pk: ProvingKey::build(k, &burn_circuit),
+            burn_pk: self.key_store.proving_key(burn_bincode, k)?,
         };
 
         eprintln!("Building second half of the swap transaction");
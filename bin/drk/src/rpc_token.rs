@@ -17,12 +17,7 @@
  */
 
 use anyhow::{anyhow, Result};
-use darkfi::{
-    tx::Transaction,
-    util::parse::decode_base10,
-    zk::{proof::ProvingKey, vm::ZkCircuit, vm_heap::empty_witnesses},
-    zkas::ZkBinary,
-};
+use darkfi::{tx::Transaction, util::parse::decode_base10, zkas::ZkBinary};
 use darkfi_money_contract::{
     client::{token_freeze_v1::TokenFreezeCallBuilder, token_mint_v1::TokenMintCallBuilder},
     MoneyFunction, MONEY_CONTRACT_ZKAS_TOKEN_FRZ_NS_V1, MONEY_CONTRACT_ZKAS_TOKEN_MINT_NS_V1,
@@ -75,9 +70,8 @@ impl Drk {
         };
 
         let k = 13;
-        let token_mint_zkbin = ZkBinary::decode(&token_mint_zkbin.1)?;
-        let token_mint_circuit =
-            ZkCircuit::new(empty_witnesses(&token_mint_zkbin), token_mint_zkbin.clone());
+        let token_mint_bincode = &token_mint_zkbin.1;
+        let token_mint_zkbin = ZkBinary::decode(token_mint_bincode)?;
 
         eprintln!("Creating token mint circuit proving keys");
         let mint_builder = TokenMintCallBuilder {
@@ -87,7 +81,7 @@ impl Drk {
             spend_hook,
             user_data,
             token_mint_zkbin,
-            token_mint_pk: ProvingKey::build(k, &token_mint_circuit),
+            token_mint_pk: self.key_store.proving_key(token_mint_bincode, k)?,
         };
 
         eprintln!("Building transaction parameters");
@@ -128,15 +122,14 @@ impl Drk {
         };
 
         let k = 13;
-        let token_freeze_zkbin = ZkBinary::decode(&token_freeze_zkbin.1)?;
-        let token_freeze_circuit =
-            ZkCircuit::new(empty_witnesses(&token_freeze_zkbin), token_freeze_zkbin.clone());
+        let token_freeze_bincode = &token_freeze_zkbin.1;
+        let token_freeze_zkbin = ZkBinary::decode(token_freeze_bincode)?;
 
         eprintln!("Creating token freeze circuit proving keys");
         let freeze_builder = TokenFreezeCallBuilder {
             mint_authority,
             token_freeze_zkbin,
-            token_freeze_pk: ProvingKey::build(k, &token_freeze_circuit),
+            token_freeze_pk: self.key_store.proving_key(token_freeze_bincode, k)?,
         };
 
         eprintln!("Building transaction parameters");
@@ -22,7 +22,7 @@ use anyhow::{anyhow, Result};
 use darkfi::{
     tx::Transaction,
     util::parse::{decode_base10, encode_base10},
-    zk::{halo2::Field, proof::ProvingKey, vm::ZkCircuit, vm_heap::empty_witnesses},
+    zk::halo2::Field,
     zkas::ZkBinary,
 };
 use darkfi_dao_contract::model::DaoBulla;
@@ -120,12 +120,12 @@ impl Drk {
             return Err(anyhow!("Burn circuit not found"))
         };
 
-        let mint_zkbin = ZkBinary::decode(&mint_zkbin.1)?;
-        let burn_zkbin = ZkBinary::decode(&burn_zkbin.1)?;
+        let mint_bincode = &mint_zkbin.1;
+        let mint_zkbin = ZkBinary::decode(mint_bincode)?;
+        let burn_bincode = &burn_zkbin.1;
+        let burn_zkbin = ZkBinary::decode(burn_bincode)?;
 
         let k = 13;
-        let mint_circuit = ZkCircuit::new(empty_witnesses(&mint_zkbin), mint_zkbin.clone());
-        let burn_circuit = ZkCircuit::new(empty_witnesses(&burn_zkbin), burn_zkbin.clone());
 
         eprintln!("Creating Mint and Burn circuit proving keys");
         let transfer_builder = TransferCallBuilder {
@@ -142,9 +142,9 @@ impl Drk {
             coins: owncoins,
             tree,
             mint_zkbin,
-            mint_pk: ProvingKey::build(k, &mint_circuit),
+            mint_pk: self.key_store.proving_key(mint_bincode, k)?,
             burn_zkbin,
-            burn_pk: ProvingKey::build(k, &burn_circuit),
+            burn_pk: self.key_store.proving_key(burn_bincode, k)?,
             clear_input: false,
         };
 
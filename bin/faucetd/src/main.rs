@@ -84,7 +84,7 @@ use darkfi::{
         path::{expand_path, get_config_path},
     },
     wallet::{WalletDb, WalletPtr},
-    zk::{halo2::Field, proof::ProvingKey, vm::ZkCircuit, vm_heap::empty_witnesses},
+    zk::{halo2::Field, key_store::DEFAULT_KEY_STORE_PATH, proof::ProvingKey, KeyStore},
     zkas::ZkBinary,
     Error, Result,
 };
@@ -119,6 +119,10 @@ struct Args {
     /// Path to blockchain database
     database: String,
 
+    #[structopt(long)]
+    /// Directory where zk verifying keys are stored (default: shared key store)
+    zk_keys: Option<String>,
+
     #[structopt(long, default_value = "tcp://127.0.0.1:9340")]
     /// JSON-RPC listen URL
     rpc_listen: Url,
@@ -147,6 +151,11 @@ struct Args {
     /// Prefered transports of outbound connections for the syncing protocol (repeatable flag)
     sync_p2p_transports: Vec<String>,
 
+    #[structopt(long, default_value = "darkfid_sync")]
+    /// Network identifier of the syncing protocol P2P network, the faucet
+    /// syncs blocks from the darkfid nodes so it joins their network
+    sync_network_id: String,
+
     #[structopt(long)]
     /// Enable localnet hosts
     localnet: bool,
@@ -212,6 +221,7 @@ impl Faucetd {
         validator_state: ValidatorStatePtr,
         sync_p2p: P2pPtr,
         wallet: WalletPtr,
+        key_store: KeyStore,
         timeout: i64,
         limit: u64,
     ) -> Result<Self> {
@@ -241,20 +251,17 @@ impl Faucetd {
             return Err(Error::ZkasBincodeNotFound)
         };
 
-        let (mint_zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&mint_zkbytes)?;
-        let (burn_zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&burn_zkbytes)?;
+        let (mint_bincode, _): (Vec<u8>, Vec<u8>) = deserialize(&mint_zkbytes)?;
+        let (burn_bincode, _): (Vec<u8>, Vec<u8>) = deserialize(&burn_zkbytes)?;
 
         let k = 13;
-        let mint_zkbin = ZkBinary::decode(&mint_zkbin)?;
-        let mint_circuit = ZkCircuit::new(empty_witnesses(&mint_zkbin), mint_zkbin.clone());
-
-        let burn_zkbin = ZkBinary::decode(&burn_zkbin)?;
-        let burn_circuit = ZkCircuit::new(empty_witnesses(&burn_zkbin), burn_zkbin.clone());
+        let mint_zkbin = ZkBinary::decode(&mint_bincode)?;
+        let burn_zkbin = ZkBinary::decode(&burn_bincode)?;
 
-        info!("Creating mint circuit proving key");
-        let mint_provingkey = ProvingKey::build(k, &mint_circuit);
-        info!("Creating burn circuit proving key");
-        let burn_provingkey = ProvingKey::build(k, &burn_circuit);
+        info!("Loading mint circuit proving key");
+        let mint_provingkey = key_store.proving_key(&mint_bincode, k)?;
+        info!("Loading burn circuit proving key");
+        let burn_provingkey = key_store.proving_key(&burn_bincode, k)?;
 
         {
             let provingkeys = vec![
@@ -699,6 +706,8 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     }
 
     // Initialize validator state
+    let key_store =
+        KeyStore::new(&expand_path(args.zk_keys.as_deref().unwrap_or(DEFAULT_KEY_STORE_PATH))?)?;
     let state = ValidatorState::new(
         &sled_db,
         bootstrap_ts,
@@ -706,6 +715,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         genesis_data,
         initial_distribution,
         wallet.clone(),
+        &key_store,
         faucet_pubkeys,
         false,
         false,
@@ -722,6 +732,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         seeds: args.sync_p2p_seed.clone(),
         allowed_transports: args.sync_p2p_transports,
         localnet: args.localnet,
+        network_id: args.sync_network_id,
         ..Default::default()
     };
 
@@ -753,6 +764,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         state.clone(),
         sync_p2p.clone(),
         wallet.clone(),
+        key_store,
         airdrop_timeout,
         airdrop_limit,
     )
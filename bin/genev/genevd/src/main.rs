@@ -27,10 +27,11 @@ use darkfi::{
         events_queue::EventsQueue,
         model::{Event, EventId, Model},
         protocol_event::{ProtocolEvent, Seen, SeenPtr},
+        retention::RetentionOpt,
         view::{View, ViewPtr},
     },
     net::{self, settings::SettingsOpt},
-    rpc::server::listen_and_serve,
+    rpc::{metrics, server::listen_and_serve},
     Result,
 };
 
@@ -44,6 +45,8 @@ use crate::rpc::JsonRpcInterface;
 
 const CONFIG_FILE: &str = "genev_config.toml";
 const CONFIG_FILE_CONTENTS: &str = include_str!("../../genev_config.toml");
+// P2P network identifier used unless configured otherwise
+const NETWORK_ID: &str = "genev";
 
 #[derive(Clone, Debug, Deserialize, StructOpt, StructOptToml)]
 #[serde(default)]
@@ -57,9 +60,17 @@ struct Args {
     #[structopt(long = "rpc", default_value = "tcp://127.0.0.1:28880")]
     pub rpc_listen: Url,
 
+    /// Prometheus metrics listen URL
+    #[structopt(long)]
+    pub metrics_listen: Option<Url>,
+
     #[structopt(flatten)]
     pub net: SettingsOpt,
 
+    /// Event graph retention settings
+    #[structopt(flatten)]
+    pub dag: RetentionOpt,
+
     #[structopt(short, parse(from_occurrences))]
     /// Increase verbosity (-vvv supported)
     verbose: u8,
@@ -87,7 +98,7 @@ async fn realmain(args: Args, executor: Arc<smol::Executor<'_>>) -> Result<()> {
     // Initialize the base structures
     ////////////////////
     let events_queue = EventsQueue::<GenEvent>::new();
-    let model = Arc::new(Mutex::new(Model::new(events_queue.clone())));
+    let model = Arc::new(Mutex::new(Model::new(events_queue.clone(), args.dag.clone().into())));
     let view = Arc::new(Mutex::new(View::new(events_queue)));
     let model_clone = model.clone();
 
@@ -99,7 +110,8 @@ async fn realmain(args: Args, executor: Arc<smol::Executor<'_>>) -> Result<()> {
     let seen_inv = Seen::new();
 
     // Check the version
-    let net_settings = args.net.clone();
+    let mut net_settings = args.net.clone();
+    net_settings.network_id.get_or_insert_with(|| NETWORK_ID.to_string());
 
     // New p2p
     let p2p = net::P2p::new(net_settings.into()).await;
@@ -144,6 +156,12 @@ async fn realmain(args: Args, executor: Arc<smol::Executor<'_>>) -> Result<()> {
     let _ex = executor.clone();
     executor.spawn(listen_and_serve(args.rpc_listen.clone(), rpc_interface, _ex)).detach();
 
+    if let Some(metrics_listen) = args.metrics_listen.clone() {
+        info!("Starting metrics server on {}", metrics_listen);
+        let _ex = executor.clone();
+        executor.spawn(metrics::listen_and_serve(metrics_listen, _ex)).detach();
+    }
+
     ////////////////////
     // Wait for SIGINT
     ////////////////////
@@ -16,13 +16,12 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{
-    collections::{HashMap, HashSet},
-    path::Path,
-    process::exit,
-};
+use std::{collections::HashMap, path::Path, process::exit};
 
-use async_std::{stream::StreamExt, sync::Arc};
+use async_std::{
+    stream::StreamExt,
+    sync::{Arc, Mutex},
+};
 use async_trait::async_trait;
 use futures::future::join_all;
 use log::{debug, error, info, warn};
@@ -38,7 +37,10 @@ use url::Url;
 
 use darkfi::{
     async_daemonize, cli_desc,
-    net::{self, connector::Connector, protocol::ProtocolVersion, session::Session, P2p, P2pPtr},
+    net::{
+        self, connector::Connector, message::VerackMessage, protocol::ProtocolVersion,
+        session::Session, P2p, P2pPtr,
+    },
     rpc::{
         jsonrpc::{
             ErrorCode::{InvalidParams, MethodNotFound},
@@ -50,13 +52,25 @@ use darkfi::{
         async_util::sleep,
         file::{load_file, save_file},
         path::{expand_path, get_config_path},
+        time::Timestamp,
     },
     Result,
 };
 
+mod stats;
+use stats::{absorb, select_healthy, HostStats};
+
 const CONFIG_FILE: &str = "lilith_config.toml";
 const CONFIG_FILE_CONTENTS: &str = include_str!("../lilith_config.toml");
 
+/// Amount of hosts the crawler checks per network on every round
+const CRAWL_BATCH: usize = 10;
+/// Interval of crawler rounds (in seconds)
+const CRAWL_INTERVAL: u64 = 60;
+
+/// Per-host crawler history, keyed by host address
+type HostStatsMap = Arc<Mutex<HashMap<Url, HostStats>>>;
+
 #[derive(Clone, Debug, serde::Deserialize, StructOpt, StructOptToml)]
 #[serde(default)]
 #[structopt(name = "lilith", about = cli_desc!())]
@@ -88,13 +102,75 @@ struct Spawn {
     pub name: String,
     /// P2P pointer
     pub p2p: P2pPtr,
+    /// Crawler history of all known hosts of this network
+    pub stats: HostStatsMap,
 }
 
 impl Spawn {
+    /// Addresses currently served to requesting peers
     async fn addresses(&self) -> Vec<String> {
         self.p2p.hosts().load_all().await.iter().map(|addr| addr.to_string()).collect()
     }
 
+    /// Crawler history of known hosts, optionally filtered by transport
+    async fn hosts_info(&self, transport: Option<&str>) -> Vec<serde_json::Value> {
+        let now = Timestamp::current_time().0;
+        let stats = self.stats.lock().await;
+
+        let mut urls: Vec<&Url> =
+            stats.keys().filter(|url| transport.map_or(true, |t| url.scheme() == t)).collect();
+        urls.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        urls.iter().map(|url| stats[*url].info(url, now)).collect()
+    }
+
+    /// Aggregated crawler statistics, optionally filtered by transport
+    async fn stats_info(&self, transport: Option<&str>) -> serde_json::Value {
+        let now = Timestamp::current_time().0;
+        let stats = self.stats.lock().await;
+
+        let mut known = 0;
+        let mut healthy = 0;
+        let mut uptime = 0.0;
+        let mut versions: HashMap<String, usize> = HashMap::new();
+        let mut transports: HashMap<String, usize> = HashMap::new();
+
+        for (url, host) in stats.iter() {
+            if transport.map_or(false, |t| url.scheme() != t) {
+                continue
+            }
+
+            known += 1;
+            uptime += host.uptime();
+            if host.is_healthy(now) {
+                healthy += 1;
+            }
+            if let Some(version) = &host.version {
+                *versions.entry(version.clone()).or_insert(0) += 1;
+            }
+            *transports.entry(url.scheme().to_string()).or_insert(0) += 1;
+        }
+
+        let served = self
+            .p2p
+            .hosts()
+            .load_all()
+            .await
+            .iter()
+            .filter(|url| transport.map_or(true, |t| url.scheme() == t))
+            .count();
+
+        json!({
+            "name": self.name.clone(),
+            "known": known,
+            "healthy": healthy,
+            "served": served,
+            "avg_uptime": if known > 0 { uptime / known as f64 } else { 0.0 },
+            "versions": versions,
+            "transports": transports,
+        })
+    }
+
     async fn info(&self) -> serde_json::Value {
         let mut addr_vec = vec![];
         for addr in &self.p2p.settings().inbound_addrs {
@@ -103,6 +179,7 @@ impl Spawn {
 
         json!({
             "name": self.name.clone(),
+            "network_id": self.p2p.settings().network_id.clone(),
             "urls": addr_vec,
             "hosts": self.addresses().await,
         })
@@ -120,6 +197,8 @@ struct NetInfo {
     pub peers: Vec<Url>,
     /// Supported network version
     pub version: Version,
+    /// Network identifier checked during the version handshake
+    pub network_id: String,
     /// Enable localnet hosts
     pub localnet: bool,
 }
@@ -131,72 +210,136 @@ struct Lilith {
 }
 
 impl Lilith {
-    /// Internal task to run a periodic purge of unreachable hosts
-    /// for a specific P2P network.
-    async fn periodic_purge(name: String, p2p: P2pPtr, ex: Arc<Executor<'_>>) {
-        info!("Starting periodic host purge task for \"{}\"", name);
+    /// Try to connect and perform a handshake with given host.
+    /// Returns the app version the host advertised on success.
+    async fn probe(p2p: P2pPtr, host: Url, ex: Arc<Executor<'_>>) -> Option<String> {
+        let session_out = p2p.session_outbound().await;
+        let session_weak = Arc::downgrade(&session_out);
+
+        let connector = Connector::new(p2p.settings(), Arc::new(session_weak));
+        debug!("Connecting to {}", host);
+        let channel = match connector.connect(host.clone()).await {
+            Ok(channel) => channel,
+            Err(e) => {
+                debug!("Failed to connect to {} ({})", host, e);
+                return None
+            }
+        };
+
+        debug!("Connected successfully!");
+        // Subscribe before starting the channel so we catch the verack
+        // carrying the host's advertised app version.
+        let verack_sub = match channel.subscribe_msg::<VerackMessage>().await {
+            Ok(sub) => sub,
+            Err(e) => {
+                debug!("Failed to subscribe to verack from {} ({})", host, e);
+                channel.stop().await;
+                return None
+            }
+        };
+
+        let proto_ver =
+            ProtocolVersion::new(channel.clone(), p2p.settings().clone(), p2p.hosts().clone())
+                .await;
+
+        let handshake_task =
+            session_out.perform_handshake_protocols(proto_ver, channel.clone(), ex.clone());
+
+        channel.clone().start(ex.clone());
+
+        let version = match handshake_task.await {
+            Ok(()) => {
+                debug!("Handshake success! Stopping channel.");
+                match verack_sub.receive().await {
+                    Ok(verack) => Some(verack.app_version.to_string()),
+                    Err(_) => None,
+                }
+            }
+            Err(e) => {
+                debug!("Handshake failure! {}", e);
+                None
+            }
+        };
+
+        verack_sub.unsubscribe().await;
+        channel.stop().await;
+        version
+    }
+
+    /// Internal task crawling the hosts of a specific P2P network.
+    /// Every round it picks the least recently checked hosts, tries to
+    /// reach them and records the outcome. Dead hosts are forgotten, and
+    /// the P2P hosts set is refreshed so that only healthy and diverse
+    /// addresses are served to requesting peers.
+    async fn periodic_crawl(name: String, p2p: P2pPtr, stats: HostStatsMap, ex: Arc<Executor<'_>>) {
+        info!("Starting periodic host crawler task for \"{}\"", name);
         loop {
-            // We'll pick up to 10 hosts every minute and try to connect to
-            // them. If we can't reach them, we'll remove them from our set.
-            sleep(60).await;
-            debug!("[{}] Picking random hosts from db", name);
-            let lottery_winners = p2p.clone().hosts().get_n_random(10).await;
-            let win_str: Vec<&str> = lottery_winners.iter().map(|x| x.as_str()).collect();
-            debug!("[{}] Got: {:?}", name, win_str);
+            sleep(CRAWL_INTERVAL).await;
 
-            let mut tasks = vec![];
+            // Pick up hosts we learned about from peers since last round
+            Self::absorb_hosts(&p2p, &stats).await;
+
+            let mut candidates: Vec<(Url, u64)> = {
+                let stats = stats.lock().await;
+                stats.iter().map(|(url, s)| (url.clone(), s.last_checked)).collect()
+            };
+            candidates.sort_by_key(|(_, last_checked)| *last_checked);
+            candidates.truncate(CRAWL_BATCH);
+
+            let win_str: Vec<&str> = candidates.iter().map(|(x, _)| x.as_str()).collect();
+            debug!("[{}] Crawling: {:?}", name, win_str);
 
-            for host in &lottery_winners {
+            let mut tasks = vec![];
+            for (host, _) in candidates {
                 let p2p_ = p2p.clone();
                 let ex_ = ex.clone();
                 tasks.push(async move {
-                    let session_out = p2p_.session_outbound().await;
-                    let session_weak = Arc::downgrade(&p2p_.session_outbound().await);
-
-                    let connector = Connector::new(p2p_.settings(), Arc::new(session_weak));
-                    debug!("Connecting to {}", host);
-                    match connector.connect(host.clone()).await {
-                        Ok(channel) => {
-                            debug!("Connected successfully!");
-                            let proto_ver = ProtocolVersion::new(
-                                channel.clone(),
-                                p2p_.settings().clone(),
-                                p2p_.hosts().clone(),
-                            )
-                            .await;
-
-                            let handshake_task = session_out.perform_handshake_protocols(
-                                proto_ver,
-                                channel.clone(),
-                                ex_.clone(),
-                            );
-
-                            channel.clone().start(ex_.clone());
-
-                            match handshake_task.await {
-                                Ok(()) => {
-                                    debug!("Handshake success! Stopping channel.");
-                                    channel.stop().await;
-                                }
-                                Err(e) => {
-                                    debug!("Handshake failure! {}", e);
-                                }
-                            }
-                        }
-
-                        Err(e) => {
-                            debug!("Failed to connect to {}, removing from set ({})", host, e);
-                            // Remove from hosts set
-                            p2p_.hosts().remove(host).await;
-                        }
-                    }
+                    let version = Self::probe(p2p_, host.clone(), ex_).await;
+                    (host, version)
                 });
             }
 
-            join_all(tasks).await;
+            let results = join_all(tasks).await;
+            let now = Timestamp::current_time().0;
+
+            let mut stats_ = stats.lock().await;
+            for (host, version) in results {
+                let Some(host_stats) = stats_.get_mut(&host) else { continue };
+                host_stats.record(now, version);
+
+                if host_stats.is_dead(now) {
+                    debug!("[{}] Host {} is dead, removing from set", name, host);
+                    stats_.remove(&host);
+                }
+            }
+            drop(stats_);
+
+            Self::refresh_served(&p2p, &stats).await;
         }
     }
 
+    /// Add any hosts we don't know about yet from the P2P hosts set
+    async fn absorb_hosts(p2p: &P2pPtr, stats: &HostStatsMap) {
+        let hosts = p2p.hosts().load_all().await;
+        absorb(&mut *stats.lock().await, hosts);
+    }
+
+    /// Replace the P2P hosts set with the healthy and diverse selection
+    async fn refresh_served(p2p: &P2pPtr, stats: &HostStatsMap) {
+        Self::absorb_hosts(p2p, stats).await;
+
+        let now = Timestamp::current_time().0;
+        let selection = select_healthy(&*stats.lock().await, now);
+
+        let hosts = p2p.hosts();
+        for addr in hosts.load_all().await {
+            if !selection.contains(&addr) {
+                hosts.remove(&addr).await;
+            }
+        }
+        hosts.store(&selection).await;
+    }
+
     // RPCAPI:
     // Replies to a ping method.
     // --> {"jsonrpc": "2.0", "method": "ping", "params": [], "id": 42}
@@ -219,6 +362,71 @@ impl Lilith {
 
         JsonResponse::new(json, id).into()
     }
+
+    /// Parse the optional `[network, transport]` filter params.
+    /// Empty strings are treated the same as omitted params.
+    fn parse_filters(params: &[serde_json::Value]) -> Option<(Option<&str>, Option<&str>)> {
+        if params.len() > 2 {
+            return None
+        }
+
+        let mut filters = [None, None];
+        for (i, param) in params.iter().enumerate() {
+            let param = param.as_str()?;
+            if !param.is_empty() {
+                filters[i] = Some(param);
+            }
+        }
+
+        Some((filters[0], filters[1]))
+    }
+
+    // RPCAPI:
+    // Returns the crawler history of known hosts of spawned networks.
+    // Optionally takes a network name and a transport to filter on.
+    // --> {"jsonrpc": "2.0", "method": "hosts", "params": ["darkirc_v4", "tcp+tls"], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": {"hosts": [host_info, ...]}, "id": 42}
+    async fn hosts(&self, id: serde_json::Value, params: &[serde_json::Value]) -> JsonResult {
+        let Some((network, transport)) = Self::parse_filters(params) else {
+            return JsonError::new(InvalidParams, None, id).into()
+        };
+
+        let mut hosts = vec![];
+        for spawn in &self.networks {
+            if network.map_or(false, |n| n != spawn.name) {
+                continue
+            }
+
+            for mut info in spawn.hosts_info(transport).await {
+                info["network"] = json!(spawn.name.clone());
+                hosts.push(info);
+            }
+        }
+
+        JsonResponse::new(json!({ "hosts": hosts }), id).into()
+    }
+
+    // RPCAPI:
+    // Returns aggregated host health statistics per spawned network.
+    // Optionally takes a network name and a transport to filter on.
+    // --> {"jsonrpc": "2.0", "method": "stats", "params": ["darkirc_v4"], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": {"stats": [stats_info, ...]}, "id": 42}
+    async fn stats(&self, id: serde_json::Value, params: &[serde_json::Value]) -> JsonResult {
+        let Some((network, transport)) = Self::parse_filters(params) else {
+            return JsonError::new(InvalidParams, None, id).into()
+        };
+
+        let mut stats = vec![];
+        for spawn in &self.networks {
+            if network.map_or(false, |n| n != spawn.name) {
+                continue
+            }
+
+            stats.push(spawn.stats_info(transport).await);
+        }
+
+        JsonResponse::new(json!({ "stats": stats }), id).into()
+    }
 }
 
 #[async_trait]
@@ -233,6 +441,8 @@ impl RequestHandler for Lilith {
         match req.method.as_str() {
             Some("spawns") => return self.spawns(req.id, params).await,
             Some("ping") => return self.pong(req.id, params).await,
+            Some("hosts") => return self.hosts(req.id, params).await,
+            Some("stats") => return self.stats(req.id, params).await,
             Some(_) | None => return JsonError::new(MethodNotFound, None, req.id).into(),
         }
     }
@@ -257,9 +467,10 @@ async fn handle_signals(mut signals: Signals, term_tx: smol::channel::Sender<()>
     Ok(())
 }
 
-/// Attempt to read existing hosts tsv
-fn load_hosts(path: &Path, networks: &[&str]) -> HashMap<String, HashSet<Url>> {
-    let mut saved_hosts = HashMap::new();
+/// Attempt to read existing hosts tsv.
+/// Lines are `network\turl` followed by optional crawler stats columns.
+fn load_hosts(path: &Path, networks: &[&str]) -> HashMap<String, HashMap<Url, HostStats>> {
+    let mut saved_hosts: HashMap<String, HashMap<Url, HostStats>> = HashMap::new();
 
     let contents = load_file(path);
     if let Err(e) = contents {
@@ -269,23 +480,20 @@ fn load_hosts(path: &Path, networks: &[&str]) -> HashMap<String, HashSet<Url>> {
 
     for line in contents.unwrap().lines() {
         let data: Vec<&str> = line.split('\t').collect();
-        if networks.contains(&data[0]) {
-            let mut hosts = match saved_hosts.get(data[0]) {
-                Some(hosts) => hosts.clone(),
-                None => HashSet::new(),
-            };
+        if data.len() < 2 || !networks.contains(&data[0]) {
+            continue
+        }
 
-            let url = match Url::parse(data[1]) {
-                Ok(u) => u,
-                Err(e) => {
-                    warn!("Skipping malformed url: {} ({})", data[1], e);
-                    continue
-                }
-            };
+        let url = match Url::parse(data[1]) {
+            Ok(u) => u,
+            Err(e) => {
+                warn!("Skipping malformed url: {} ({})", data[1], e);
+                continue
+            }
+        };
 
-            hosts.insert(url);
-            saved_hosts.insert(data[0].to_string(), hosts);
-        }
+        let stats = HostStats::from_columns(&data[2..]);
+        saved_hosts.entry(data[0].to_string()).or_default().insert(url, stats);
     }
 
     saved_hosts
@@ -295,8 +503,11 @@ async fn save_hosts(path: &Path, networks: &[Spawn]) {
     let mut tsv = String::new();
 
     for spawn in networks {
-        for host in spawn.p2p.hosts().load_all().await {
-            tsv.push_str(&format!("{}\t{}\n", spawn.name, host.as_str()));
+        // Make sure freshly learned hosts are persisted as well
+        Lilith::absorb_hosts(&spawn.p2p, &spawn.stats).await;
+
+        for (host, stats) in spawn.stats.lock().await.iter() {
+            tsv.push_str(&format!("{}\t{}\t{}\n", spawn.name, host.as_str(), stats.to_columns()));
         }
     }
 
@@ -364,7 +575,12 @@ fn parse_configured_networks(data: &str) -> Result<HashMap<String, NetInfo>> {
                     semver::Version::parse(option_env!("CARGO_PKG_VERSION").unwrap_or("0.0.0"))?
                 };
 
-                let net_info = NetInfo { port, seeds, peers, version, localnet };
+                let network_id = match table.get("network_id") {
+                    Some(id) => id.as_str().unwrap_or_default().to_string(),
+                    None => String::new(),
+                };
+
+                let net_info = NetInfo { port, seeds, peers, version, network_id, localnet };
                 ret.insert(name, net_info);
             }
         }
@@ -377,7 +593,7 @@ async fn spawn_net(
     name: String,
     info: &NetInfo,
     accept_addrs: &[Url],
-    saved_hosts: &HashSet<Url>,
+    saved_hosts: HashMap<Url, HostStats>,
     ex: Arc<Executor<'_>>,
 ) -> Result<Spawn> {
     let mut listen_urls = vec![];
@@ -396,6 +612,7 @@ async fn spawn_net(
         peers: info.peers.clone(),
         outbound_connections: 0,
         app_version: info.version.clone(),
+        network_id: info.network_id.clone(),
         localnet: info.localnet,
         allowed_transports: vec![
             "tcp".to_string(),
@@ -411,9 +628,9 @@ async fn spawn_net(
     // Create P2P instance
     let p2p = P2p::new(settings).await;
 
-    // Fill db with cached hosts
-    let hosts: Vec<Url> = saved_hosts.iter().cloned().collect();
-    p2p.hosts().store(&hosts).await;
+    // Fill db with the healthy subset of cached hosts
+    let stats = Arc::new(Mutex::new(saved_hosts));
+    Lilith::refresh_served(&p2p, &stats).await;
 
     let addrs_str: Vec<&str> = listen_urls.iter().map(|x| x.as_str()).collect();
     info!("Starting seed network node for \"{}\" on {:?}", name, addrs_str);
@@ -428,7 +645,7 @@ async fn spawn_net(
     })
     .detach();
 
-    let spawn = Spawn { name, p2p };
+    let spawn = Spawn { name, p2p, stats };
     Ok(spawn)
 }
 
@@ -446,20 +663,16 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
 
     // Retrieve any saved hosts for configured networks
     let net_names: Vec<&str> = configured_nets.keys().map(|x| x.as_str()).collect();
-    let saved_hosts = load_hosts(&expand_path(&args.hosts_file)?, &net_names);
+    let mut saved_hosts = load_hosts(&expand_path(&args.hosts_file)?, &net_names);
 
     // Spawn configured networks
     let mut networks = vec![];
     for (name, info) in &configured_nets {
-        // TODO: Here we could actually differentiate between network versions
-        // e.g. p2p_v3, p2p_v4, etc. Therefore we can spawn multiple networks
-        // and they would all be version-checked, so we avoid mismatches when
-        // seeding peers.
         match spawn_net(
             name.to_string(),
             info,
             &args.accept_addrs,
-            saved_hosts.get(name).unwrap_or(&HashSet::new()),
+            saved_hosts.remove(name).unwrap_or_default(),
             ex.clone(),
         )
         .await
@@ -482,7 +695,8 @@ async fn realmain(args: Args, ex: Arc<Executor<'_>>) -> Result<()> {
     let lilith = Arc::new(Lilith { networks });
     for network in &lilith.networks {
         let name = network.name.clone();
-        ex.spawn(Lilith::periodic_purge(name, network.p2p.clone(), ex.clone())).detach();
+        let stats = network.stats.clone();
+        ex.spawn(Lilith::periodic_crawl(name, network.p2p.clone(), stats, ex.clone())).detach();
     }
 
     // JSON-RPC server
@@ -0,0 +1,311 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use serde_json::json;
+use url::Url;
+
+/// Minimum uptime percentage a host must have to be served to peers
+pub const HEALTHY_UPTIME: f64 = 50.0;
+/// A host is only served if it was reachable within this many seconds
+pub const HEALTHY_LAST_SEEN: u64 = 60 * 60 * 6;
+/// Hosts that were never reachable are dropped after this many checks
+pub const MAX_FAILED_CHECKS: u64 = 5;
+/// Hosts that haven't been reachable for this many seconds are dropped
+pub const STALE_AFTER: u64 = 60 * 60 * 24 * 7;
+/// Maximum amount of served hosts sharing the same network prefix
+pub const MAX_PER_GROUP: usize = 2;
+/// Maximum amount of hosts kept per network that were never checked yet
+pub const MAX_UNCHECKED: usize = 1000;
+
+/// Crawler history of a single host
+#[derive(Clone, Debug, Default)]
+pub struct HostStats {
+    /// Timestamp of the first successful handshake, 0 if never reached
+    pub first_seen: u64,
+    /// Timestamp of the last successful handshake, 0 if never reached
+    pub last_seen: u64,
+    /// Timestamp of the last connection attempt
+    pub last_checked: u64,
+    /// Amount of connection attempts
+    pub checks: u64,
+    /// Amount of successful handshakes
+    pub successes: u64,
+    /// App version advertised by the host in its last handshake
+    pub version: Option<String>,
+}
+
+impl HostStats {
+    /// Percentage of checks that resulted in a successful handshake
+    pub fn uptime(&self) -> f64 {
+        if self.checks == 0 {
+            return 0.0
+        }
+
+        self.successes as f64 / self.checks as f64 * 100.0
+    }
+
+    /// Record a connection attempt at given time
+    pub fn record(&mut self, now: u64, version: Option<String>) {
+        self.checks += 1;
+        self.last_checked = now;
+
+        if let Some(version) = version {
+            if self.first_seen == 0 {
+                self.first_seen = now;
+            }
+            self.last_seen = now;
+            self.successes += 1;
+            self.version = Some(version);
+        }
+    }
+
+    /// A healthy host has been reached recently and is up most of the time
+    pub fn is_healthy(&self, now: u64) -> bool {
+        self.last_seen > 0 &&
+            now.saturating_sub(self.last_seen) <= HEALTHY_LAST_SEEN &&
+            self.uptime() >= HEALTHY_UPTIME
+    }
+
+    /// A host is only served once the crawler reached it, so hosts peers
+    /// advertise are never passed on before being checked
+    pub fn is_servable(&self, now: u64) -> bool {
+        self.is_healthy(now)
+    }
+
+    /// A dead host was either never reachable, or hasn't been for a long time
+    pub fn is_dead(&self, now: u64) -> bool {
+        if self.last_seen == 0 {
+            return self.checks >= MAX_FAILED_CHECKS
+        }
+
+        now.saturating_sub(self.last_seen) > STALE_AFTER
+    }
+
+    /// Parse stats from the extra columns of a hosts tsv line.
+    /// Missing or malformed columns are left at their defaults,
+    /// so plain `network\turl` lines keep working.
+    pub fn from_columns(columns: &[&str]) -> Self {
+        let num = |i: usize| columns.get(i).and_then(|x| x.parse().ok()).unwrap_or(0);
+        let version = columns.get(5).filter(|x| !x.is_empty()).map(|x| x.to_string());
+
+        Self {
+            first_seen: num(0),
+            last_seen: num(1),
+            last_checked: num(2),
+            checks: num(3),
+            successes: num(4),
+            version,
+        }
+    }
+
+    /// Serialize stats into tsv columns, mirroring `from_columns`
+    pub fn to_columns(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.first_seen,
+            self.last_seen,
+            self.last_checked,
+            self.checks,
+            self.successes,
+            self.version.clone().unwrap_or_default(),
+        )
+    }
+
+    pub fn info(&self, url: &Url, now: u64) -> serde_json::Value {
+        json!({
+            "url": url.to_string(),
+            "transport": url.scheme(),
+            "first_seen": self.first_seen,
+            "last_seen": self.last_seen,
+            "last_checked": self.last_checked,
+            "checks": self.checks,
+            "successes": self.successes,
+            "uptime": self.uptime(),
+            "version": self.version,
+            "healthy": self.is_healthy(now),
+        })
+    }
+}
+
+/// Returns the group a host belongs to for diversity purposes.
+/// IPv4 hosts are grouped by /16, IPv6 hosts by /32, and anything
+/// else (domains, onions) by the host string itself.
+pub fn host_group(url: &Url) -> String {
+    // Same hack as in `net::hosts`, so IPs are parsed properly.
+    // https://github.com/whatwg/url/issues/749
+    let Ok(addr) = Url::parse(&url.as_str().replace(url.scheme(), "http")) else {
+        return url.to_string()
+    };
+
+    match addr.host() {
+        Some(url::Host::Ipv4(ip)) => {
+            let o = ip.octets();
+            format!("{}.{}", o[0], o[1])
+        }
+        Some(url::Host::Ipv6(ip)) => {
+            let s = ip.segments();
+            format!("{:x}:{:x}", s[0], s[1])
+        }
+        Some(url::Host::Domain(d)) => d.to_string(),
+        None => url.to_string(),
+    }
+}
+
+/// Add hosts learned from peers to the stats map. The crawler only checks
+/// a few hosts per round, so at most `MAX_UNCHECKED` hosts that were never
+/// checked are kept, and further ones are ignored until some got checked.
+pub fn absorb(stats: &mut HashMap<Url, HostStats>, hosts: Vec<Url>) {
+    let mut unchecked = stats.values().filter(|s| s.checks == 0).count();
+    for addr in hosts {
+        if unchecked >= MAX_UNCHECKED {
+            break
+        }
+
+        if let Entry::Vacant(e) = stats.entry(addr) {
+            e.insert(HostStats::default());
+            unchecked += 1;
+        }
+    }
+}
+
+/// Select healthy hosts, allowing at most `MAX_PER_GROUP` hosts per
+/// network group. Hosts with better uptime and more recent activity are
+/// preferred.
+pub fn select_healthy(stats: &HashMap<Url, HostStats>, now: u64) -> Vec<Url> {
+    let mut healthy: Vec<(&Url, &HostStats)> =
+        stats.iter().filter(|(_, s)| s.is_servable(now)).collect();
+
+    healthy.sort_by(|a, b| {
+        b.1.uptime()
+            .partial_cmp(&a.1.uptime())
+            .unwrap()
+            .then(b.1.last_seen.cmp(&a.1.last_seen))
+            .then(a.0.as_str().cmp(b.0.as_str()))
+    });
+
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    let mut ret = vec![];
+    for (url, _) in healthy {
+        let count = groups.entry(host_group(url)).or_insert(0);
+        if *count >= MAX_PER_GROUP {
+            continue
+        }
+        *count += 1;
+        ret.push(url.clone());
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn checked(successes: u64, checks: u64, last_seen: u64) -> HostStats {
+        HostStats {
+            first_seen: last_seen,
+            last_seen,
+            last_checked: NOW,
+            checks,
+            successes,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_health() {
+        let unchecked = HostStats::default();
+        assert!(!unchecked.is_healthy(NOW));
+        assert!(!unchecked.is_servable(NOW));
+        assert!(!unchecked.is_dead(NOW));
+
+        assert!(checked(3, 4, NOW).is_servable(NOW));
+        assert!(!checked(1, 4, NOW).is_servable(NOW));
+        assert!(!checked(4, 4, NOW - HEALTHY_LAST_SEEN - 1).is_servable(NOW));
+
+        assert!(checked(0, MAX_FAILED_CHECKS, 0).is_dead(NOW));
+        assert!(!checked(0, MAX_FAILED_CHECKS - 1, 0).is_dead(NOW));
+        assert!(checked(4, 4, NOW - STALE_AFTER - 1).is_dead(NOW));
+
+        let mut stats = HostStats::default();
+        stats.record(NOW, None);
+        stats.record(NOW, Some("0.4.1".to_string()));
+        assert_eq!(stats.uptime(), 50.0);
+        assert_eq!(stats.first_seen, NOW);
+        assert!(stats.is_healthy(NOW));
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut stats = checked(3, 4, NOW);
+        stats.version = Some("0.4.1".to_string());
+        let columns = stats.to_columns();
+        let parsed = HostStats::from_columns(&columns.split('\t').collect::<Vec<_>>());
+        assert_eq!(parsed.to_columns(), columns);
+
+        let parsed = HostStats::from_columns(&[]);
+        assert_eq!(parsed.checks, 0);
+        assert!(parsed.version.is_none());
+    }
+
+    #[test]
+    fn test_host_group() {
+        let group = |x: &str| host_group(&Url::parse(x).unwrap());
+        assert_eq!(group("tcp+tls://10.2.3.4:26661"), "10.2");
+        assert_eq!(group("tcp://[2001:db8::1]:26661"), "2001:db8");
+        assert_eq!(group("tor://abcdef.onion:26661"), "abcdef.onion");
+    }
+
+    #[test]
+    fn test_select_healthy() {
+        let url = |x: &str| Url::parse(x).unwrap();
+        let mut stats = HashMap::new();
+        stats.insert(url("tcp://10.0.0.1:1"), checked(4, 4, NOW));
+        stats.insert(url("tcp://10.0.0.2:1"), checked(3, 4, NOW));
+        stats.insert(url("tcp://10.0.0.3:1"), HostStats::default());
+        stats.insert(url("tcp://10.1.0.1:1"), HostStats::default());
+        stats.insert(url("tcp://10.2.0.1:1"), checked(0, 4, 0));
+
+        // Only hosts the crawler reached are served
+        let selection = select_healthy(&stats, NOW);
+        assert_eq!(selection, vec![url("tcp://10.0.0.1:1"), url("tcp://10.0.0.2:1")]);
+    }
+
+    #[test]
+    fn test_absorb() {
+        let url = |i: usize| Url::parse(&format!("tcp://10.0.{}.{}:1", i / 256, i % 256)).unwrap();
+        let mut stats = HashMap::new();
+        stats.insert(url(0), checked(4, 4, NOW));
+
+        // Known hosts keep their stats, and unchecked ones are capped
+        absorb(&mut stats, (0..MAX_UNCHECKED + 10).map(url).collect());
+        assert_eq!(stats.len(), MAX_UNCHECKED + 1);
+        assert_eq!(stats[&url(0)].checks, 4);
+        assert!(!stats.contains_key(&url(MAX_UNCHECKED + 1)));
+
+        // Checked hosts make room for new ones
+        stats.get_mut(&url(1)).unwrap().record(NOW, None);
+        absorb(&mut stats, vec![url(MAX_UNCHECKED + 1), url(MAX_UNCHECKED + 2)]);
+        assert!(stats.contains_key(&url(MAX_UNCHECKED + 1)));
+        assert!(!stats.contains_key(&url(MAX_UNCHECKED + 2)));
+    }
+}
@@ -47,7 +47,7 @@ use darkfi::{
         EventMsg,
     },
     net::{self, P2pPtr},
-    rpc::server::listen_and_serve,
+    rpc::{metrics, server::listen_and_serve},
     util::{path::expand_path, time::Timestamp},
     Error, Result,
 };
@@ -62,7 +62,7 @@ mod util;
 use crate::{
     error::TaudResult,
     jsonrpc::JsonRpcInterface,
-    settings::{Args, CONFIG_FILE, CONFIG_FILE_CONTENTS},
+    settings::{Args, CONFIG_FILE, CONFIG_FILE_CONTENTS, NETWORK_ID},
     task_info::{TaskEvent, TaskInfo},
     util::pipe_write,
 };
@@ -316,7 +316,7 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
     // Initialize the base structures
     ////////////////////
     let events_queue = EventsQueue::<EncryptedTask>::new();
-    let model = Arc::new(Mutex::new(Model::new(events_queue.clone())));
+    let model = Arc::new(Mutex::new(Model::new(events_queue.clone(), settings.dag.clone().into())));
     let view = Arc::new(Mutex::new(View::new(events_queue)));
     let model_clone = model.clone();
 
@@ -331,7 +331,8 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
     //
     // P2p setup
     //
-    let net_settings = settings.net.clone();
+    let mut net_settings = settings.net.clone();
+    net_settings.network_id.get_or_insert_with(|| NETWORK_ID.to_string());
 
     let p2p = net::P2p::new(net_settings.into()).await;
     let registry = p2p.protocol_registry();
@@ -382,6 +383,12 @@ async fn realmain(settings: Args, executor: Arc<smol::Executor<'_>>) -> Result<(
     let _ex = executor.clone();
     executor.spawn(listen_and_serve(settings.rpc_listen.clone(), rpc_interface, _ex)).detach();
 
+    if let Some(metrics_listen) = settings.metrics_listen.clone() {
+        info!("Starting metrics server on {}", metrics_listen);
+        let _ex = executor.clone();
+        executor.spawn(metrics::listen_and_serve(metrics_listen, _ex)).detach();
+    }
+
     //
     // Waiting Exit signal
     //
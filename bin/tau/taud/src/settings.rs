@@ -21,10 +21,12 @@ use structopt::StructOpt;
 use structopt_toml::StructOptToml;
 use url::Url;
 
-use darkfi::net::settings::SettingsOpt;
+use darkfi::{event_graph::retention::RetentionOpt, net::settings::SettingsOpt};
 
 pub const CONFIG_FILE: &str = "taud_config.toml";
 pub const CONFIG_FILE_CONTENTS: &str = include_str!("../../taud_config.toml");
+// P2P network identifier used unless configured otherwise
+pub const NETWORK_ID: &str = "taud";
 
 /// taud cli
 #[derive(Clone, Debug, Deserialize, StructOpt, StructOptToml)]
@@ -39,6 +41,10 @@ pub struct Args {
     #[structopt(long = "rpc", default_value = "tcp://127.0.0.1:23330")]
     pub rpc_listen: Url,
 
+    /// Prometheus metrics listen URL
+    #[structopt(long)]
+    pub metrics_listen: Option<Url>,
+
     /// Sets Datastore Path
     #[structopt(long, default_value = "~/.tau")]
     pub datastore: String,
@@ -46,6 +52,10 @@ pub struct Args {
     #[structopt(flatten)]
     pub net: SettingsOpt,
 
+    /// Event graph retention settings
+    #[structopt(flatten)]
+    pub dag: RetentionOpt,
+
     /// Increase verbosity
     #[structopt(short, parse(from_occurrences))]
     pub verbose: u8,
@@ -16,27 +16,61 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{process::exit, sync::mpsc::channel};
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    sync::{mpsc::channel, Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
 use darkfi_sdk::crypto::{ContractId, PublicKey, SecretKey, TokenId};
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::rngs::OsRng;
 use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde_json::json;
 
 use darkfi::cli_desc;
 
+/// Base58 alphabet used for encoding all the searchable targets
+const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Size in bytes of the encoded target values
+const VALUE_SIZE: i32 = 32;
+
+/// Amount of leading pattern characters whose chance is computed exactly,
+/// after the ones matching leading zero bytes
+const EXACT_DIGITS: usize = 2;
+
+/// Amount of candidates generated to estimate the search speed
+const BENCH_ATTEMPTS: u64 = 500;
+
+/// Interval in seconds at which the search progress is saved
+const SAVE_INTERVAL: u64 = 10;
+
 #[derive(Parser)]
 #[clap(name = "vanityaddr", about = cli_desc!(), version)]
 #[clap(arg_required_else_help(true))]
 struct Args {
-    /// Prefixes to search
-    prefix: Vec<String>,
+    /// Patterns to search (prefixes by default)
+    pattern: Vec<String>,
 
     /// Should the search be case-sensitive
     #[clap(short)]
     case_sensitive: bool,
 
+    /// Match patterns as suffixes instead of prefixes
+    #[clap(long, conflicts_with = "regex")]
+    suffix: bool,
+
+    /// Match patterns as regular expressions
+    #[clap(long)]
+    regex: bool,
+
     /// Search for an Address
     #[clap(long)]
     address: bool,
@@ -52,113 +86,339 @@ struct Args {
     /// Number of threads to use (defaults to number of available CPUs)
     #[clap(short)]
     threads: Option<usize>,
+
+    /// Append found matches as JSON lines to this file
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Skip the patterns already found in the output file
+    #[clap(long, requires = "output")]
+    resume: bool,
 }
 
-struct DrkAddr {
-    pub public: PublicKey,
-    pub secret: SecretKey,
+/// Kinds of values we can search for
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Address,
+    TokenId,
+    ContractId,
+}
+
+impl Target {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Address => "address",
+            Self::TokenId => "token_id",
+            Self::ContractId => "contract_id",
+        }
+    }
+
+    /// Derive the encoded target value from a secret key
+    fn derive(&self, secret: SecretKey) -> String {
+        match self {
+            Self::Address => PublicKey::from_secret(secret).to_string(),
+            Self::TokenId => TokenId::derive(secret).to_string(),
+            Self::ContractId => ContractId::derive(secret).to_string(),
+        }
+    }
 }
 
-struct DrkToken {
-    pub token_id: TokenId,
-    pub secret: SecretKey,
+/// How a pattern is matched against the encoded target value
+enum Matcher {
+    Prefix(String),
+    Suffix(String),
+    Regex(Regex),
 }
 
-struct DrkContract {
-    pub contract_id: ContractId,
-    pub secret: SecretKey,
+struct Pattern {
+    /// The pattern as given on the command line
+    source: String,
+    matcher: Matcher,
+    case_sensitive: bool,
 }
 
-trait Prefixable {
-    fn new() -> Self;
-    fn to_string(&self) -> String;
-    fn get_secret(&self) -> SecretKey;
+impl Pattern {
+    fn new(source: &str, args: &Args) -> Result<Self, String> {
+        if args.regex {
+            let regex = RegexBuilder::new(source)
+                .case_insensitive(!args.case_sensitive)
+                .build()
+                .map_err(|e| e.to_string())?;
+
+            return Ok(Self {
+                source: source.to_string(),
+                matcher: Matcher::Regex(regex),
+                case_sensitive: args.case_sensitive,
+            })
+        }
 
-    fn starts_with(&self, prefix: &str, case_sensitive: bool) -> bool {
-        if case_sensitive {
-            self.to_string().starts_with(prefix)
+        // Literal patterns have to be base58, otherwise they can never match.
+        // Case-insensitive characters only need one of their variants in it.
+        if let Some(c) = source.chars().find(|c| digits(*c, args.case_sensitive).is_empty()) {
+            return Err(format!("'{}' is not in the base58 alphabet", c))
+        }
+
+        let literal = if args.case_sensitive { source.to_string() } else { source.to_lowercase() };
+
+        let matcher = if args.suffix { Matcher::Suffix(literal) } else { Matcher::Prefix(literal) };
+
+        Ok(Self { source: source.to_string(), matcher, case_sensitive: args.case_sensitive })
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        let lowered;
+        let value = if self.case_sensitive {
+            value
         } else {
-            self.to_string().to_lowercase().starts_with(prefix.to_lowercase().as_str())
+            lowered = value.to_lowercase();
+            &lowered
+        };
+
+        match &self.matcher {
+            Matcher::Prefix(p) => value.starts_with(p.as_str()),
+            Matcher::Suffix(s) => value.ends_with(s.as_str()),
+            Matcher::Regex(r) => r.is_match(value),
         }
     }
 
-    fn starts_with_any(&self, prefixes: &[String], case_sensitive: bool) -> bool {
-        prefixes.iter().any(|prefix| self.starts_with(prefix, case_sensitive))
+    /// Expected amount of attempts to find a match for a single target.
+    /// Returns `None` for regular expressions.
+    fn difficulty(&self) -> Option<f64> {
+        let (Matcher::Prefix(literal) | Matcher::Suffix(literal)) = &self.matcher else {
+            return None
+        };
+
+        let digits: Vec<Vec<usize>> =
+            literal.chars().map(|c| digits(c, self.case_sensitive)).collect();
+
+        // The last digits of the encoded values are uniformly distributed
+        if let Matcher::Suffix(_) = self.matcher {
+            return Some(digits.iter().map(|x| BASE58.len() as f64 / x.len() as f64).product())
+        }
+
+        // The first ones aren't, so their chance is computed exactly, and the
+        // following ones are uniformly distributed again.
+        let zeros = digits.iter().take_while(|x| x[..] == [0]).count();
+        let (head, tail) = digits.split_at((zeros + EXACT_DIGITS).min(digits.len()));
+        let head: f64 = combinations(head).iter().map(|x| prefix_chance(x)).sum();
+        let tail: f64 = tail.iter().map(|x| x.len() as f64 / BASE58.len() as f64).product();
+
+        Some(1.0 / (head * tail))
     }
 }
 
-impl Prefixable for DrkAddr {
-    fn new() -> Self {
-        let secret = SecretKey::random(&mut OsRng);
-        let public = PublicKey::from_secret(secret);
-        Self { public, secret }
-    }
+/// Base58 digits a pattern character matches. Case-insensitive characters
+/// match any of their variants which exist in the base58 alphabet.
+fn digits(c: char, case_sensitive: bool) -> Vec<usize> {
+    BASE58
+        .chars()
+        .enumerate()
+        .filter(
+            |(_, x)| if case_sensitive { *x == c } else { x.to_lowercase().eq(c.to_lowercase()) },
+        )
+        .map(|(i, _)| i)
+        .collect()
+}
 
-    fn to_string(&self) -> String {
-        self.public.to_string()
-    }
+/// All the digit sequences made of one of the given digits per position
+fn combinations(digits: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    digits.iter().fold(vec![vec![]], |acc, options| {
+        acc.iter()
+            .flat_map(|prefix| {
+                options.iter().map(move |x| {
+                    let mut seq = prefix.clone();
+                    seq.push(*x);
+                    seq
+                })
+            })
+            .collect()
+    })
+}
 
-    fn get_secret(&self) -> SecretKey {
-        self.secret
+/// Chance that the base58 encoding of a uniformly random `VALUE_SIZE` byte
+/// value starts with the given digits. The values have a fixed width, so
+/// the first digit of the longest encodings can only be a small one, while
+/// the shorter encodings are rarer. Leading zero bytes are encoded as `1`.
+fn prefix_chance(digits: &[usize]) -> f64 {
+    let total = 256f64.powi(VALUE_SIZE);
+    let zeros = digits.iter().take_while(|x| **x == 0).count() as i32;
+    let rest = &digits[zeros as usize..];
+
+    // Values starting with exactly `zeros` zero bytes, or at least as many
+    // if the prefix is only made of them
+    let hi = 256f64.powi(VALUE_SIZE - zeros);
+    if rest.is_empty() {
+        return hi / total
     }
+    let lo = hi / 256.0;
+
+    // Values of each encoding length whose first digits are the given ones
+    let value = rest.iter().fold(0.0, |acc, x| acc * BASE58.len() as f64 + *x as f64);
+    let mut count = 0.0;
+    let mut scale = 1.0;
+    while value * scale < hi {
+        let (start, end) = (value * scale, (value + 1.0) * scale);
+        count +=
+            if start >= lo && end <= hi { scale } else { (end.min(hi) - start.max(lo)).max(0.0) };
+        scale *= BASE58.len() as f64;
+    }
+
+    count / total
 }
 
-impl Prefixable for DrkToken {
-    fn new() -> Self {
-        let secret = SecretKey::random(&mut OsRng);
-        let token_id = TokenId::derive(secret);
-        Self { token_id, secret }
+/// Found match for a pattern
+struct Found {
+    pattern: String,
+    target: Target,
+    value: String,
+    attempts: u64,
+    secret: SecretKey,
+}
+
+impl Found {
+    /// JSON representation of the match. The secret key is encoded the same
+    /// way `drk wallet --import-secrets` expects it on its stdin, though only
+    /// address secrets are money keys which make sense to import.
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "pattern": self.pattern,
+            (self.target.name()): self.value,
+            "attempts": self.attempts,
+            "secret": self.secret.to_string(),
+        })
     }
+}
 
-    fn to_string(&self) -> String {
-        self.token_id.to_string()
+/// Path of the file holding the attempts counter of a resumable search
+fn progress_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".progress");
+    PathBuf::from(path)
+}
+
+/// Read the patterns already found in a previous run from the output file
+fn load_found(output: &Path) -> HashSet<String> {
+    let Ok(file) = File::open(output) else { return HashSet::new() };
+
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(&line).ok())
+        .filter_map(|v| v["pattern"].as_str().map(|p| p.to_string()))
+        .collect()
+}
+
+/// Read the attempts counter of a previous run. The counter is only
+/// restored if the previous run searched for the same patterns.
+fn load_attempts(output: &Path, key: &str) -> u64 {
+    let Ok(data) = std::fs::read_to_string(progress_path(output)) else { return 0 };
+    let Ok(progress) = serde_json::from_str::<serde_json::Value>(&data) else { return 0 };
+
+    if progress["search"].as_str() != Some(key) {
+        return 0
     }
 
-    fn get_secret(&self) -> SecretKey {
-        self.secret
+    progress["attempts"].as_u64().unwrap_or(0)
+}
+
+fn save_attempts(output: &Path, key: &str, attempts: u64) {
+    let progress = json!({ "search": key, "attempts": attempts });
+    if let Err(e) = std::fs::write(progress_path(output), progress.to_string()) {
+        eprintln!("\rWarning: Failed to save search progress: {}", e);
     }
 }
 
-impl Prefixable for DrkContract {
-    fn new() -> Self {
+/// Estimate the amount of candidates checked per second by all threads
+fn benchmark(targets: &[Target], num_threads: usize) -> f64 {
+    let start = Instant::now();
+    for _ in 0..BENCH_ATTEMPTS {
         let secret = SecretKey::random(&mut OsRng);
-        let contract_id = ContractId::derive(secret);
-        Self { contract_id, secret }
+        for target in targets {
+            target.derive(secret);
+        }
     }
 
-    fn to_string(&self) -> String {
-        self.contract_id.to_string()
+    BENCH_ATTEMPTS as f64 / start.elapsed().as_secs_f64() * num_threads as f64
+}
+
+fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return "forever".to_string()
     }
 
-    fn get_secret(&self) -> SecretKey {
-        self.secret
+    match secs as u64 {
+        0 => "<1s".to_string(),
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h{}m", s / 3600, (s % 3600) / 60),
+        s => format!("{}d{}h", s / 86400, (s % 86400) / 3600),
     }
 }
 
 fn main() {
     let args = Args::parse();
 
-    if !((args.address ^ args.contract_id ^ args.token_id) &&
-        !(args.address && args.contract_id && args.token_id))
-    {
-        eprintln!("Error: Can only search for one of Address/ContractId/TokenId");
+    let mut targets = vec![];
+    if args.address {
+        targets.push(Target::Address);
+    }
+    if args.token_id {
+        targets.push(Target::TokenId);
+    }
+    if args.contract_id {
+        targets.push(Target::ContractId);
+    }
+
+    if targets.is_empty() {
+        eprintln!("Error: Need to search for at least one of Address/ContractId/TokenId");
         exit(1);
     }
 
-    if args.prefix.is_empty() {
-        eprintln!("Error: No prefix given to search.");
+    if args.pattern.is_empty() {
+        eprintln!("Error: No pattern given to search.");
         exit(1);
     }
 
-    // Check if prefixes are valid base58
-    for (idx, prefix) in args.prefix.iter().enumerate() {
-        match bs58::decode(prefix).into_vec() {
-            Ok(_) => {}
+    let mut patterns = vec![];
+    for (idx, source) in args.pattern.iter().enumerate() {
+        match Pattern::new(source, &args) {
+            Ok(pattern) => patterns.push(pattern),
             Err(e) => {
-                eprintln!("Error: Invalid base58 for prefix {}: {}", idx, e);
+                eprintln!("Error: Invalid pattern {}: {}", idx, e);
                 exit(1);
             }
-        };
+        }
+    }
+
+    // Identifies a search, so a resumed run only continues the same one
+    let search_key = format!(
+        "{}|{}|{}|{}|{}",
+        targets.iter().map(|t| t.name()).collect::<Vec<_>>().join(","),
+        if args.regex {
+            "regex"
+        } else if args.suffix {
+            "suffix"
+        } else {
+            "prefix"
+        },
+        args.case_sensitive,
+        args.pattern.len(),
+        args.pattern.join(","),
+    );
+
+    let mut attempts = 0;
+    if args.resume {
+        let output = args.output.as_ref().unwrap();
+        let found = load_found(output);
+        patterns.retain(|p| !found.contains(&p.source));
+        attempts = load_attempts(output, &search_key);
+
+        if patterns.is_empty() {
+            eprintln!("All patterns were already found in {:?}", output);
+            exit(0);
+        }
+
+        eprintln!("Resuming search after {} attempts, {} patterns left", attempts, patterns.len());
     }
 
     // Threadpool
@@ -169,6 +429,46 @@ fn main() {
     };
     let rayon_pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
 
+    // Print the difficulty and ETA estimates before starting the search.
+    // Every candidate is checked against all the targets, so the chance of
+    // a hit per attempt grows with the amount of targets.
+    let speed = benchmark(&targets, num_threads);
+    eprintln!("Searching with {} threads at ~{:.0} attempts/s", num_threads, speed);
+    let mut any_chance = 0.0;
+    let mut estimated = true;
+    for pattern in &patterns {
+        match pattern.difficulty() {
+            Some(difficulty) => {
+                let difficulty = difficulty / targets.len() as f64;
+                any_chance += 1.0 / difficulty;
+                eprintln!(
+                    "  {}: difficulty {:.0}, ETA {}",
+                    pattern.source,
+                    difficulty,
+                    format_duration(difficulty / speed)
+                );
+            }
+            None => {
+                estimated = false;
+                eprintln!("  {}: difficulty unknown for regular expressions", pattern.source);
+            }
+        }
+    }
+    if estimated && patterns.len() > 1 {
+        eprintln!("  First match ETA {}", format_duration(1.0 / any_chance / speed));
+    }
+
+    let output = args.output.as_ref().map(|path| {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Error: Failed to open output file {:?}: {}", path, e);
+                exit(1);
+            }
+        }
+    });
+    let output = Mutex::new(output);
+
     // Handle SIGINT
     let (tx, rx) = channel();
     ctrlc::set_handler(move || tx.send(()).expect("Could not send signal on channel"))
@@ -176,66 +476,160 @@ fn main() {
 
     // Something fancy
     let progress = ProgressBar::new_spinner();
-    let template =
-        ProgressStyle::default_bar().template("[{elapsed_precise}] {pos} attempts").unwrap();
+    let template = ProgressStyle::default_bar()
+        .template("[{elapsed_precise}] {pos} attempts ({per_sec}) {msg}")
+        .unwrap();
     progress.set_style(template);
+    progress.set_position(attempts);
+    progress.set_message(format!("{} patterns left", patterns.len()));
+
+    // Periodically save the attempts counter so the search can be resumed
+    if let Some(path) = args.output.clone() {
+        let progress = progress.clone();
+        let search_key = search_key.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(SAVE_INTERVAL));
+            save_attempts(&path, &search_key, progress.position());
+        });
+    }
+
+    // Patterns still being searched for. Matches are rare, so threads only
+    // take the write lock when they find one.
+    let patterns = Arc::new(RwLock::new(patterns));
 
     // Fire off the threadpool
+    let progress_ = progress.clone();
+    let output_path = args.output.clone();
+    let search_key_ = search_key.clone();
     rayon_pool.spawn(move || {
-        if args.token_id {
-            let tid = rayon::iter::repeat(DrkToken::new)
-                .inspect(|_| progress.inc(1))
-                .map(|create| create())
-                .find_any(|token_id| token_id.starts_with_any(&args.prefix, args.case_sensitive))
-                .expect("Failed to find a token ID match");
-
-            // The above will keep running until it finds a match or until the
-            // program terminates. Only if a match is found shall the following
-            // code be executed and the program exit successfully:
-            let attempts = progress.position();
-            progress.finish_and_clear();
-
-            println!(
-                "{{\"token_id\":\"{}\",\"attempts\":{},\"secret\":\"{}\"}}",
-                tid.token_id, attempts, tid.secret,
-            );
-        } else if args.address {
-            let addr = rayon::iter::repeat(DrkAddr::new)
-                .inspect(|_| progress.inc(1))
-                .map(|create| create())
-                .find_any(|address| address.starts_with_any(&args.prefix, args.case_sensitive))
-                .expect("Failed to find an address match");
-
-            let attempts = progress.position();
-            progress.finish_and_clear();
-
-            println!(
-                "{{\"address\":\"{}\",\"attempts\":{},\"secret\":\"{}\"}}",
-                addr.public, attempts, addr.secret,
-            );
-        } else if args.contract_id {
-            let cid = rayon::iter::repeat(DrkContract::new)
-                .inspect(|_| progress.inc(1))
-                .map(|create| create())
-                .find_any(|contract_id| {
-                    contract_id.starts_with_any(&args.prefix, args.case_sensitive)
-                })
-                .expect("Failed to find a contract ID match");
-
-            let attempts = progress.position();
-            progress.finish_and_clear();
-
-            println!(
-                "{{\"contract_id\":\"{}\",\"attempts\":{},\"secret\":\"{}\"}}",
-                cid.contract_id, attempts, cid.secret,
-            );
+        rayon::iter::repeat(())
+            .map(|_| SecretKey::random(&mut OsRng))
+            .inspect(|_| progress_.inc(1))
+            .find_any(|secret| {
+                for target in &targets {
+                    let value = target.derive(*secret);
+                    if !patterns.read().unwrap().iter().any(|p| p.is_match(&value)) {
+                        continue
+                    }
+
+                    let mut remaining = patterns.write().unwrap();
+                    // Another thread could have taken the pattern in the meantime
+                    let Some(idx) = remaining.iter().position(|p| p.is_match(&value)) else {
+                        continue
+                    };
+                    let pattern = remaining.remove(idx);
+
+                    let found = Found {
+                        pattern: pattern.source,
+                        target: *target,
+                        value,
+                        attempts: progress_.position(),
+                        secret: *secret,
+                    };
+
+                    let line = found.to_json().to_string();
+                    progress_.suspend(|| println!("{}", line));
+                    if let Some(file) = output.lock().unwrap().as_mut() {
+                        if let Err(e) = writeln!(file, "{}", line) {
+                            eprintln!("\rError: Failed to write match to output file: {}", e);
+                        }
+                    }
+                    progress_.set_message(format!("{} patterns left", remaining.len()));
+
+                    return remaining.is_empty()
+                }
+
+                false
+            })
+            .expect("Failed to find a match");
+
+        // The above will keep running until all patterns are found or until
+        // the program terminates. Only if everything was found shall the
+        // following code be executed and the program exit successfully:
+        if let Some(path) = output_path {
+            save_attempts(&path, &search_key_, progress_.position());
         }
-
+        progress_.finish_and_clear();
         exit(0);
     });
 
     // This now blocks and lets our threadpool execute in the background.
     rx.recv().expect("Could not receive from channel");
+    if let Some(path) = args.output {
+        save_attempts(&path, &search_key, progress.position());
+    }
     eprintln!("\rCaught SIGINT, exiting...");
     exit(127);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+    fn pattern(source: &str, flags: &[&str]) -> Result<Pattern, String> {
+        let args =
+            Args::parse_from(["vanityaddr", "--address"].iter().chain(flags).chain([&source]));
+        Pattern::new(source, &args)
+    }
+
+    fn difficulty(source: &str, flags: &[&str]) -> f64 {
+        pattern(source, flags).unwrap().difficulty().unwrap()
+    }
+
+    #[test]
+    fn test_pattern_validation() {
+        // Only some case variants of I, l and o are base58
+        assert!(pattern("Ill", &[]).is_ok());
+        assert!(pattern("ioL", &[]).is_ok());
+        assert!(pattern("Ill", &["-c"]).is_err());
+        assert!(pattern("drk0", &[]).is_err());
+        assert!(pattern("drk_", &["--suffix"]).is_err());
+        assert!(pattern("^drk[0_]", &["--regex"]).is_ok());
+
+        let ill = pattern("Ill", &[]).unwrap();
+        assert!(ill.is_match("iLLxyz"));
+        assert!(!ill.is_match("xyziLL"));
+        assert!(pattern("drk", &["-c", "--suffix"]).unwrap().is_match("xyzdrk"));
+        assert!(pattern("drk", &["--regex"]).unwrap().difficulty().is_none());
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let close = |a: f64, b: f64| (a - b).abs() / b < 1e-9;
+
+        assert!(close(difficulty("z", &["-c", "--suffix"]), 58.0));
+        assert!(close(difficulty("drk", &["--suffix"]), 58f64.powi(3) / 8.0));
+        assert!(close(difficulty("drk", &["-c", "--suffix"]), 58f64.powi(3)));
+
+        // A leading zero byte is encoded as a leading 1
+        assert!(close(difficulty("1", &[]), 256.0));
+        assert!(close(difficulty("11", &[]), 65536.0));
+
+        // z is only the first digit of the shorter encodings, where it's
+        // followed by 42 more digits
+        assert!(close(difficulty("z", &["-c"]), 2f64.powi(256) / 58f64.powi(42)));
+        assert!(difficulty("2", &[]) < 58.0);
+        assert!(difficulty("drk", &[]) < 58f64.powi(3) / 8.0);
+    }
+
+    #[test]
+    fn test_prefix_chance() {
+        // Compare against the first digits of random values
+        let mut rng = StdRng::seed_from_u64(42);
+        let samples = 20000;
+        let mut encoded = vec![];
+        for _ in 0..samples {
+            let mut bytes = [0u8; VALUE_SIZE as usize];
+            rng.fill_bytes(&mut bytes);
+            encoded.push(bs58::encode(bytes).into_string());
+        }
+
+        for prefix in ["2", "D", "H", "d", "z", "Dr"] {
+            let hits = encoded.iter().filter(|x| x.starts_with(prefix)).count() as f64;
+            let chance = 1.0 / difficulty(prefix, &["-c"]);
+            let expected = chance * samples as f64;
+            assert!((hits - expected).abs() < 5.0 * expected.sqrt() + 5.0, "{}", prefix);
+        }
+    }
+}
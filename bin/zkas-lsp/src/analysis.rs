@@ -0,0 +1,303 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Runs the zkas stages over a document and answers queries about it.
+
+use darkfi::{
+    consensus::fees::circuit_gas_use,
+    zkas::{
+        ast::{Constant, Variable, Witness},
+        capture_diagnostics,
+        lexer::{Token, TokenType},
+        Analyzer, Compiler, Diagnostic, Lexer, Opcode, Parser, ZkBinary,
+    },
+};
+
+/// What we know about a document after running it through zkas
+#[derive(Default)]
+pub struct Analysis {
+    /// Tokens of the document, if it could be lexed
+    pub tokens: Vec<Token>,
+    pub constants: Vec<Constant>,
+    pub witnesses: Vec<Witness>,
+    /// Heap variables assigned in the circuit, as typed by the analyzer
+    pub heap: Vec<Variable>,
+    /// Estimated verification gas of the circuit, if it compiles
+    pub gas: Option<u64>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Run the lexer, parser, analyzer and compiler over `source`, stopping
+/// at the first stage that fails.
+pub fn analyze(filename: &str, source: &str) -> Analysis {
+    // Keep a tab a single column wide, so positions match the editor's.
+    let source = source.replace('\t', " ").replace("\r\n", "\n");
+    let mut analysis = Analysis::default();
+
+    let (tokens, diagnostics) = capture_diagnostics(|| Lexer::new(filename, source.chars()).lex());
+    analysis.diagnostics.extend(diagnostics);
+    let Some(tokens) = tokens else { return analysis };
+    analysis.tokens = tokens.clone();
+
+    let (parsed, diagnostics) =
+        capture_diagnostics(|| Parser::new(filename, source.chars(), tokens).parse());
+    analysis.diagnostics.extend(diagnostics);
+    let Some((namespace, constants, witnesses, statements)) = parsed else { return analysis };
+    analysis.constants = constants.clone();
+    analysis.witnesses = witnesses.clone();
+
+    let (analyzer, diagnostics) = capture_diagnostics(|| {
+        let mut analyzer =
+            Analyzer::new(filename, source.chars(), constants, witnesses, statements);
+        analyzer.analyze_types();
+        analyzer
+    });
+    analysis.diagnostics.extend(diagnostics);
+    let Some(analyzer) = analyzer else { return analysis };
+    analysis.heap = analyzer.heap.clone();
+
+    let (bincode, diagnostics) = capture_diagnostics(|| {
+        Compiler::new(
+            filename,
+            source.chars(),
+            namespace,
+            analyzer.constants,
+            analyzer.witnesses,
+            analyzer.statements,
+            analyzer.literals,
+            false,
+        )
+        .compile()
+    });
+    analysis.diagnostics.extend(diagnostics);
+    if let Some(bincode) = bincode {
+        analysis.gas = ZkBinary::decode(&bincode).ok().map(|zkbin| circuit_gas_use(&zkbin));
+    }
+
+    analysis
+}
+
+/// Render the signature of an opcode, e.g. `ec_add(EcPoint, EcPoint) -> EcPoint`
+pub fn opcode_signature(opcode: &Opcode) -> (String, Vec<String>) {
+    let (return_types, arg_types) = opcode.arg_types();
+    let args: Vec<String> = arg_types.iter().map(|x| format!("{:?}", x)).collect();
+
+    let mut label = format!("{}({})", opcode.name(), args.join(", "));
+    if !return_types.is_empty() {
+        let ret: Vec<String> = return_types.iter().map(|x| format!("{:?}", x)).collect();
+        label.push_str(&format!(" -> {}", ret.join(", ")));
+    }
+
+    (label, args)
+}
+
+impl Analysis {
+    /// Find the token at the given 1-indexed line and column
+    pub fn token_at(&self, line: usize, column: usize) -> Option<&Token> {
+        self.tokens.iter().find(|t| {
+            t.line == line && t.column <= column && column < t.column + t.token.chars().count()
+        })
+    }
+
+    /// Line of the `circuit` section declaration
+    pub fn circuit_line(&self) -> Option<usize> {
+        self.tokens
+            .iter()
+            .find(|t| t.token_type == TokenType::Symbol && t.token == "circuit")
+            .map(|t| t.line)
+    }
+
+    /// Describe a symbol for hovering
+    pub fn describe(&self, name: &str) -> Option<String> {
+        if let Some(c) = self.constants.iter().find(|c| c.name == name) {
+            return Some(format!("constant `{}`: {:?}", c.name, c.typ))
+        }
+
+        if let Some(w) = self.witnesses.iter().find(|w| w.name == name) {
+            return Some(format!("witness `{}`: {:?}", w.name, w.typ))
+        }
+
+        if let Some(v) = self.heap.iter().find(|v| v.name == name) {
+            return Some(format!("`{}`: {:?}", v.name, v.typ))
+        }
+
+        if let Some(opcode) = Opcode::from_name(name) {
+            return Some(opcode_signature(&opcode).0)
+        }
+
+        if name == "circuit" {
+            return self.gas.map(|gas| format!("Estimated verification gas: {}", gas))
+        }
+
+        None
+    }
+
+    /// Find where a constant, witness or heap variable is declared,
+    /// as a 1-indexed (line, column) pair.
+    pub fn definition(&self, name: &str) -> Option<(usize, usize)> {
+        // Constants and witnesses are positioned at their type, so look
+        // for the name on the same line.
+        let declared = self
+            .constants
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.line)
+            .or_else(|| self.witnesses.iter().find(|w| w.name == name).map(|w| w.line));
+
+        if let Some(line) = declared {
+            return self
+                .tokens
+                .iter()
+                .find(|t| t.line == line && t.token == name)
+                .map(|t| (t.line, t.column))
+        }
+
+        self.heap.iter().find(|v| v.name == name).map(|v| (v.line, v.column))
+    }
+
+    /// Find the opcode call around the given 1-indexed position, returning
+    /// the opcode and the index of the argument the position is at.
+    pub fn call_at(&self, line: usize, column: usize) -> Option<(Opcode, usize)> {
+        let before: Vec<&Token> = self
+            .tokens
+            .iter()
+            .take_while(|t| t.line < line || (t.line == line && t.column < column))
+            .collect();
+
+        let mut depth = 0;
+        let mut arg = 0;
+        for (idx, token) in before.iter().enumerate().rev() {
+            match token.token_type {
+                TokenType::RightParen => depth += 1,
+                TokenType::Comma if depth == 0 => arg += 1,
+                TokenType::Semicolon | TokenType::LeftBrace | TokenType::RightBrace => return None,
+                TokenType::LeftParen if depth > 0 => depth -= 1,
+                TokenType::LeftParen => {
+                    let name = before.get(idx.checked_sub(1)?)?;
+                    return Some((Opcode::from_name(&name.token)?, arg))
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "constant \"Test\" {
+\tEcFixedPointShort VALUE_COMMIT_VALUE,
+}
+
+witness \"Test\" {
+\tBase a,
+}
+
+circuit \"Test\" {
+\tsum = base_add(a, a);
+\tpoint = ec_mul_short(sum, VALUE_COMMIT_VALUE);
+\tconstrain_instance(ec_get_x(point));
+}
+";
+
+    #[test]
+    fn test_token_at() {
+        let analysis = analyze("test.zk", SOURCE);
+        assert!(analysis.diagnostics.is_empty());
+
+        // The tab is a single column, so `sum` spans columns 2 to 4
+        assert!(analysis.token_at(10, 1).is_none());
+        assert_eq!(analysis.token_at(10, 2).unwrap().token, "sum");
+        assert_eq!(analysis.token_at(10, 4).unwrap().token, "sum");
+        assert!(analysis.token_at(10, 5).is_none());
+        assert_eq!(analysis.token_at(10, 6).unwrap().token_type, TokenType::Assign);
+
+        // `base_add` ends right before its parenthesis
+        assert_eq!(analysis.token_at(10, 15).unwrap().token, "base_add");
+        assert_eq!(analysis.token_at(10, 16).unwrap().token_type, TokenType::LeftParen);
+
+        // Past the end of a line and of the document
+        assert!(analysis.token_at(10, 100).is_none());
+        assert!(analysis.token_at(100, 1).is_none());
+    }
+
+    #[test]
+    fn test_describe() {
+        let analysis = analyze("test.zk", SOURCE);
+
+        assert_eq!(
+            analysis.describe("VALUE_COMMIT_VALUE").unwrap(),
+            "constant `VALUE_COMMIT_VALUE`: EcFixedPointShort"
+        );
+        assert_eq!(analysis.describe("a").unwrap(), "witness `a`: Base");
+        assert_eq!(analysis.describe("sum").unwrap(), "`sum`: Base");
+        assert_eq!(analysis.describe("point").unwrap(), "`point`: EcPoint");
+        assert_eq!(analysis.describe("base_add").unwrap(), "base_add(Base, Base) -> Base");
+        assert_eq!(
+            analysis.describe("circuit").unwrap(),
+            format!("Estimated verification gas: {}", analysis.gas.unwrap())
+        );
+
+        assert!(analysis.describe("unknown").is_none());
+        assert!(analysis.describe("Test").is_none());
+    }
+
+    #[test]
+    fn test_definition() {
+        let analysis = analyze("test.zk", SOURCE);
+
+        assert_eq!(analysis.definition("VALUE_COMMIT_VALUE"), Some((2, 20)));
+        assert_eq!(analysis.definition("a"), Some((6, 7)));
+        assert_eq!(analysis.definition("sum"), Some((10, 2)));
+        assert_eq!(analysis.definition("point"), Some((11, 2)));
+
+        // Opcodes and unknown identifiers aren't declared anywhere
+        assert!(analysis.definition("base_add").is_none());
+        assert!(analysis.definition("unknown").is_none());
+    }
+
+    #[test]
+    fn test_call_at() {
+        let analysis = analyze("test.zk", SOURCE);
+
+        // Outside of any call
+        assert!(analysis.call_at(10, 2).is_none());
+        assert!(analysis.call_at(10, 16).is_none());
+        assert!(analysis.call_at(6, 7).is_none());
+
+        // Right after the parenthesis, and at each argument
+        assert!(matches!(analysis.call_at(10, 17), Some((Opcode::BaseAdd, 0))));
+        assert!(matches!(analysis.call_at(10, 20), Some((Opcode::BaseAdd, 1))));
+        assert!(matches!(analysis.call_at(11, 31), Some((Opcode::EcMulShort, 1))));
+
+        // Nested calls resolve to the innermost one until it's closed
+        assert!(matches!(analysis.call_at(12, 30), Some((Opcode::EcGetX, 0))));
+        assert!(matches!(analysis.call_at(12, 36), Some((Opcode::ConstrainInstance, 0))));
+
+        // Past the end of the statement
+        assert!(analysis.call_at(12, 38).is_none());
+
+        // Calls to unknown opcodes are still lexed, but not resolved
+        let analysis = analyze("test.zk", "circuit \"Test\" {\n\tx = unknown(a, b);\n}\n");
+        assert!(!analysis.tokens.is_empty());
+        assert!(analysis.call_at(2, 17).is_none());
+    }
+}
@@ -0,0 +1,351 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Language server for zkas circuits, speaking LSP over stdio.
+
+use std::{collections::HashMap, error::Error, panic, process::exit};
+
+use lsp_server::{
+    Connection, ErrorCode, ExtractError, Message, Notification, Request, Response, ResponseError,
+};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{CodeLensRequest, GotoDefinition, HoverRequest, Request as _, SignatureHelpRequest},
+    CodeLens, CodeLensOptions, Command, Diagnostic, DiagnosticSeverity, GotoDefinitionResponse,
+    Hover, HoverContents, HoverProviderCapability, Location, MarkedString, OneOf,
+    ParameterInformation, ParameterLabel, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, SignatureHelp, SignatureHelpOptions, SignatureInformation,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+mod analysis;
+use analysis::{analyze, opcode_signature, Analysis};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+/// Convert a 1-indexed zkas position into an LSP range spanning `len` characters
+fn range(line: usize, column: usize, len: usize) -> Range {
+    let line = line.saturating_sub(1) as u32;
+    let column = column.saturating_sub(1) as u32;
+    Range::new(Position::new(line, column), Position::new(line, column + len as u32))
+}
+
+/// Run the zkas stages over a document. A panic in zkas is reported as
+/// a diagnostic instead of taking down the server.
+fn analyze_document(uri: &Url, text: &str) -> Analysis {
+    let filename = match uri.to_file_path() {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(()) => uri.path().to_string(),
+    };
+
+    match panic::catch_unwind(|| analyze(&filename, text)) {
+        Ok(analysis) => analysis,
+        Err(_) => {
+            let mut analysis = Analysis::default();
+            analysis.diagnostics.push(darkfi::zkas::Diagnostic {
+                namespace: "zkas-lsp".to_string(),
+                file: filename,
+                message: "Internal compiler error".to_string(),
+                line: 0,
+                column: 0,
+                is_error: true,
+            });
+            analysis
+        }
+    }
+}
+
+/// Convert the zkas diagnostics of a document into LSP ones. Diagnostics
+/// coming from imported files are shown at the top of the document.
+fn diagnostics(uri: &Url, analysis: &Analysis) -> Vec<Diagnostic> {
+    let path = uri.to_file_path().ok();
+
+    analysis
+        .diagnostics
+        .iter()
+        .map(|d| {
+            let severity =
+                if d.is_error { DiagnosticSeverity::ERROR } else { DiagnosticSeverity::WARNING };
+
+            let same_file = path.as_ref().map(|p| p.to_string_lossy() == d.file).unwrap_or(true);
+            let (range, message) = if same_file {
+                (range(d.line, d.column, 1), d.message.clone())
+            } else {
+                (range(0, 0, 0), format!("{}:{}:{}: {}", d.file, d.line, d.column, d.message))
+            };
+
+            Diagnostic {
+                range,
+                severity: Some(severity),
+                source: Some(format!("zkas {}", d.namespace)),
+                message,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Analysis>,
+}
+
+impl Server {
+    fn update(&mut self, uri: Url, text: &str) -> Result<()> {
+        let analysis = analyze_document(&uri, text);
+        let params = PublishDiagnosticsParams::new(uri.clone(), diagnostics(&uri, &analysis), None);
+        self.documents.insert(uri, analysis);
+
+        let notif = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(Message::Notification(notif))?;
+        Ok(())
+    }
+
+    fn handle_notification(&mut self, notif: Notification) -> Result<()> {
+        match notif.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidOpenTextDocument>(notif) else {
+                    return Ok(())
+                };
+                self.update(params.text_document.uri, &params.text_document.text)?;
+            }
+
+            DidChangeTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidChangeTextDocument>(notif) else {
+                    return Ok(())
+                };
+                // We use full document sync, so the last change holds the text.
+                if let Some(change) = params.content_changes.last() {
+                    self.update(params.text_document.uri, &change.text)?;
+                }
+            }
+
+            DidSaveTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidSaveTextDocument>(notif) else {
+                    return Ok(())
+                };
+                // Imported files might have changed, so reanalyze from disk.
+                let uri = params.text_document.uri;
+                match uri.to_file_path().map(std::fs::read_to_string) {
+                    Ok(Ok(text)) => self.update(uri, &text)?,
+                    Ok(Err(e)) => eprintln!("Warning: Failed reading {}: {}", uri, e),
+                    Err(()) => {}
+                }
+            }
+
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = notification_params::<DidCloseTextDocument>(notif) else {
+                    return Ok(())
+                };
+                self.documents.remove(&params.text_document.uri);
+            }
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn hover(&self, uri: &Url, pos: Position) -> Option<Hover> {
+        let analysis = self.documents.get(uri)?;
+        let token = analysis.token_at(pos.line as usize + 1, pos.character as usize + 1)?;
+        let contents = analysis.describe(&token.token)?;
+
+        Some(Hover {
+            contents: HoverContents::Scalar(MarkedString::String(contents)),
+            range: Some(range(token.line, token.column, token.token.chars().count())),
+        })
+    }
+
+    fn definition(&self, uri: &Url, pos: Position) -> Option<GotoDefinitionResponse> {
+        let analysis = self.documents.get(uri)?;
+        let token = analysis.token_at(pos.line as usize + 1, pos.character as usize + 1)?;
+        let (line, column) = analysis.definition(&token.token)?;
+
+        let range = range(line, column, token.token.chars().count());
+        Some(GotoDefinitionResponse::Scalar(Location::new(uri.clone(), range)))
+    }
+
+    fn signature_help(&self, uri: &Url, pos: Position) -> Option<SignatureHelp> {
+        let analysis = self.documents.get(uri)?;
+        let (opcode, arg) = analysis.call_at(pos.line as usize + 1, pos.character as usize + 1)?;
+        let (label, args) = opcode_signature(&opcode);
+
+        let parameters = args
+            .into_iter()
+            .map(|x| ParameterInformation { label: ParameterLabel::Simple(x), documentation: None })
+            .collect();
+
+        let signature = SignatureInformation {
+            label,
+            documentation: None,
+            parameters: Some(parameters),
+            active_parameter: None,
+        };
+
+        Some(SignatureHelp {
+            signatures: vec![signature],
+            active_signature: Some(0),
+            active_parameter: Some(arg as u32),
+        })
+    }
+
+    fn code_lens(&self, uri: &Url) -> Vec<CodeLens> {
+        let Some(analysis) = self.documents.get(uri) else { return vec![] };
+        let (Some(line), Some(gas)) = (analysis.circuit_line(), analysis.gas) else {
+            return vec![]
+        };
+
+        let command = Command::new(format!("Estimated gas: {}", gas), String::new(), None);
+        vec![CodeLens { range: range(line, 1, 0), command: Some(command), data: None }]
+    }
+
+    /// Answer a request. Unknown methods and malformed parameters get an
+    /// error response, so the client isn't left waiting.
+    fn handle_request(&self, req: Request) -> Result<()> {
+        let id = req.id.clone();
+
+        let resp = match req.method.as_str() {
+            HoverRequest::METHOD => request_params::<HoverRequest>(req).map(|params| {
+                let doc = params.text_document_position_params;
+                Response::new_ok(id.clone(), self.hover(&doc.text_document.uri, doc.position))
+            }),
+
+            GotoDefinition::METHOD => request_params::<GotoDefinition>(req).map(|params| {
+                let doc = params.text_document_position_params;
+                Response::new_ok(id.clone(), self.definition(&doc.text_document.uri, doc.position))
+            }),
+
+            SignatureHelpRequest::METHOD => {
+                request_params::<SignatureHelpRequest>(req).map(|params| {
+                    let doc = params.text_document_position_params;
+                    let help = self.signature_help(&doc.text_document.uri, doc.position);
+                    Response::new_ok(id.clone(), help)
+                })
+            }
+
+            CodeLensRequest::METHOD => request_params::<CodeLensRequest>(req).map(|params| {
+                Response::new_ok(id.clone(), self.code_lens(&params.text_document.uri))
+            }),
+
+            method => Err(ResponseError {
+                code: ErrorCode::MethodNotFound as i32,
+                message: format!("Unhandled method {}", method),
+                data: None,
+            }),
+        };
+
+        let resp = resp.unwrap_or_else(|e| Response { id, result: None, error: Some(e) });
+        self.connection.sender.send(Message::Response(resp))?;
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<()> {
+        while let Ok(msg) = self.connection.receiver.recv() {
+            match msg {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        return Ok(())
+                    }
+                    self.handle_request(req)?;
+                }
+                Message::Notification(notif) => self.handle_notification(notif)?,
+                Message::Response(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the parameters of a notification, logging the malformed ones
+fn notification_params<N: lsp_types::notification::Notification>(
+    notif: Notification,
+) -> Option<N::Params> {
+    match serde_json::from_value(notif.params) {
+        Ok(params) => Some(params),
+        Err(e) => {
+            eprintln!("Warning: Malformed {} notification: {}", N::METHOD, e);
+            None
+        }
+    }
+}
+
+/// Parse the parameters of a request, logging the malformed ones
+fn request_params<R: lsp_types::request::Request>(
+    req: Request,
+) -> std::result::Result<R::Params, ResponseError> {
+    match req.extract(R::METHOD) {
+        Ok((_, params)) => Ok(params),
+        Err(e) => {
+            let message = match e {
+                ExtractError::JsonError { error, .. } => error.to_string(),
+                ExtractError::MethodMismatch(req) => format!("Unexpected method {}", req.method),
+            };
+            eprintln!("Warning: Malformed {} request: {}", R::METHOD, message);
+            Err(ResponseError { code: ErrorCode::InvalidParams as i32, message, data: None })
+        }
+    }
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+            retrigger_characters: None,
+            work_done_progress_options: Default::default(),
+        }),
+        code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(false) }),
+        ..Default::default()
+    }
+}
+
+fn main() {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = match serde_json::to_value(capabilities()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: Failed serializing server capabilities: {}", e);
+            exit(1);
+        }
+    };
+
+    if let Err(e) = connection.initialize(capabilities) {
+        eprintln!("Error: Failed initializing the connection: {}", e);
+        exit(1);
+    }
+
+    let mut server = Server { connection, documents: HashMap::new() };
+    if let Err(e) = server.run() {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+
+    drop(server);
+    if let Err(e) = io_threads.join() {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
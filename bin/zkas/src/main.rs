@@ -36,7 +36,11 @@ struct Args {
     #[clap(short = 'o', value_name = "FILE")]
     output: Option<String>,
 
-    /// Strip debug symbols
+    /// Emit debug symbols (`.debug` section)
+    #[clap(short = 'g')]
+    debug: bool,
+
+    /// Strip debug symbols (default, kept for compatibility)
     #[clap(short = 's')]
     strip: bool,
 
@@ -109,7 +113,7 @@ fn main() {
         analyzer.witnesses,
         analyzer.statements,
         analyzer.literals,
-        !args.strip,
+        args.debug && !args.strip,
     );
 
     let bincode = compiler.compile();
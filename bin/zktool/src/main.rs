@@ -16,7 +16,25 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::{
+    fs::{read, read_to_string, write},
+    io::{stdin, stdout, Write},
+    path::{Path, PathBuf},
+    process::exit,
+};
+
 use clap::{Parser, Subcommand};
+use darkfi::{
+    util::path::expand_path,
+    zk::{key_store::DEFAULT_KEY_STORE_PATH, trace::TraceStep, vm::ZkCircuit, KeyStore, Proof},
+    zkas::ZkBinary,
+    Error, Result,
+};
+use halo2_proofs::dev::MockProver;
+use rand::rngs::OsRng;
+use serde_json::{json, Value as JsonValue};
+
+mod witness;
 
 #[derive(Subcommand)]
 pub enum CliSubCommands {
@@ -38,14 +56,58 @@ pub enum CliSubCommands {
         value_a: String,
         value_b: String,
     },
-    MakeProof {
-        bincode: String,
-        witness: String,
-        publics: String,
+    /// Print a witness file for a circuit, to be filled with values
+    Template {
+        /// Compiled circuit (`.zk.bin`)
+        circuit: PathBuf,
+    },
+    /// Build the verifying key of a circuit and store it in the key store
+    Keygen {
+        /// Compiled circuit (`.zk.bin`)
+        circuit: PathBuf,
+        /// Circuit size (number of rows is 2^k)
+        #[clap(short, default_value = "13")]
+        k: u32,
+    },
+    /// Create a proof from a witness file
+    Prove {
+        /// Compiled circuit (`.zk.bin`)
+        circuit: PathBuf,
+        /// JSON file holding the witnesses and public inputs
+        witness: PathBuf,
+        /// Write the proof to a file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Circuit size (number of rows is 2^k)
+        #[clap(short, default_value = "13")]
+        k: u32,
+    },
+    /// Verify a proof created with `prove`
+    Verify {
+        /// Compiled circuit (`.zk.bin`)
+        circuit: PathBuf,
+        /// JSON file holding the proof and public inputs
+        proof: PathBuf,
+        /// Circuit size (number of rows is 2^k)
+        #[clap(short, default_value = "13")]
+        k: u32,
     },
-    VerifyProof {
-        bincode: String,
-        publics: String,
+    /// Run a circuit with the MockProver, tracing every executed opcode
+    /// and reporting the first failing constraint
+    Trace {
+        /// Compiled circuit (`.zk.bin`)
+        circuit: PathBuf,
+        /// JSON file holding the witnesses and public inputs
+        witness: PathBuf,
+        /// Circuit source, defaults to the circuit path without `.bin`
+        #[clap(short, long)]
+        source: Option<PathBuf>,
+        /// Circuit size (number of rows is 2^k)
+        #[clap(short, default_value = "13")]
+        k: u32,
+        /// Step through the opcodes one by one
+        #[clap(short, long)]
+        interactive: bool,
     },
 }
 
@@ -56,17 +118,246 @@ pub struct CliDao {
     /// Increase verbosity
     #[clap(short, action = clap::ArgAction::Count)]
     pub verbose: u8,
+    /// Directory holding the verifying keys of circuits
+    #[clap(long, default_value = DEFAULT_KEY_STORE_PATH)]
+    pub key_store: String,
     #[clap(subcommand)]
     pub command: Option<CliSubCommands>,
 }
 
+/// Source code of a circuit, used to print the lines opcodes come from
+struct Source(Vec<String>);
+
+impl Source {
+    fn load(circuit: &Path, source: Option<PathBuf>) -> Option<Self> {
+        // zkas writes `<input>.bin` by default
+        let path = source.unwrap_or_else(|| circuit.with_extension(""));
+        let source = read_to_string(path).ok()?;
+        Some(Self(source.replace('\t', "    ").lines().map(|x| x.to_string()).collect()))
+    }
+
+    fn line(&self, line: usize) -> Option<&str> {
+        self.0.get(line.checked_sub(1)?).map(|x| x.as_str())
+    }
+}
+
+fn print_step(step: &TraceStep, source: &Option<Source>, last_line: &mut Option<usize>) {
+    // Nested calls are compiled into several opcodes on the same line,
+    // so only print the source line once.
+    if step.line != *last_line {
+        if let (Some(line), Some(source)) = (step.line, source) {
+            if let Some(code) = source.line(line) {
+                println!("{:>5} | {}", line, code.trim());
+            }
+        }
+        *last_line = step.line;
+    }
+
+    print!("{}", step);
+}
+
+fn trace(
+    circuit_path: PathBuf,
+    witness_path: PathBuf,
+    source: Option<PathBuf>,
+    k: u32,
+    mut interactive: bool,
+) -> Result<()> {
+    let bincode = std::fs::read(&circuit_path)?;
+    let zkbin = ZkBinary::decode(&bincode)?;
+
+    if zkbin.debug_info.is_none() {
+        eprintln!(
+            "Warning: Circuit was compiled without debug info (zkas -g), source lines are unavailable"
+        );
+    }
+
+    let source = Source::load(&circuit_path, source);
+    let (witnesses, instances) = witness::load(&witness_path, &zkbin)?;
+
+    let mut circuit = ZkCircuit::new(witnesses, zkbin.clone());
+    let tracer = circuit.enable_trace();
+    let prover = MockProver::run(k, &circuit, vec![instances]);
+
+    let steps = tracer.steps();
+    let mut last_line = None;
+    for step in &steps {
+        print_step(step, &source, &mut last_line);
+
+        if interactive {
+            print!("[enter: step, c: continue, q: quit] ");
+            stdout().flush()?;
+
+            let mut input = String::new();
+            stdin().read_line(&mut input)?;
+            match input.trim() {
+                "c" => interactive = false,
+                "q" => break,
+                _ => {}
+            }
+        }
+    }
+
+    let prover = match prover {
+        Ok(v) => v,
+        Err(e) => {
+            // Synthesis stops at the failing opcode, so it's the one after
+            // the last recorded step.
+            let index = steps.len();
+            let line = zkbin.opcode_line(index).map_or("?".to_string(), |x| x.to_string());
+            return Err(Error::Custom(format!(
+                "Synthesis failed at opcode #{} (line {}): {:?}",
+                index, line, e
+            )))
+        }
+    };
+
+    let failures = match prover.verify() {
+        Ok(()) => {
+            println!("Circuit is satisfied");
+            return Ok(())
+        }
+        Err(failures) => failures,
+    };
+
+    let failure = tracer.locate(&failures).unwrap();
+    println!();
+    match failure.step {
+        Some(step) => {
+            let line = step.line.map_or("?".to_string(), |x| x.to_string());
+            println!("First failing constraint at line {}:", line);
+            print_step(&step, &source, &mut None);
+        }
+        None => println!("First failing constraint is not part of any opcode:"),
+    }
+    println!("    {}", failure.failure);
+
+    Err(Error::Custom(format!("Circuit is not satisfied ({} failures)", failures.len())))
+}
+
+fn open_key_store(path: &str) -> Result<KeyStore> {
+    KeyStore::new(&expand_path(path)?)
+}
+
+fn load_circuit(path: &Path) -> Result<(Vec<u8>, ZkBinary)> {
+    let bincode = read(path)?;
+    let zkbin = ZkBinary::decode(&bincode)?;
+    Ok((bincode, zkbin))
+}
+
+fn template(circuit_path: PathBuf) -> Result<()> {
+    let (_, zkbin) = load_circuit(&circuit_path)?;
+    println!("{}", serde_json::to_string_pretty(&witness::template(&zkbin)?).unwrap());
+    Ok(())
+}
+
+fn keygen(key_store: &KeyStore, circuit_path: PathBuf, k: u32) -> Result<()> {
+    let (bincode, _) = load_circuit(&circuit_path)?;
+    key_store.verifying_key(&bincode, k)?;
+    Ok(())
+}
+
+fn prove(
+    key_store: &KeyStore,
+    circuit_path: PathBuf,
+    witness_path: PathBuf,
+    output: Option<PathBuf>,
+    k: u32,
+) -> Result<()> {
+    let (bincode, zkbin) = load_circuit(&circuit_path)?;
+    let (witnesses, instances) = witness::load(&witness_path, &zkbin)?;
+    let pk = key_store.proving_key(&bincode, k)?;
+
+    let circuit = ZkCircuit::new(witnesses, zkbin);
+    let proof = match Proof::create(&pk, &[circuit], &instances, &mut OsRng) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(Error::Custom(format!(
+                "Proof creation failed: {:?} (run `zktool trace` to find the failing opcode)",
+                e
+            )))
+        }
+    };
+
+    let proof_hex: String = proof.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    let instances: Vec<String> = instances.iter().map(witness::format_field).collect();
+    let json = serde_json::to_string_pretty(&json!({
+        "proof": proof_hex,
+        "instances": instances,
+    }))
+    .unwrap();
+
+    match output {
+        Some(path) => {
+            write(&path, json)?;
+            eprintln!("Wrote proof to {:?}", path);
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+fn verify(key_store: &KeyStore, circuit_path: PathBuf, proof_path: PathBuf, k: u32) -> Result<()> {
+    let (bincode, _) = load_circuit(&circuit_path)?;
+
+    let json: JsonValue = match serde_json::from_str(&read_to_string(&proof_path)?) {
+        Ok(v) => v,
+        Err(e) => return Err(Error::Custom(format!("Invalid proof file: {}", e))),
+    };
+
+    let proof_hex = json["proof"].as_str().unwrap_or_default();
+    if proof_hex.len() % 2 != 0 || !proof_hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::Custom("Invalid proof encoding".to_string()))
+    }
+    let proof_bytes = (0..proof_hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&proof_hex[i..i + 2], 16).unwrap())
+        .collect();
+
+    let mut instances = vec![];
+    for value in json["instances"].as_array().unwrap_or(&vec![]) {
+        instances.push(witness::parse_field(value)?);
+    }
+
+    let vk = key_store.verifying_key(&bincode, k)?;
+    match Proof::new(proof_bytes).verify(&vk, &instances) {
+        Ok(()) => {
+            println!("Proof is valid");
+            Ok(())
+        }
+        Err(e) => Err(Error::Custom(format!("Proof is invalid: {:?}", e))),
+    }
+}
+
 fn main() {
     let args = CliDao::parse();
-    match args.command {
+
+    let result = match args.command {
+        Some(CliSubCommands::Trace { circuit, witness, source, k, interactive }) => {
+            trace(circuit, witness, source, k, interactive)
+        }
+        Some(CliSubCommands::Template { circuit }) => template(circuit),
+        Some(CliSubCommands::Keygen { circuit, k }) => {
+            open_key_store(&args.key_store).and_then(|ks| keygen(&ks, circuit, k))
+        }
+        Some(CliSubCommands::Prove { circuit, witness, output, k }) => {
+            open_key_store(&args.key_store).and_then(|ks| prove(&ks, circuit, witness, output, k))
+        }
+        Some(CliSubCommands::Verify { circuit, proof, k }) => {
+            open_key_store(&args.key_store).and_then(|ks| verify(&ks, circuit, proof, k))
+        }
+
         Some(_) => {
             println!("Some arg!");
+            Ok(())
         }
 
         None => todo!(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        exit(1);
     }
 }
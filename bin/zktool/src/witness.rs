@@ -0,0 +1,361 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Witness files hold the values a circuit is run with:
+//!
+//! ```json
+//! {
+//!     "witnesses": [
+//!         {"Base": "0x2a"},
+//!         {"EcNiPoint": ["0x...", "0x..."]},
+//!         {"Uint64": 42}
+//!     ],
+//!     "instances": ["0x...", "1337"]
+//! }
+//! ```
+//!
+//! Witnesses are given in the order they're declared in the circuit,
+//! tagged with their type. Field elements are either a big-endian hex
+//! string prefixed with `0x`, or a decimal `u64`. Other keys, like the
+//! `name` written by [`template`], are ignored.
+
+use std::path::Path;
+
+use darkfi::{
+    zk::{halo2::Value, vm_heap::Witness},
+    zkas::{VarType, ZkBinary},
+    Error, Result,
+};
+use darkfi_sdk::{
+    crypto::{
+        constants::{MERKLE_DEPTH_ORCHARD, SPARSE_MERKLE_DEPTH},
+        MerkleNode,
+    },
+    pasta::{arithmetic::CurveAffine, group::ff::PrimeField, pallas},
+};
+use serde_json::{json, Value as JsonValue};
+
+/// Parse a field element from a JSON number or string
+pub fn parse_field<F: PrimeField<Repr = [u8; 32]>>(value: &JsonValue) -> Result<F> {
+    let value = match value {
+        JsonValue::Number(n) => n.to_string(),
+        JsonValue::String(s) => s.clone(),
+        _ => return Err(Error::Custom(format!("Invalid field element: {}", value))),
+    };
+
+    let Some(hex) = value.strip_prefix("0x") else {
+        return match value.parse::<u64>() {
+            Ok(v) => Ok(F::from(v)),
+            Err(_) => Err(Error::Custom(format!("Invalid field element: {}", value))),
+        }
+    };
+
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::Custom(format!("Invalid field element: {}", value)))
+    }
+
+    // The hex string is big-endian, while the field repr is little-endian
+    let hex = format!("{:0>64}", hex);
+    let mut repr = [0u8; 32];
+    for (i, byte) in repr.iter_mut().rev().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    }
+
+    match Option::from(F::from_repr(repr)) {
+        Some(v) => Ok(v),
+        None => Err(Error::Custom(format!("Field element out of range: {}", value))),
+    }
+}
+
+/// Format a field element as a big-endian hex string
+pub fn format_field<F: PrimeField<Repr = [u8; 32]>>(value: &F) -> String {
+    let hex: String = value.to_repr().iter().rev().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", hex)
+}
+
+fn parse_array<'a>(value: &'a JsonValue, len: usize) -> Result<&'a Vec<JsonValue>> {
+    match value.as_array() {
+        Some(v) if v.len() == len => Ok(v),
+        _ => Err(Error::Custom(format!("Expected an array of {} elements: {}", len, value))),
+    }
+}
+
+fn parse_point(value: &JsonValue) -> Result<pallas::Point> {
+    let coords = parse_array(value, 2)?;
+    let x = parse_field(&coords[0])?;
+    let y = parse_field(&coords[1])?;
+
+    match Option::<pallas::Affine>::from(pallas::Affine::from_xy(x, y)) {
+        Some(p) => Ok(p.into()),
+        None => Err(Error::Custom(format!("Point is not on the curve: {}", value))),
+    }
+}
+
+fn parse_uint<T: TryFrom<u64>>(value: &JsonValue) -> Result<T> {
+    match value.as_u64().map(T::try_from) {
+        Some(Ok(v)) => Ok(v),
+        _ => Err(Error::Custom(format!("Invalid integer: {}", value))),
+    }
+}
+
+/// Parse a single witness of the given type
+fn parse_witness(typ: VarType, value: &JsonValue) -> Result<Witness> {
+    let witness = match typ {
+        VarType::EcPoint => Witness::EcPoint(Value::known(parse_point(value)?)),
+        VarType::EcNiPoint => Witness::EcNiPoint(Value::known(parse_point(value)?)),
+        VarType::Base => Witness::Base(Value::known(parse_field(value)?)),
+        VarType::Scalar => Witness::Scalar(Value::known(parse_field(value)?)),
+        VarType::MerklePath => {
+            let mut path = [MerkleNode::from(pallas::Base::zero()); MERKLE_DEPTH_ORCHARD];
+            for (node, v) in path.iter_mut().zip(parse_array(value, MERKLE_DEPTH_ORCHARD)?) {
+                *node = MerkleNode::from(parse_field::<pallas::Base>(v)?);
+            }
+            Witness::MerklePath(Value::known(path))
+        }
+        VarType::SparseMerklePath => {
+            let mut path = [(pallas::Base::zero(), pallas::Base::zero()); SPARSE_MERKLE_DEPTH];
+            for (pair, v) in path.iter_mut().zip(parse_array(value, SPARSE_MERKLE_DEPTH)?) {
+                let v = parse_array(v, 2)?;
+                *pair = (parse_field(&v[0])?, parse_field(&v[1])?);
+            }
+            Witness::SparseMerklePath(Value::known(path))
+        }
+        VarType::Uint32 => Witness::Uint32(Value::known(parse_uint(value)?)),
+        VarType::Uint64 => Witness::Uint64(Value::known(parse_uint(value)?)),
+        _ => return Err(Error::Custom(format!("Unsupported witness type {:?}", typ))),
+    };
+
+    Ok(witness)
+}
+
+/// Load witnesses and public inputs for the given circuit from a witness file
+pub fn load(path: &Path, zkbin: &ZkBinary) -> Result<(Vec<Witness>, Vec<pallas::Base>)> {
+    parse(&std::fs::read_to_string(path)?, zkbin)
+}
+
+/// Parse witnesses and public inputs for the given circuit from the
+/// contents of a witness file
+fn parse(contents: &str, zkbin: &ZkBinary) -> Result<(Vec<Witness>, Vec<pallas::Base>)> {
+    let json: JsonValue = match serde_json::from_str(contents) {
+        Ok(v) => v,
+        Err(e) => return Err(Error::Custom(format!("Invalid witness file: {}", e))),
+    };
+
+    let Some(values) = json["witnesses"].as_array() else {
+        return Err(Error::Custom("Witness file is missing the witnesses array".to_string()))
+    };
+
+    if values.len() != zkbin.witnesses.len() {
+        return Err(Error::Custom(format!(
+            "Circuit has {} witnesses, but {} were given",
+            zkbin.witnesses.len(),
+            values.len()
+        )))
+    }
+
+    let mut witnesses = vec![];
+    for (i, (typ, value)) in zkbin.witnesses.iter().zip(values).enumerate() {
+        // Witnesses come after the constants on the heap
+        let name = zkbin.heap_name(zkbin.constants.len() + i).unwrap_or("?").to_string();
+
+        let tag = format!("{:?}", typ);
+        let Some(value) = value.get(&tag) else {
+            return Err(Error::Custom(format!("Witness {} ({}) should be a {}", i, name, tag)))
+        };
+
+        match parse_witness(*typ, value) {
+            Ok(w) => witnesses.push(w),
+            Err(e) => return Err(Error::Custom(format!("Witness {} ({}): {}", i, name, e))),
+        }
+    }
+
+    let mut instances = vec![];
+    if let Some(values) = json["instances"].as_array() {
+        for value in values {
+            instances.push(parse_field(value)?);
+        }
+    }
+
+    Ok((witnesses, instances))
+}
+
+/// Create a witness file for the given circuit with all values zeroed
+pub fn template(zkbin: &ZkBinary) -> Result<JsonValue> {
+    let mut witnesses = vec![];
+
+    for (i, typ) in zkbin.witnesses.iter().enumerate() {
+        let value = match typ {
+            VarType::EcPoint | VarType::EcNiPoint => json!(["0x0", "0x0"]),
+            VarType::Base | VarType::Scalar => json!("0x0"),
+            VarType::MerklePath => json!(vec!["0x0"; MERKLE_DEPTH_ORCHARD]),
+            VarType::SparseMerklePath => json!(vec![["0x0", "0x0"]; SPARSE_MERKLE_DEPTH]),
+            VarType::Uint32 | VarType::Uint64 => json!(0),
+            _ => return Err(Error::Custom(format!("Unsupported witness type {:?}", typ))),
+        };
+
+        let tag = format!("{:?}", typ);
+        let mut witness = json!({ tag: value });
+        if let Some(name) = zkbin.heap_name(zkbin.constants.len() + i) {
+            witness["name"] = json!(name);
+        }
+
+        witnesses.push(witness);
+    }
+
+    Ok(json!({ "witnesses": witnesses, "instances": [] }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use darkfi::zkas::DebugInfo;
+    use darkfi_sdk::crypto::{PublicKey, SecretKey};
+
+    /// Circuit with the given witnesses, named `w0`, `w1`, ...
+    fn circuit(witnesses: Vec<VarType>) -> ZkBinary {
+        let heap_names = (0..witnesses.len()).map(|i| format!("w{}", i)).collect();
+        ZkBinary {
+            namespace: "Test".to_string(),
+            constants: vec![],
+            literals: vec![],
+            witnesses,
+            opcodes: vec![],
+            debug_info: Some(DebugInfo { opcode_lines: vec![], heap_names }),
+        }
+    }
+
+    fn error(result: Result<impl std::fmt::Debug>) -> String {
+        match result.unwrap_err() {
+            Error::Custom(e) => e,
+            e => panic!("Unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_parse_field() {
+        let field = |v: JsonValue| parse_field::<pallas::Base>(&v);
+
+        assert_eq!(field(json!("0x2a")).unwrap(), pallas::Base::from(42));
+        assert_eq!(field(json!("0x000000000000002A")).unwrap(), pallas::Base::from(42));
+        assert_eq!(field(json!(1337)).unwrap(), pallas::Base::from(1337));
+        assert_eq!(field(json!("1337")).unwrap(), pallas::Base::from(1337));
+
+        let value = -pallas::Base::one();
+        assert_eq!(field(json!(format_field(&value))).unwrap(), value);
+
+        assert!(field(json!("0x")).is_err());
+        assert!(field(json!("0xzz")).is_err());
+        assert!(field(json!("-1")).is_err());
+        assert!(field(json!(-1)).is_err());
+        assert!(field(json!(1.5)).is_err());
+        assert!(field(json!(true)).is_err());
+        assert!(field(json!(format!("0x1{}", "0".repeat(64)))).is_err());
+        assert!(error(field(json!(format!("0x{}", "f".repeat(64))))).contains("out of range"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let zkbin =
+            circuit(vec![VarType::Base, VarType::EcNiPoint, VarType::Uint32, VarType::MerklePath]);
+
+        let (x, y) = PublicKey::from_secret(SecretKey::from(pallas::Base::from(42))).xy();
+        let witnesses = json!({
+            "witnesses": [
+                {"Base": "0x2a", "name": "ignored"},
+                {"EcNiPoint": [format_field(&x), format_field(&y)]},
+                {"Uint32": 42},
+                {"MerklePath": vec!["0x1"; MERKLE_DEPTH_ORCHARD]},
+            ],
+            "instances": ["0x1", 2],
+        });
+
+        let (witnesses, instances) = parse(&witnesses.to_string(), &zkbin).unwrap();
+        assert_eq!(witnesses.len(), 4);
+        assert!(matches!(witnesses[0], Witness::Base(_)));
+        assert!(matches!(witnesses[1], Witness::EcNiPoint(_)));
+        assert!(matches!(witnesses[2], Witness::Uint32(_)));
+        assert!(matches!(witnesses[3], Witness::MerklePath(_)));
+        assert_eq!(instances, vec![pallas::Base::one(), pallas::Base::from(2)]);
+
+        // Instances are optional
+        let json = json!({"witnesses": [{"Base": 1}]});
+        assert!(parse(&json.to_string(), &circuit(vec![VarType::Base])).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let zkbin = circuit(vec![VarType::Base, VarType::EcNiPoint, VarType::Uint32]);
+        let parse_err = |witnesses: JsonValue| error(parse(&witnesses.to_string(), &zkbin));
+        let point = json!({"EcNiPoint": ["0x0", "0x0"]});
+
+        assert!(error(parse("{", &zkbin)).contains("Invalid witness file"));
+        assert!(parse_err(json!({})).contains("missing the witnesses"));
+        assert!(parse_err(json!({"witnesses": [{"Base": 1}]})).contains("has 3 witnesses"));
+
+        // Errors name the offending witness
+        let e = parse_err(json!({"witnesses": [{"Scalar": 1}, point, {"Uint32": 1}]}));
+        assert!(e.contains("w0") && e.contains("should be a Base"), "{}", e);
+        let e = parse_err(json!({"witnesses": [{"Base": 1}, point, {"Uint32": u64::MAX}]}));
+        assert!(e.contains("w2") && e.contains("Invalid integer"), "{}", e);
+        let e = parse_err(json!({"witnesses": [{"Base": 1}, point, {"Uint32": -1}]}));
+        assert!(e.contains("Invalid integer"), "{}", e);
+        let e = parse_err(
+            json!({"witnesses": [{"Base": 1}, {"EcNiPoint": ["0x1", "0x1"]}, {"Uint32": 1}]}),
+        );
+        assert!(e.contains("w1") && e.contains("not on the curve"), "{}", e);
+        let e =
+            parse_err(json!({"witnesses": [{"Base": 1}, {"EcNiPoint": ["0x0"]}, {"Uint32": 1}]}));
+        assert!(e.contains("array of 2 elements"), "{}", e);
+
+        let e = parse_err(
+            json!({"witnesses": [{"Base": 1}, point, {"Uint32": 1}], "instances": ["x"]}),
+        );
+        assert!(e.contains("Invalid field element"), "{}", e);
+    }
+
+    #[test]
+    fn test_template() {
+        let zkbin = circuit(vec![
+            VarType::Base,
+            VarType::EcPoint,
+            VarType::Uint64,
+            VarType::MerklePath,
+            VarType::SparseMerklePath,
+        ]);
+
+        let template = template(&zkbin).unwrap();
+        let witnesses = template["witnesses"].as_array().unwrap();
+        assert_eq!(witnesses.len(), 5);
+        assert_eq!(witnesses[0], json!({"Base": "0x0", "name": "w0"}));
+        assert_eq!(witnesses[1], json!({"EcPoint": ["0x0", "0x0"], "name": "w1"}));
+        assert_eq!(witnesses[2], json!({"Uint64": 0, "name": "w2"}));
+        assert_eq!(witnesses[3]["MerklePath"].as_array().unwrap().len(), MERKLE_DEPTH_ORCHARD);
+        assert_eq!(witnesses[4]["SparseMerklePath"].as_array().unwrap().len(), SPARSE_MERKLE_DEPTH);
+        assert_eq!(template["instances"], json!([]));
+
+        // A template can be filled in and loaded back
+        assert_eq!(parse(&template.to_string(), &zkbin).unwrap().0.len(), 5);
+
+        // Names are left out without debug info
+        let mut zkbin = circuit(vec![VarType::Base]);
+        zkbin.debug_info = None;
+        assert_eq!(template(&zkbin).unwrap()["witnesses"][0], json!({"Base": "0x0"}));
+
+        assert!(template(&circuit(vec![VarType::Uint64, VarType::Any])).is_err());
+    }
+}
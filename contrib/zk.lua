@@ -20,7 +20,7 @@ local number = token(l.NUMBER, l.integer)
 
 -- Keywords.
 local keyword = token(l.KEYWORD, word_match{
-  'constant', 'witness', 'circuit',
+  'constant', 'witness', 'circuit', 'import', 'function', 'return',
 })
 
 -- Constants.
@@ -33,16 +33,18 @@ local constant = token(l.CONSTANT, word_match{
 local type = token(l.TYPE, word_match{
   'EcPoint', 'EcFixedPoint', 'EcFixedPointBase', 'EcFixedPointShort',
   'EcNiPoint', 'Base', 'BaseArray', 'Scalar', 'ScalarArray',
-  'MerklePath', 'Uint32', 'Uint64',
+  'MerklePath', 'SparseMerklePath', 'Uint32', 'Uint64',
 })
 
 -- Instructions.
 local instruction = token('instruction', word_match{
   'ec_add', 'ec_mul', 'ec_mul_base', 'ec_mul_short', 'ec_mul_var_base',
   'ec_get_x', 'ec_get_y',
-  'base_add', 'base_mul', 'base_sub',
-  'poseidon_hash', 'merkle_root',
+  'base_add', 'base_mul', 'base_sub', 'base_div', 'base_invert',
+  'poseidon_hash', 'merkle_root', 'sparse_merkle_root',
   'range_check', 'less_than_strict', 'less_than_loose', 'bool_check',
+  'is_zero', 'is_equal', 'bool_not', 'bool_and', 'bool_or',
+  'u64_add', 'u64_sub', 'u64_mul', 'bit_decompose', 'bit_and', 'bit_xor',
   'cond_select', 'witness_base',
   'constrain_equal_base', 'constrain_equal_point',
   'constrain_instance', 'debug',
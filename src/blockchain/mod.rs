@@ -16,7 +16,10 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
 
 use log::debug;
 use sled::Transactional;
@@ -24,7 +27,7 @@ use sled::Transactional;
 use darkfi_sdk::blockchain::Slot;
 use darkfi_serial::{deserialize, serialize, Decodable};
 
-use crate::{tx::Transaction, Error, Result};
+use crate::{system::METRICS, tx::Transaction, Error, Result};
 
 /// Block related definitions and storage implementations
 pub mod block_store;
@@ -88,6 +91,9 @@ impl Blockchain {
         let contracts = ContractStateStore::new(db)?;
         let wasm_bincode = WasmStore::new(db)?;
 
+        // Counting is O(n), so the gauge is only updated with deltas afterwards
+        METRICS.gauge_set("darkfi_mempool_transactions", &[], pending_txs.0.len() as f64);
+
         Ok(Self {
             sled_db: db.clone(),
             headers,
@@ -300,12 +306,21 @@ impl Blockchain {
     /// as the input transactions.
     pub fn add_pending_txs(&self, txs: &[Transaction]) -> Result<Vec<blake3::Hash>> {
         let (txs_batch, txs_hashes) = self.pending_txs.insert_batch(txs)?;
+
+        let mut new_txs = HashSet::new();
+        for tx_hash in &txs_hashes {
+            if !self.pending_txs.contains(tx_hash)? {
+                new_txs.insert(tx_hash);
+            }
+        }
+
         let txs_order_batch = self.pending_txs_order.insert_batch(&txs_hashes)?;
 
         // Perform an atomic transaction over the trees and apply the batches.
         let trees = [self.pending_txs.0.clone(), self.pending_txs_order.0.clone()];
         let batches = [txs_batch, txs_order_batch];
         self.atomic_write(&trees, &batches)?;
+        METRICS.gauge_add("darkfi_mempool_transactions", &[], new_txs.len() as f64);
 
         Ok(txs_hashes)
     }
@@ -341,6 +356,13 @@ impl Blockchain {
             }
         }
 
+        let mut removed_txs = HashSet::new();
+        for tx_hash in &txs_hashes {
+            if self.pending_txs.contains(tx_hash)? {
+                removed_txs.insert(tx_hash);
+            }
+        }
+
         let txs_batch = self.pending_txs.remove_batch(&txs_hashes);
         let txs_order_batch = self.pending_txs_order.remove_batch(&removed_indexes);
 
@@ -348,6 +370,7 @@ impl Blockchain {
         let trees = [self.pending_txs.0.clone(), self.pending_txs_order.0.clone()];
         let batches = [txs_batch, txs_order_batch];
         self.atomic_write(&trees, &batches)?;
+        METRICS.gauge_add("darkfi_mempool_transactions", &[], -(removed_txs.len() as f64));
 
         Ok(())
     }
@@ -43,6 +43,7 @@ pub fn circuit_gas_use(zkbin: &ZkBinary) -> u64 {
             VarType::Scalar => 20,
             VarType::ScalarArray => unreachable!(),
             VarType::MerklePath => 40,
+            VarType::SparseMerklePath => 80,
             VarType::Uint32 => 10,
             VarType::Uint64 => 10,
             VarType::Any => 10,
@@ -64,15 +65,29 @@ pub fn circuit_gas_use(zkbin: &ZkBinary) -> u64 {
             Opcode::EcGetY => 5,
             Opcode::PoseidonHash => 20 + 10 * opcode.1.len() as u64,
             Opcode::MerkleRoot => 50,
+            Opcode::SparseMerkleRoot => 60,
             Opcode::BaseAdd => 15,
             Opcode::BaseMul => 15,
             Opcode::BaseSub => 15,
+            Opcode::BaseDiv => 40,
+            Opcode::BaseInvert => 25,
             Opcode::WitnessBase => 10,
             Opcode::RangeCheck => 60,
             Opcode::LessThanStrict => 100,
             Opcode::LessThanLoose => 100,
             Opcode::BoolCheck => 20,
+            Opcode::IsZero => 25,
+            Opcode::IsEqual => 25,
+            Opcode::BoolNot => 35,
+            Opcode::BoolAnd => 55,
+            Opcode::BoolOr => 85,
             Opcode::CondSelect => 30,
+            Opcode::U64Add => 200,
+            Opcode::U64Sub => 200,
+            Opcode::U64Mul => 200,
+            Opcode::BitDecompose => 400,
+            Opcode::BitAnd => 300,
+            Opcode::BitXor => 400,
             Opcode::ConstrainEqualBase => 10,
             Opcode::ConstrainEqualPoint => 20,
             Opcode::ConstrainInstance => 10,
@@ -16,7 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::HashMap, io::Cursor, time::Instant};
 
 use async_std::sync::{Arc, RwLock};
 use darkfi_sdk::{
@@ -38,16 +38,14 @@ use crate::{
     blockchain::{BlockInfo, Blockchain, BlockchainOverlay, BlockchainOverlayPtr},
     rpc::jsonrpc::JsonNotification,
     runtime::vm_runtime::Runtime,
-    system::{Subscriber, SubscriberPtr},
+    system::{Subscriber, SubscriberPtr, METRICS},
     tx::Transaction,
     util::time::{TimeKeeper, Timestamp},
     wallet::WalletPtr,
     zk::{
         proof::{ProvingKey, VerifyingKey},
-        vm::ZkCircuit,
-        vm_heap::empty_witnesses,
+        KeyStore,
     },
-    zkas::ZkBinary,
     Error, Result,
 };
 
@@ -93,6 +91,7 @@ impl ValidatorState {
         genesis_data: blake3::Hash,
         initial_distribution: u64,
         wallet: WalletPtr,
+        key_store: &KeyStore,
         faucet_pubkeys: Vec<PublicKey>,
         enable_participation: bool,
         single_node: bool,
@@ -107,16 +106,12 @@ impl ValidatorState {
             wallet.exec_sql(include_str!("consensus_coin.sql")).await?;
         }
 
-        debug!(target: "consensus::validator", "Generating leader proof keys with k: {}", constants::LEADER_PROOF_K);
+        debug!(target: "consensus::validator", "Loading leader proof keys with k: {}", constants::LEADER_PROOF_K);
         let bincode = include_bytes!("../../proof/lead.zk.bin");
-        let zkbin = ZkBinary::decode(bincode)?;
-        let witnesses = empty_witnesses(&zkbin);
-        let circuit = ZkCircuit::new(witnesses, zkbin);
-
-        let lead_verifying_key = VerifyingKey::build(constants::LEADER_PROOF_K, &circuit);
+        let lead_verifying_key = key_store.verifying_key(bincode, constants::LEADER_PROOF_K)?;
         // We only need this proving key if we're going to participate in the consensus.
         let lead_proving_key = if enable_participation {
-            Some(ProvingKey::build(constants::LEADER_PROOF_K, &circuit))
+            Some(key_store.proving_key(bincode, constants::LEADER_PROOF_K)?)
         } else {
             None
         };
@@ -186,6 +181,7 @@ impl ValidatorState {
                 nc.1,
                 consensus.time_keeper.clone(),
             )?;
+            runtime.set_key_store(key_store.clone());
             runtime.deploy(&nc.3)?;
             info!(target: "consensus::validator", "Successfully deployed {}", nc.0);
         }
@@ -988,15 +984,19 @@ impl ValidatorState {
         Ok(())
     }
 
-    /// Validate signatures, wasm execution, and zk proofs for given transaction in
-    /// provided runtimes. If all of those succeed, try to execute a state update
-    /// for the contract calls.
+    /// Validate wasm execution for given transaction in provided runtimes. If it
+    /// succeeds, try to execute a state update for the contract calls. Signatures
+    /// and ZK proofs are verified by the caller, using the returned tables of
+    /// public keys and public inputs. With `exec` set to `false`, only the
+    /// "metadata" calls are run to get the tables.
     async fn verify_transaction(
         &self,
         overlay: &BlockchainOverlayPtr,
         tx: &Transaction,
         verifying_slot: u64,
-    ) -> Result<()> {
+        verifying_keys: &mut HashMap<[u8; 32], HashMap<String, VerifyingKey>>,
+        exec: bool,
+    ) -> Result<(Vec<Vec<PublicKey>>, Vec<Vec<(String, Vec<pallas::Base>)>>)> {
         let mut runtimes = HashMap::new();
         let tx_hash = blake3::hash(&serialize(tx));
         info!(target: "consensus::validator", "Verifying transaction {}", tx_hash);
@@ -1007,13 +1007,6 @@ impl ValidatorState {
         let mut sig_table = vec![];
         // State updates produced by contract execution
         let mut updates = vec![];
-        // Map of zk proof verifying keys for the current transaction
-        let mut verifying_keys: HashMap<[u8; 32], HashMap<String, VerifyingKey>> = HashMap::new();
-
-        // Initialize the map
-        for call in tx.calls.iter() {
-            verifying_keys.insert(call.contract_id.to_bytes(), HashMap::new());
-        }
 
         // Generate a time keeper using transaction verifying slot
         let time_keeper = TimeKeeper::new(
@@ -1076,6 +1069,10 @@ impl ValidatorState {
             zkp_table.push(zkp_pub);
             sig_table.push(sig_pub);
 
+            if !exec {
+                continue
+            }
+
             // After getting the metadata, we run the "exec" function with the same
             // runtime and the same payload.
             info!(target: "consensus::validator", "Executing \"exec\" call");
@@ -1088,36 +1085,17 @@ impl ValidatorState {
         }
 
         // When we're done looping and executing over the tx's contract calls, we
-        // move on with verification. First we verify the signatures as that's
-        // cheaper, and then finally we verify the ZK proofs.
-        info!(target: "consensus::validator", "Verifying signatures for transaction {}", tx_hash);
+        // can apply the state updates. The signatures and ZK proofs are verified
+        // by the caller, along with those of the rest of the transaction set.
         if sig_table.len() != tx.signatures.len() {
             error!(target: "consensus::validator", "Incorrect number of signatures in tx {}", tx_hash);
             return Err(Error::InvalidSignature)
         }
 
-        match tx.verify_sigs(sig_table) {
-            Ok(()) => {
-                info!(target: "consensus::validator", "Signatures verification for tx {} successful", tx_hash)
-            }
-            Err(e) => {
-                error!(target: "consensus::validator", "Signature verification for tx {} failed: {}", tx_hash, e);
-                return Err(e)
-            }
-        };
-
-        info!(target: "consensus::validator", "Verifying ZK proofs for transaction {}", tx_hash);
-        match tx.verify_zkps(&verifying_keys, zkp_table).await {
-            Ok(()) => {
-                info!(target: "consensus::validator", "ZK proof verification for tx {} successful", tx_hash)
-            }
-            Err(e) => {
-                error!(target: "consensus::validator", "ZK proof verification for tx {} failed: {}", tx_hash, e);
-                return Err(e)
-            }
-        };
+        if !exec {
+            return Ok((sig_table, zkp_table))
+        }
 
-        // After the verifications stage passes we can apply the state updates.
         assert!(tx.calls.len() == updates.len());
 
         info!(target: "consensus::validator", "Performing state updates");
@@ -1131,15 +1109,22 @@ impl ValidatorState {
             info!(target: "consensus::validator", "State update applied successfully")
         }
 
-        info!(target: "consensus::validator", "Transaction {} verified successfully", tx_hash);
+        info!(target: "consensus::validator", "Transaction {} executed successfully", tx_hash);
 
-        Ok(())
+        Ok((sig_table, zkp_table))
     }
 
     /// Validate a set of [`Transaction`] in sequence and apply them if all are valid.
     /// Erroneous transactions are filtered out of the set and returned to caller.
     /// The function takes a boolean called `write` which tells it to actually write
     /// the state transitions to the database.
+    ///
+    /// Signatures and ZK proofs of the whole set are verified in parallel first,
+    /// over the initial state. Their result is only used for a transaction if
+    /// executing it in order produces the same tables, otherwise it's verified
+    /// again with the new ones. Conflicts between transactions, like two of them
+    /// revealing the same nullifier, are caught by the contract calls of the
+    /// later transaction, which run over the state changes of the earlier ones.
     pub async fn verify_transactions(
         &self,
         txs: &[Transaction],
@@ -1148,16 +1133,117 @@ impl ValidatorState {
     ) -> Result<Vec<Transaction>> {
         info!(target: "consensus::validator", "Verifying {} transaction(s)", txs.len());
 
-        let mut erroneous_txs = vec![];
         let overlay = BlockchainOverlay::new(&self.blockchain)?;
 
+        // Map of zk proof verifying keys for the current transaction set
+        let mut verifying_keys: HashMap<[u8; 32], HashMap<String, VerifyingKey>> = HashMap::new();
+
+        // Initialize the map
         for tx in txs {
-            if let Err(e) = self.verify_transaction(&overlay, tx, verifying_slot).await {
-                warn!(target: "consensus::validator", "Transaction verification failed: {}", e);
-                erroneous_txs.push(tx.clone());
+            for call in &tx.calls {
+                verifying_keys.insert(call.contract_id.to_bytes(), HashMap::new());
+            }
+        }
+
+        // Run the "metadata" calls over the initial state, to get the signature
+        // and ZK proof tables of the transactions.
+        let start = Instant::now();
+        let mut indexes = vec![];
+        let mut sig_batch = vec![];
+        let mut zkp_batch = vec![];
+        for (i, tx) in txs.iter().enumerate() {
+            match self
+                .verify_transaction(&overlay, tx, verifying_slot, &mut verifying_keys, false)
+                .await
+            {
+                Ok((sig_table, zkp_table)) => {
+                    indexes.push(i);
+                    sig_batch.push((tx, sig_table));
+                    zkp_batch.push((tx, zkp_table));
+                }
+                // It may depend on the state changes of the transactions before
+                // it, so it's verified after its execution instead.
+                Err(e) => {
+                    debug!(target: "consensus::validator", "Metadata of tx {} unavailable: {}", blake3::hash(&serialize(tx)), e)
+                }
             }
         }
 
+        // Verify the signatures and ZK proofs of the whole set
+        info!(target: "consensus::validator", "Verifying signatures and ZK proofs for {} transaction(s)", indexes.len());
+        let (invalid_sigs, invalid_zkps) = rayon::join(
+            || Transaction::batch_verify_sigs(&sig_batch),
+            || Transaction::batch_verify_zkps(&zkp_batch, &verifying_keys),
+        );
+
+        let mut valid = vec![true; indexes.len()];
+        for idx in invalid_sigs.into_iter().chain(invalid_zkps) {
+            valid[idx] = false;
+        }
+
+        // Share of the set verification time of each transaction
+        let verify_time = start.elapsed() / indexes.len().max(1) as u32;
+
+        // Set verification result of each transaction, along with the tables
+        // it was computed from
+        let mut precomputed = vec![None; txs.len()];
+        for (((i, (_, sig_table)), (_, zkp_table)), valid) in
+            indexes.into_iter().zip(sig_batch).zip(zkp_batch).zip(valid)
+        {
+            precomputed[i] = Some((sig_table, zkp_table, valid));
+        }
+
+        // Execute the transactions in order, reverting the failing ones
+        let mut rejected = vec![false; txs.len()];
+        for (i, tx) in txs.iter().enumerate() {
+            let start = Instant::now();
+            overlay.lock().unwrap().checkpoint();
+            let (sig_table, zkp_table) = match self
+                .verify_transaction(&overlay, tx, verifying_slot, &mut verifying_keys, true)
+                .await
+            {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(target: "consensus::validator", "Transaction verification failed: {}", e);
+                    rejected[i] = true;
+                    overlay.lock().unwrap().revert_to_checkpoint()?;
+                    continue
+                }
+            };
+
+            // The tables of a transaction differ from the precomputed ones if its
+            // "metadata" calls depend on the state changes of the transactions
+            // before it, in which case we verify it again with the new ones.
+            let valid = match &precomputed[i] {
+                Some((sigs, zkps, valid)) if *sigs == sig_table && *zkps == zkp_table => *valid,
+                _ => {
+                    Transaction::batch_verify_sigs(&[(tx, sig_table)]).is_empty() &&
+                        Transaction::batch_verify_zkps(&[(tx, zkp_table)], &verifying_keys)
+                            .is_empty()
+                }
+            };
+
+            if !valid {
+                error!(target: "consensus::validator", "Signature or ZK proof verification for tx {} failed", blake3::hash(&serialize(tx)));
+                rejected[i] = true;
+                overlay.lock().unwrap().revert_to_checkpoint()?;
+                continue
+            }
+
+            METRICS.observe(
+                "darkfi_validator_tx_verify_seconds",
+                &[],
+                verify_time + start.elapsed(),
+            );
+        }
+
+        let erroneous_txs: Vec<Transaction> = txs
+            .iter()
+            .zip(rejected)
+            .filter(|(_, rejected)| *rejected)
+            .map(|(tx, _)| tx.clone())
+            .collect();
+
         let lock = overlay.lock().unwrap();
         let mut overlay = lock.overlay.lock().unwrap();
         if !erroneous_txs.is_empty() {
@@ -0,0 +1,118 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Test for the verification of transaction sets holding invalid and
+//! conflicting transactions.
+//!
+//! Alice creates several payments to Bob spending the same coin, and one
+//! of them carries an invalid ZK proof. The invalid payment must be
+//! rejected without its state changes affecting the others, so the first
+//! valid payment is accepted and the later ones are rejected as double
+//! spends of its nullifier.
+
+use darkfi::{tx::Transaction, Result};
+use darkfi_contract_test_harness::{init_logger, Holder, TestHarness};
+use log::info;
+
+/// Verify a set of transactions with the validator of the given holder,
+/// returning the erroneous ones
+async fn erroneous_txs(
+    th: &TestHarness,
+    holder: &Holder,
+    txs: &[Transaction],
+    slot: u64,
+) -> Result<Vec<Transaction>> {
+    let wallet = th.holders.get(holder).unwrap();
+    match wallet.validator.read().await.add_transactions(txs, slot, false).await {
+        Ok(()) => Ok(vec![]),
+        Err(e) => e.retrieve_erroneous_txs(),
+    }
+}
+
+#[async_std::test]
+async fn txs_conflicts() -> Result<()> {
+    init_logger();
+
+    // Holders this test will use
+    const HOLDERS: [Holder; 3] = [Holder::Faucet, Holder::Alice, Holder::Bob];
+
+    // Some numbers we want to assert
+    const ALICE_INITIAL: u64 = 100;
+    const ALICE_SEND: u64 = ALICE_INITIAL - 50;
+
+    // Slot to verify against
+    let current_slot = 0;
+
+    // Initialize harness
+    let mut th = TestHarness::new(&["money".to_string()]).await?;
+
+    info!(target: "money", "[Alice] ================================");
+    info!(target: "money", "[Alice] Building token mint tx for Alice");
+    info!(target: "money", "[Alice] ================================");
+    let (token_mint_tx, token_mint_params) =
+        th.token_mint(ALICE_INITIAL, Holder::Alice, Holder::Alice)?;
+
+    for holder in HOLDERS {
+        th.execute_token_mint_tx(holder, &token_mint_tx, &token_mint_params, current_slot).await?;
+    }
+
+    th.assert_trees(&HOLDERS);
+
+    let alice_oc = th.gather_owncoin(Holder::Alice, token_mint_params.output, None)?;
+    let alice_token_id = alice_oc.note.token_id;
+    let alice_owncoins = vec![alice_oc];
+
+    info!(target: "money", "[Alice] ===================================================");
+    info!(target: "money", "[Alice] Building Money::Transfer payments spending one coin");
+    info!(target: "money", "[Alice] ===================================================");
+    let mut txs = vec![];
+    for _ in 0..3 {
+        let (tx, _, _) =
+            th.transfer(ALICE_SEND, Holder::Alice, Holder::Bob, &alice_owncoins, alice_token_id)?;
+        txs.push(tx);
+    }
+
+    // The first payment gets the burn proof of the second one, which doesn't
+    // match its public inputs.
+    let mut invalid = txs[0].clone();
+    invalid.proofs[0][0] = txs[1].proofs[0][0].clone();
+
+    for holder in HOLDERS {
+        info!(target: "money", "[{:?}] ===================================", holder);
+        info!(target: "money", "[{:?}] Verifying conflicting payment sets", holder);
+        info!(target: "money", "[{:?}] ===================================", holder);
+
+        // The invalid payment is never applied, so the next one can spend the coin
+        let set = [invalid.clone(), txs[1].clone(), txs[2].clone()];
+        let erroneous = erroneous_txs(&th, &holder, &set, current_slot).await?;
+        assert_eq!(erroneous, vec![invalid.clone(), txs[2].clone()]);
+
+        // Order decides which of the valid payments is accepted
+        let set = [txs[2].clone(), invalid.clone(), txs[0].clone(), txs[1].clone()];
+        let erroneous = erroneous_txs(&th, &holder, &set, current_slot).await?;
+        assert_eq!(erroneous, vec![invalid.clone(), txs[0].clone(), txs[1].clone()]);
+
+        // Each payment is still valid on its own
+        for tx in &txs {
+            assert!(erroneous_txs(&th, &holder, &[tx.clone()], current_slot).await?.is_empty());
+        }
+    }
+
+    // Thanks for reading
+    Ok(())
+}
@@ -24,7 +24,7 @@ use darkfi::{
     util::time::TimeKeeper,
     validator::{Validator, ValidatorConfig, ValidatorPtr},
     wallet::{WalletDb, WalletPtr},
-    zk::{empty_witnesses, ProvingKey, ZkCircuit},
+    zk::ProvingKey,
     zkas::ZkBinary,
     Result,
 };
@@ -146,6 +146,7 @@ impl Wallet {
             genesis_block.clone(),
             faucet_pubkeys.to_vec(),
             false,
+            Some(vks::key_store()?),
         );
         let validator = Validator::new(&sled_db, config).await?;
 
@@ -206,19 +207,18 @@ impl TestHarness {
         let rachel = Wallet::new(rachel_kp, &genesis_block, &faucet_pubkeys).await?;
         holders.insert(Holder::Rachel, rachel);
 
-        // Get the zkas circuits and build proving keys
+        // Get the zkas circuits and load their proving keys
         let mut proving_keys = HashMap::new();
         let alice_sled = alice.validator.read().await.blockchain.sled_db.clone();
+        let key_store = vks::key_store()?;
 
         macro_rules! mkpk {
             ($db:expr, $ns:expr) => {
-                info!("Building ProvingKey for {}", $ns);
+                info!("Loading ProvingKey for {}", $ns);
                 let zkas_bytes = $db.get(&serialize(&$ns))?.unwrap();
-                let (zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&zkas_bytes)?;
-                let zkbin = ZkBinary::decode(&zkbin)?;
-                let witnesses = empty_witnesses(&zkbin);
-                let circuit = ZkCircuit::new(witnesses, zkbin.clone());
-                let pk = ProvingKey::build(13, &circuit);
+                let (bincode, _): (Vec<u8>, Vec<u8>) = deserialize(&zkas_bytes)?;
+                let pk = key_store.proving_key(&bincode, 13)?;
+                let zkbin = ZkBinary::decode(&bincode)?;
                 proving_keys.insert($ns, (pk, zkbin));
             };
         }
@@ -24,10 +24,7 @@ use std::{
 };
 
 use darkfi::{
-    runtime::vm_runtime::SMART_CONTRACT_ZKAS_DB_NAME,
-    zk::{empty_witnesses, VerifyingKey, ZkCircuit},
-    zkas::ZkBinary,
-    Result,
+    runtime::vm_runtime::SMART_CONTRACT_ZKAS_DB_NAME, zk::KeyStore, zkas::ZkBinary, Result,
 };
 use darkfi_dao_contract::{
     DAO_CONTRACT_ZKAS_DAO_EXEC_NS, DAO_CONTRACT_ZKAS_DAO_MINT_NS,
@@ -50,16 +47,24 @@ use log::debug;
 /// Update this if any circuits are changed
 const VKS_HASH: &str = "e5dcc83e82020f26d6b4479051332de04c200cfb77dae45c26b8252f499149cf";
 
-fn vks_path() -> Result<PathBuf> {
+fn harness_path() -> Result<PathBuf> {
     let output = Command::new("git").arg("rev-parse").arg("--show-toplevel").output()?.stdout;
     let mut path = PathBuf::from(String::from_utf8(output[..output.len() - 1].to_vec())?);
     path.push("src");
     path.push("contract");
     path.push("test-harness");
-    path.push("vks.bin");
     Ok(path)
 }
 
+fn vks_path() -> Result<PathBuf> {
+    Ok(harness_path()?.join("vks.bin"))
+}
+
+/// Key store shared by all test harness instances
+pub fn key_store() -> Result<KeyStore> {
+    KeyStore::new(&harness_path()?.join("zk_keys"))
+}
+
 /// (Bincode, Namespace, VK)
 pub type Vks = Vec<(Vec<u8>, String, Vec<u8>)>;
 
@@ -106,14 +111,13 @@ fn read_or_gen_vks() -> Result<Vks> {
         &include_bytes!("../../deployooor/proof/derive_contract_id.zk.bin")[..],
     ];
 
+    let key_store = key_store()?;
     let mut vks = vec![];
 
     for bincode in bins.iter() {
         let zkbin = ZkBinary::decode(bincode)?;
-        debug!("Building VK for {}", zkbin.namespace);
-        let witnesses = empty_witnesses(&zkbin);
-        let circuit = ZkCircuit::new(witnesses, zkbin.clone());
-        let vk = VerifyingKey::build(13, &circuit);
+        debug!("Loading VK for {}", zkbin.namespace);
+        let vk = key_store.verifying_key(bincode, 13)?;
         let mut vk_buf = vec![];
         vk.write(&mut vk_buf)?;
         vks.push((bincode.to_vec(), zkbin.namespace, vk_buf))
@@ -21,10 +21,18 @@ use rand::{distributions::Alphanumeric, thread_rng, Rng};
 pub mod events_queue;
 pub mod model;
 pub mod protocol_event;
+pub mod retention;
 pub mod view;
 
 pub trait EventMsg {
     fn new() -> Self;
+
+    /// Protocol feature a peer has to advertise in the version handshake
+    /// for events of this type to be exchanged with it. Used when the
+    /// encoding of the events changes, as peers can't decode each other's.
+    fn feature() -> Option<&'static str> {
+        None
+    }
 }
 
 pub fn gen_id(len: usize) -> String {
@@ -37,6 +45,7 @@ mod tests {
         events_queue::EventsQueue,
         model::{Event, EventId, Model},
         protocol_event::{Inv, InvItem, Seen, SeenPtr},
+        retention::Retention,
         view::View,
         EventMsg,
     };
@@ -59,7 +68,7 @@ mod tests {
     async fn event_graph_integration() {
         // Base structures
         let events_queue = EventsQueue::<TestEvent>::new();
-        let mut model = Model::new(events_queue.clone());
+        let mut model = Model::new(events_queue.clone(), Retention::default());
         let _view = View::new(events_queue);
 
         // Buffers
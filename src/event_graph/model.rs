@@ -16,22 +16,27 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{cmp::Ordering, collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
 
 use async_std::sync::{Arc, Mutex};
 use blake3;
 use darkfi_serial::{serialize, Decodable, Encodable, SerialDecodable, SerialEncodable};
-use log::{error, info};
+use log::{debug, info};
 
-use crate::{event_graph::events_queue::EventsQueuePtr, util::time::Timestamp};
+use crate::{event_graph::events_queue::EventsQueuePtr, system::METRICS, util::time::Timestamp};
 
-use super::EventMsg;
+use super::{retention::Retention, EventMsg};
 
 //pub type EventId = [u8; blake3::OUT_LEN];
 pub type EventId = blake3::Hash;
 
-const MAX_DEPTH: u32 = 300;
-const MAX_HEIGHT: u32 = 300;
+/// Maximum number of events kept while waiting for their parent
+const MAX_ORPHANS: usize = 1000;
+/// Seconds an event waits for its parent before it is dropped
+const ORPHAN_TIMEOUT: u64 = 600;
 
 #[derive(SerialEncodable, SerialDecodable, Clone, Debug)]
 pub struct Event<T: Send + Sync> {
@@ -55,23 +60,34 @@ struct EventNode<T: Send + Sync> {
     parent: Option<EventId>,
     event: Event<T>,
     children: Vec<EventId>,
+    // Distance from the root, only meaningful compared to other nodes.
+    // It goes below zero when the root is extended backwards.
+    height: i64,
 }
 
 pub type ModelPtr<T> = Arc<Mutex<Model<T>>>;
 
 pub struct Model<T: Send + Sync + Debug> {
-    // This is periodically updated so we discard old nodes
+    // This is updated as events fall out of the retention window
     current_root: EventId,
-    orphans: HashMap<EventId, Event<T>>,
+    // Leaf of the longest chain, updated as events get linked
+    head: EventId,
+    // Root every node starts with, until it's pruned
+    genesis: EventId,
+    // Events waiting for their parent, with the time they were received
+    orphans: HashMap<EventId, (Event<T>, Timestamp)>,
+    // Orphans waiting for each missing parent
+    pending: HashMap<EventId, Vec<EventId>>,
     event_map: HashMap<EventId, EventNode<T>>,
     events_queue: EventsQueuePtr<T>,
+    retention: Retention,
 }
 
 impl<T> Model<T>
 where
     T: Send + Sync + Encodable + Decodable + Clone + EventMsg + Debug,
 {
-    pub fn new(events_queue: EventsQueuePtr<T>) -> Self {
+    pub fn new(events_queue: EventsQueuePtr<T>, retention: Retention) -> Self {
         let root_node = EventNode {
             parent: None,
             event: Event {
@@ -80,6 +96,7 @@ where
                 timestamp: Timestamp(1674512021323),
             },
             children: Vec::new(),
+            height: 0,
         };
 
         let root_node_id = root_node.event.hash();
@@ -87,30 +104,30 @@ where
         let mut event_map = HashMap::new();
         event_map.insert(root_node_id, root_node);
 
-        Self { current_root: root_node_id, orphans: HashMap::new(), event_map, events_queue }
+        Self {
+            current_root: root_node_id,
+            head: root_node_id,
+            genesis: root_node_id,
+            orphans: HashMap::new(),
+            pending: HashMap::new(),
+            event_map,
+            events_queue,
+            retention,
+        }
     }
 
-    pub fn reset_root(&mut self, timestamp: Timestamp) {
-        let root_node = EventNode {
-            parent: None,
-            event: Event {
-                previous_event_hash: blake3::hash(b""), // This is a blake3 hash of NULL
-                action: T::new(),
-                timestamp,
-            },
-            children: Vec::new(),
-        };
-
-        let root_node_id = root_node.event.hash();
-
-        let mut event_map = HashMap::new();
-        event_map.insert(root_node_id, root_node);
+    pub fn get_root_hash(&self) -> EventId {
+        self.current_root
+    }
 
-        self.current_root = root_node_id;
-        self.orphans = HashMap::new();
-        self.event_map = event_map;
+    pub fn get_genesis_hash(&self) -> EventId {
+        self.genesis
+    }
 
-        info!("reset current root to: {:?}", self.current_root);
+    /// Update the DAG size gauges in the metrics registry
+    fn record_metrics(&self) {
+        METRICS.gauge_set("darkfi_event_graph_events", &[], self.event_map.len() as f64);
+        METRICS.gauge_set("darkfi_event_graph_orphans", &[], self.orphans.len() as f64);
     }
 
     pub fn get_head_hash(&self) -> EventId {
@@ -118,8 +135,24 @@ where
     }
 
     pub async fn add(&mut self, event: Event<T>) {
-        self.orphans.insert(event.hash(), event);
-        self.reorganize().await;
+        let hash = event.hash();
+        if self.event_map.contains_key(&hash) || self.orphans.contains_key(&hash) {
+            return
+        }
+
+        self.expire_orphans();
+
+        if self.event_map[&self.current_root].event.previous_event_hash == hash {
+            // The parent of our root, sent by a peer which keeps more history
+            self.extend_root(hash, event).await;
+        } else if self.event_map.contains_key(&event.previous_event_hash) {
+            self.link(vec![(hash, event)]).await;
+        } else {
+            debug!(target: "event_graph", "No parent found, orphan is not relinked");
+            self.insert_orphan(hash, event);
+        }
+
+        self.record_metrics();
     }
 
     pub fn is_orphan(&self, event: &Event<T>) -> bool {
@@ -166,36 +199,132 @@ where
         offspring
     }
 
-    async fn reorganize(&mut self) {
-        for (_, orphan) in std::mem::take(&mut self.orphans) {
-            // if self.is_orphan(&orphan) {
-            //     // TODO should we remove orphan if it's too old
-            //     continue
-            // }
-
-            let prev_event = orphan.previous_event_hash;
-
-            let node =
-                EventNode { parent: Some(prev_event), event: orphan.clone(), children: Vec::new() };
-            let node_hash = node.event.hash();
-
-            let parent = match self.event_map.get_mut(&prev_event) {
-                Some(parent) => parent,
-                None => {
-                    error!("No parent found, Orphan is not relinked");
-                    self.orphans.insert(orphan.hash(), orphan);
-                    continue
-                }
-            };
-            parent.children.push(node_hash);
-
-            self.event_map.insert(node_hash, node.clone());
+    /// Link events to their parent, along with the orphans waiting for
+    /// them. Events whose parent got pruned in the meantime are dropped.
+    async fn link(&mut self, mut events: Vec<(EventId, Event<T>)>) {
+        while let Some((hash, event)) = events.pop() {
+            let prev_event = event.previous_event_hash;
+            let Some(parent) = self.event_map.get_mut(&prev_event) else { continue };
+            parent.children.push(hash);
 
+            let height = parent.height + 1;
+            let node = EventNode { parent: Some(prev_event), event, children: vec![], height };
             self.events_queue.dispatch(&node.event).await.expect("error dispatching the event");
+            self.event_map.insert(hash, node);
+            self.update_head(hash);
 
             // clean up the tree from old eventnodes
             self.prune_chains();
             self.update_root();
+
+            events.extend(self.take_pending(&hash));
+        }
+    }
+
+    /// Add the parent of the current root as the new root, as long as it
+    /// is within the retention window. Orphans received earlier extend it
+    /// further back.
+    async fn extend_root(&mut self, mut hash: EventId, mut event: Event<T>) {
+        loop {
+            let old_root = self.current_root;
+            let old_root_node = self.event_map.get_mut(&old_root).unwrap();
+            old_root_node.parent = Some(hash);
+            let height = old_root_node.height - 1;
+            self.event_map
+                .insert(hash, EventNode { parent: None, event, children: vec![old_root], height });
+            self.current_root = hash;
+
+            self.update_root();
+
+            let Some(node) = self.event_map.get(&hash) else { break };
+            self.events_queue.dispatch(&node.event).await.expect("error dispatching the event");
+            let children = self.take_pending(&hash);
+            self.link(children).await;
+
+            hash = self.event_map[&self.current_root].event.previous_event_hash;
+            let Some((parent, _)) = self.orphans.get(&hash) else { break };
+            event = parent.clone();
+            self.remove_orphan(&hash);
+        }
+    }
+
+    /// Adopt the root a peer offered while syncing. This is only done when
+    /// we hold nothing but the genesis, which means our peers have already
+    /// pruned it. Events received earlier get linked to the adopted root,
+    /// and older ones received later on extend it backwards.
+    pub async fn adopt_root(&mut self, event: Event<T>) -> bool {
+        if self.current_root != self.genesis || self.event_map.len() > 1 {
+            return false
+        }
+
+        let hash = event.hash();
+        self.remove_orphan(&hash);
+        self.events_queue.dispatch(&event).await.expect("error dispatching the event");
+
+        self.event_map.clear();
+        self.event_map.insert(hash, EventNode { parent: None, event, children: vec![], height: 0 });
+        self.current_root = hash;
+        self.head = hash;
+
+        info!(target: "event_graph", "Adopted {} as the root of the event graph", hash);
+
+        let children = self.take_pending(&hash);
+        self.link(children).await;
+        self.record_metrics();
+        true
+    }
+
+    /// Keep an event until its parent arrives. When the pool is full, the
+    /// orphan waiting the longest is dropped to make room.
+    fn insert_orphan(&mut self, hash: EventId, event: Event<T>) {
+        if self.orphans.len() >= MAX_ORPHANS {
+            let oldest = self
+                .orphans
+                .iter()
+                .min_by_key(|(hash, (_, received))| (received.0, *hash.as_bytes()))
+                .map(|(hash, _)| *hash);
+
+            if let Some(oldest) = oldest {
+                self.remove_orphan(&oldest);
+            }
+        }
+
+        self.pending.entry(event.previous_event_hash).or_default().push(hash);
+        self.orphans.insert(hash, (event, Timestamp::current_time()));
+    }
+
+    fn remove_orphan(&mut self, hash: &EventId) {
+        let Some((event, _)) = self.orphans.remove(hash) else { return };
+        let parent = event.previous_event_hash;
+        if let Some(children) = self.pending.get_mut(&parent) {
+            children.retain(|x| x != hash);
+            if children.is_empty() {
+                self.pending.remove(&parent);
+            }
+        }
+    }
+
+    /// Remove the orphans waiting for the given event
+    fn take_pending(&mut self, hash: &EventId) -> Vec<(EventId, Event<T>)> {
+        let children = self.pending.remove(hash).unwrap_or_default();
+        children
+            .into_iter()
+            .filter_map(|child| self.orphans.remove(&child).map(|(event, _)| (child, event)))
+            .collect()
+    }
+
+    /// Drop the orphans whose parent never arrived
+    fn expire_orphans(&mut self) {
+        let now = Timestamp::current_time().0;
+        let expired: Vec<_> = self
+            .orphans
+            .iter()
+            .filter(|(_, (_, received))| now.saturating_sub(received.0) > ORPHAN_TIMEOUT)
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        for hash in expired {
+            self.remove_orphan(&hash);
         }
     }
 
@@ -211,60 +340,56 @@ where
                 continue
             }
 
-            let depth = self.diff_depth(leaf, head);
-            if depth > MAX_DEPTH {
+            let depth = self.event_map[&head].height - self.event_map[&leaf].height + 1;
+            if depth > self.retention.max_depth as i64 {
                 self.remove_node(leaf);
             }
         }
     }
 
+    /// Move the root forward, discarding the events which fell out of the
+    /// retention window along with the branches forking before the new
+    /// root. The window only depends on the events we hold, so nodes with
+    /// the same events end up with the same root.
     fn update_root(&mut self) {
         let head = self.find_head();
-        let leaves = self.find_leaves();
-
-        // find the common ancestor for each leaf and the head event
-        let mut ancestors = vec![];
-        for leaf in leaves {
-            if leaf == head {
-                continue
+        let cutoff = self
+            .retention
+            .max_age
+            .map(|age| self.event_map[&head].event.timestamp.0.saturating_sub(age));
+
+        let mut root = head;
+        let mut count = 1;
+        while let Some(parent) = self.event_map[&root].parent {
+            if count >= self.retention.max_events {
+                break
             }
 
-            let ancestor = self.find_ancestor(leaf, head);
-            ancestors.push(ancestor);
-        }
-
-        // find the highest ancestor
-        let highest_ancestor = ancestors
-            .iter()
-            .max_by(|&a, &b| self.find_depth(*a, &head).cmp(&self.find_depth(*b, &head)));
-
-        // set the new root
-        if let Some(ancestor) = highest_ancestor {
-            // the ancestor must have at least height > MAX_HEIGHT
-            let ancestor_height = self.find_height(&self.current_root, ancestor).unwrap();
-            if ancestor_height < MAX_HEIGHT {
-                return
+            if cutoff.map_or(false, |cutoff| self.event_map[&parent].event.timestamp.0 < cutoff) {
+                break
             }
 
-            // removing the parents of the new root node
-            let mut root = self.event_map.get(&self.current_root).unwrap();
-            loop {
-                let root_hash = root.event.hash();
+            root = parent;
+            count += 1;
+        }
 
-                if &root_hash == ancestor {
-                    break
-                }
+        if root == self.current_root {
+            return
+        }
 
-                let root_childs = &root.children;
-                assert_eq!(root_childs.len(), 1);
-                let child = *root_childs.first().unwrap();
+        // Only keep the new root and its descendants
+        let mut keep = HashSet::new();
+        let mut pending = vec![root];
+        while let Some(id) = pending.pop() {
+            pending.extend(self.event_map[&id].children.iter().copied());
+            keep.insert(id);
+        }
 
-                self.event_map.remove(&root_hash);
-                root = self.event_map.get(&child).unwrap();
-            }
+        self.event_map.retain(|id, _| keep.contains(id));
+        self.event_map.get_mut(&root).unwrap().parent = None;
+        self.current_root = root;
 
-            self.current_root = *ancestor;
-        }
+        debug!(target: "event_graph", "Moved the root to {}", root);
     }
 
     fn remove_node(&mut self, mut event_id: EventId) {
@@ -287,120 +412,28 @@ where
         }
     }
 
-    // find_head
-    // -> recursively call itself
-    // -> + 1 for every recursion, return self if no children
-    // -> select max from returned values
-    // Gets the lead node with the maximal number of events counting from root
-    fn find_head(&self) -> EventId {
-        self.find_longest_chain(&self.current_root, 0).0
-    }
-
-    fn find_longest_chain(&self, parent_node: &EventId, i: u32) -> (EventId, u32) {
-        let children = &self.event_map.get(parent_node).unwrap().children;
-        if children.is_empty() {
-            return (*parent_node, i)
-        }
-
-        let mut current_max = 0;
-        let mut current_node = None;
-        for node in children.iter() {
-            let (grandchild_node, grandchild_i) = self.find_longest_chain(node, i + 1);
-
-            match &grandchild_i.cmp(&current_max) {
-                Ordering::Greater => {
-                    current_max = grandchild_i;
-                    current_node = Some(grandchild_node);
-                }
-                Ordering::Equal => {
-                    // Break ties using the timestamp
-                    let grandchild_node_timestamp =
-                        self.event_map.get(&grandchild_node).unwrap().event.timestamp;
-                    let current_node_timestamp =
-                        self.event_map.get(&current_node.unwrap()).unwrap().event.timestamp;
-
-                    if grandchild_node_timestamp > current_node_timestamp {
-                        current_max = grandchild_i;
-                        current_node = Some(grandchild_node);
-                    }
-                }
-                Ordering::Less => {
-                    // Left a todo here, not sure if it should be handled
-                    continue
-                }
-            }
-        }
-        assert_ne!(current_max, 0);
-        (current_node.expect("internal logic error"), current_max)
-    }
-
-    fn find_depth(&self, mut node: EventId, ancestor_id: &EventId) -> u32 {
-        let mut depth = 0;
-        while &node != ancestor_id {
-            depth += 1;
-            if let Some(parent) = self.event_map.get(&node).unwrap().parent {
-                node = parent
-            } else {
-                break
-            }
-        }
-        depth
-    }
-
-    fn find_height(&self, node: &EventId, child_id: &EventId) -> Option<u32> {
-        let mut height = 0;
-
-        if node == child_id {
-            return Some(height)
-        }
-        height += 1;
-        let children = &self.event_map.get(node).unwrap().children;
-        if children.is_empty() {
-            return None
-        }
-
-        for child in children.iter() {
-            if let Some(h) = self.find_height(child, child_id) {
-                return Some(height + h)
-            }
-        }
-        None
-    }
-
-    fn find_ancestor(&self, mut node_a: EventId, mut node_b: EventId) -> EventId {
-        // node_a is a child of node_b
-        let is_child = node_b == self.event_map.get(&node_a).unwrap().parent.unwrap();
-
-        if is_child {
-            return node_b
-        }
-
-        while node_a != node_b {
-            let node_a_parent = self.event_map.get(&node_a).unwrap().parent.unwrap();
-            let node_b_parent = self.event_map.get(&node_b).unwrap().parent.unwrap();
-
-            if node_a_parent == self.current_root || node_b_parent == self.current_root {
-                return self.current_root
-            }
+    /// Make the given event the head if it extends a longer chain than
+    /// the current one. Ties are broken by the timestamp, then the hash,
+    /// so every node picks the same head.
+    fn update_head(&mut self, event_id: EventId) {
+        let key = |id: &EventId| {
+            let node = &self.event_map[id];
+            (node.height, node.event.timestamp.0, *id.as_bytes())
+        };
 
-            node_a = node_a_parent;
-            node_b = node_b_parent;
+        if key(&event_id) > key(&self.head) {
+            self.head = event_id;
         }
-
-        node_a
     }
 
-    fn diff_depth(&self, node_a: EventId, node_b: EventId) -> u32 {
-        let ancestor = self.find_ancestor(node_a, node_b);
-        let node_a_depth = self.find_depth(node_a, &ancestor);
-        let node_b_depth = self.find_depth(node_b, &ancestor);
-
-        (node_b_depth + 1) - node_a_depth
+    /// Gets the leaf node with the maximal number of events counting from root
+    fn find_head(&self) -> EventId {
+        self.head
     }
 
     fn _debug(&self) {
         for (event_id, event_node) in &self.event_map {
-            let depth = self.find_depth(*event_id, &self.current_root);
+            let depth = event_node.height - self.event_map[&self.current_root].height;
             println!("{}: {:?} [depth={}]", event_id, event_node.event, depth);
         }
 
@@ -624,7 +657,7 @@ mod tests {
     #[test]
     fn test_event_hash() {
         let events_queue = EventsQueue::<PrivMsgEvent>::new();
-        let model = Model::new(events_queue);
+        let model = Model::new(events_queue, Retention::default());
         let root_id = model.current_root;
 
         let timestamp = Timestamp::current_time().0 + 1;
@@ -637,4 +670,153 @@ mod tests {
 
         assert_eq!(event2_hash, event_hash);
     }
+
+    /// Add a chain of `count` events on top of `id`, `interval` seconds apart
+    async fn add_chain(
+        model: &mut Model<PrivMsgEvent>,
+        mut id: EventId,
+        count: u64,
+        start: u64,
+        interval: u64,
+    ) -> Vec<Event<PrivMsgEvent>> {
+        let mut events = vec![];
+        for x in 0..count {
+            let event = create_message(id, start + x * interval);
+            id = event.hash();
+            model.add(event.clone()).await;
+            events.push(event);
+        }
+        events
+    }
+
+    #[async_std::test]
+    async fn test_retention_max_events() {
+        let retention = Retention { max_age: None, max_events: 10, max_depth: 300 };
+        let mut model = Model::new(EventsQueue::new(), retention);
+        let root_id = model.current_root;
+
+        let events = add_chain(&mut model, root_id, 25, 1000, 1).await;
+
+        assert_eq!(model.event_map.len(), 10);
+        assert_eq!(model.current_root, events[15].hash());
+        assert_eq!(model.find_head(), events[24].hash());
+        assert!(model.event_map[&model.current_root].parent.is_none());
+    }
+
+    #[async_std::test]
+    async fn test_retention_max_age() {
+        let retention = Retention { max_age: Some(50), max_events: 300, max_depth: 300 };
+        let mut model = Model::new(EventsQueue::new(), retention);
+        let root_id = model.current_root;
+
+        // Events are 10 seconds apart, so the last 6 are within 50 seconds of the head
+        let events = add_chain(&mut model, root_id, 20, 1000, 10).await;
+
+        assert_eq!(model.event_map.len(), 6);
+        assert_eq!(model.current_root, events[14].hash());
+        assert_eq!(model.find_head(), events[19].hash());
+    }
+
+    #[async_std::test]
+    async fn test_retention_prunes_old_branches() {
+        let retention = Retention { max_age: None, max_events: 10, max_depth: 300 };
+        let mut model = Model::new(EventsQueue::new(), retention);
+        let root_id = model.current_root;
+
+        let main = add_chain(&mut model, root_id, 5, 1000, 1).await;
+        let branch = add_chain(&mut model, main[1].hash(), 2, 2000, 1).await;
+        let main = add_chain(&mut model, main[4].hash(), 10, 1005, 1).await;
+
+        assert_eq!(model.current_root, main[0].hash());
+        for event in branch {
+            assert!(!model.event_map.contains_key(&event.hash()));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_find_head() {
+        let mut model = Model::new(EventsQueue::new(), Retention::default());
+        let root_id = model.current_root;
+
+        let first = add_chain(&mut model, root_id, 5, 1000, 1).await;
+        assert_eq!(model.find_head(), first[4].hash());
+
+        // A longer branch takes over
+        let second = add_chain(&mut model, first[1].hash(), 4, 2000, 1).await;
+        assert_eq!(model.find_head(), second[3].hash());
+
+        // On equal length, the latest event wins
+        let third = add_chain(&mut model, first[1].hash(), 4, 3000, 1).await;
+        assert_eq!(model.find_head(), third[3].hash());
+        let fourth = add_chain(&mut model, first[1].hash(), 4, 500, 1).await;
+        assert_eq!(model.find_head(), third[3].hash());
+
+        // The head stays the same whatever the order events arrive in
+        let mut new_model = Model::new(EventsQueue::new(), Retention::default());
+        for event in [fourth, third, second, first].concat().into_iter().rev() {
+            new_model.add(event).await;
+        }
+        assert_eq!(new_model.find_head(), model.find_head());
+    }
+
+    #[async_std::test]
+    async fn test_retention_age_is_capped() {
+        let retention = Retention { max_age: Some(1000), max_events: 10, max_depth: 300 };
+        let mut model = Model::new(EventsQueue::new(), retention);
+        let root_id = model.current_root;
+
+        let events = add_chain(&mut model, root_id, 25, 1000, 1).await;
+
+        assert_eq!(model.event_map.len(), 10);
+        assert_eq!(model.current_root, events[15].hash());
+    }
+
+    #[async_std::test]
+    async fn test_adopt_root() {
+        let retention = Retention { max_age: None, max_events: 10, max_depth: 300 };
+        let mut model = Model::new(EventsQueue::new(), retention.clone());
+        let root_id = model.current_root;
+        let events = add_chain(&mut model, root_id, 25, 1000, 1).await;
+
+        // Events missing their parent are never adopted on their own
+        let mut new_model = Model::new(EventsQueue::new(), retention.clone());
+        for event in events[15..].iter().rev() {
+            new_model.add(event.clone()).await;
+        }
+        assert_eq!(new_model.current_root, new_model.genesis);
+        assert_eq!(new_model.orphans.len(), 10);
+
+        // Until a peer offers our root, which they get linked to
+        assert!(new_model.adopt_root(events[15].clone()).await);
+        assert_eq!(new_model.current_root, model.current_root);
+        assert_eq!(new_model.find_head(), model.find_head());
+        assert!(new_model.orphans.is_empty() && new_model.pending.is_empty());
+
+        // Once adopted, the root only changes through the events
+        assert!(!new_model.adopt_root(events[20].clone()).await);
+
+        // Older events extend a newer adopted root backwards
+        let mut new_model = Model::new(EventsQueue::new(), retention);
+        assert!(new_model.adopt_root(events[20].clone()).await);
+        for event in events[10..].iter() {
+            new_model.add(event.clone()).await;
+        }
+        assert_eq!(new_model.current_root, model.current_root);
+        assert_eq!(new_model.find_head(), model.find_head());
+        assert_eq!(new_model.event_map.len(), 10);
+    }
+
+    #[async_std::test]
+    async fn test_orphans_bounded() {
+        let mut model = Model::new(EventsQueue::new(), Retention::default());
+
+        for x in 0..(MAX_ORPHANS as u64 + 10) {
+            let event = create_message(blake3::hash(&x.to_le_bytes()), 1000 + x);
+            model.add(event).await;
+        }
+
+        assert_eq!(model.orphans.len(), MAX_ORPHANS);
+        assert_eq!(model.pending.len(), MAX_ORPHANS);
+        assert_eq!(model.event_map.len(), 1);
+    }
 }
@@ -22,17 +22,21 @@ use async_std::sync::{Arc, Mutex};
 use async_trait::async_trait;
 use darkfi_serial::{Decodable, Encodable, SerialDecodable, SerialEncodable};
 use log::debug;
+use url::Url;
 
 use super::EventMsg;
 use crate::{
     event_graph::model::{Event, EventId, ModelPtr},
     impl_p2p_message, net,
     net::Message,
-    util::{async_util::sleep, ringbuffer::RingBuffer},
+    util::{async_util::sleep, ringbuffer::RingBuffer, time::Timestamp},
     Result,
 };
 
 const SIZE_OF_SEEN_BUFFER: usize = 65536;
+/// Seconds an event may be ahead of our clock. Events further in the
+/// future are dropped, so they can't move the retention window forward.
+const MAX_FUTURE_SKEW: u64 = 60;
 
 #[derive(SerialEncodable, SerialDecodable, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct InvItem {
@@ -57,6 +61,41 @@ struct GetData {
 }
 impl_p2p_message!(GetData, "getdata");
 
+/// Root of the event graph, sent to a peer syncing from the genesis we
+/// already pruned
+#[derive(SerialDecodable, SerialEncodable, Clone, Debug)]
+struct SyncRoot<T: Send + Sync> {
+    event: Event<T>,
+}
+
+impl<T> net::Message for SyncRoot<T>
+where
+    T: Send + Sync + Decodable + Encodable + 'static,
+{
+    const NAME: &'static str = "syncroot";
+}
+
+/// Broadcast a message to the peers able to exchange events of type `T`,
+/// excluding the ones provided in `exclude_list`.
+pub async fn broadcast<T: EventMsg, M: net::Message>(
+    p2p: &net::P2pPtr,
+    message: &M,
+    exclude_list: &[Url],
+) {
+    let Some(feature) = T::feature() else {
+        return p2p.broadcast_with_exclude(message, exclude_list).await
+    };
+
+    let mut exclude_list = exclude_list.to_vec();
+    for channel in p2p.channels().lock().await.values() {
+        if !channel.has_feature(feature).await {
+            exclude_list.push(channel.address().clone());
+        }
+    }
+
+    p2p.broadcast_with_exclude(message, &exclude_list).await
+}
+
 pub type SeenPtr<T> = Arc<Seen<T>>;
 
 pub struct Seen<T> {
@@ -87,11 +126,14 @@ where
     inv_sub: net::MessageSubscription<Inv>,
     getdata_sub: net::MessageSubscription<GetData>,
     syncevent_sub: net::MessageSubscription<SyncEvent>,
+    syncroot_sub: net::MessageSubscription<SyncRoot<T>>,
     p2p: net::P2pPtr,
     channel: net::ChannelPtr,
     model: ModelPtr<T>,
     seen_event: SeenPtr<EventId>,
     seen_inv: SeenPtr<EventId>,
+    // Set while we wait for the peer to answer our sync from the genesis
+    awaiting_root: Mutex<bool>,
 }
 
 impl<T> ProtocolEvent<T>
@@ -110,6 +152,7 @@ where
         message_subsytem.add_dispatch::<Inv>().await;
         message_subsytem.add_dispatch::<GetData>().await;
         message_subsytem.add_dispatch::<SyncEvent>().await;
+        message_subsytem.add_dispatch::<SyncRoot<T>>().await;
 
         let event_sub =
             channel.clone().subscribe_msg::<Event<T>>().await.expect("Missing Event dispatcher!");
@@ -125,17 +168,25 @@ where
             .await
             .expect("Missing SyncEvent dispatcher!");
 
+        let syncroot_sub = channel
+            .clone()
+            .subscribe_msg::<SyncRoot<T>>()
+            .await
+            .expect("Missing SyncRoot dispatcher!");
+
         Arc::new(Self {
             jobsman: net::ProtocolJobsManager::new("ProtocolEvent", channel.clone()),
             event_sub,
             inv_sub,
             getdata_sub,
             syncevent_sub,
+            syncroot_sub,
             p2p,
             channel,
             model,
             seen_event,
             seen_inv,
+            awaiting_root: Mutex::new(false),
         })
     }
 
@@ -146,6 +197,10 @@ where
             let event = self.event_sub.receive().await?;
             let event = (*event).to_owned();
 
+            if is_from_future(&event) {
+                continue
+            }
+
             if !self.seen_event.push(&event.hash()).await {
                 continue
             }
@@ -156,7 +211,7 @@ where
             self.send_inv(&event).await?;
 
             // Broadcast the msg
-            self.p2p.broadcast_with_exclude(&event, &exclude_list).await;
+            broadcast::<T, _>(&self.p2p, &event, &exclude_list).await;
         }
     }
 
@@ -180,7 +235,7 @@ where
             // }
 
             // Broadcast the inv msg
-            self.p2p.broadcast_with_exclude(&inv, &exclude_list).await;
+            broadcast::<T, _>(&self.p2p, &inv, &exclude_list).await;
         }
     }
     async fn handle_receive_getdata(self: Arc<Self>) -> Result<()> {
@@ -215,7 +270,16 @@ where
                     continue
                 }
 
-                let children = model.get_offspring(leaf);
+                // A new peer only holding the genesis we already pruned gets
+                // our root, so it can adopt it and the events following it.
+                let mut leaf = *leaf;
+                if leaf == model.get_genesis_hash() && model.get_event(&leaf).is_none() {
+                    leaf = model.get_root_hash();
+                    let event = model.get_event(&leaf).unwrap();
+                    self.channel.send(&SyncRoot { event }).await?;
+                }
+
+                let children = model.get_offspring(&leaf);
 
                 for child in children {
                     self.channel.send(&child).await?;
@@ -224,12 +288,44 @@ where
         }
     }
 
+    async fn handle_receive_syncroot(self: Arc<Self>) -> Result<()> {
+        debug!(target: "event_graph", "ProtocolEvent::handle_receive_syncroot() [START]");
+        loop {
+            let syncroot = self.syncroot_sub.receive().await?;
+            let event = syncroot.event.clone();
+
+            // A root is only taken as the answer to our own sync request
+            if !std::mem::take(&mut *self.awaiting_root.lock().await) {
+                debug!(
+                    target: "event_graph",
+                    "Dropping unrequested root from {}", self.channel.address(),
+                );
+                continue
+            }
+
+            if is_from_future(&event) {
+                continue
+            }
+
+            // Only adopted while we hold nothing but the genesis
+            self.model.lock().await.adopt_root(event).await;
+        }
+    }
+
     // every 6 seconds send a SyncEvent msg
     async fn send_sync_hash_loop(self: Arc<Self>) -> Result<()> {
         debug!(target: "event_graph", "ProtocolEvent::send_sync_hash_loop() [START]");
         loop {
             sleep(6).await;
-            let leaves = self.model.lock().await.find_leaves();
+            let model = self.model.lock().await;
+            let leaves = model.find_leaves();
+
+            // Holding nothing but the genesis, the peer may answer with its root
+            let genesis = model.get_genesis_hash();
+            *self.awaiting_root.lock().await =
+                leaves == [genesis] && model.get_root_hash() == genesis;
+            drop(model);
+
             self.channel.send(&SyncEvent { leaves }).await?;
         }
     }
@@ -244,7 +340,8 @@ where
 
     async fn send_inv(&self, event: &Event<T>) -> Result<()> {
         debug!(target: "event_graph", "ProtocolEvent::send_inv()");
-        self.p2p.broadcast(&Inv { invs: vec![InvItem { hash: event.hash() }] }).await;
+        broadcast::<T, _>(&self.p2p, &Inv { invs: vec![InvItem { hash: event.hash() }] }, &[])
+            .await;
 
         Ok(())
     }
@@ -263,11 +360,25 @@ where
 {
     async fn start(self: Arc<Self>, executor: Arc<smol::Executor<'_>>) -> Result<()> {
         debug!(target: "event_graph", "ProtocolEvent::start() [START]");
+
+        // Peers unable to decode our events are left alone
+        if let Some(feature) = T::feature() {
+            if !self.channel.has_feature(feature).await {
+                debug!(
+                    target: "event_graph",
+                    "ProtocolEvent::start() {} lacks feature {}, not exchanging events",
+                    self.channel.address(), feature,
+                );
+                return Ok(())
+            }
+        }
+
         self.jobsman.clone().start(executor.clone());
         self.jobsman.clone().spawn(self.clone().handle_receive_event(), executor.clone()).await;
         self.jobsman.clone().spawn(self.clone().handle_receive_inv(), executor.clone()).await;
         self.jobsman.clone().spawn(self.clone().handle_receive_getdata(), executor.clone()).await;
         self.jobsman.clone().spawn(self.clone().handle_receive_syncevent(), executor.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_syncroot(), executor.clone()).await;
         self.jobsman.clone().spawn(self.clone().send_sync_hash_loop(), executor.clone()).await;
         debug!(target: "event_graph", "ProtocolEvent::start() [END]");
         Ok(())
@@ -278,6 +389,16 @@ where
     }
 }
 
+/// Check if an event is too far ahead of our clock to be accepted
+fn is_from_future<T: Send + Sync>(event: &Event<T>) -> bool {
+    if event.timestamp.0 <= Timestamp::current_time().0 + MAX_FUTURE_SKEW {
+        return false
+    }
+
+    debug!(target: "event_graph", "Dropping event from the future: {}", event.timestamp.0);
+    true
+}
+
 impl<T> net::Message for Event<T>
 where
     T: Send + Sync + Decodable + Encodable + 'static,
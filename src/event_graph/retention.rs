@@ -0,0 +1,77 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/// Number of events kept on the main chain when no other limit is configured
+pub const DEFAULT_MAX_EVENTS: u32 = 300;
+/// Depth behind the head past which side branches are discarded
+pub const DEFAULT_MAX_DEPTH: u32 = 300;
+
+/// Retention policy of the event graph.
+///
+/// The window is measured back from the head of the DAG, using the
+/// event count and the timestamps of the events themselves rather than
+/// the local clock. This way, nodes holding the same events prune them
+/// the same way and agree on the root. Events more than a minute ahead
+/// of the local clock are refused, so they can't push the window forward.
+/// The event count is always bounded, so walking the window stays cheap
+/// even when only a maximum age is configured.
+#[derive(Clone, Debug)]
+pub struct Retention {
+    /// Maximum age in seconds of the events kept, relative to the head
+    pub max_age: Option<u64>,
+    /// Maximum number of events kept on the main chain, including the root
+    pub max_events: u32,
+    /// Maximum depth behind the head of the side branches kept
+    pub max_depth: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self { max_age: None, max_events: DEFAULT_MAX_EVENTS, max_depth: DEFAULT_MAX_DEPTH }
+    }
+}
+
+// The following is used so we can have the retention policy configurable
+// from TOML files.
+
+/// Defines the event graph retention settings.
+#[derive(Clone, Debug, serde::Deserialize, structopt::StructOpt, structopt_toml::StructOptToml)]
+#[structopt()]
+pub struct RetentionOpt {
+    /// Maximum age in seconds of the events kept in the DAG
+    #[structopt(long = "dag-max-age")]
+    pub max_age: Option<u64>,
+
+    /// Maximum number of events kept in the DAG (default: 300)
+    #[structopt(long = "dag-max-events")]
+    pub max_events: Option<u32>,
+
+    /// Maximum depth behind the head of the DAG branches kept
+    #[structopt(long = "dag-max-depth")]
+    pub max_depth: Option<u32>,
+}
+
+impl From<RetentionOpt> for Retention {
+    fn from(opt: RetentionOpt) -> Self {
+        Self {
+            max_age: opt.max_age,
+            max_events: opt.max_events.unwrap_or(DEFAULT_MAX_EVENTS),
+            max_depth: opt.max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
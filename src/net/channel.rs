@@ -16,6 +16,8 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::time::Instant;
+
 use async_std::sync::{Arc, Mutex};
 use darkfi_serial::serialize;
 use futures::{
@@ -37,7 +39,7 @@ use super::{
     transport::PtStream,
 };
 use crate::{
-    system::{StoppableTask, StoppableTaskPtr, Subscriber, SubscriberPtr, Subscription},
+    system::{StoppableTask, StoppableTaskPtr, Subscriber, SubscriberPtr, Subscription, METRICS},
     util::{ringbuffer::RingBuffer, time::NanoTimestamp},
     Error, Result,
 };
@@ -105,6 +107,10 @@ pub struct Channel {
     stopped: Mutex<bool>,
     /// Weak pointer to respective session
     session: SessionWeakPtr,
+    /// Time of channel creation, used for lifetime metrics
+    created: Instant,
+    /// Version information the remote node sent during the handshake
+    version: Mutex<Option<Arc<message::VersionMessage>>>,
     /// Channel debug info
     info: Mutex<Option<ChannelInfo>>,
 }
@@ -146,6 +152,8 @@ impl Channel {
             receive_task: StoppableTask::new(),
             stopped: Mutex::new(false),
             session,
+            created: Instant::now(),
+            version: Mutex::new(None),
             info,
         })
     }
@@ -204,6 +212,8 @@ impl Channel {
             self.stop_subscriber.notify(Error::ChannelStopped).await;
             self.receive_task.stop().await;
             self.message_subsystem.trigger_error(Error::ChannelStopped).await;
+
+            METRICS.observe("darkfi_p2p_channel_lifetime_seconds", &[], self.created.elapsed());
         }
 
         debug!(target: "net::channel::stop()", "END => address={}", self.address());
@@ -270,8 +280,13 @@ impl Channel {
             log.push((time, "send".to_string(), packet.command.clone()));
         );
 
+        let command = packet.command.clone();
         let stream = &mut *self.writer.lock().await;
-        let _written = message::send_packet(stream, packet).await?;
+        let written = message::send_packet(stream, packet).await?;
+
+        let labels = [("command", command.as_str())];
+        METRICS.counter_add("darkfi_p2p_messages_sent_total", &labels, 1);
+        METRICS.counter_add("darkfi_p2p_bytes_sent_total", &labels, written as u64);
 
         Ok(())
     }
@@ -343,7 +358,18 @@ impl Channel {
             };
 
             // Send result to our subscribers
-            self.message_subsystem.notify(&packet.command, &packet.payload).await;
+            let known = self.message_subsystem.notify(&packet.command, &packet.payload).await;
+
+            // Peers choose the command, so only the ones we dispatch get their
+            // own series. Anything else would grow the registry without bound.
+            let command = if known { packet.command.as_str() } else { "unknown" };
+            let labels = [("command", command)];
+            METRICS.counter_add("darkfi_p2p_messages_received_total", &labels, 1);
+            METRICS.counter_add(
+                "darkfi_p2p_bytes_received_total",
+                &labels,
+                packet.payload.len() as u64,
+            );
         }
     }
 
@@ -352,6 +378,25 @@ impl Channel {
         &self.address
     }
 
+    /// Set the version information received from the remote node
+    pub async fn set_version(&self, version: Arc<message::VersionMessage>) {
+        *self.version.lock().await = Some(version);
+    }
+
+    /// Returns the version information received from the remote node,
+    /// if the version handshake has already happened.
+    pub async fn version(&self) -> Option<Arc<message::VersionMessage>> {
+        self.version.lock().await.clone()
+    }
+
+    /// Check if the remote node advertised support for given feature
+    pub async fn has_feature(&self, feature: &str) -> bool {
+        match self.version.lock().await.as_ref() {
+            Some(version) => version.features.iter().any(|f| f == feature),
+            None => false,
+        }
+    }
+
     /// Returns the inner [`MessageSubsystem`] reference
     pub fn message_subsystem(&self) -> &MessageSubsystem {
         &self.message_subsystem
@@ -74,6 +74,10 @@ impl_p2p_message!(AddrsMessage, "addr");
 pub struct VersionMessage {
     /// Only used for debugging. Compromises privacy when set.
     pub node_id: String,
+    /// Identifier of the P2P network (overlay) the node belongs to
+    pub network_id: String,
+    /// Protocol features supported by the node
+    pub features: Vec<String>,
 }
 impl_p2p_message!(VersionMessage, "version");
 
@@ -240,18 +240,19 @@ impl MessageSubsystem {
     }
 
     /// Transmits a payload to a dispatcher.
-    /// Returns an error if the payload fails to transmit.
-    pub async fn notify(&self, command: &str, payload: &[u8]) {
+    /// Returns `false` if there is no dispatcher for the command.
+    pub async fn notify(&self, command: &str, payload: &[u8]) -> bool {
         let Some(dispatcher) = self.dispatchers.lock().await.get(command).cloned() else {
             warn!(
                 target: "net::message_subscriber::notify",
                 "message_subscriber::notify: Command '{}' did not find a dispatcher",
                 command,
             );
-            return
+            return false
         };
 
         dispatcher.trigger(payload).await;
+        true
     }
 
     /// Concurrently transmits an error message across dispatchers.
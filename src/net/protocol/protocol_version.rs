@@ -29,7 +29,7 @@ use super::super::{
     message_subscriber::MessageSubscription,
     settings::SettingsPtr,
 };
-use crate::{Error, Result};
+use crate::{system::METRICS, Error, Result};
 
 /// Implements the protocol version handshake sent out by nodes at
 /// the beginning of a connection.
@@ -78,6 +78,8 @@ impl ProtocolVersion {
                 self.channel.address(), e,
             );
 
+            METRICS.counter_add("darkfi_p2p_handshake_failures_total", &[("reason", "timeout")], 1);
+
             // Remove from hosts
             self.hosts.remove(self.channel.address()).await;
             self.channel.stop().await;
@@ -116,7 +118,11 @@ impl ProtocolVersion {
             "START => address={}", self.channel.address(),
         );
 
-        let version = VersionMessage { node_id: self.settings.node_id.clone() };
+        let version = VersionMessage {
+            node_id: self.settings.node_id.clone(),
+            network_id: self.settings.network_id.clone(),
+            features: self.settings.features.clone(),
+        };
         self.channel.send(&version).await?;
 
         // Wait for verack
@@ -145,6 +151,7 @@ impl ProtocolVersion {
                 "[P2P] Version mismatch from {}. Disconnecting...",
                 self.channel.address(),
             );
+            METRICS.counter_add("darkfi_p2p_handshake_failures_total", &[("reason", "version")], 1);
 
             self.hosts.remove(self.channel.address()).await;
             self.channel.stop().await;
@@ -156,7 +163,7 @@ impl ProtocolVersion {
     }
 
     /// Receive version info, check the message is okay and send verack
-    /// with app version attached.
+    /// with app version attached. Peers on a different network are rejected.
     async fn recv_version(self: Arc<Self>) -> Result<()> {
         debug!(
             target: "net::protocol_version::recv_version()",
@@ -164,9 +171,29 @@ impl ProtocolVersion {
         );
 
         // Receive version message
-        let _version = self.version_sub.receive().await?;
+        let version = self.version_sub.receive().await?;
         //self.channel.set_remote_node_id(version.node_id.clone()).await;
 
+        // Nodes belonging to different overlays should never share channels.
+        if self.settings.network_id != version.network_id {
+            error!(
+                target: "net::protocol_version::recv_version()",
+                "[P2P] Network mismatch from {} (ours: \"{}\", theirs: \"{}\"). Disconnecting...",
+                self.channel.address(), self.settings.network_id, version.network_id,
+            );
+            METRICS.counter_add(
+                "darkfi_p2p_handshake_failures_total",
+                &[("reason", "network_id")],
+                1,
+            );
+
+            self.hosts.remove(self.channel.address()).await;
+            self.channel.stop().await;
+            return Err(Error::ChannelStopped)
+        }
+
+        self.channel.set_version(version).await;
+
         // Send verack
         let verack = VerackMessage { app_version: self.settings.app_version.clone() };
         self.channel.send(&verack).await?;
@@ -41,6 +41,11 @@ pub struct Settings {
     pub seeds: Vec<Url>,
     /// Application version, used for convenient protocol matching
     pub app_version: semver::Version,
+    /// Network identifier. Peers advertising a different one are rejected
+    /// during the version handshake, so apps and testnets stay isolated.
+    pub network_id: String,
+    /// Protocol features this node supports, advertised in the handshake
+    pub features: Vec<String>,
     /// Whitelisted network transports for outbound connections
     pub allowed_transports: Vec<String>,
     /// Allow transport mixing (e.g. Tor would be allowed to connect to `tcp://`)
@@ -74,6 +79,8 @@ impl Default for Settings {
             peers: vec![],
             seeds: vec![],
             app_version,
+            network_id: String::new(),
+            features: vec![],
             allowed_transports: vec![],
             transport_mixing: true,
             outbound_connections: 0,
@@ -146,6 +153,15 @@ pub struct SettingsOpt {
     #[structopt(skip)]
     pub node_id: String,
 
+    /// Network identifier, peers on a different network are rejected
+    #[structopt(long)]
+    pub network_id: Option<String>,
+
+    /// Protocol features to advertise in the version handshake
+    #[serde(default)]
+    #[structopt(skip)]
+    pub features: Vec<String>,
+
     /// Preferred transports for outbound connections    
     #[serde(default)]
     #[structopt(long = "transports")]
@@ -172,6 +188,8 @@ impl From<SettingsOpt> for Settings {
             peers: opt.peers,
             seeds: opt.seeds,
             app_version,
+            network_id: opt.network_id.unwrap_or_default(),
+            features: opt.features,
             allowed_transports: opt.allowed_transports,
             transport_mixing: opt.transport_mixing.unwrap_or(false),
             outbound_connections: opt.outbound_connections.unwrap_or(0),
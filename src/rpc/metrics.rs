@@ -0,0 +1,66 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Minimal HTTP endpoint for scraping the metrics registry.
+use async_std::sync::Arc;
+use futures::{AsyncReadExt, AsyncWriteExt};
+use log::{debug, error, info};
+use url::Url;
+
+use crate::{
+    net::transport::{Listener, PtListener, PtStream},
+    system::METRICS,
+    Result,
+};
+
+/// Read the incoming HTTP request and reply with the rendered registry.
+/// The request itself is not inspected, any path serves the metrics.
+async fn serve(mut stream: Box<dyn PtStream>, peer_addr: Url) -> Result<()> {
+    let mut buf = vec![0; 4096];
+    let n = stream.read(&mut buf).await?;
+    debug!(target: "rpc::metrics", "{} --> {}", peer_addr, String::from_utf8_lossy(&buf[..n]));
+
+    let body = METRICS.render();
+    let reply = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body,
+    );
+
+    stream.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+/// Start an HTTP server bound to the given accept URL serving the global
+/// metrics registry in the Prometheus text exposition format.
+pub async fn listen_and_serve(accept_url: Url, ex: Arc<smol::Executor<'_>>) -> Result<()> {
+    debug!(target: "rpc::metrics", "Trying to bind listener on {}", accept_url);
+
+    let listener = Listener::new(accept_url).await?.listen().await?;
+    while let Ok((stream, peer_addr)) = listener.next().await {
+        info!(target: "rpc::metrics", "Metrics server accepted connection from {}", peer_addr);
+        ex.spawn(async move {
+            if let Err(e) = serve(stream, peer_addr.clone()).await {
+                error!(target: "rpc::metrics", "Metrics server failed serving {}: {}", peer_addr, e);
+            }
+        })
+        .detach();
+    }
+
+    Ok(())
+}
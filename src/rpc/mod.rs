@@ -25,5 +25,8 @@ pub mod client;
 /// Server-side JSON-RPC implementation
 pub mod server;
 
+/// HTTP endpoint serving the metrics registry in Prometheus text format
+pub mod metrics;
+
 /// Clock sync utility module
 pub mod clock_sync;
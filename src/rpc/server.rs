@@ -17,15 +17,18 @@
  */
 
 //! JSON-RPC server-side implementation.
+use std::time::Instant;
+
 use async_std::sync::Arc;
 use async_trait::async_trait;
 use futures::{AsyncReadExt, AsyncWriteExt};
 use log::{debug, error, info, warn};
 use url::Url;
 
-use super::jsonrpc::{JsonRequest, JsonResult};
+use super::jsonrpc::{ErrorCode, JsonRequest, JsonResult};
 use crate::{
     net::transport::{Listener, PtListener, PtStream},
+    system::METRICS,
     Result,
 };
 
@@ -37,6 +40,17 @@ pub trait RequestHandler: Sync + Send {
     async fn handle_request(&self, req: JsonRequest) -> JsonResult;
 }
 
+/// Returns `false` if the handler couldn't resolve the requested method.
+/// Invalid requests and params are included, since handlers may reject
+/// them before looking at the method.
+fn resolved(reply: &JsonResult) -> bool {
+    let JsonResult::Error(e) = reply else { return true };
+
+    let unresolved =
+        [ErrorCode::MethodNotFound, ErrorCode::InvalidRequest, ErrorCode::InvalidParams];
+    !unresolved.iter().any(|c| e.error.code == c.code())
+}
+
 /// Internal accept function that runs inside a loop for accepting incoming
 /// JSON-RPC requests and passing them to the [`RequestHandler`].
 async fn accept(
@@ -73,7 +87,19 @@ async fn accept(
             }
         };
 
+        let method = r.method.as_str().unwrap_or_default().to_string();
+        let start = Instant::now();
         let reply = rh.handle_request(r).await;
+
+        // Clients choose the method, so only the ones the handler resolved
+        // get their own series. Anything else would grow the registry
+        // without bound.
+        let method = if resolved(&reply) { method.as_str() } else { "unknown" };
+        METRICS.observe(
+            "darkfi_rpc_request_duration_seconds",
+            &[("method", method)],
+            start.elapsed(),
+        );
         match reply {
             JsonResult::Subscriber(sub) => {
                 let subscription = sub.subscriber.subscribe().await;
@@ -596,11 +596,24 @@ pub(crate) fn zkas_db_set(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32)
         }
     };
 
-    // We didn't find any existing bincode, so let's create a new VerifyingKey and write it all.
-    info!(target: "runtime::db::zkas_db_set()", "Creating VerifyingKey for {} zkas circuit", zkbin.namespace);
-    let witnesses = empty_witnesses(&zkbin);
-    let circuit = ZkCircuit::new(witnesses, zkbin.clone());
-    let vk = VerifyingKey::build(13, &circuit);
+    // We didn't find any existing bincode, so let's get a VerifyingKey and write it all.
+    // If the runtime has a key store, the key is taken from there so it's only built
+    // once per circuit, otherwise it's created here.
+    let vk = match &env.key_store {
+        Some(key_store) => match key_store.verifying_key(&zkas_bincode, 13) {
+            Ok(vk) => vk,
+            Err(e) => {
+                error!(target: "runtime::db::zkas_db_set()", "Failed to get VerifyingKey from key store: {}", e);
+                return DB_SET_FAILED
+            }
+        },
+        None => {
+            info!(target: "runtime::db::zkas_db_set()", "Creating VerifyingKey for {} zkas circuit", zkbin.namespace);
+            let witnesses = empty_witnesses(&zkbin);
+            let circuit = ZkCircuit::new(witnesses, zkbin.clone());
+            VerifyingKey::build(13, &circuit)
+        }
+    };
     let mut vk_buf = vec![];
     if let Err(e) = vk.write(&mut vk_buf) {
         error!(target: "runtime::db::zkas_db_set()", "Failed to serialize VerifyingKey: {}", e);
@@ -35,7 +35,9 @@ use wasmer_middlewares::{
 };
 
 use super::{import, import::db::DbHandle, memory::MemoryManipulation};
-use crate::{blockchain::BlockchainOverlayPtr, util::time::TimeKeeper, Error, Result};
+use crate::{
+    blockchain::BlockchainOverlayPtr, util::time::TimeKeeper, zk::KeyStore, Error, Result,
+};
 
 /// Name of the wasm linear memory in our guest module
 const MEMORY: &str = "memory";
@@ -94,6 +96,8 @@ pub struct Env {
     pub objects: RefCell<Vec<Vec<u8>>>,
     /// Helper structure to calculate time related operations
     pub time_keeper: TimeKeeper,
+    /// Key store used to get the verifying keys of deployed zkas circuits
+    pub key_store: Option<KeyStore>,
 }
 
 impl Env {
@@ -168,6 +172,7 @@ impl Runtime {
                 memory: None,
                 objects: RefCell::new(vec![]),
                 time_keeper,
+                key_store: None,
             },
         );
 
@@ -374,6 +379,13 @@ impl Runtime {
         }
     }
 
+    /// Use the given [`KeyStore`] for the verifying keys of the zkas circuits
+    /// the contract sets up on deploy, instead of building them every time.
+    pub fn set_key_store(&mut self, key_store: KeyStore) {
+        let env_mut = self.ctx.as_mut(&mut self.store);
+        env_mut.key_store = Some(key_store);
+    }
+
     /// This function runs when a smart contract is initially deployed, or re-deployed.
     /// The runtime will look for an `INITIALIZE` symbol in the wasm code, and execute
     /// it if found. Optionally, it is possible to pass in a payload for any kind of special
@@ -32,6 +32,10 @@ pub const MERKLE_DEPTH_ORCHARD: usize = 32;
 
 pub const MERKLE_DEPTH: u8 = MERKLE_DEPTH_ORCHARD as u8;
 
+/// Depth of the Sparse Merkle tree verified by the zkVM, i.e. keys are
+/// 32-bit wide. The tree has to use Poseidon and an all-zero empty leaf.
+pub const SPARSE_MERKLE_DEPTH: usize = 32;
+
 #[allow(dead_code)]
 /// $\ell^\mathsf{Orchard}_\mathsf{base}$
 pub(crate) const L_ORCHARD_BASE: usize = 255;
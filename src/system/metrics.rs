@@ -0,0 +1,199 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Process-wide metrics registry.
+//!
+//! Library modules record counters, gauges and histograms into the global
+//! [`METRICS`] registry, which can then be rendered in the Prometheus text
+//! exposition format and served by `rpc::metrics::listen_and_serve`.
+
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+/// Upper bounds (in seconds) of the histogram buckets. These are spread
+/// wide enough to cover both RPC latencies and channel lifetimes.
+const BUCKETS: [f64; 14] =
+    [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0, 300.0, 1800.0, 3600.0, 86400.0];
+
+/// Metric name and its sorted label pairs
+type MetricKey = (&'static str, Vec<(&'static str, String)>);
+
+#[derive(Clone, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (i, bound) in BUCKETS.iter().enumerate() {
+            if value <= *bound {
+                self.buckets[i] += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Registry holding all recorded metrics
+pub struct Registry {
+    counters: Mutex<BTreeMap<MetricKey, u64>>,
+    gauges: Mutex<BTreeMap<MetricKey, f64>>,
+    histograms: Mutex<BTreeMap<MetricKey, Histogram>>,
+}
+
+/// Global metrics registry
+pub static METRICS: Registry = Registry::new();
+
+fn key(name: &'static str, labels: &[(&'static str, &str)]) -> MetricKey {
+    let mut labels: Vec<(&'static str, String)> =
+        labels.iter().map(|(k, v)| (*k, v.to_string())).collect();
+    labels.sort();
+    (name, labels)
+}
+
+/// Render label pairs, optionally appending an extra pair
+fn render_labels(labels: &[(&'static str, String)], extra: Option<(&str, &str)>) -> String {
+    let escape = |v: &str| v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+
+    let mut pairs: Vec<String> =
+        labels.iter().map(|(k, v)| format!("{}=\"{}\"", k, escape(v))).collect();
+    if let Some((k, v)) = extra {
+        pairs.push(format!("{}=\"{}\"", k, escape(v)));
+    }
+
+    if pairs.is_empty() {
+        return String::new()
+    }
+
+    format!("{{{}}}", pairs.join(","))
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            counters: Mutex::new(BTreeMap::new()),
+            gauges: Mutex::new(BTreeMap::new()),
+            histograms: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Increment a counter by given value
+    pub fn counter_add(&self, name: &'static str, labels: &[(&'static str, &str)], value: u64) {
+        *self.counters.lock().unwrap().entry(key(name, labels)).or_insert(0) += value;
+    }
+
+    /// Set a gauge to given value
+    pub fn gauge_set(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+        self.gauges.lock().unwrap().insert(key(name, labels), value);
+    }
+
+    /// Add given value to a gauge, which may be negative
+    pub fn gauge_add(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+        *self.gauges.lock().unwrap().entry(key(name, labels)).or_insert(0.0) += value;
+    }
+
+    /// Record a duration into a histogram
+    pub fn observe(&self, name: &'static str, labels: &[(&'static str, &str)], value: Duration) {
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry(key(name, labels))
+            .or_default()
+            .observe(value.as_secs_f64());
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let mut last = "";
+        for ((name, labels), value) in self.counters.lock().unwrap().iter() {
+            if *name != last {
+                writeln!(out, "# TYPE {} counter", name).unwrap();
+                last = *name;
+            }
+            writeln!(out, "{}{} {}", name, render_labels(labels, None), value).unwrap();
+        }
+
+        let mut last = "";
+        for ((name, labels), value) in self.gauges.lock().unwrap().iter() {
+            if *name != last {
+                writeln!(out, "# TYPE {} gauge", name).unwrap();
+                last = *name;
+            }
+            writeln!(out, "{}{} {}", name, render_labels(labels, None), value).unwrap();
+        }
+
+        let mut last = "";
+        for ((name, labels), hist) in self.histograms.lock().unwrap().iter() {
+            if *name != last {
+                writeln!(out, "# TYPE {} histogram", name).unwrap();
+                last = *name;
+            }
+            for (bound, count) in BUCKETS.iter().zip(hist.buckets.iter()) {
+                let le = bound.to_string();
+                let labels = render_labels(labels, Some(("le", &le)));
+                writeln!(out, "{}_bucket{} {}", name, labels, count).unwrap();
+            }
+            let labels_inf = render_labels(labels, Some(("le", "+Inf")));
+            writeln!(out, "{}_bucket{} {}", name, labels_inf, hist.count).unwrap();
+            writeln!(out, "{}_sum{} {}", name, render_labels(labels, None), hist.sum).unwrap();
+            writeln!(out, "{}_count{} {}", name, render_labels(labels, None), hist.count).unwrap();
+        }
+
+        out
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::new();
+        registry.counter_add("foo_total", &[("command", "ping")], 2);
+        registry.counter_add("foo_total", &[("command", "ping")], 3);
+        registry.counter_add("foo_total", &[("command", "pong")], 1);
+        registry.gauge_set("bar", &[], 40.0);
+        registry.gauge_add("bar", &[], 3.0);
+        registry.gauge_add("bar", &[], -1.0);
+        registry.gauge_add("qux", &[], 1.0);
+        registry.observe("baz_seconds", &[("method", "ping")], Duration::from_millis(20));
+
+        let out = registry.render();
+        assert!(out.contains("# TYPE foo_total counter\n"));
+        assert!(out.contains("foo_total{command=\"ping\"} 5\n"));
+        assert!(out.contains("foo_total{command=\"pong\"} 1\n"));
+        assert!(out.contains("# TYPE bar gauge\nbar 42\n"));
+        assert!(out.contains("# TYPE qux gauge\nqux 1\n"));
+        assert!(out.contains("baz_seconds_bucket{method=\"ping\",le=\"0.01\"} 0\n"));
+        assert!(out.contains("baz_seconds_bucket{method=\"ping\",le=\"0.05\"} 1\n"));
+        assert!(out.contains("baz_seconds_bucket{method=\"ping\",le=\"+Inf\"} 1\n"));
+        assert!(out.contains("baz_seconds_count{method=\"ping\"} 1\n"));
+        assert_eq!(out.matches("# TYPE foo_total").count(), 1);
+    }
+}
@@ -16,10 +16,12 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+pub mod metrics;
 pub mod stoppable_task;
 pub mod subscriber;
 pub mod types;
 
+pub use metrics::METRICS;
 pub use stoppable_task::{StoppableTask, StoppableTaskPtr};
 pub use subscriber::{Subscriber, SubscriberPtr, Subscription};
 pub use types::ExecutorPtr;
@@ -16,7 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use darkfi_sdk::{
     crypto::{
@@ -27,8 +27,9 @@ use darkfi_sdk::{
     tx::ContractCall,
 };
 use darkfi_serial::{serialize, Encodable, SerialDecodable, SerialEncodable};
-use log::{debug, error};
+use log::{debug, error, warn};
 use rand::{CryptoRng, RngCore};
+use rayon::prelude::*;
 
 use crate::{
     error::TxVerifyFailed,
@@ -64,41 +65,104 @@ impl Transaction {
         verifying_keys: &HashMap<[u8; 32], HashMap<String, VerifyingKey>>,
         zkp_table: Vec<Vec<(String, Vec<pallas::Base>)>>,
     ) -> Result<()> {
-        // TODO: Are we sure we should assert here?
-        assert_eq!(self.calls.len(), self.proofs.len());
-        assert_eq!(self.calls.len(), zkp_table.len());
+        if !Self::batch_verify_zkps(&[(self, zkp_table)], verifying_keys).is_empty() {
+            return Err(TxVerifyFailed::InvalidZkProof.into())
+        }
 
-        for (call, (proofs, pubvals)) in zip!(self.calls, self.proofs, zkp_table) {
-            assert_eq!(proofs.len(), pubvals.len());
+        Ok(())
+    }
 
-            let Some(contract_map) = verifying_keys.get(&call.contract_id.to_bytes()) else {
-                error!("Verifying keys not found for contract {}", call.contract_id);
-                return Err(TxVerifyFailed::InvalidZkProof.into())
-            };
+    /// Verify ZK proofs for a set of transactions, given along with their
+    /// public inputs table. Proofs sharing a verifying key are verified in
+    /// a single batch, and only if the batch fails are they verified one by
+    /// one to find the culprits. Batches are verified in parallel. Returns
+    /// the indexes of the transactions that failed verification, in
+    /// ascending order.
+    pub fn batch_verify_zkps(
+        txs: &[(&Self, Vec<Vec<(String, Vec<pallas::Base>)>>)],
+        verifying_keys: &HashMap<[u8; 32], HashMap<String, VerifyingKey>>,
+    ) -> Vec<usize> {
+        let mut erroneous = HashSet::new();
+
+        // Proofs grouped by their verifying key, as (tx index, proof, public inputs)
+        let mut batches: HashMap<([u8; 32], &str), Vec<(usize, &Proof, &[pallas::Base])>> =
+            HashMap::new();
+
+        for (i, (tx, zkp_table)) in txs.iter().enumerate() {
+            if tx.calls.len() != tx.proofs.len() || tx.calls.len() != zkp_table.len() {
+                error!("Incorrect number of ZK proofs in tx {}", tx.hash());
+                erroneous.insert(i);
+                continue
+            }
 
-            for (proof, (zk_ns, public_vals)) in proofs.iter().zip(pubvals.iter()) {
-                if let Some(vk) = contract_map.get(zk_ns) {
-                    // We have a verifying key for this
-                    debug!("public inputs: {:#?}", public_vals);
-                    if let Err(e) = proof.verify(vk, public_vals) {
-                        error!(
-                            target: "",
-                            "Failed verifying {}::{} ZK proof: {:#?}",
-                            call.contract_id, zk_ns, e
-                        );
-                        return Err(TxVerifyFailed::InvalidZkProof.into())
-                    }
-                    debug!("Successfully verified {}::{} ZK proof", call.contract_id, zk_ns);
+            for (call, (proofs, pubvals)) in zip!(tx.calls, tx.proofs, zkp_table) {
+                if proofs.len() != pubvals.len() {
+                    error!(
+                        "Incorrect number of {} ZK proofs in tx {}",
+                        call.contract_id,
+                        tx.hash()
+                    );
+                    erroneous.insert(i);
                     continue
                 }
 
-                let e = format!("{}:{} circuit VK nonexistent", call.contract_id, zk_ns);
-                error!("{}", e);
-                return Err(TxVerifyFailed::InvalidZkProof.into())
+                let contract_id = call.contract_id.to_bytes();
+                let Some(contract_map) = verifying_keys.get(&contract_id) else {
+                    error!("Verifying keys not found for contract {}", call.contract_id);
+                    erroneous.insert(i);
+                    continue
+                };
+
+                for (proof, (zk_ns, public_vals)) in proofs.iter().zip(pubvals.iter()) {
+                    if !contract_map.contains_key(zk_ns) {
+                        error!("{}:{} circuit VK nonexistent", call.contract_id, zk_ns);
+                        erroneous.insert(i);
+                        continue
+                    }
+
+                    debug!("public inputs: {:#?}", public_vals);
+                    let batch = batches.entry((contract_id, zk_ns.as_str())).or_default();
+                    batch.push((i, proof, public_vals.as_slice()));
+                }
             }
         }
 
-        Ok(())
+        // Batches are independent of each other, so they're verified in parallel
+        let batches: Vec<_> = batches.into_iter().collect();
+        let failed: Vec<usize> = batches
+            .into_par_iter()
+            .flat_map_iter(|((contract_id, zk_ns), batch)| {
+                let vk = &verifying_keys[&contract_id][zk_ns];
+
+                if batch.len() > 1 {
+                    let proofs: Vec<_> =
+                        batch.iter().map(|(_, proof, vals)| (*proof, *vals)).collect();
+                    if Proof::batch_verify(vk, &proofs) {
+                        debug!("Successfully batch verified {} {} ZK proofs", proofs.len(), zk_ns);
+                        return vec![]
+                    }
+
+                    warn!("Batch verification of {} ZK proofs failed, verifying one by one", zk_ns);
+                }
+
+                batch
+                    .into_par_iter()
+                    .filter_map(|(i, proof, public_vals)| {
+                        if let Err(e) = proof.verify(vk, public_vals) {
+                            error!("Failed verifying {} ZK proof: {:#?}", zk_ns, e);
+                            return Some(i)
+                        }
+                        debug!("Successfully verified {} ZK proof", zk_ns);
+                        None
+                    })
+                    .collect()
+            })
+            .collect();
+        erroneous.extend(failed);
+
+        let mut erroneous: Vec<usize> = erroneous.into_iter().collect();
+        erroneous.sort_unstable();
+        erroneous
     }
 
     /// Verify Schnorr signatures for the entire transaction.
@@ -125,6 +189,24 @@ impl Transaction {
         Ok(())
     }
 
+    /// Verify Schnorr signatures for a set of transactions, given along with
+    /// their public keys table. Transactions are verified in parallel.
+    /// Returns the indexes of the transactions that failed verification,
+    /// in ascending order.
+    pub fn batch_verify_sigs(txs: &[(&Self, Vec<Vec<PublicKey>>)]) -> Vec<usize> {
+        txs.par_iter()
+            .enumerate()
+            .filter_map(|(i, (tx, pub_table))| {
+                if pub_table.len() != tx.signatures.len() {
+                    error!("Incorrect number of signatures in tx {}", tx.hash());
+                    return Some(i)
+                }
+
+                tx.verify_sigs(pub_table.clone()).err().map(|_| i)
+            })
+            .collect()
+    }
+
     /// Create Schnorr signatures for the entire transaction.
     pub fn create_sigs(
         &self,
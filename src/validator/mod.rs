@@ -25,6 +25,7 @@ use crate::{
     error::TxVerifyFailed,
     tx::Transaction,
     util::time::TimeKeeper,
+    zk::KeyStore,
     Error, Result,
 };
 
@@ -51,6 +52,8 @@ pub struct ValidatorConfig {
     pub faucet_pubkeys: Vec<PublicKey>,
     /// Flag to enable testing mode
     pub testing_mode: bool,
+    /// Key store for the verifying keys of the native contracts' circuits
+    pub key_store: Option<KeyStore>,
 }
 
 impl ValidatorConfig {
@@ -59,8 +62,9 @@ impl ValidatorConfig {
         genesis_block: BlockInfo,
         faucet_pubkeys: Vec<PublicKey>,
         testing_mode: bool,
+        key_store: Option<KeyStore>,
     ) -> Self {
-        Self { time_keeper, genesis_block, faucet_pubkeys, testing_mode }
+        Self { time_keeper, genesis_block, faucet_pubkeys, testing_mode, key_store }
     }
 }
 
@@ -96,7 +100,12 @@ impl Validator {
         };
 
         // Deploy native wasm contracts
-        deploy_native_contracts(&overlay, &config.time_keeper, &config.faucet_pubkeys)?;
+        deploy_native_contracts(
+            &overlay,
+            &config.time_keeper,
+            &config.faucet_pubkeys,
+            config.key_store.as_ref(),
+        )?;
 
         // Write the changes to the actual chain db
         overlay.lock().unwrap().overlay.lock().unwrap().apply()?;
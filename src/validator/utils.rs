@@ -21,7 +21,8 @@ use darkfi_serial::serialize;
 use log::info;
 
 use crate::{
-    blockchain::BlockchainOverlayPtr, runtime::vm_runtime::Runtime, util::time::TimeKeeper, Result,
+    blockchain::BlockchainOverlayPtr, runtime::vm_runtime::Runtime, util::time::TimeKeeper,
+    zk::KeyStore, Result,
 };
 
 /// Deploy DarkFi native wasm contracts to provided blockchain overlay.
@@ -34,10 +35,13 @@ use crate::{
 /// touch anything, or just potentially update the db schemas or whatever
 /// is necessary. This logic should be handled in the init function of
 /// the actual contract, so make sure the native contracts handle this well.
+/// If a [`KeyStore`] is given, the verifying keys of the contracts' zkas
+/// circuits are taken from it.
 pub fn deploy_native_contracts(
     overlay: &BlockchainOverlayPtr,
     time_keeper: &TimeKeeper,
     faucet_pubkeys: &Vec<PublicKey>,
+    key_store: Option<&KeyStore>,
 ) -> Result<()> {
     info!(target: "validator", "Deploying native WASM contracts");
 
@@ -76,6 +80,9 @@ pub fn deploy_native_contracts(
         info!(target: "validator", "Deploying {} with ContractID {}", nc.0, nc.1);
 
         let mut runtime = Runtime::new(&nc.2[..], overlay.clone(), nc.1, time_keeper.clone())?;
+        if let Some(key_store) = key_store {
+            runtime.set_key_store(key_store.clone());
+        }
 
         runtime.deploy(&nc.3)?;
 
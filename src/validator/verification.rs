@@ -16,7 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::HashMap, io::Cursor, time::Instant};
 
 use darkfi_sdk::{
     crypto::{PublicKey, CONSENSUS_CONTRACT_ID},
@@ -29,6 +29,7 @@ use crate::{
     blockchain::{BlockInfo, BlockchainOverlayPtr},
     error::TxVerifyFailed,
     runtime::vm_runtime::Runtime,
+    system::METRICS,
     tx::Transaction,
     util::time::TimeKeeper,
     zk::VerifyingKey,
@@ -45,6 +46,7 @@ pub async fn verify_block(
 ) -> Result<()> {
     let block_hash = block.blockhash();
     debug!(target: "validator", "Validating block {}", block_hash);
+    let start = Instant::now();
 
     // Check if block already exists
     if overlay.lock().unwrap().has_block(block)? {
@@ -74,6 +76,7 @@ pub async fn verify_block(
 
     // Insert block
     overlay.lock().unwrap().add_block(block)?;
+    METRICS.observe("darkfi_validator_block_verify_seconds", &[], start.elapsed());
 
     debug!(target: "validator", "Block {} verified successfully", block_hash);
     Ok(())
@@ -132,6 +135,53 @@ pub async fn verify_transaction(
 ) -> Result<()> {
     let tx_hash = tx.hash();
     debug!(target: "validator", "Validating transaction {}", tx_hash);
+    let start = Instant::now();
+
+    let (sig_table, zkp_table) =
+        execute_transaction(overlay, time_keeper, tx, verifying_keys, true).await?;
+
+    // When we're done executing over the tx's contract calls, we now move on with
+    // verification. First we verify the signatures as that's cheaper, and then
+    // finally we verify the ZK proofs.
+    debug!(target: "validator", "Verifying signatures for transaction {}", tx_hash);
+
+    // TODO: Go through the ZK circuits that have to be verified and account for the opcodes.
+
+    if let Err(e) = tx.verify_sigs(sig_table) {
+        error!(target: "validator", "Signature verification for tx {} failed: {}", tx_hash, e);
+        return Err(TxVerifyFailed::InvalidSignature.into())
+    }
+
+    debug!(target: "validator", "Signature verification successful");
+
+    debug!(target: "validator", "Verifying ZK proofs for transaction {}", tx_hash);
+    if let Err(e) = tx.verify_zkps(verifying_keys, zkp_table).await {
+        error!(target: "validator", "ZK proof verification for tx {} failed: {}", tx_hash, e);
+        return Err(TxVerifyFailed::InvalidZkProof.into())
+    }
+
+    debug!(target: "validator", "ZK proof verification successful");
+    debug!(target: "validator", "Transaction {} verified successfully", tx_hash);
+    METRICS.observe("darkfi_validator_tx_verify_seconds", &[], start.elapsed());
+
+    Ok(())
+}
+
+/// Validate WASM execution for a given [`Transaction`], and apply it to the
+/// provided overlay. Signatures and ZK proofs are not verified here; instead,
+/// the tables of their public keys and public inputs are returned, so the
+/// caller can verify them, possibly along with those of other transactions.
+/// With `exec` set to `false`, only the "metadata" calls are run to get the
+/// tables, and the overlay is left untouched.
+async fn execute_transaction(
+    overlay: &BlockchainOverlayPtr,
+    time_keeper: &TimeKeeper,
+    tx: &Transaction,
+    verifying_keys: &mut HashMap<[u8; 32], HashMap<String, VerifyingKey>>,
+    exec: bool,
+) -> Result<(Vec<Vec<PublicKey>>, Vec<Vec<(String, Vec<pallas::Base>)>>)> {
+    let tx_hash = tx.hash();
+    debug!(target: "validator", "Executing transaction {}", tx_hash);
 
     // Table of public inputs used for ZK proof verification
     let mut zkp_table = vec![];
@@ -185,6 +235,10 @@ pub async fn verify_transaction(
         zkp_table.push(zkp_pub);
         sig_table.push(sig_pub);
 
+        if !exec {
+            continue
+        }
+
         // After getting the metadata, we run the "exec" function with the same runtime
         // and the same payload.
         debug!(target: "validator", "Executing \"exec\" call");
@@ -199,49 +253,35 @@ pub async fn verify_transaction(
         // At this point we're done with the call and move on to the next one.
     }
 
-    // When we're done looping and executing over the tx's contract calls, we now
-    // move on with verification. First we verify the signatures as that's cheaper,
-    // and then finally we verify the ZK proofs.
-    debug!(target: "validator", "Verifying signatures for transaction {}", tx_hash);
     if sig_table.len() != tx.signatures.len() {
         error!(target: "validator", "Incorrect number of signatures in tx {}", tx_hash);
         return Err(TxVerifyFailed::MissingSignatures.into())
     }
 
-    // TODO: Go through the ZK circuits that have to be verified and account for the opcodes.
-
-    if let Err(e) = tx.verify_sigs(sig_table) {
-        error!(target: "validator", "Signature verification for tx {} failed: {}", tx_hash, e);
-        return Err(TxVerifyFailed::InvalidSignature.into())
-    }
-
-    debug!(target: "validator", "Signature verification successful");
-
-    debug!(target: "validator", "Verifying ZK proofs for transaction {}", tx_hash);
-    if let Err(e) = tx.verify_zkps(verifying_keys, zkp_table).await {
-        error!(target: "validator", "ZK proof verification for tx {} failed: {}", tx_hash, e);
-        return Err(TxVerifyFailed::InvalidZkProof.into())
-    }
-
-    debug!(target: "validator", "ZK proof verification successful");
-    debug!(target: "validator", "Transaction {} verified successfully", tx_hash);
-
-    Ok(())
+    Ok((sig_table, zkp_table))
 }
 
 /// Validate a set of [`Transaction`] in sequence and apply them if all are valid.
 /// In case any of the transactions fail, they will be returned to the caller.
-/// The function takes a boolean called `write` which tells it to actually write
-/// the state transitions to the database.
+///
+/// The "metadata" calls of the whole set are run first over the initial state,
+/// and the resulting signatures and ZK proofs are verified in parallel, batching
+/// the proofs that share a verifying key. The transactions are then executed in
+/// order, each one reverted on its own if it fails. The set verification result
+/// of a transaction is only used if executing it produces the same tables,
+/// otherwise it's verified again with the tables produced in order.
+///
+/// Conflicts between transactions, like two of them revealing the same
+/// nullifier, are not checked separately. They are caught by the contract
+/// calls of the later transaction, which run over the state changes of the
+/// earlier ones.
 pub async fn verify_transactions(
     overlay: &BlockchainOverlayPtr,
     time_keeper: &TimeKeeper,
     txs: &[Transaction],
 ) -> Result<Vec<Transaction>> {
     debug!(target: "validator", "Verifying {} transactions", txs.len());
-
-    // Tracker for failed txs
-    let mut erroneous_txs = vec![];
+    let start = Instant::now();
 
     // Map of ZK proof verifying keys for the current transaction batch
     let mut vks: HashMap<[u8; 32], HashMap<String, VerifyingKey>> = HashMap::new();
@@ -253,16 +293,88 @@ pub async fn verify_transactions(
         }
     }
 
-    // Iterate over transactions and attempt to verify them
-    for tx in txs {
+    // Run the "metadata" calls over the initial state, to get the signature and
+    // ZK proof tables of the transactions.
+    let mut indexes = vec![];
+    let mut sig_batch = vec![];
+    let mut zkp_batch = vec![];
+    for (i, tx) in txs.iter().enumerate() {
+        match execute_transaction(overlay, time_keeper, tx, &mut vks, false).await {
+            Ok((sig_table, zkp_table)) => {
+                indexes.push(i);
+                sig_batch.push((tx, sig_table));
+                zkp_batch.push((tx, zkp_table));
+            }
+            // It may depend on the state changes of the transactions before it,
+            // so it's verified after its execution instead.
+            Err(e) => {
+                debug!(target: "validator", "Metadata of tx {} unavailable: {}", tx.hash(), e)
+            }
+        }
+    }
+
+    // Verify the signatures and ZK proofs of the whole set
+    debug!(target: "validator", "Verifying signatures and ZK proofs of {} transactions", indexes.len());
+    let (invalid_sigs, invalid_zkps) = rayon::join(
+        || Transaction::batch_verify_sigs(&sig_batch),
+        || Transaction::batch_verify_zkps(&zkp_batch, &vks),
+    );
+
+    let mut valid = vec![true; indexes.len()];
+    for idx in invalid_sigs.into_iter().chain(invalid_zkps) {
+        valid[idx] = false;
+    }
+
+    // Set verification result of each transaction, along with the tables it
+    // was computed from
+    let mut precomputed = vec![None; txs.len()];
+    for (((i, (_, sig_table)), (_, zkp_table)), valid) in
+        indexes.into_iter().zip(sig_batch).zip(zkp_batch).zip(valid)
+    {
+        precomputed[i] = Some((sig_table, zkp_table, valid));
+    }
+
+    // Execute the transactions in order, reverting the failing ones
+    let mut rejected = vec![false; txs.len()];
+    for (i, tx) in txs.iter().enumerate() {
         overlay.lock().unwrap().checkpoint();
-        if let Err(e) = verify_transaction(overlay, time_keeper, tx, &mut vks).await {
-            warn!(target: "validator", "Transaction verification failed: {}", e);
-            erroneous_txs.push(tx.clone());
-            // TODO: verify this works as expected
+        let (sig_table, zkp_table) =
+            match execute_transaction(overlay, time_keeper, tx, &mut vks, true).await {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(target: "validator", "Transaction execution failed: {}", e);
+                    rejected[i] = true;
+                    overlay.lock().unwrap().revert_to_checkpoint()?;
+                    continue
+                }
+            };
+
+        // The tables of a transaction differ from the precomputed ones if its
+        // "metadata" calls depend on the state changes of the transactions
+        // before it, in which case we verify it again with the new ones.
+        let valid = match &precomputed[i] {
+            Some((sigs, zkps, valid)) if *sigs == sig_table && *zkps == zkp_table => *valid,
+            _ => {
+                Transaction::batch_verify_sigs(&[(tx, sig_table)]).is_empty() &&
+                    Transaction::batch_verify_zkps(&[(tx, zkp_table)], &vks).is_empty()
+            }
+        };
+
+        if !valid {
+            warn!(target: "validator", "Signature or ZK proof verification for tx {} failed", tx.hash());
+            rejected[i] = true;
             overlay.lock().unwrap().revert_to_checkpoint()?;
         }
     }
 
+    METRICS.observe("darkfi_validator_txs_verify_seconds", &[], start.elapsed());
+
+    let erroneous_txs = txs
+        .iter()
+        .zip(rejected)
+        .filter(|(_, rejected)| *rejected)
+        .map(|(tx, _)| tx.clone())
+        .collect();
+
     Ok(erroneous_txs)
 }
@@ -0,0 +1,399 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Gadget verifying a leaf at a given key against the root of a
+//! [`SparseMerkleTree`](darkfi_sdk::crypto::smt::SparseMerkleTree) using
+//! the Poseidon hash.
+//!
+//! The key is decomposed into `SPARSE_MERKLE_DEPTH` bits, where bit `i`
+//! tells whether the node on level `i` is the right child of its parent.
+//! The tree has to be built with an all-zero empty leaf, so an empty leaf
+//! is `pallas::Base::ZERO` and calculating the root with a zero leaf at a
+//! key proves that the key is not a member of the tree.
+
+use darkfi_sdk::crypto::constants::SPARSE_MERKLE_DEPTH;
+use halo2_gadgets::poseidon::{
+    primitives as poseidon, Hash as PoseidonHash, Pow5Chip as PoseidonChip,
+    Pow5Config as PoseidonConfig,
+};
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Chip, Layouter, Value},
+    pasta::{group::ff::PrimeField, pallas},
+    plonk,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Expression, Selector},
+    poly::Rotation,
+};
+
+/// Sparse Merkle path as returned by the native tree, a sequence of
+/// (left, right) node pairs from the leaf level up to the root.
+pub type SparseMerklePathValue = [(pallas::Base, pallas::Base); SPARSE_MERKLE_DEPTH];
+
+/// Configuration for the Sparse Merkle tree chip
+#[derive(Clone, Debug)]
+pub struct SparseMerkleConfig {
+    /// `[bit, acc/cur, sibling, left, right]`
+    advices: [Column<Advice>; 5],
+    /// Selector for the key decomposition
+    s_decompose: Selector,
+    /// Selector for ordering a node and its sibling
+    s_swap: Selector,
+    /// Configuration of the Poseidon chip hashing the node pairs
+    poseidon_config: PoseidonConfig<pallas::Base, 3, 2>,
+}
+
+/// Sparse Merkle tree chip
+pub struct SparseMerkleChip {
+    config: SparseMerkleConfig,
+}
+
+impl Chip<pallas::Base> for SparseMerkleChip {
+    type Config = SparseMerkleConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl SparseMerkleChip {
+    pub fn construct(config: SparseMerkleConfig) -> Self {
+        Self { config }
+    }
+
+    /// Configure the chip. The circuit needs to have a fixed column enabled
+    /// for constants, since the key decomposition is constrained to end in zero.
+    pub fn configure(
+        meta: &mut ConstraintSystem<pallas::Base>,
+        advices: [Column<Advice>; 5],
+        poseidon_config: PoseidonConfig<pallas::Base, 3, 2>,
+    ) -> SparseMerkleConfig {
+        for column in &advices {
+            meta.enable_equality(*column);
+        }
+
+        let s_decompose = meta.selector();
+        let s_swap = meta.selector();
+
+        meta.create_gate("sparse merkle key decomposition", |meta| {
+            let s_decompose = meta.query_selector(s_decompose);
+            let bit = meta.query_advice(advices[0], Rotation::cur());
+            let acc = meta.query_advice(advices[1], Rotation::cur());
+            let acc_next = meta.query_advice(advices[1], Rotation::next());
+            let one = Expression::Constant(pallas::Base::ONE);
+            let two = Expression::Constant(pallas::Base::from(2));
+
+            Constraints::with_selector(
+                s_decompose,
+                [
+                    ("bit is boolean", bit.clone() * (one - bit.clone())),
+                    ("acc = 2 * acc_next + bit", acc - (acc_next * two + bit)),
+                ],
+            )
+        });
+
+        meta.create_gate("sparse merkle swap", |meta| {
+            let s_swap = meta.query_selector(s_swap);
+            let bit = meta.query_advice(advices[0], Rotation::cur());
+            let cur = meta.query_advice(advices[1], Rotation::cur());
+            let sibling = meta.query_advice(advices[2], Rotation::cur());
+            let left = meta.query_advice(advices[3], Rotation::cur());
+            let right = meta.query_advice(advices[4], Rotation::cur());
+
+            // bit = 0: (left, right) = (cur, sibling)
+            // bit = 1: (left, right) = (sibling, cur)
+            Constraints::with_selector(
+                s_swap,
+                [
+                    ("left", left - (cur.clone() + bit.clone() * (sibling.clone() - cur.clone()))),
+                    ("right", right - (sibling.clone() + bit * (cur - sibling))),
+                ],
+            )
+        });
+
+        SparseMerkleConfig { advices, s_decompose, s_swap, poseidon_config }
+    }
+
+    /// Decompose the key into its little-endian bits. The running sum has
+    /// to reach zero, which enforces the key to fit in the tree depth.
+    fn decompose_key(
+        &self,
+        mut layouter: impl Layouter<pallas::Base>,
+        key: &AssignedCell<pallas::Base, pallas::Base>,
+    ) -> Result<Vec<AssignedCell<pallas::Base, pallas::Base>>, plonk::Error> {
+        let config = self.config();
+        let two_inv = pallas::Base::from(2).invert().unwrap();
+
+        layouter.assign_region(
+            || "sparse merkle key decomposition",
+            |mut region| {
+                let mut acc = key.copy_advice(|| "key", &mut region, config.advices[1], 0)?;
+                let mut bits = Vec::with_capacity(SPARSE_MERKLE_DEPTH);
+
+                for i in 0..SPARSE_MERKLE_DEPTH {
+                    config.s_decompose.enable(&mut region, i)?;
+
+                    let bit =
+                        acc.value().map(|acc| pallas::Base::from((acc.to_repr()[0] & 1) as u64));
+                    let bit = region.assign_advice(|| "bit", config.advices[0], i, || bit)?;
+
+                    let next =
+                        acc.value().zip(bit.value()).map(|(acc, bit)| (*acc - bit) * two_inv);
+                    acc = region.assign_advice(|| "acc", config.advices[1], i + 1, || next)?;
+
+                    bits.push(bit);
+                }
+
+                region.constrain_constant(acc.cell(), pallas::Base::ZERO)?;
+                Ok(bits)
+            },
+        )
+    }
+
+    /// Order the current node and its sibling into a (left, right) pair
+    fn swap(
+        &self,
+        mut layouter: impl Layouter<pallas::Base>,
+        bit: &AssignedCell<pallas::Base, pallas::Base>,
+        cur: &AssignedCell<pallas::Base, pallas::Base>,
+        sibling: Value<pallas::Base>,
+    ) -> Result<
+        (AssignedCell<pallas::Base, pallas::Base>, AssignedCell<pallas::Base, pallas::Base>),
+        plonk::Error,
+    > {
+        let config = self.config();
+
+        layouter.assign_region(
+            || "sparse merkle swap",
+            |mut region| {
+                config.s_swap.enable(&mut region, 0)?;
+
+                let bit = bit.copy_advice(|| "copy bit", &mut region, config.advices[0], 0)?;
+                let cur = cur.copy_advice(|| "copy cur", &mut region, config.advices[1], 0)?;
+                let sibling =
+                    region.assign_advice(|| "sibling", config.advices[2], 0, || sibling)?;
+
+                let pair = bit.value().zip(cur.value()).zip(sibling.value()).map(
+                    |((bit, cur), sibling)| {
+                        if *bit == pallas::Base::ZERO {
+                            (*cur, *sibling)
+                        } else {
+                            (*sibling, *cur)
+                        }
+                    },
+                );
+
+                let left =
+                    region.assign_advice(|| "left", config.advices[3], 0, || pair.map(|p| p.0))?;
+                let right =
+                    region.assign_advice(|| "right", config.advices[4], 0, || pair.map(|p| p.1))?;
+
+                Ok((left, right))
+            },
+        )
+    }
+
+    /// Calculate the root of the tree given a key, its path, and the leaf
+    /// stored at the key.
+    pub fn calculate_root(
+        &self,
+        mut layouter: impl Layouter<pallas::Base>,
+        key: &AssignedCell<pallas::Base, pallas::Base>,
+        path: Value<SparseMerklePathValue>,
+        leaf: &AssignedCell<pallas::Base, pallas::Base>,
+    ) -> Result<AssignedCell<pallas::Base, pallas::Base>, plonk::Error> {
+        let bits = self.decompose_key(layouter.namespace(|| "decompose key"), key)?;
+
+        let mut cur = leaf.clone();
+        for (level, bit) in bits.iter().enumerate() {
+            // The node on our path is the left one of the pair if the bit is unset,
+            // the other one is its sibling. Whether the node matches the pair is
+            // implied by the resulting root.
+            let sibling = path.zip(bit.value()).map(|(path, bit)| {
+                if *bit == pallas::Base::ZERO {
+                    path[level].1
+                } else {
+                    path[level].0
+                }
+            });
+
+            let (left, right) =
+                self.swap(layouter.namespace(|| "sparse merkle swap"), bit, &cur, sibling)?;
+
+            let hasher = PoseidonHash::<
+                _,
+                _,
+                poseidon::P128Pow5T3,
+                poseidon::ConstantLength<2>,
+                3,
+                2,
+            >::init(
+                PoseidonChip::construct(self.config.poseidon_config.clone()),
+                layouter.namespace(|| "sparse merkle poseidon init"),
+            )?;
+
+            cur =
+                hasher.hash(layouter.namespace(|| "sparse merkle poseidon hash"), [left, right])?;
+        }
+
+        Ok(cur)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zk::assign_free_advice;
+    use darkfi_sdk::crypto::smt::{Poseidon, SparseMerkleTree};
+    use halo2_proofs::{
+        circuit::floor_planner,
+        dev::MockProver,
+        plonk::{Circuit, Instance as InstanceColumn},
+    };
+    use rand::rngs::OsRng;
+    use std::collections::BTreeMap;
+
+    #[derive(Clone)]
+    struct SmtCircuitConfig {
+        primary: Column<InstanceColumn>,
+        advices: [Column<Advice>; 5],
+        smt_config: SparseMerkleConfig,
+    }
+
+    #[derive(Default)]
+    struct SmtCircuit {
+        key: Value<pallas::Base>,
+        path: Value<SparseMerklePathValue>,
+        leaf: Value<pallas::Base>,
+    }
+
+    impl Circuit<pallas::Base> for SmtCircuit {
+        type Config = SmtCircuitConfig;
+        type FloorPlanner = floor_planner::V1;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<pallas::Base>) -> Self::Config {
+            let advices = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+
+            let primary = meta.instance_column();
+            meta.enable_equality(primary);
+
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+
+            let rc_a = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+            let rc_b = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+            let poseidon_config = PoseidonChip::configure::<poseidon::P128Pow5T3>(
+                meta,
+                advices[1..4].try_into().unwrap(),
+                advices[4],
+                rc_a,
+                rc_b,
+            );
+
+            let smt_config = SparseMerkleChip::configure(meta, advices, poseidon_config);
+
+            Self::Config { primary, advices, smt_config }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<pallas::Base>,
+        ) -> Result<(), plonk::Error> {
+            let smt_chip = SparseMerkleChip::construct(config.smt_config.clone());
+
+            let key =
+                assign_free_advice(layouter.namespace(|| "Load key"), config.advices[0], self.key)?;
+            let leaf = assign_free_advice(
+                layouter.namespace(|| "Load leaf"),
+                config.advices[0],
+                self.leaf,
+            )?;
+
+            let root = smt_chip.calculate_root(
+                layouter.namespace(|| "SMT root"),
+                &key,
+                self.path,
+                &leaf,
+            )?;
+            layouter.constrain_instance(root.cell(), config.primary, 0)?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sparse_merkle_chip() -> crate::Result<()> {
+        let hasher = Poseidon::<pallas::Base, 2>::new();
+        let leaves: BTreeMap<u32, pallas::Base> = [
+            (3, pallas::Base::random(&mut OsRng)),
+            (8, pallas::Base::random(&mut OsRng)),
+            (u32::MAX, pallas::Base::random(&mut OsRng)),
+        ]
+        .into_iter()
+        .collect();
+
+        let smt =
+            SparseMerkleTree::<_, _, SPARSE_MERKLE_DEPTH>::new(&leaves, &hasher, &[0; 64]).unwrap();
+        let root = smt.root();
+
+        let prove = |key: u64, leaf: pallas::Base, root: pallas::Base| {
+            let circuit = SmtCircuit {
+                key: Value::known(pallas::Base::from(key)),
+                path: Value::known(smt.generate_membership_proof(key).path),
+                leaf: Value::known(leaf),
+            };
+            MockProver::run(12, &circuit, vec![vec![root]]).unwrap().verify()
+        };
+
+        // Membership
+        assert!(prove(8, leaves[&8], root).is_ok());
+        assert!(prove(u32::MAX as u64, leaves[&u32::MAX], root).is_ok());
+
+        // Non-membership with an empty leaf
+        assert!(prove(5, pallas::Base::ZERO, root).is_ok());
+        assert!(prove(8, pallas::Base::ZERO, root).is_err());
+
+        // Wrong leaf at a key
+        assert!(prove(3, leaves[&8], root).is_err());
+
+        // Keys wider than the tree depth can't be decomposed
+        let circuit = SmtCircuit {
+            key: Value::known(pallas::Base::from(1u64 << SPARSE_MERKLE_DEPTH)),
+            path: Value::known(smt.generate_membership_proof(0).path),
+            leaf: Value::known(pallas::Base::ZERO),
+        };
+        assert!(MockProver::run(12, &circuit, vec![vec![root]]).unwrap().verify().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! On-disk store of verifying keys for zkas circuits.
+//!
+//! Keys are stored as `<id>.vk`, where `<id>` is the blake3 hash of the
+//! zkas bincode and `k`. A changed circuit therefore maps to a new file,
+//! and the store can be shared by several processes.
+//!
+//! Each file starts with the blake3 hash of `<id>` and the serialized key,
+//! which is checked on load. Truncated or corrupted keys, and keys copied
+//! over from another circuit, are rebuilt instead of being used to verify
+//! proofs. The checksum does not protect against someone able to write to
+//! the store, so it has to be as trusted as the binaries using it.
+//!
+//! halo2 can not serialize proving keys, so they are not persisted. They are
+//! built from the stored verifying key instead, which skips generating the
+//! params and the verifying key, but still runs `keygen_pk` once per circuit
+//! and process. Built proving keys are kept in memory and shared by all the
+//! clones of a store, so later lookups don't build them again.
+
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, read, rename, write},
+    io::Cursor,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+};
+
+use log::{debug, info, warn};
+
+use super::{empty_witnesses, ProvingKey, VerifyingKey, ZkCircuit};
+use crate::{zkas::ZkBinary, Error, Result};
+
+/// Default key store path used by the DarkFi binaries
+pub const DEFAULT_KEY_STORE_PATH: &str = "~/.local/darkfi/zk_keys";
+
+/// Verifying key store, see the module documentation
+#[derive(Clone, Debug)]
+pub struct KeyStore {
+    path: PathBuf,
+    /// Proving keys built in this process, by key ID
+    proving_keys: Arc<Mutex<HashMap<blake3::Hash, ProvingKey>>>,
+}
+
+impl KeyStore {
+    /// Open a key store in the given directory, creating it if needed
+    pub fn new(path: &Path) -> Result<Self> {
+        create_dir_all(path)?;
+        Ok(Self { path: path.to_path_buf(), proving_keys: Arc::new(Mutex::new(HashMap::new())) })
+    }
+
+    fn key_id(bincode: &[u8], k: u32) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(bincode);
+        hasher.update(&k.to_le_bytes());
+        hasher.finalize()
+    }
+
+    fn checksum(id: &blake3::Hash, key: &[u8]) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(id.as_bytes());
+        hasher.update(key);
+        hasher.finalize()
+    }
+
+    /// Read a key file, returning the key if its checksum matches
+    fn read_key(&self, id: &blake3::Hash, path: &Path) -> Option<Vec<u8>> {
+        let data = read(path).ok()?;
+        if data.len() < blake3::OUT_LEN {
+            warn!(target: "zk::key_store", "Key file {:?} is truncated", path);
+            return None
+        }
+
+        let (checksum, key) = data.split_at(blake3::OUT_LEN);
+        if checksum != Self::checksum(id, key).as_bytes() {
+            warn!(target: "zk::key_store", "Checksum mismatch for {:?}", path);
+            return None
+        }
+
+        Some(key.to_vec())
+    }
+
+    /// Write a key file along with its checksum. The data is written to a
+    /// temporary file first, so other processes never read a partially
+    /// written key.
+    fn write_key(&self, id: &blake3::Hash, path: &Path, key: &[u8]) -> Result<()> {
+        let mut data = Self::checksum(id, key).as_bytes().to_vec();
+        data.extend_from_slice(key);
+
+        let tmp = path.with_extension(format!("tmp.{}", process::id()));
+        write(&tmp, data)?;
+        rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Returns the [`VerifyingKey`] of a circuit given its zkas bincode,
+    /// building and storing it if it's not in the store yet.
+    pub fn verifying_key(&self, bincode: &[u8], k: u32) -> Result<VerifyingKey> {
+        let id = Self::key_id(bincode, k);
+        let path = self.path.join(format!("{}.vk", id.to_hex()));
+
+        if let Some(data) = self.read_key(&id, &path) {
+            match VerifyingKey::read::<_, ZkCircuit>(&mut Cursor::new(data)) {
+                Ok(vk) => {
+                    debug!(target: "zk::key_store", "Loaded VerifyingKey from {:?}", path);
+                    return Ok(vk)
+                }
+                Err(e) => warn!(target: "zk::key_store", "Failed reading {:?}: {}", path, e),
+            }
+        }
+
+        let zkbin = ZkBinary::decode(bincode)?;
+        info!(target: "zk::key_store", "Building VerifyingKey for {} zkas circuit", zkbin.namespace);
+        let circuit = ZkCircuit::new(empty_witnesses(&zkbin), zkbin);
+        let vk = VerifyingKey::build(k, &circuit);
+
+        let mut buf = vec![];
+        vk.write(&mut buf)?;
+        self.write_key(&id, &path, &buf)?;
+
+        Ok(vk)
+    }
+
+    /// Returns the [`ProvingKey`] of a circuit given its zkas bincode.
+    /// The first lookup in a process builds it from the stored
+    /// [`VerifyingKey`], later ones return the key kept in memory.
+    pub fn proving_key(&self, bincode: &[u8], k: u32) -> Result<ProvingKey> {
+        let id = Self::key_id(bincode, k);
+        if let Some(pk) = self.proving_keys.lock().unwrap().get(&id) {
+            return Ok(pk.clone())
+        }
+
+        let vk = self.verifying_key(bincode, k)?;
+
+        let zkbin = ZkBinary::decode(bincode)?;
+        info!(target: "zk::key_store", "Building ProvingKey for {} zkas circuit", zkbin.namespace);
+        let circuit = ZkCircuit::new(empty_witnesses(&zkbin), zkbin);
+
+        let pk = match ProvingKey::from_verifying_key(vk, &circuit) {
+            Ok(pk) => pk,
+            Err(e) => return Err(Error::Custom(format!("Failed building ProvingKey: {:?}", e))),
+        };
+
+        self.proving_keys.lock().unwrap().insert(id, pk.clone());
+        Ok(pk)
+    }
+}
@@ -24,6 +24,10 @@ pub use vm::ZkCircuit;
 pub mod vm_heap;
 pub use vm_heap::{empty_witnesses, Witness};
 
+/// Structured tracing of circuit synthesis
+pub mod trace;
+pub use trace::Tracer;
+
 /// ZK gadget implementations
 pub mod gadget;
 
@@ -31,6 +35,10 @@ pub mod gadget;
 pub mod proof;
 pub use proof::{Proof, ProvingKey, VerifyingKey};
 
+/// Proving and verifying key store
+pub mod key_store;
+pub use key_store::KeyStore;
+
 pub mod halo2 {
     pub use halo2_proofs::{
         arithmetic::Field,
@@ -21,7 +21,7 @@ use darkfi_serial::{SerialDecodable, SerialEncodable};
 use halo2_proofs::{
     pasta::{pallas, vesta},
     plonk,
-    plonk::{Circuit, SingleVerifier},
+    plonk::{BatchVerifier, Circuit, SingleVerifier},
     poly::commitment::Params,
     transcript::{Blake2bRead, Blake2bWrite},
 };
@@ -108,6 +108,17 @@ impl ProvingKey {
         let pk = plonk::keygen_pk(&params, vk, c).unwrap();
         ProvingKey { params, pk }
     }
+
+    /// Build the proving key of a circuit from its existing verifying key.
+    /// halo2 can not serialize proving keys, so this skips generating the
+    /// params and the verifying key, but still runs `keygen_pk`.
+    pub fn from_verifying_key(
+        vk: VerifyingKey,
+        c: &impl Circuit<pallas::Base>,
+    ) -> std::result::Result<Self, plonk::Error> {
+        let pk = plonk::keygen_pk(&vk.params, vk.vk, c)?;
+        Ok(ProvingKey { params: vk.params, pk })
+    }
 }
 
 #[derive(Clone, Default, PartialEq, Eq, SerialEncodable, SerialDecodable)]
@@ -156,6 +167,19 @@ impl Proof {
         plonk::verify_proof(&vk.params, &vk.vk, strategy, &[&[instances]], &mut transcript)
     }
 
+    /// Verify a set of proofs created with the same verifying key in a
+    /// single batch. Returns `false` if any of the proofs is invalid, in
+    /// which case the proofs have to be verified one by one to find out
+    /// which ones.
+    pub fn batch_verify(vk: &VerifyingKey, proofs: &[(&Proof, &[pallas::Base])]) -> bool {
+        let mut batch = BatchVerifier::new();
+        for (proof, instances) in proofs {
+            batch.add_proof(vec![vec![instances.to_vec()]], proof.0.clone());
+        }
+
+        batch.finalize(&vk.params, &vk.vk)
+    }
+
     pub fn new(bytes: Vec<u8>) -> Self {
         Proof(bytes)
     }
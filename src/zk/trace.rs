@@ -0,0 +1,311 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Structured tracing of zkVM circuit synthesis.
+//!
+//! When enabled with [`ZkCircuit::enable_trace`](super::ZkCircuit::enable_trace),
+//! the VM records every executed opcode along with its inputs, outputs,
+//! the source line it was compiled from, and the circuit regions it
+//! assigned. The latter allows mapping `MockProver` failures back to
+//! the `.zk` source.
+
+use std::{
+    fmt,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{Cell, Layouter, Region, Table, Value},
+    dev::VerifyFailure,
+    plonk::{Column, Error, Instance},
+};
+
+use super::vm_heap::HeapVar;
+use crate::zkas::{types::HeapType, DebugInfo, Opcode};
+
+/// A heap value read or written by an opcode
+#[derive(Clone, Debug)]
+pub struct TraceValue {
+    /// Heap the value lives on
+    pub heap_type: HeapType,
+    /// Index on the heap
+    pub index: usize,
+    /// Variable name, if the binary has debug info
+    pub name: Option<String>,
+    /// The value, `None` if it is unknown (e.g. during keygen)
+    pub value: Option<String>,
+}
+
+/// A single executed opcode
+#[derive(Clone, Debug)]
+pub struct TraceStep {
+    /// Index of the opcode in the `.circuit` section
+    pub index: usize,
+    /// The executed opcode
+    pub opcode: Opcode,
+    /// Source line, if the binary has debug info
+    pub line: Option<usize>,
+    /// Values the opcode was called with
+    pub inputs: Vec<TraceValue>,
+    /// Values the opcode pushed to the heap
+    pub outputs: Vec<TraceValue>,
+    /// Indices of the circuit regions assigned by the opcode
+    pub regions: Range<usize>,
+}
+
+/// A `MockProver` failure mapped back to the opcode causing it
+#[derive(Clone, Debug)]
+pub struct TraceFailure {
+    /// The opcode which assigned the failing region, if any
+    pub step: Option<TraceStep>,
+    /// The failure as reported by `MockProver`
+    pub failure: String,
+}
+
+/// Handle to the trace recorded by a [`ZkCircuit`](super::ZkCircuit).
+/// The trace is reset on every `synthesize()` call, so after running
+/// the circuit it holds the steps of the last synthesis pass.
+#[derive(Clone, Debug)]
+pub struct Tracer {
+    debug_info: Option<DebugInfo>,
+    steps: Arc<Mutex<Vec<TraceStep>>>,
+}
+
+impl Tracer {
+    pub(super) fn new(debug_info: Option<DebugInfo>) -> Self {
+        Self { debug_info, steps: Arc::new(Mutex::new(vec![])) }
+    }
+
+    pub(super) fn reset(&self) {
+        self.steps.lock().unwrap().clear();
+    }
+
+    /// Record an executed opcode. `heap_offset` is the heap size before
+    /// execution, so everything after it is considered the output.
+    pub(super) fn record(
+        &self,
+        index: usize,
+        opcode: &(Opcode, Vec<(HeapType, usize)>),
+        heap: &[HeapVar],
+        litheap: &[u64],
+        heap_offset: usize,
+        regions: Range<usize>,
+    ) {
+        let inputs = opcode
+            .1
+            .iter()
+            .map(|(heap_type, idx)| match heap_type {
+                HeapType::Var => self.heap_value(heap, *idx),
+                HeapType::Lit => TraceValue {
+                    heap_type: HeapType::Lit,
+                    index: *idx,
+                    name: None,
+                    value: litheap.get(*idx).map(|x| x.to_string()),
+                },
+            })
+            .collect();
+
+        let outputs = (heap_offset..heap.len()).map(|idx| self.heap_value(heap, idx)).collect();
+
+        let line = self.debug_info.as_ref().and_then(|d| d.opcode_lines.get(index).copied());
+
+        self.steps.lock().unwrap().push(TraceStep {
+            index,
+            opcode: opcode.0,
+            line,
+            inputs,
+            outputs,
+            regions,
+        });
+    }
+
+    fn heap_value(&self, heap: &[HeapVar], index: usize) -> TraceValue {
+        let name = self.debug_info.as_ref().and_then(|d| d.heap_names.get(index).cloned());
+        let value = heap.get(index).and_then(heap_var_value);
+        TraceValue { heap_type: HeapType::Var, index, name, value }
+    }
+
+    /// Returns the recorded steps
+    pub fn steps(&self) -> Vec<TraceStep> {
+        self.steps.lock().unwrap().clone()
+    }
+
+    /// Map `MockProver` failures back to the recorded steps. Returns the
+    /// failure which happened first in program order. Failures outside of
+    /// any opcode's regions (e.g. mismatching public inputs) are only
+    /// returned if nothing else failed.
+    pub fn locate(&self, failures: &[VerifyFailure]) -> Option<TraceFailure> {
+        let steps = self.steps();
+
+        let located = failures
+            .iter()
+            .filter_map(|failure| {
+                let region = failure_region(failure)?;
+                let step = steps.iter().find(|step| step.regions.contains(&region))?;
+                Some(TraceFailure { step: Some(step.clone()), failure: failure.to_string() })
+            })
+            .min_by_key(|located| located.step.as_ref().unwrap().index);
+
+        located.or_else(|| {
+            failures
+                .first()
+                .map(|failure| TraceFailure { step: None, failure: failure.to_string() })
+        })
+    }
+}
+
+/// Extract the index of the region a `MockProver` failure happened in.
+/// Failures render their location as `Region <index> ('<name>')`.
+fn failure_region(failure: &VerifyFailure) -> Option<usize> {
+    let failure = failure.to_string();
+    let (_, rest) = failure.split_once("Region ")?;
+    let index: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    index.parse().ok()
+}
+
+/// Format a value if it is known
+fn known<T: fmt::Debug>(value: Value<T>) -> Option<String> {
+    let mut ret = None;
+    value.map(|v| ret = Some(format!("{:?}", v)));
+    ret
+}
+
+fn heap_var_value(var: &HeapVar) -> Option<String> {
+    match var {
+        HeapVar::EcPoint(v) => known(v.inner().x().value().zip(v.inner().y().value())),
+        HeapVar::EcNiPoint(v) => known(v.inner().x().value().zip(v.inner().y().value())),
+        HeapVar::EcFixedPoint(_) | HeapVar::EcFixedPointShort(_) | HeapVar::EcFixedPointBase(_) => {
+            Some("<constant>".to_string())
+        }
+        HeapVar::Base(v) => known(v.value()),
+        HeapVar::BaseArray(v) => {
+            let values: Option<Vec<String>> = v.iter().map(|x| known(x.value())).collect();
+            values.map(|x| format!("[{}]", x.join(", ")))
+        }
+        HeapVar::Scalar(v) => known(*v),
+        HeapVar::MerklePath(v) => known(*v),
+        HeapVar::SparseMerklePath(v) => known(*v),
+        HeapVar::Uint32(v) => known(*v),
+        HeapVar::Uint64(v) => known(*v),
+    }
+}
+
+impl fmt::Display for TraceValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.heap_type, &self.name) {
+            (HeapType::Lit, _) => write!(f, "literal")?,
+            (HeapType::Var, Some(name)) => write!(f, "{}", name)?,
+            (HeapType::Var, None) => write!(f, "heap[{}]", self.index)?,
+        }
+
+        match &self.value {
+            Some(value) => write!(f, " = {}", value),
+            None => write!(f, " = <unknown>"),
+        }
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => writeln!(f, "#{} line {}: {}", self.index, line, self.opcode.name())?,
+            None => writeln!(f, "#{}: {}", self.index, self.opcode.name())?,
+        }
+
+        for input in &self.inputs {
+            writeln!(f, "    in  {}", input)?;
+        }
+
+        for output in &self.outputs {
+            writeln!(f, "    out {}", output)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Layouter wrapper counting the regions assigned through it, so opcodes
+/// can be matched with the region indices `MockProver` reports.
+pub(super) struct RegionCounter<L> {
+    inner: L,
+    regions: usize,
+}
+
+impl<L> RegionCounter<L> {
+    pub(super) fn new(inner: L) -> Self {
+        Self { inner, regions: 0 }
+    }
+
+    /// Amount of regions assigned so far
+    pub(super) fn regions(&self) -> usize {
+        self.regions
+    }
+}
+
+impl<F: Field, L: Layouter<F>> Layouter<F> for RegionCounter<L> {
+    type Root = Self;
+
+    fn assign_region<A, AR, N, NR>(&mut self, name: N, assignment: A) -> Result<AR, Error>
+    where
+        A: FnMut(Region<'_, F>) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        self.regions += 1;
+        self.inner.assign_region(name, assignment)
+    }
+
+    fn assign_table<A, N, NR>(&mut self, name: N, assignment: A) -> Result<(), Error>
+    where
+        A: FnMut(Table<'_, F>) -> Result<(), Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        // Lookup tables are regions as well from the point of view
+        // of the `MockProver`.
+        self.regions += 1;
+        self.inner.assign_table(name, assignment)
+    }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        column: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.inner.constrain_instance(cell, column, row)
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.inner.get_root().push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self, gadget_name: Option<String>) {
+        self.inner.get_root().pop_namespace(gadget_name)
+    }
+}
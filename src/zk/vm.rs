@@ -43,7 +43,10 @@ use halo2_gadgets::{
 use halo2_proofs::{
     arithmetic::Field,
     circuit::{floor_planner, AssignedCell, Layouter, Value},
-    pasta::{group::Curve, pallas, Fp},
+    pasta::{
+        group::{ff::PrimeField, Curve},
+        pallas, Fp,
+    },
     plonk,
     plonk::{Advice, Circuit, Column, ConstraintSystem, Instance as InstanceColumn},
 };
@@ -54,15 +57,17 @@ use super::{
     assign_free_advice,
     gadget::{
         arithmetic::{ArithChip, ArithConfig, ArithInstruction},
-        cond_select::{ConditionalSelectChip, ConditionalSelectConfig},
+        cond_select::{ConditionalSelectChip, ConditionalSelectConfig, IsEqualChip, IsEqualConfig},
         less_than::{LessThanChip, LessThanConfig},
         native_range_check::{NativeRangeCheckChip, NativeRangeCheckConfig},
         small_range_check::{SmallRangeCheckChip, SmallRangeCheckConfig},
+        smt::{SparseMerkleChip, SparseMerkleConfig},
     },
+    trace::{RegionCounter, Tracer},
 };
 use crate::zkas::{
     types::{HeapType, LitType},
-    Opcode, ZkBinary,
+    DebugInfo, Opcode, ZkBinary,
 };
 
 #[derive(Clone)]
@@ -82,6 +87,8 @@ pub struct VmConfig {
     lessthan_config: LessThanConfig<3, 253, 85>,
     boolcheck_config: SmallRangeCheckConfig,
     condselect_config: ConditionalSelectConfig<pallas::Base>,
+    iseq_config: IsEqualConfig<pallas::Base>,
+    smt_config: SparseMerkleConfig,
 }
 
 impl VmConfig {
@@ -112,6 +119,14 @@ impl VmConfig {
     fn condselect_chip(&self) -> ConditionalSelectChip<pallas::Base> {
         ConditionalSelectChip::construct(self.condselect_config.clone(), ())
     }
+
+    fn iseq_chip(&self) -> IsEqualChip<pallas::Base> {
+        IsEqualChip::construct(self.iseq_config.clone(), ())
+    }
+
+    fn smt_chip(&self) -> SparseMerkleChip {
+        SparseMerkleChip::construct(self.smt_config.clone())
+    }
 }
 
 #[derive(Clone)]
@@ -120,6 +135,8 @@ pub struct ZkCircuit {
     witnesses: Vec<Witness>,
     literals: Vec<(LitType, String)>,
     opcodes: Vec<(Opcode, Vec<(HeapType, usize)>)>,
+    debug_info: Option<DebugInfo>,
+    tracer: Option<Tracer>,
 }
 
 impl ZkCircuit {
@@ -127,7 +144,25 @@ impl ZkCircuit {
         let constants = circuit_code.constants.iter().map(|x| x.1.clone()).collect();
         #[allow(clippy::map_clone)]
         let literals = circuit_code.literals.iter().map(|x| x.clone()).collect();
-        Self { constants, witnesses, literals, opcodes: circuit_code.opcodes }
+        Self {
+            constants,
+            witnesses,
+            literals,
+            opcodes: circuit_code.opcodes,
+            debug_info: circuit_code.debug_info,
+            tracer: None,
+        }
+    }
+
+    /// Enable debug mode, recording every executed opcode with its inputs,
+    /// outputs and source line. The returned [`Tracer`] holds the trace
+    /// after the circuit was synthesized, e.g. by `MockProver::run()`.
+    /// Source lines and variable names are only available if the circuit
+    /// was compiled with debug info.
+    pub fn enable_trace(&mut self) -> Tracer {
+        let tracer = Tracer::new(self.debug_info.clone());
+        self.tracer = Some(tracer.clone());
+        tracer
     }
 }
 
@@ -141,6 +176,8 @@ impl Circuit<pallas::Base> for ZkCircuit {
             witnesses: self.witnesses.clone(),
             literals: self.literals.clone(),
             opcodes: self.opcodes.clone(),
+            debug_info: self.debug_info.clone(),
+            tracer: self.tracer.clone(),
         }
     }
 
@@ -275,6 +312,17 @@ impl Circuit<pallas::Base> for ZkCircuit {
         let condselect_config =
             ConditionalSelectChip::configure(meta, advices[1..5].try_into().unwrap());
 
+        // Configuration for the equality comparison chip, used for both
+        // `is_equal` and `is_zero`.
+        let iseq_config = IsEqualChip::configure(meta, advices[1..5].try_into().unwrap());
+
+        // Configuration for the Sparse Merkle tree chip, sharing the Poseidon config
+        let smt_config = SparseMerkleChip::configure(
+            meta,
+            advices[..5].try_into().unwrap(),
+            poseidon_config.clone(),
+        );
+
         VmConfig {
             primary,
             advices,
@@ -290,16 +338,44 @@ impl Circuit<pallas::Base> for ZkCircuit {
             lessthan_config,
             boolcheck_config,
             condselect_config,
+            iseq_config,
+            smt_config,
         }
     }
 
     fn synthesize(
         &self,
         config: Self::Config,
-        mut layouter: impl Layouter<pallas::Base>,
+        layouter: impl Layouter<pallas::Base>,
     ) -> std::result::Result<(), plonk::Error> {
         trace!(target: "zk::vm", "Entering synthesize()");
 
+        // Only count the assigned regions when tracing, so the tracer can map
+        // opcodes to them, and proving uses the layouter as it is.
+        match self.tracer {
+            Some(_) => {
+                self.synthesize_vm(config, RegionCounter::new(layouter), RegionCounter::regions)
+            }
+            None => self.synthesize_vm(config, layouter, |_| 0),
+        }
+    }
+}
+
+impl ZkCircuit {
+    /// Synthesize the circuit opcodes. `regions` returns the amount of
+    /// regions assigned through the layouter so far.
+    fn synthesize_vm<L: Layouter<pallas::Base>>(
+        &self,
+        config: VmConfig,
+        mut layouter: L,
+        regions: impl Fn(&L) -> usize,
+    ) -> std::result::Result<(), plonk::Error> {
+        // The floor planner may synthesize multiple times, we only want
+        // to keep the trace of the last pass.
+        if let Some(tracer) = &self.tracer {
+            tracer.reset();
+        }
+
         // ===================
         // VM Setup
         //====================
@@ -351,6 +427,12 @@ impl Circuit<pallas::Base> for ZkCircuit {
         // Construct the conditional selectiono chip
         let condselect_chip = config.condselect_chip();
 
+        // Construct the equality comparison chip
+        let iseq_chip = config.iseq_chip();
+
+        // Construct the Sparse Merkle tree chip
+        let smt_chip = config.smt_chip();
+
         // ==========================
         // Constants setup
         // ==========================
@@ -366,6 +448,28 @@ impl Circuit<pallas::Base> for ZkCircuit {
             |mut region| region.constrain_constant(one.cell(), pallas::Base::ONE),
         )?;
 
+        // This constant zero is used for `is_zero` comparisons
+        let zero = assign_free_advice(
+            layouter.namespace(|| "Load constant zero"),
+            config.advices[0],
+            Value::known(pallas::Base::ZERO),
+        )?;
+        layouter.assign_region(
+            || "constrain constant",
+            |mut region| region.constrain_constant(zero.cell(), pallas::Base::ZERO),
+        )?;
+
+        // This constant two is used for bit recomposition
+        let two = assign_free_advice(
+            layouter.namespace(|| "Load constant two"),
+            config.advices[0],
+            Value::known(pallas::Base::from(2)),
+        )?;
+        layouter.assign_region(
+            || "constrain constant",
+            |mut region| region.constrain_constant(two.cell(), pallas::Base::from(2)),
+        )?;
+
         // ANCHOR: constant_init
         // Lookup and push constants onto the heap
         for constant in &self.constants {
@@ -484,6 +588,11 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     heap.push(HeapVar::MerklePath(path));
                 }
 
+                Witness::SparseMerklePath(w) => {
+                    trace!(target: "zk::vm", "Pushing SparseMerklePath to heap address {}", heap.len());
+                    heap.push(HeapVar::SparseMerklePath(*w));
+                }
+
                 Witness::Uint32(w) => {
                     trace!(target: "zk::vm", "Pushing Uint32 to heap address {}", heap.len());
                     heap.push(HeapVar::Uint32(*w));
@@ -502,7 +611,10 @@ impl Circuit<pallas::Base> for ZkCircuit {
         // =============================
         // TODO: Copy constraints
         // ANCHOR: opcode_begin
-        for opcode in &self.opcodes {
+        for (idx, opcode) in self.opcodes.iter().enumerate() {
+            let heap_offset = heap.len();
+            let regions_offset = regions(&layouter);
+
             match opcode.0 {
                 Opcode::EcAdd => {
                     trace!(target: "zk::vm", "Executing `EcAdd{:?}` opcode", opcode.1);
@@ -695,6 +807,25 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     heap.push(HeapVar::Base(root));
                 }
 
+                Opcode::SparseMerkleRoot => {
+                    trace!(target: "zk::vm", "Executing `SparseMerkleRoot{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let key: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let path = heap[args[1].1].clone().into();
+                    let leaf: AssignedCell<Fp, Fp> = heap[args[2].1].clone().into();
+
+                    let root = smt_chip.calculate_root(
+                        layouter.namespace(|| "SparseMerkleRoot()"),
+                        &key,
+                        path,
+                        &leaf,
+                    )?;
+
+                    trace!(target: "zk::vm", "Pushing sparse merkle root to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(root));
+                }
+
                 Opcode::BaseAdd => {
                     trace!(target: "zk::vm", "Executing `BaseAdd{:?}` opcode", opcode.1);
                     let args = &opcode.1;
@@ -735,6 +866,33 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     heap.push(HeapVar::Base(difference));
                 }
 
+                Opcode::BaseDiv => {
+                    trace!(target: "zk::vm", "Executing `BaseDiv{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs = &heap[args[0].1].clone().into();
+                    let rhs = heap[args[1].1].clone().into();
+
+                    let inverse = base_invert(&mut layouter, &config, &arith_chip, &one, rhs)?;
+                    let quotient =
+                        arith_chip.mul(layouter.namespace(|| "BaseDiv()"), lhs, &inverse)?;
+
+                    trace!(target: "zk::vm", "Pushing quotient to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(quotient));
+                }
+
+                Opcode::BaseInvert => {
+                    trace!(target: "zk::vm", "Executing `BaseInvert{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let w = heap[args[0].1].clone().into();
+
+                    let inverse = base_invert(&mut layouter, &config, &arith_chip, &one, w)?;
+
+                    trace!(target: "zk::vm", "Pushing inverse to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(inverse));
+                }
+
                 Opcode::WitnessBase => {
                     trace!(target: "zk::vm", "Executing `WitnessBase{:?}` opcode", opcode.1);
                     //let args = &opcode.1;
@@ -831,6 +989,93 @@ impl Circuit<pallas::Base> for ZkCircuit {
                         .small_range_check(layouter.namespace(|| "copy boolean check"), w)?;
                 }
 
+                Opcode::IsZero => {
+                    trace!(target: "zk::vm", "Executing `IsZero{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let w: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+
+                    let out = iseq_chip.is_eq_with_output(
+                        &mut layouter.namespace(|| "is_zero"),
+                        w,
+                        zero.clone(),
+                    )?;
+
+                    trace!(target: "zk::vm", "Pushing assignment to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
+                Opcode::IsEqual => {
+                    trace!(target: "zk::vm", "Executing `IsEqual{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let rhs: AssignedCell<Fp, Fp> = heap[args[1].1].clone().into();
+
+                    let out = iseq_chip.is_eq_with_output(
+                        &mut layouter.namespace(|| "is_equal"),
+                        lhs,
+                        rhs,
+                    )?;
+
+                    trace!(target: "zk::vm", "Pushing assignment to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
+                Opcode::BoolNot => {
+                    trace!(target: "zk::vm", "Executing `BoolNot{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let w: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    boolcheck_chip
+                        .small_range_check(layouter.namespace(|| "bool_not check"), w.clone())?;
+
+                    // !a = 1 - a
+                    let out = arith_chip.sub(layouter.namespace(|| "BoolNot()"), &one, &w)?;
+
+                    trace!(target: "zk::vm", "Pushing assignment to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
+                Opcode::BoolAnd => {
+                    trace!(target: "zk::vm", "Executing `BoolAnd{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let rhs: AssignedCell<Fp, Fp> = heap[args[1].1].clone().into();
+                    boolcheck_chip
+                        .small_range_check(layouter.namespace(|| "bool_and check"), lhs.clone())?;
+                    boolcheck_chip
+                        .small_range_check(layouter.namespace(|| "bool_and check"), rhs.clone())?;
+
+                    // a & b = a * b
+                    let out = arith_chip.mul(layouter.namespace(|| "BoolAnd()"), &lhs, &rhs)?;
+
+                    trace!(target: "zk::vm", "Pushing assignment to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
+                Opcode::BoolOr => {
+                    trace!(target: "zk::vm", "Executing `BoolOr{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let rhs: AssignedCell<Fp, Fp> = heap[args[1].1].clone().into();
+                    boolcheck_chip
+                        .small_range_check(layouter.namespace(|| "bool_or check"), lhs.clone())?;
+                    boolcheck_chip
+                        .small_range_check(layouter.namespace(|| "bool_or check"), rhs.clone())?;
+
+                    // a | b = a + b - a * b
+                    let sum = arith_chip.add(layouter.namespace(|| "BoolOr() sum"), &lhs, &rhs)?;
+                    let product =
+                        arith_chip.mul(layouter.namespace(|| "BoolOr() product"), &lhs, &rhs)?;
+                    let out = arith_chip.sub(layouter.namespace(|| "BoolOr()"), &sum, &product)?;
+
+                    trace!(target: "zk::vm", "Pushing assignment to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
                 Opcode::CondSelect => {
                     trace!(target: "zk::vm", "Executing `CondSelect{:?}` opcode", opcode.1);
                     let args = &opcode.1;
@@ -850,6 +1095,138 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     heap.push(HeapVar::Base(out));
                 }
 
+                Opcode::U64Add => {
+                    trace!(target: "zk::vm", "Executing `U64Add{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let rhs: AssignedCell<Fp, Fp> = heap[args[1].1].clone().into();
+
+                    let sum = arith_chip.add(layouter.namespace(|| "U64Add()"), &lhs, &rhs)?;
+                    range_check_u64(&mut layouter, &rangecheck64_chip, &[&lhs, &rhs, &sum])?;
+
+                    trace!(target: "zk::vm", "Pushing sum to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(sum));
+                }
+
+                Opcode::U64Sub => {
+                    trace!(target: "zk::vm", "Executing `U64Sub{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let rhs: AssignedCell<Fp, Fp> = heap[args[1].1].clone().into();
+
+                    // An underflow wraps around the field, so it fails the range check
+                    let difference =
+                        arith_chip.sub(layouter.namespace(|| "U64Sub()"), &lhs, &rhs)?;
+                    range_check_u64(&mut layouter, &rangecheck64_chip, &[&lhs, &rhs, &difference])?;
+
+                    trace!(target: "zk::vm", "Pushing difference to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(difference));
+                }
+
+                Opcode::U64Mul => {
+                    trace!(target: "zk::vm", "Executing `U64Mul{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+                    let rhs: AssignedCell<Fp, Fp> = heap[args[1].1].clone().into();
+
+                    // The product of two 64-bit values can't wrap around the field
+                    let product = arith_chip.mul(layouter.namespace(|| "U64Mul()"), &lhs, &rhs)?;
+                    range_check_u64(&mut layouter, &rangecheck64_chip, &[&lhs, &rhs, &product])?;
+
+                    trace!(target: "zk::vm", "Pushing product to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(product));
+                }
+
+                Opcode::BitDecompose => {
+                    trace!(target: "zk::vm", "Executing `BitDecompose{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let w: AssignedCell<Fp, Fp> = heap[args[0].1].clone().into();
+
+                    rangecheck64_chip.copy_range_check(
+                        layouter.namespace(|| "BitDecompose range check"),
+                        w.clone(),
+                        true,
+                    )?;
+
+                    let mut bits = Vec::with_capacity(64);
+                    for i in 0..64 {
+                        let bit = w.value().map(|v| {
+                            pallas::Base::from(((v.to_repr()[i / 8] >> (i % 8)) & 1) as u64)
+                        });
+                        let bit = assign_free_advice(
+                            layouter.namespace(|| "Witness bit"),
+                            config.advices[0],
+                            bit,
+                        )?;
+                        boolcheck_chip
+                            .small_range_check(layouter.namespace(|| "bit check"), bit.clone())?;
+                        bits.push(bit);
+                    }
+
+                    let recomposed = bit_compose(&mut layouter, &arith_chip, &two, &bits)?;
+                    layouter.assign_region(
+                        || "constrain bit decomposition",
+                        |mut region| region.constrain_equal(recomposed.cell(), w.cell()),
+                    )?;
+
+                    trace!(target: "zk::vm", "Pushing bits to heap address {}", heap.len());
+                    heap.push(HeapVar::BaseArray(bits));
+                }
+
+                Opcode::BitAnd => {
+                    trace!(target: "zk::vm", "Executing `BitAnd{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: Vec<AssignedCell<Fp, Fp>> = heap[args[0].1].clone().into();
+                    let rhs: Vec<AssignedCell<Fp, Fp>> = heap[args[1].1].clone().into();
+
+                    // a & b = a * b
+                    let mut bits = Vec::with_capacity(lhs.len());
+                    for (a, b) in lhs.iter().zip(rhs.iter()) {
+                        bits.push(arith_chip.mul(layouter.namespace(|| "BitAnd()"), a, b)?);
+                    }
+
+                    let out = bit_compose(&mut layouter, &arith_chip, &two, &bits)?;
+
+                    trace!(target: "zk::vm", "Pushing result to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
+                Opcode::BitXor => {
+                    trace!(target: "zk::vm", "Executing `BitXor{:?}` opcode", opcode.1);
+                    let args = &opcode.1;
+
+                    let lhs: Vec<AssignedCell<Fp, Fp>> = heap[args[0].1].clone().into();
+                    let rhs: Vec<AssignedCell<Fp, Fp>> = heap[args[1].1].clone().into();
+
+                    // a ^ b = a + b - 2 * a * b
+                    let mut bits = Vec::with_capacity(lhs.len());
+                    for (a, b) in lhs.iter().zip(rhs.iter()) {
+                        let sum = arith_chip.add(layouter.namespace(|| "BitXor() sum"), a, b)?;
+                        let product =
+                            arith_chip.mul(layouter.namespace(|| "BitXor() product"), a, b)?;
+                        let product = arith_chip.mul(
+                            layouter.namespace(|| "BitXor() double product"),
+                            &product,
+                            &two,
+                        )?;
+                        bits.push(arith_chip.sub(
+                            layouter.namespace(|| "BitXor()"),
+                            &sum,
+                            &product,
+                        )?);
+                    }
+
+                    let out = bit_compose(&mut layouter, &arith_chip, &two, &bits)?;
+
+                    trace!(target: "zk::vm", "Pushing result to heap address {}", heap.len());
+                    heap.push(HeapVar::Base(out));
+                }
+
                 Opcode::ConstrainEqualBase => {
                     trace!(target: "zk::vm", "Executing `ConstrainEqualBase{:?}` opcode", opcode.1);
                     let args = &opcode.1;
@@ -907,9 +1284,80 @@ impl Circuit<pallas::Base> for ZkCircuit {
                     return Err(plonk::Error::Synthesis)
                 }
             }
+
+            if let Some(tracer) = &self.tracer {
+                tracer.record(
+                    idx,
+                    opcode,
+                    &heap,
+                    &litheap,
+                    heap_offset,
+                    regions_offset..regions(&layouter),
+                );
+            }
         }
 
         trace!(target: "zk::vm", "Exiting synthesize() successfully");
         Ok(())
     }
 }
+
+/// Witness the inverse of a Base field element and constrain `w * w^-1 = 1`.
+/// Inverting zero makes the circuit unsatisfiable.
+fn base_invert(
+    layouter: &mut impl Layouter<pallas::Base>,
+    config: &VmConfig,
+    arith_chip: &ArithChip<pallas::Base>,
+    one: &AssignedCell<Fp, Fp>,
+    w: AssignedCell<Fp, Fp>,
+) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+    let inverse = assign_free_advice(
+        layouter.namespace(|| "Witness inverse"),
+        config.advices[0],
+        w.value().map(|x| x.invert().unwrap_or(pallas::Base::ZERO)),
+    )?;
+
+    let product = arith_chip.mul(layouter.namespace(|| "w * w^-1"), &w, &inverse)?;
+    layouter.assign_region(
+        || "constrain w * w^-1 = 1",
+        |mut region| region.constrain_equal(product.cell(), one.cell()),
+    )?;
+
+    Ok(inverse)
+}
+
+/// Constrain the operands and the result of an unsigned integer
+/// operation to be 64-bit values.
+fn range_check_u64(
+    layouter: &mut impl Layouter<pallas::Base>,
+    rangecheck64_chip: &NativeRangeCheckChip<3, 64, 22>,
+    cells: &[&AssignedCell<Fp, Fp>],
+) -> std::result::Result<(), plonk::Error> {
+    for cell in cells {
+        rangecheck64_chip.copy_range_check(
+            layouter.namespace(|| "u64 range check"),
+            (*cell).clone(),
+            true,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Recompose little-endian bits into a Base field element
+fn bit_compose(
+    layouter: &mut impl Layouter<pallas::Base>,
+    arith_chip: &ArithChip<pallas::Base>,
+    two: &AssignedCell<Fp, Fp>,
+    bits: &[AssignedCell<Fp, Fp>],
+) -> std::result::Result<AssignedCell<Fp, Fp>, plonk::Error> {
+    let (msb, rest) = bits.split_last().ok_or(plonk::Error::Synthesis)?;
+
+    let mut acc = msb.clone();
+    for bit in rest.iter().rev() {
+        let doubled = arith_chip.mul(layouter.namespace(|| "acc * 2"), &acc, two)?;
+        acc = arith_chip.add(layouter.namespace(|| "acc + bit"), &doubled, bit)?;
+    }
+
+    Ok(acc)
+}
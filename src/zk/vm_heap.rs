@@ -26,6 +26,7 @@ use halo2_proofs::{
     pasta::pallas,
 };
 
+use super::gadget::smt::SparseMerklePathValue;
 use crate::zkas::{decoder::ZkBinary, types::VarType};
 
 /// These represent the witness types outside of the circuit
@@ -38,6 +39,7 @@ pub enum Witness {
     Base(Value<pallas::Base>),
     Scalar(Value<pallas::Scalar>),
     MerklePath(Value<[MerkleNode; 32]>),
+    SparseMerklePath(Value<SparseMerklePathValue>),
     Uint32(Value<u32>),
     Uint64(Value<u64>),
 }
@@ -59,6 +61,7 @@ pub fn empty_witnesses(zkbin: &ZkBinary) -> Vec<Witness> {
             VarType::Base => ret.push(Witness::Base(Value::unknown())),
             VarType::Scalar => ret.push(Witness::Scalar(Value::unknown())),
             VarType::MerklePath => ret.push(Witness::MerklePath(Value::unknown())),
+            VarType::SparseMerklePath => ret.push(Witness::SparseMerklePath(Value::unknown())),
             VarType::Uint32 => ret.push(Witness::Uint32(Value::unknown())),
             VarType::Uint64 => ret.push(Witness::Uint64(Value::unknown())),
             _ => todo!("Handle this gracefully"),
@@ -78,8 +81,10 @@ pub enum HeapVar {
     EcFixedPointShort(FixedPointShort<pallas::Affine, EccChip<OrchardFixedBases>>),
     EcFixedPointBase(FixedPointBaseField<pallas::Affine, EccChip<OrchardFixedBases>>),
     Base(AssignedCell<pallas::Base, pallas::Base>),
+    BaseArray(Vec<AssignedCell<pallas::Base, pallas::Base>>),
     Scalar(Value<pallas::Scalar>),
     MerklePath(Value<[pallas::Base; 32]>),
+    SparseMerklePath(Value<SparseMerklePathValue>),
     Uint32(Value<u32>),
     Uint64(Value<u64>),
 }
@@ -105,5 +110,7 @@ impl_from!(EcFixedPointShort, FixedPointShort<pallas::Affine, EccChip<OrchardFix
 impl_from!(EcFixedPointBase, FixedPointBaseField<pallas::Affine, EccChip<OrchardFixedBases>>);
 impl_from!(Scalar, Value<pallas::Scalar>);
 impl_from!(Base, AssignedCell<pallas::Base, pallas::Base>);
+impl_from!(BaseArray, Vec<AssignedCell<pallas::Base, pallas::Base>>);
 impl_from!(Uint32, Value<u32>);
 impl_from!(MerklePath, Value<[pallas::Base; 32]>);
+impl_from!(SparseMerklePath, Value<SparseMerklePathValue>);
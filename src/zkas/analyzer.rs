@@ -66,8 +66,14 @@ impl Analyzer {
             let (return_types, arg_types) = statement.opcode.arg_types();
             let mut rhs = vec![];
 
+            // A single array argument means the opcode takes a variable amount
+            // of elements, e.g. `poseidon_hash(a, b, c)`. Otherwise arrays are
+            // passed around as a single variable, e.g. `bit_and(a_bits, b_bits)`.
+            let variadic = arg_types.len() == 1 &&
+                (arg_types[0] == VarType::BaseArray || arg_types[0] == VarType::ScalarArray);
+
             // This handling is kinda limiting, but it'll do for now.
-            if !(arg_types[0] == VarType::BaseArray || arg_types[0] == VarType::ScalarArray) {
+            if !variadic {
                 // Check that number of args is correct
                 if statement.rhs.len() != arg_types.len() {
                     self.error.abort(
@@ -144,7 +150,7 @@ impl Analyzer {
                         column: func.lhs.clone().unwrap().column,
                     };
 
-                    if arg_types[0] == VarType::BaseArray {
+                    if variadic && arg_types[0] == VarType::BaseArray {
                         if f_return_types[0] != VarType::Base {
                             self.error.abort(
                                 &format!(
@@ -156,7 +162,7 @@ impl Analyzer {
                                 v.column,
                             );
                         }
-                    } else if arg_types[0] == VarType::ScalarArray {
+                    } else if variadic && arg_types[0] == VarType::ScalarArray {
                         if f_return_types[0] != VarType::Scalar {
                             self.error.abort(
                                 &format!(
@@ -194,6 +200,19 @@ impl Analyzer {
                                     Var::Variable(c) => (c.typ, c.line, c.column),
                                 };
 
+                                // Arguments of inlined functions carry the declared
+                                // parameter type.
+                                if v.typ != VarType::Dummy && v.typ != var_type {
+                                    self.error.abort(
+                                        &format!(
+                                            "Incorrect argument type. Expected `{:?}`, got `{:?}`.",
+                                            v.typ, var_type
+                                        ),
+                                        v.line,
+                                        v.column,
+                                    );
+                                }
+
                                 if var_type != f_arg_types[inner_idx] {
                                     self.error.abort(
                                         &format!(
@@ -279,7 +298,20 @@ impl Analyzer {
                             Var::Variable(c) => (c.typ, c.line, c.column),
                         };
 
-                        if arg_types[0] == VarType::BaseArray {
+                        // Arguments of inlined functions carry the declared
+                        // parameter type.
+                        if v.typ != VarType::Dummy && v.typ != var_type {
+                            self.error.abort(
+                                &format!(
+                                    "Incorrect argument type. Expected `{:?}`, got `{:?}`.",
+                                    v.typ, var_type
+                                ),
+                                v.line,
+                                v.column,
+                            );
+                        }
+
+                        if variadic && arg_types[0] == VarType::BaseArray {
                             if var_type != VarType::Base {
                                 self.error.abort(
                                     &format!(
@@ -291,7 +323,7 @@ impl Analyzer {
                                     v.column,
                                 );
                             }
-                        } else if arg_types[0] == VarType::ScalarArray {
+                        } else if variadic && arg_types[0] == VarType::ScalarArray {
                             if var_type != VarType::Scalar {
                                 self.error.abort(
                                     &format!(
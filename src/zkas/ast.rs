@@ -86,3 +86,16 @@ impl Default for Statement {
         Self { typ: StatementType::Noop, opcode: Opcode::Noop, lhs: None, rhs: vec![], line: 0 }
     }
 }
+
+/// A named function which gets inlined at its call sites
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    /// Typed parameters, substituted with the arguments of a call
+    pub params: Vec<Variable>,
+    pub statements: Vec<Statement>,
+    /// Name of the local variable holding the return value, if any
+    pub ret: Option<String>,
+    pub line: usize,
+    pub column: usize,
+}
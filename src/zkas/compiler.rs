@@ -151,7 +151,20 @@ impl Compiler {
             return bincode
         }
 
-        // TODO: Otherwise, we proceed appending debug info.
+        // Otherwise, we proceed appending debug info. In the .debug section
+        // we write the source line of every opcode, followed by the names of
+        // all the variables on the heap, so the VM is able to map its state
+        // back to the source code.
+        bincode.extend_from_slice(b".debug");
+        bincode.extend_from_slice(&serialize(&VarInt(self.statements.len() as u64)));
+        for i in &self.statements {
+            bincode.extend_from_slice(&serialize(&VarInt(i.line as u64)));
+        }
+
+        bincode.extend_from_slice(&serialize(&VarInt(tmp_heap.len() as u64)));
+        for name in &tmp_heap {
+            bincode.extend_from_slice(&serialize(&name.to_string()));
+        }
 
         bincode
     }
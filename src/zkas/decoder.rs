@@ -17,6 +17,7 @@
  */
 
 use darkfi_serial::{deserialize_partial, VarInt};
+use log::warn;
 
 use super::{compiler::MAGIC_BYTES, types::HeapType, LitType, Opcode, VarType};
 use crate::{Error::ZkasDecoderError as ZkasErr, Result};
@@ -30,6 +31,17 @@ pub struct ZkBinary {
     pub literals: Vec<(LitType, String)>,
     pub witnesses: Vec<VarType>,
     pub opcodes: Vec<(Opcode, Vec<(HeapType, usize)>)>,
+    pub debug_info: Option<DebugInfo>,
+}
+
+/// Debug symbols found in the optional `.debug` section.
+/// These are only emitted when compiling with `zkas -g`.
+#[derive(Clone, Debug)]
+pub struct DebugInfo {
+    /// Source line of each opcode, in order of the `.circuit` section
+    pub opcode_lines: Vec<usize>,
+    /// Variable names, in order of their heap index
+    pub heap_names: Vec<String>,
 }
 
 // https://stackoverflow.com/questions/35901547/how-can-i-find-a-subsequence-in-a-u8-slice
@@ -105,9 +117,38 @@ impl ZkBinary {
         let witnesses = ZkBinary::parse_witness(witness_section)?;
         let opcodes = ZkBinary::parse_circuit(circuit_section)?;
 
-        // TODO: Debug info
+        // The debug section is not needed for proving, so a malformed one
+        // is skipped instead of rejecting the whole binary.
+        let debug_info = if debug_offset < bytes.len() {
+            let debug_section = &bytes[debug_offset + b".debug".len()..];
+            match ZkBinary::parse_debug(debug_section) {
+                Ok(v) if v.opcode_lines.len() == opcodes.len() => Some(v),
+                Ok(_) => {
+                    warn!(target: "zkas::decoder", "Skipping .debug section of {}: Does not match the .circuit section", namespace);
+                    None
+                }
+                Err(e) => {
+                    warn!(target: "zkas::decoder", "Skipping .debug section of {}: {}", namespace, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
-        Ok(Self { namespace, constants, literals, witnesses, opcodes })
+        Ok(Self { namespace, constants, literals, witnesses, opcodes, debug_info })
+    }
+
+    /// Returns the source line of the opcode at the given index,
+    /// if the binary was compiled with debug info.
+    pub fn opcode_line(&self, index: usize) -> Option<usize> {
+        self.debug_info.as_ref().and_then(|d| d.opcode_lines.get(index).copied())
+    }
+
+    /// Returns the variable name of the given heap index,
+    /// if the binary was compiled with debug info.
+    pub fn heap_name(&self, index: usize) -> Option<&str> {
+        self.debug_info.as_ref().and_then(|d| d.heap_names.get(index).map(|x| x.as_str()))
     }
 
     fn parse_constants(bytes: &[u8]) -> Result<Vec<(VarType, String)>> {
@@ -223,4 +264,34 @@ impl ZkBinary {
 
         Ok(opcodes)
     }
+
+    fn parse_debug(bytes: &[u8]) -> Result<DebugInfo> {
+        let mut iter_offset = 0;
+
+        let (lines_num, offset) = deserialize_partial::<VarInt>(&bytes[iter_offset..])?;
+        iter_offset += offset;
+
+        let mut opcode_lines = vec![];
+        for _ in 0..lines_num.0 {
+            let (line, offset) = deserialize_partial::<VarInt>(&bytes[iter_offset..])?;
+            iter_offset += offset;
+            opcode_lines.push(line.0 as usize);
+        }
+
+        let (names_num, offset) = deserialize_partial::<VarInt>(&bytes[iter_offset..])?;
+        iter_offset += offset;
+
+        let mut heap_names = vec![];
+        for _ in 0..names_num.0 {
+            let (name, offset) = deserialize_partial::<String>(&bytes[iter_offset..])?;
+            iter_offset += offset;
+            heap_names.push(name);
+        }
+
+        if iter_offset != bytes.len() {
+            return Err(ZkasErr("Trailing bytes in .debug section".to_string()))
+        }
+
+        Ok(DebugInfo { opcode_lines, heap_names })
+    }
 }
@@ -16,7 +16,53 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{io, io::Write, process};
+use std::{
+    cell::RefCell,
+    io,
+    io::Write,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    process,
+};
+
+/// An error or warning emitted by one of the zkas stages, as collected
+/// by [`capture_diagnostics`].
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// Stage emitting the diagnostic, e.g. `Parser`
+    pub namespace: String,
+    /// File the diagnostic refers to
+    pub file: String,
+    pub message: String,
+    /// 1-indexed line, 0 if the diagnostic has no position
+    pub line: usize,
+    /// 1-indexed column
+    pub column: usize,
+    /// `true` for errors, `false` for warnings
+    pub is_error: bool,
+}
+
+thread_local! {
+    /// Diagnostics collected on this thread, if capturing
+    static CAPTURED: RefCell<Option<Vec<Diagnostic>>> = RefCell::new(None);
+}
+
+/// Unwinding payload used to abort the zkas stages while capturing
+struct Aborted;
+
+/// Run zkas stages, collecting their errors and warnings instead of
+/// printing them. Errors don't exit the process, but abort `f` early,
+/// in which case `None` is returned along with the diagnostics.
+pub fn capture_diagnostics<T>(f: impl FnOnce() -> T) -> (Option<T>, Vec<Diagnostic>) {
+    let prev = CAPTURED.with(|c| c.replace(Some(vec![])));
+    let res = catch_unwind(AssertUnwindSafe(f));
+    let diagnostics = CAPTURED.with(|c| c.replace(prev)).unwrap();
+
+    match res {
+        Ok(v) => (Some(v), diagnostics),
+        Err(e) if e.is::<Aborted>() => (None, diagnostics),
+        Err(e) => resume_unwind(e),
+    }
+}
 
 pub(super) struct ErrorEmitter {
     namespace: String,
@@ -43,13 +89,39 @@ impl ErrorEmitter {
         format!("{}\n{}\n{}\n", err_msg, dbg_msg, caret)
     }
 
+    /// Push a diagnostic if capturing, returning `false` otherwise
+    fn capture(&self, msg: &str, ln: usize, col: usize, is_error: bool) -> bool {
+        CAPTURED.with(|c| match c.borrow_mut().as_mut() {
+            Some(diagnostics) => {
+                diagnostics.push(Diagnostic {
+                    namespace: self.namespace.clone(),
+                    file: self.file.clone(),
+                    message: msg.to_string(),
+                    line: ln,
+                    column: col,
+                    is_error,
+                });
+                true
+            }
+            None => false,
+        })
+    }
+
     pub fn abort(&self, msg: &str, ln: usize, col: usize) {
+        if self.capture(msg, ln, col, true) {
+            resume_unwind(Box::new(Aborted))
+        }
+
         let m = self.fmt(msg.to_string(), ln, col);
         self.emit("error", &m);
         process::exit(1);
     }
 
     pub fn warn(&self, msg: &str, ln: usize, col: usize) {
+        if self.capture(msg, ln, col, false) {
+            return
+        }
+
         let m = self.fmt(msg.to_string(), ln, col);
         self.emit("warning", &m);
     }
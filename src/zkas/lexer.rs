@@ -22,6 +22,9 @@ use super::error::ErrorEmitter;
 
 const SPECIAL_CHARS: [char; 7] = ['{', '}', '(', ')', ',', ';', '='];
 
+/// Characters allowed in strings besides letters and digits
+const IMPORT_PATH_CHARS: [char; 3] = ['.', '/', '-'];
+
 fn is_letter(ch: char) -> bool {
     ch.is_ascii_lowercase() || ch.is_ascii_uppercase() || ch == '_'
 }
@@ -201,6 +204,12 @@ impl<'a> Lexer<'a> {
                 continue
             }
 
+            // Strings are also used for import paths
+            if in_string && IMPORT_PATH_CHARS.contains(&c) {
+                buf.push(c);
+                continue
+            }
+
             if in_string && c == '"' {
                 // " I need to fix my vis lexer
                 if buf.is_empty() {
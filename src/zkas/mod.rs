@@ -22,6 +22,7 @@
 
 /// Error emitter
 mod error;
+pub use error::{capture_diagnostics, Diagnostic};
 
 /// Language opcodes
 pub mod opcode;
@@ -52,4 +53,4 @@ pub use compiler::Compiler;
 
 /// Decoder module
 pub mod decoder;
-pub use decoder::ZkBinary;
+pub use decoder::{DebugInfo, ZkBinary};
@@ -52,6 +52,10 @@ pub enum Opcode {
     /// Calculate Merkle root, given a position, Merkle path, and an element
     MerkleRoot = 0x20,
 
+    /// Calculate Sparse Merkle root, given a key, Sparse Merkle path, and
+    /// the leaf at the key (zero for an empty leaf)
+    SparseMerkleRoot = 0x21,
+
     /// Base field element addition
     BaseAdd = 0x30,
 
@@ -61,6 +65,12 @@ pub enum Opcode {
     /// Base field element subtraction
     BaseSub = 0x32,
 
+    /// Base field element division, given a non-zero divisor
+    BaseDiv = 0x33,
+
+    /// Base field element inversion, given a non-zero element
+    BaseInvert = 0x34,
+
     /// Witness an unsigned integer into a Base field element
     WitnessBase = 0x40,
 
@@ -78,9 +88,42 @@ pub enum Opcode {
     /// Check if a field element fits in a boolean (Either 0 or 1)
     BoolCheck = 0x53,
 
+    /// Output 1 if a Base field element is zero, otherwise 0
+    IsZero = 0x54,
+
+    /// Output 1 if two Base field elements are equal, otherwise 0
+    IsEqual = 0x55,
+
+    /// Boolean negation of a field element constrained to be 0 or 1
+    BoolNot = 0x56,
+
+    /// Boolean conjunction of two field elements constrained to be 0 or 1
+    BoolAnd = 0x57,
+
+    /// Boolean disjunction of two field elements constrained to be 0 or 1
+    BoolOr = 0x58,
+
     /// Conditionally select between two base field elements given a boolean
     CondSelect = 0x60,
 
+    /// Add two 64-bit unsigned integers, constraining the sum not to overflow
+    U64Add = 0x70,
+
+    /// Subtract two 64-bit unsigned integers, constraining the difference not to underflow
+    U64Sub = 0x71,
+
+    /// Multiply two 64-bit unsigned integers, constraining the product not to overflow
+    U64Mul = 0x72,
+
+    /// Decompose a 64-bit unsigned integer into an array of 64 little-endian bits
+    BitDecompose = 0x73,
+
+    /// Bitwise AND of two decomposed 64-bit unsigned integers
+    BitAnd = 0x74,
+
+    /// Bitwise XOR of two decomposed 64-bit unsigned integers
+    BitXor = 0x75,
+
     /// Constrain equality of two Base field elements inside the circuit
     ConstrainEqualBase = 0xe0,
 
@@ -106,15 +149,29 @@ impl Opcode {
             "ec_get_y" => Some(Self::EcGetY),
             "poseidon_hash" => Some(Self::PoseidonHash),
             "merkle_root" => Some(Self::MerkleRoot),
+            "sparse_merkle_root" => Some(Self::SparseMerkleRoot),
             "base_add" => Some(Self::BaseAdd),
             "base_mul" => Some(Self::BaseMul),
             "base_sub" => Some(Self::BaseSub),
+            "base_div" => Some(Self::BaseDiv),
+            "base_invert" => Some(Self::BaseInvert),
             "witness_base" => Some(Self::WitnessBase),
             "range_check" => Some(Self::RangeCheck),
             "less_than_strict" => Some(Self::LessThanStrict),
             "less_than_loose" => Some(Self::LessThanLoose),
             "bool_check" => Some(Self::BoolCheck),
+            "is_zero" => Some(Self::IsZero),
+            "is_equal" => Some(Self::IsEqual),
+            "bool_not" => Some(Self::BoolNot),
+            "bool_and" => Some(Self::BoolAnd),
+            "bool_or" => Some(Self::BoolOr),
             "cond_select" => Some(Self::CondSelect),
+            "u64_add" => Some(Self::U64Add),
+            "u64_sub" => Some(Self::U64Sub),
+            "u64_mul" => Some(Self::U64Mul),
+            "bit_decompose" => Some(Self::BitDecompose),
+            "bit_and" => Some(Self::BitAnd),
+            "bit_xor" => Some(Self::BitXor),
             "constrain_equal_base" => Some(Self::ConstrainEqualBase),
             "constrain_equal_point" => Some(Self::ConstrainEqualPoint),
             "constrain_instance" => Some(Self::ConstrainInstance),
@@ -123,6 +180,48 @@ impl Opcode {
         }
     }
 
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Noop => "noop",
+            Self::EcAdd => "ec_add",
+            Self::EcMul => "ec_mul",
+            Self::EcMulBase => "ec_mul_base",
+            Self::EcMulShort => "ec_mul_short",
+            Self::EcMulVarBase => "ec_mul_var_base",
+            Self::EcGetX => "ec_get_x",
+            Self::EcGetY => "ec_get_y",
+            Self::PoseidonHash => "poseidon_hash",
+            Self::MerkleRoot => "merkle_root",
+            Self::SparseMerkleRoot => "sparse_merkle_root",
+            Self::BaseAdd => "base_add",
+            Self::BaseMul => "base_mul",
+            Self::BaseSub => "base_sub",
+            Self::BaseDiv => "base_div",
+            Self::BaseInvert => "base_invert",
+            Self::WitnessBase => "witness_base",
+            Self::RangeCheck => "range_check",
+            Self::LessThanStrict => "less_than_strict",
+            Self::LessThanLoose => "less_than_loose",
+            Self::BoolCheck => "bool_check",
+            Self::IsZero => "is_zero",
+            Self::IsEqual => "is_equal",
+            Self::BoolNot => "bool_not",
+            Self::BoolAnd => "bool_and",
+            Self::BoolOr => "bool_or",
+            Self::CondSelect => "cond_select",
+            Self::U64Add => "u64_add",
+            Self::U64Sub => "u64_sub",
+            Self::U64Mul => "u64_mul",
+            Self::BitDecompose => "bit_decompose",
+            Self::BitAnd => "bit_and",
+            Self::BitXor => "bit_xor",
+            Self::ConstrainEqualBase => "constrain_equal_base",
+            Self::ConstrainEqualPoint => "constrain_equal_point",
+            Self::ConstrainInstance => "constrain_instance",
+            Self::DebugPrint => "debug",
+        }
+    }
+
     pub fn from_repr(b: u8) -> Option<Self> {
         match b {
             0x01 => Some(Self::EcAdd),
@@ -134,15 +233,29 @@ impl Opcode {
             0x09 => Some(Self::EcGetY),
             0x10 => Some(Self::PoseidonHash),
             0x20 => Some(Self::MerkleRoot),
+            0x21 => Some(Self::SparseMerkleRoot),
             0x30 => Some(Self::BaseAdd),
             0x31 => Some(Self::BaseMul),
             0x32 => Some(Self::BaseSub),
+            0x33 => Some(Self::BaseDiv),
+            0x34 => Some(Self::BaseInvert),
             0x40 => Some(Self::WitnessBase),
             0x50 => Some(Self::RangeCheck),
             0x51 => Some(Self::LessThanStrict),
             0x52 => Some(Self::LessThanLoose),
             0x53 => Some(Self::BoolCheck),
+            0x54 => Some(Self::IsZero),
+            0x55 => Some(Self::IsEqual),
+            0x56 => Some(Self::BoolNot),
+            0x57 => Some(Self::BoolAnd),
+            0x58 => Some(Self::BoolOr),
             0x60 => Some(Self::CondSelect),
+            0x70 => Some(Self::U64Add),
+            0x71 => Some(Self::U64Sub),
+            0x72 => Some(Self::U64Mul),
+            0x73 => Some(Self::BitDecompose),
+            0x74 => Some(Self::BitAnd),
+            0x75 => Some(Self::BitXor),
             0xe0 => Some(Self::ConstrainEqualBase),
             0xe1 => Some(Self::ConstrainEqualPoint),
             0xf0 => Some(Self::ConstrainInstance),
@@ -183,12 +296,20 @@ impl Opcode {
                 (vec![VarType::Base], vec![VarType::Uint32, VarType::MerklePath, VarType::Base])
             }
 
+            Opcode::SparseMerkleRoot => {
+                (vec![VarType::Base], vec![VarType::Base, VarType::SparseMerklePath, VarType::Base])
+            }
+
             Opcode::BaseAdd => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
 
             Opcode::BaseMul => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
 
             Opcode::BaseSub => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
 
+            Opcode::BaseDiv => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
+            Opcode::BaseInvert => (vec![VarType::Base], vec![VarType::Base]),
+
             Opcode::WitnessBase => (vec![VarType::Base], vec![VarType::Uint64]),
 
             Opcode::RangeCheck => (vec![], vec![VarType::Uint64, VarType::Base]),
@@ -199,10 +320,32 @@ impl Opcode {
 
             Opcode::BoolCheck => (vec![], vec![VarType::Base]),
 
+            Opcode::IsZero => (vec![VarType::Base], vec![VarType::Base]),
+
+            Opcode::IsEqual => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
+            Opcode::BoolNot => (vec![VarType::Base], vec![VarType::Base]),
+
+            Opcode::BoolAnd => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
+            Opcode::BoolOr => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
             Opcode::CondSelect => {
                 (vec![VarType::Base], vec![VarType::Base, VarType::Base, VarType::Base])
             }
 
+            Opcode::U64Add => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
+            Opcode::U64Sub => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
+            Opcode::U64Mul => (vec![VarType::Base], vec![VarType::Base, VarType::Base]),
+
+            Opcode::BitDecompose => (vec![VarType::BaseArray], vec![VarType::Base]),
+
+            Opcode::BitAnd => (vec![VarType::Base], vec![VarType::BaseArray, VarType::BaseArray]),
+
+            Opcode::BitXor => (vec![VarType::Base], vec![VarType::BaseArray, VarType::BaseArray]),
+
             Opcode::ConstrainEqualBase => (vec![], vec![VarType::Base, VarType::Base]),
 
             Opcode::ConstrainEqualPoint => (vec![], vec![VarType::EcPoint, VarType::EcPoint]),
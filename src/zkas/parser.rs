@@ -16,24 +16,33 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{iter::Peekable, str::Chars};
+use std::{
+    collections::HashMap,
+    fs::{canonicalize, read_to_string},
+    iter::Peekable,
+    path::Path,
+    str::Chars,
+};
 
 use indexmap::IndexMap;
 use itertools::Itertools;
 
 use super::{
-    ast::{Arg, Constant, Literal, Statement, StatementType, Variable, Witness},
+    ast::{Arg, Constant, Function, Literal, Statement, StatementType, Variable, Witness},
     error::ErrorEmitter,
     lexer::{Token, TokenType},
-    LitType, Opcode, VarType,
+    Lexer, LitType, Opcode, VarType,
 };
 
 /// zkas language builtin keywords.
 /// These can not be used anywhere except where they are expected.
-const KEYWORDS: [&str; 3] = ["constant", "witness", "circuit"];
+const KEYWORDS: [&str; 5] = ["constant", "witness", "circuit", "import", "function"];
 
 /// Forbidden namespaces
-const NOPE_NS: [&str; 4] = [".constant", ".literal", ".witness", ".circuit"];
+const NOPE_NS: [&str; 5] = [".constant", ".literal", ".witness", ".circuit", ".debug"];
+
+/// Modules of the zkas standard library, bundled with the compiler.
+const STDLIB: [(&str, &str); 1] = [("std/money.zk", include_str!("stdlib/money.zk"))];
 
 /// Valid EcFixedPoint constant names supported by the VM.
 const VALID_ECFIXEDPOINT: [&str; 1] = ["VALUE_COMMIT_RANDOM"];
@@ -45,10 +54,48 @@ const VALID_ECFIXEDPOINTSHORT: [&str; 1] = ["VALUE_COMMIT_VALUE"];
 const VALID_ECFIXEDPOINTBASE: [&str; 1] = ["NULLIFIER_K"];
 
 pub struct Parser {
+    filename: String,
     tokens: Vec<Token>,
     error: ErrorEmitter,
 }
 
+/// State used when inlining a function at a call site
+struct InlineContext<'a> {
+    function: &'a Function,
+    /// Token of the call site
+    token: &'a Token,
+    /// Parameter names mapped to the arguments of the call
+    params: HashMap<String, Arg>,
+    /// Variables assigned in the function body
+    locals: Vec<String>,
+    /// Variable the return value is assigned to at the call site
+    lhs: Option<Variable>,
+    /// Constants of the circuit, `None` when inlining into another function
+    constants: Option<&'a [String]>,
+}
+
+impl InlineContext<'_> {
+    /// Local variables get a name unique to the call site, except for the
+    /// returned one, which becomes the assigned variable of the call.
+    fn rename(&self, name: &str) -> Variable {
+        if let (Some(lhs), Some(ret)) = (&self.lhs, &self.function.ret) {
+            if name == ret {
+                return lhs.clone()
+            }
+        }
+
+        Variable {
+            name: format!(
+                "_fn_{}_{}_{}_{}",
+                self.function.name, self.token.line, self.token.column, name
+            ),
+            typ: VarType::Dummy,
+            line: self.token.line,
+            column: self.token.column,
+        }
+    }
+}
+
 impl Parser {
     pub fn new(filename: &str, source: Chars, tokens: Vec<Token>) -> Self {
         // For nice error reporting, we'll load everything into a string
@@ -56,7 +103,7 @@ impl Parser {
         let lines: Vec<String> = source.as_str().lines().map(|x| x.to_string()).collect();
         let error = ErrorEmitter::new("Parser", filename, lines);
 
-        Self { tokens, error }
+        Self { filename: filename.to_string(), tokens, error }
     }
 
     pub fn parse(&self) -> (String, Vec<Constant>, Vec<Witness>, Vec<Statement>) {
@@ -78,6 +125,10 @@ impl Parser {
         // Contains constant and witness sections
         let mut ast_inner = IndexMap::new();
         let mut ast = IndexMap::new();
+        // Functions defined in the source or imported from modules
+        let mut functions = IndexMap::new();
+        let mut imported = HashMap::new();
+        let mut file_imports = vec![];
 
         if self.tokens[0].token_type != TokenType::Symbol {
             self.error.abort(
@@ -135,6 +186,22 @@ impl Parser {
                         declaring_circuit = true;
                         absorb_inner_tokens!(circuit_tokens);
                     }
+                    "import" => {
+                        let (module, module_functions) =
+                            self.parse_import(t, &mut iter, &mut imported);
+                        if !file_imports.contains(&module) {
+                            file_imports.push(module);
+                            for function in module_functions {
+                                self.add_function(&mut functions, function, t);
+                            }
+                        }
+                        continue
+                    }
+                    "function" => {
+                        let function = self.parse_function(&mut iter, &functions);
+                        self.add_function(&mut functions, function, t);
+                        continue
+                    }
 
                     x => self.error.abort(
                         &format!("Section `{}` is not a valid section", x),
@@ -303,7 +370,8 @@ impl Parser {
             self.parse_ast_witness(c)
         };
 
-        let statements = self.parse_ast_circuit(circuit_stmts);
+        let constant_names: Vec<String> = constants.iter().map(|x| x.name.clone()).collect();
+        let statements = self.parse_ast_circuit(circuit_stmts, &functions, Some(&constant_names));
         if statements.is_empty() {
             self.error.abort("Circuit section is empty.", 0, 0);
         }
@@ -554,6 +622,15 @@ impl Parser {
                     });
                 }
 
+                "SparseMerklePath" => {
+                    ret.push(Witness {
+                        name: k.to_string(),
+                        typ: VarType::SparseMerklePath,
+                        line: v.0.line,
+                        column: v.0.column,
+                    });
+                }
+
                 "Uint32" => {
                     ret.push(Witness {
                         name: k.to_string(),
@@ -585,7 +662,12 @@ impl Parser {
         ret
     }
 
-    fn parse_ast_circuit(&self, statements: Vec<Vec<Token>>) -> Vec<Statement> {
+    fn parse_ast_circuit(
+        &self,
+        statements: Vec<Vec<Token>>,
+        functions: &IndexMap<String, Function>,
+        constants: Option<&[String]>,
+    ) -> Vec<Statement> {
         // The statement layouts/syntax in the language are as follows:
         //
         // C = poseidon_hash(pub_x, pub_y, value, token, serial);
@@ -631,6 +713,15 @@ impl Parser {
         // too expressive and noisy, we'll consider having multiple return
         // types. It also very much depends on the type of functions/opcodes
         // that we want to support.
+        //
+        // Calls to functions defined with `function` are inlined: their
+        // statements are copied in place of the call, with parameters
+        // replaced by the given arguments:
+        //
+        //            nf = nullifier(secret, serial);
+        //
+        // If such a call is nested in another one, it gets inlined before
+        // the statement and its return value is passed as the argument.
 
         // Vec of statements to return from this entire parsing operation.
         let mut ret = vec![];
@@ -725,11 +816,34 @@ impl Parser {
                 // call, so we check if it's legit and start digging.
                 let func_name = token.token.as_str();
 
+                // Inlined function calls nested in the arguments
+                let mut hoisted = vec![];
+
                 // TODO: MAKE SURE IT'S A SYMBOL
                 if let Some(op) = Opcode::from_name(func_name) {
-                    let rhs = self.parse_function_call(token, &mut iter);
+                    let rhs = self.parse_function_call(
+                        token,
+                        &mut iter,
+                        functions,
+                        constants,
+                        &mut hoisted,
+                    );
                     stmt.opcode = op;
                     stmt.rhs = rhs;
+                    ret.extend(hoisted);
+                    ret.push(stmt);
+                } else if let Some(function) = functions.get(func_name) {
+                    let args = self.parse_function_call(
+                        token,
+                        &mut iter,
+                        functions,
+                        constants,
+                        &mut hoisted,
+                    );
+                    let (body, _) =
+                        self.inline_function(function, token, args, stmt.lhs.take(), constants);
+                    ret.extend(hoisted);
+                    ret.extend(body);
                 } else {
                     self.error.abort(
                         &format!("Unimplemented opcode `{}`.", func_name),
@@ -738,7 +852,6 @@ impl Parser {
                     );
                 }
 
-                ret.push(stmt);
                 stmt = Statement::default();
             }
         }
@@ -750,6 +863,9 @@ impl Parser {
         &self,
         token: &Token,
         iter: &mut Peekable<std::slice::Iter<'_, Token>>,
+        functions: &IndexMap<String, Function>,
+        constants: Option<&[String]>,
+        hoisted: &mut Vec<Statement>,
     ) -> Vec<Arg> {
         if let Some(next_token) = iter.peek() {
             if next_token.token_type != TokenType::LeftParen {
@@ -786,7 +902,7 @@ impl Parser {
                     }
 
                     // Recurse this function to get the params of the nested one.
-                    let args = self.parse_function_call(arg, iter);
+                    let args = self.parse_function_call(arg, iter, functions, constants, hoisted);
 
                     // Then we assign a "fake" variable that serves as a heap
                     // reference.
@@ -816,6 +932,29 @@ impl Parser {
                 );
             }
 
+            // ==================================
+            // Parse a nested inlined function call
+            // ==================================
+            if let Some(function) = functions.get(&arg.token) {
+                if iter.peek().map(|x| x.token_type) == Some(TokenType::LeftParen) {
+                    let args = self.parse_function_call(arg, iter, functions, constants, hoisted);
+                    let (body, var) = self.inline_function(function, arg, args, None, constants);
+
+                    let Some(var) = var else {
+                        self.error.abort(
+                            &format!("Function `{}` has no return value.", function.name),
+                            arg.line,
+                            arg.column,
+                        );
+                        unreachable!();
+                    };
+
+                    hoisted.extend(body);
+                    ret.push(Arg::Var(var));
+                    continue
+                }
+            }
+
             // ==========================================
             // Parse normal argument, not a function call
             // ==========================================
@@ -884,4 +1023,533 @@ impl Parser {
 
         ret
     }
+
+    /// Parse an `import "path";` declaration and return the name of the
+    /// imported module along with the functions it defines. Paths starting
+    /// with `std/` refer to the bundled standard library, others are
+    /// relative to the file containing the import.
+    ///
+    /// `imported` holds the functions of every module parsed so far, or
+    /// `None` while a module is still being parsed.
+    fn parse_import(
+        &self,
+        keyword: &Token,
+        iter: &mut std::slice::Iter<'_, Token>,
+        imported: &mut HashMap<String, Option<Vec<Function>>>,
+    ) -> (String, Vec<Function>) {
+        let (Some(path), Some(semicolon)) = (iter.next(), iter.next()) else {
+            self.error.abort("Premature ending of import.", keyword.line, keyword.column);
+            unreachable!();
+        };
+
+        if path.token_type != TokenType::String {
+            self.error.abort("Import path must be a string.", path.line, path.column);
+        }
+
+        if semicolon.token_type != TokenType::Semicolon {
+            self.error.abort("Import does not end with a semicolon.", path.line, path.column);
+        }
+
+        let (filename, source) = match STDLIB.iter().find(|(name, _)| name == &path.token) {
+            Some((name, source)) => (name.to_string(), source.to_string()),
+            None => {
+                if path.token.starts_with("std/") {
+                    self.error.abort(
+                        &format!("Unknown standard library module `{}`.", path.token),
+                        path.line,
+                        path.column,
+                    );
+                }
+
+                let dir = Path::new(&self.filename).parent().unwrap_or(Path::new(""));
+                let file = dir.join(&path.token);
+                // The canonical path identifies the module, so it's only imported once.
+                match canonicalize(&file).and_then(|file| Ok((read_to_string(&file)?, file))) {
+                    Ok((source, file)) => (file.to_string_lossy().to_string(), source),
+                    Err(e) => {
+                        self.error.abort(
+                            &format!("Failed reading import `{}`: {}", path.token, e),
+                            path.line,
+                            path.column,
+                        );
+                        unreachable!();
+                    }
+                }
+            }
+        };
+
+        // Modules are only parsed once, even when imported by several files.
+        match imported.get(&filename) {
+            Some(Some(functions)) => return (filename, functions.clone()),
+            Some(None) => {
+                self.error.abort(
+                    &format!("Circular import of `{}`.", path.token),
+                    path.line,
+                    path.column,
+                );
+            }
+            None => {}
+        }
+        imported.insert(filename.clone(), None);
+
+        // Clean up tabs, and convert CRLF to LF, like we do for the source file.
+        let source = source.replace('\t', "    ").replace("\r\n", "\n");
+
+        let lexer = Lexer::new(&filename, source.chars());
+        let tokens = lexer.lex();
+        let parser = Parser::new(&filename, source.chars(), tokens);
+        let functions = parser.parse_module(imported);
+
+        imported.insert(filename.clone(), Some(functions.clone()));
+        (filename, functions)
+    }
+
+    /// Parse a module which may only contain imports and function definitions.
+    /// Returns the functions defined in the module itself.
+    fn parse_module(&self, imported: &mut HashMap<String, Option<Vec<Function>>>) -> Vec<Function> {
+        // Functions usable inside this module
+        let mut functions = IndexMap::new();
+        let mut file_imports = vec![];
+        // Functions defined by this module
+        let mut ret = vec![];
+
+        let mut iter = self.tokens.iter();
+        while let Some(t) = iter.next() {
+            match t.token.as_str() {
+                "import" => {
+                    let (module, module_functions) = self.parse_import(t, &mut iter, imported);
+                    if !file_imports.contains(&module) {
+                        file_imports.push(module);
+                        for function in module_functions {
+                            self.add_function(&mut functions, function, t);
+                        }
+                    }
+                }
+                "function" => {
+                    let function = self.parse_function(&mut iter, &functions);
+                    ret.push(function.clone());
+                    self.add_function(&mut functions, function, t);
+                }
+                x => self.error.abort(
+                    &format!("Modules can only contain `import` and `function` declarations, found `{}`.", x),
+                    t.line,
+                    t.column,
+                ),
+            }
+        }
+
+        ret
+    }
+
+    fn add_function(
+        &self,
+        functions: &mut IndexMap<String, Function>,
+        function: Function,
+        token: &Token,
+    ) {
+        if functions.contains_key(&function.name) {
+            self.error.abort(
+                &format!("Function `{}` is already defined.", function.name),
+                token.line,
+                token.column,
+            );
+        }
+
+        functions.insert(function.name.clone(), function);
+    }
+
+    /// Parse a function definition following the `function` keyword:
+    ///
+    /// ```text
+    /// function value_commit(Base value, Scalar blind) {
+    ///     vcv = ec_mul_short(value, VALUE_COMMIT_VALUE);
+    ///     vcr = ec_mul(blind, VALUE_COMMIT_RANDOM);
+    ///     return ec_add(vcv, vcr);
+    /// }
+    /// ```
+    ///
+    /// The body can call previously defined functions, which get inlined
+    /// right away, so recursion is not possible.
+    fn parse_function(
+        &self,
+        iter: &mut std::slice::Iter<'_, Token>,
+        functions: &IndexMap<String, Function>,
+    ) -> Function {
+        let mut tokens = vec![];
+        for inner in iter.by_ref() {
+            if KEYWORDS.contains(&inner.token.as_str()) && inner.token_type == TokenType::Symbol {
+                self.error.abort(
+                    &format!("Keyword '{}' used in improper place.", inner.token),
+                    inner.line,
+                    inner.column,
+                );
+            }
+
+            tokens.push(inner.clone());
+            if inner.token_type == TokenType::RightBrace {
+                break
+            }
+        }
+
+        let Some(name) = tokens.first() else {
+            self.error.abort("Premature ending of function definition.", 0, 0);
+            unreachable!();
+        };
+
+        if name.token_type != TokenType::Symbol {
+            self.error.abort("Function name must be a symbol.", name.line, name.column);
+        }
+
+        if Opcode::from_name(&name.token).is_some() {
+            self.error.abort(
+                &format!("Function `{}` has the same name as an opcode.", name.token),
+                name.line,
+                name.column,
+            );
+        }
+
+        // Parse the parameters: (Type name, Type name, ...)
+        if tokens.get(1).map(|x| x.token_type) != Some(TokenType::LeftParen) {
+            self.error.abort(
+                "Function parameters must be opened with a '('.",
+                name.line,
+                name.column,
+            );
+        }
+
+        let mut params: Vec<Variable> = vec![];
+        let mut idx = 2;
+        loop {
+            let (Some(typ), Some(param)) = (tokens.get(idx), tokens.get(idx + 1)) else {
+                self.error.abort(
+                    "Premature ending of function definition.",
+                    name.line,
+                    name.column,
+                );
+                unreachable!();
+            };
+
+            if typ.token_type == TokenType::RightParen {
+                idx += 1;
+                break
+            }
+
+            if param.token_type != TokenType::Symbol {
+                self.error.abort(
+                    &format!("Parameter name `{}` is not a symbol.", param.token),
+                    param.line,
+                    param.column,
+                );
+            }
+
+            if params.iter().any(|x| x.name == param.token) {
+                self.error.abort(
+                    &format!("Duplicate parameter `{}`.", param.token),
+                    param.line,
+                    param.column,
+                );
+            }
+
+            params.push(Variable {
+                name: param.token.clone(),
+                typ: self.parse_param_type(typ),
+                line: param.line,
+                column: param.column,
+            });
+
+            idx += 2;
+            match tokens.get(idx).map(|x| x.token_type) {
+                Some(TokenType::Comma) => idx += 1,
+                Some(TokenType::RightParen) => {
+                    idx += 1;
+                    break
+                }
+                _ => self.error.abort(
+                    "Parameter separator is not a comma (`,`)",
+                    param.line,
+                    param.column,
+                ),
+            }
+        }
+
+        // Then the body: { statement; statement; ... }
+        if tokens.get(idx).map(|x| x.token_type) != Some(TokenType::LeftBrace) {
+            self.error.abort(
+                "Function body must be opened with a left brace '{'",
+                name.line,
+                name.column,
+            );
+        }
+
+        if tokens.last().unwrap().token_type != TokenType::RightBrace {
+            self.error.abort(
+                "Function body must be closed with a right brace '}'",
+                name.line,
+                name.column,
+            );
+        }
+
+        let body = &tokens[idx + 1..tokens.len() - 1];
+        if body.is_empty() {
+            self.error.abort("Function body is empty.", name.line, name.column);
+        }
+
+        if body.last().unwrap().token_type != TokenType::Semicolon {
+            self.error.abort(
+                "Function body does not end with a semicolon.",
+                body.last().unwrap().line,
+                body.last().unwrap().column,
+            );
+        }
+
+        let mut statements: Vec<Vec<Token>> =
+            body.split(|x| x.token_type == TokenType::Semicolon).map(|x| x.to_vec()).collect();
+        // Splitting leaves an empty statement after the last semicolon
+        statements.retain(|x| !x.is_empty());
+
+        // `return x;` returns a local variable, while `return op(...);`
+        // is rewritten into an assignment to a local variable.
+        let mut ret = None;
+        let last = statements.len() - 1;
+        for (i, statement) in statements.iter_mut().enumerate() {
+            let keyword = statement[0].clone();
+            if keyword.token != "return" || keyword.token_type != TokenType::Symbol {
+                continue
+            }
+
+            if i != last {
+                self.error.abort(
+                    "`return` must be the last statement of a function.",
+                    keyword.line,
+                    keyword.column,
+                );
+            }
+
+            match statement.len() {
+                1 => self.error.abort("Missing return value.", keyword.line, keyword.column),
+                2 => {
+                    ret = Some(statement[1].clone());
+                    statement.clear();
+                }
+                _ => {
+                    let var = Token { token: "_return".to_string(), ..keyword.clone() };
+                    let assign =
+                        Token { token: "=".to_string(), token_type: TokenType::Assign, ..keyword };
+                    statement.splice(0..1, [var.clone(), assign]);
+                    ret = Some(var);
+                }
+            }
+        }
+        statements.retain(|x| !x.is_empty());
+
+        let statements = self.parse_ast_circuit(statements, functions, None);
+
+        let locals = Parser::collect_locals(&statements);
+        for param in &params {
+            if locals.contains(&param.name) {
+                self.error.abort(
+                    &format!("Parameter `{}` is assigned in the function body.", param.name),
+                    param.line,
+                    param.column,
+                );
+            }
+        }
+
+        if let Some(ret) = &ret {
+            if !locals.contains(&ret.token) {
+                self.error.abort(
+                    "Functions can only return variables assigned in their body.",
+                    ret.line,
+                    ret.column,
+                );
+            }
+        }
+
+        Function {
+            name: name.token.clone(),
+            params,
+            statements,
+            ret: ret.map(|x| x.token),
+            line: name.line,
+            column: name.column,
+        }
+    }
+
+    fn parse_param_type(&self, token: &Token) -> VarType {
+        match token.token.as_str() {
+            "EcPoint" => VarType::EcPoint,
+            "EcNiPoint" => VarType::EcNiPoint,
+            "EcFixedPoint" => VarType::EcFixedPoint,
+            "EcFixedPointShort" => VarType::EcFixedPointShort,
+            "EcFixedPointBase" => VarType::EcFixedPointBase,
+            "Base" => VarType::Base,
+            "BaseArray" => VarType::BaseArray,
+            "Scalar" => VarType::Scalar,
+            "MerklePath" => VarType::MerklePath,
+            "SparseMerklePath" => VarType::SparseMerklePath,
+            "Uint32" => VarType::Uint32,
+            "Uint64" => VarType::Uint64,
+            x => {
+                self.error.abort(
+                    &format!("`{}` is an unsupported parameter type.", x),
+                    token.line,
+                    token.column,
+                );
+                unreachable!();
+            }
+        }
+    }
+
+    /// Names of all variables assigned by the given statements,
+    /// including the ones of nested calls.
+    fn collect_locals(statements: &[Statement]) -> Vec<String> {
+        let mut ret = vec![];
+
+        for statement in statements {
+            if let Some(lhs) = &statement.lhs {
+                ret.push(lhs.name.clone());
+            }
+
+            for arg in &statement.rhs {
+                if let Arg::Func(func) = arg {
+                    ret.extend(Parser::collect_locals(std::slice::from_ref(func)));
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Inline a function at a call site. Returns the statements of the
+    /// function body and the variable holding the return value.
+    fn inline_function(
+        &self,
+        function: &Function,
+        token: &Token,
+        args: Vec<Arg>,
+        lhs: Option<Variable>,
+        constants: Option<&[String]>,
+    ) -> (Vec<Statement>, Option<Variable>) {
+        if args.len() != function.params.len() {
+            self.error.abort(
+                &format!(
+                    "Incorrect number of arguments for function `{}`. Expected {}, got {}.",
+                    function.name,
+                    function.params.len(),
+                    args.len()
+                ),
+                token.line,
+                token.column,
+            );
+        }
+
+        if lhs.is_some() && function.ret.is_none() {
+            self.error.abort(
+                &format!("Function `{}` has no return value.", function.name),
+                token.line,
+                token.column,
+            );
+        }
+
+        let mut ret = vec![];
+
+        // Variables passed as arguments carry the parameter type, so the
+        // analyzer can check them. Nested opcode calls are executed before
+        // the function body and their result is passed instead.
+        let mut params = HashMap::new();
+        for (param, arg) in function.params.iter().zip(args) {
+            let arg = match arg {
+                Arg::Var(mut v) => {
+                    v.typ = param.typ;
+                    Arg::Var(v)
+                }
+                Arg::Lit(l) => {
+                    if l.typ.to_vartype() != param.typ {
+                        self.error.abort(
+                            &format!(
+                                "Incorrect argument type. Expected `{:?}`, got `{:?}`.",
+                                param.typ,
+                                l.typ.to_vartype()
+                            ),
+                            l.line,
+                            l.column,
+                        );
+                    }
+                    Arg::Lit(l)
+                }
+                Arg::Func(func) => {
+                    let mut v = func.lhs.clone().unwrap();
+                    v.typ = param.typ;
+                    ret.push(func);
+                    Arg::Var(v)
+                }
+            };
+
+            params.insert(param.name.clone(), arg);
+        }
+
+        let ctx = InlineContext {
+            function,
+            token,
+            params,
+            locals: Parser::collect_locals(&function.statements),
+            lhs,
+            constants,
+        };
+
+        for statement in &function.statements {
+            ret.push(self.inline_statement(&ctx, statement));
+        }
+
+        let var = function.ret.as_ref().map(|x| ctx.rename(x));
+        (ret, var)
+    }
+
+    fn inline_statement(&self, ctx: &InlineContext, statement: &Statement) -> Statement {
+        let mut rhs = vec![];
+
+        for arg in &statement.rhs {
+            let arg = match arg {
+                Arg::Var(v) if ctx.params.contains_key(&v.name) => ctx.params[&v.name].clone(),
+                Arg::Var(v) if ctx.locals.contains(&v.name) => Arg::Var(ctx.rename(&v.name)),
+                Arg::Var(v) => {
+                    // Anything else must be a constant of the circuit
+                    if let Some(constants) = ctx.constants {
+                        if !constants.contains(&v.name) {
+                            self.error.abort(
+                                &format!(
+                                    "Function `{}` uses `{}`, which is neither a parameter nor a constant of the circuit.",
+                                    ctx.function.name, v.name
+                                ),
+                                ctx.token.line,
+                                ctx.token.column,
+                            );
+                        }
+                    }
+
+                    Arg::Var(Variable {
+                        line: ctx.token.line,
+                        column: ctx.token.column,
+                        ..v.clone()
+                    })
+                }
+                Arg::Lit(l) => Arg::Lit(Literal {
+                    line: ctx.token.line,
+                    column: ctx.token.column,
+                    ..l.clone()
+                }),
+                Arg::Func(func) => Arg::Func(self.inline_statement(ctx, func)),
+            };
+
+            rhs.push(arg);
+        }
+
+        Statement {
+            typ: statement.typ,
+            opcode: statement.opcode,
+            lhs: statement.lhs.as_ref().map(|x| ctx.rename(&x.name)),
+            rhs,
+            line: ctx.token.line,
+        }
+    }
 }
@@ -71,6 +71,9 @@ pub enum VarType {
     /// A Merkle tree path
     MerklePath = 0x20,
 
+    /// A Sparse Merkle tree path
+    SparseMerklePath = 0x21,
+
     /// Unsigned 32-bit integer
     Uint32 = 0x30,
 
@@ -94,6 +97,7 @@ impl VarType {
             0x12 => Some(Self::Scalar),
             0x13 => Some(Self::ScalarArray),
             0x20 => Some(Self::MerklePath),
+            0x21 => Some(Self::SparseMerklePath),
             0x30 => Some(Self::Uint32),
             0x31 => Some(Self::Uint64),
             0xff => Some(Self::Any),
@@ -0,0 +1,84 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use smol::Executor;
+use url::Url;
+
+use darkfi::{
+    net::{P2p, Settings},
+    util::async_util::sleep,
+};
+
+/// Start a listening node with `listen_id` and a node dialing it with
+/// `dial_id`, and return the amount of channels each ended up with
+async fn handshake(listen_id: &str, dial_id: &str, port: u16) -> (usize, usize) {
+    let ex = Arc::new(Executor::new());
+    let (signal, shutdown) = smol::channel::unbounded::<()>();
+    for _ in 0..4 {
+        let ex = ex.clone();
+        let shutdown = shutdown.clone();
+        std::thread::spawn(move || smol::block_on(ex.run(shutdown.recv())));
+    }
+
+    let addr = Url::parse(&format!("tcp://127.0.0.1:{}", port)).unwrap();
+
+    let listener = P2p::new(Settings {
+        inbound_addrs: vec![addr.clone()],
+        network_id: listen_id.to_string(),
+        localnet: true,
+        ..Default::default()
+    })
+    .await;
+
+    let dialer = P2p::new(Settings {
+        peers: vec![addr],
+        network_id: dial_id.to_string(),
+        manual_attempt_limit: 1,
+        localnet: true,
+        ..Default::default()
+    })
+    .await;
+
+    ex.spawn(listener.clone().run(ex.clone())).detach();
+    sleep(1).await;
+    ex.spawn(dialer.clone().run(ex.clone())).detach();
+    sleep(3).await;
+
+    let ret = (listener.channels().lock().await.len(), dialer.channels().lock().await.len());
+
+    listener.stop().await;
+    dialer.stop().await;
+    smol::Timer::after(Duration::from_millis(100)).await;
+    drop(signal);
+
+    ret
+}
+
+#[async_std::test]
+async fn handshake_same_network_id() {
+    assert_eq!(handshake("testnet", "testnet", 13371).await, (1, 1));
+}
+
+#[async_std::test]
+async fn handshake_different_network_id() {
+    // Both sides reject the other one during the version exchange
+    assert_eq!(handshake("testnet", "mainnet", 13372).await, (0, 0));
+    assert_eq!(handshake("testnet", "", 13373).await, (0, 0));
+}
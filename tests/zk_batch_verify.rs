@@ -0,0 +1,99 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+
+use darkfi_sdk::{
+    crypto::{ContractId, SecretKey},
+    tx::ContractCall,
+};
+use halo2_proofs::{circuit::Value, pasta::pallas};
+use rand::rngs::OsRng;
+
+use darkfi::{
+    tx::Transaction,
+    zk::{
+        proof::{ProvingKey, VerifyingKey},
+        vm::ZkCircuit,
+        vm_heap::{empty_witnesses, Witness},
+        Proof,
+    },
+    zkas::ZkBinary,
+    Result,
+};
+
+#[test]
+fn zk_batch_verify() -> Result<()> {
+    let bincode = include_bytes!("../proof/arithmetic.zk.bin");
+    let zkbin = ZkBinary::decode(bincode)?;
+
+    let circuit = ZkCircuit::new(empty_witnesses(&zkbin), zkbin.clone());
+    let pk = ProvingKey::build(13, &circuit);
+    let vk = VerifyingKey::build(13, &circuit);
+
+    let mut proofs = vec![];
+    for i in 0..3 {
+        let a = pallas::Base::from(42 + i);
+        let b = pallas::Base::from(69);
+        let witnesses = vec![Witness::Base(Value::known(a)), Witness::Base(Value::known(b))];
+        let public_inputs = vec![a + b, a * b, a - b];
+
+        let circuit = ZkCircuit::new(witnesses, zkbin.clone());
+        let proof = Proof::create(&pk, &[circuit], &public_inputs, &mut OsRng)?;
+        proofs.push((proof, public_inputs));
+    }
+
+    let mut batch: Vec<_> = proofs.iter().map(|(p, v)| (p, v.as_slice())).collect();
+    assert!(Proof::batch_verify(&vk, &batch));
+
+    // A single bad proof fails the whole batch
+    let bad_inputs = vec![pallas::Base::from(0); 3];
+    batch[1].1 = bad_inputs.as_slice();
+    assert!(!Proof::batch_verify(&vk, &batch));
+
+    // Transactions carrying the proofs, one call each
+    let contract_id = ContractId::derive(SecretKey::random(&mut OsRng));
+    let txs: Vec<_> = proofs
+        .iter()
+        .map(|(proof, _)| Transaction {
+            calls: vec![ContractCall { contract_id, data: vec![] }],
+            proofs: vec![vec![proof.clone()]],
+            signatures: vec![vec![]],
+        })
+        .collect();
+
+    let mut vks = HashMap::new();
+    vks.insert(contract_id.to_bytes(), HashMap::from([(zkbin.namespace.clone(), vk)]));
+
+    let mut set: Vec<_> = txs
+        .iter()
+        .zip(proofs.iter())
+        .map(|(tx, (_, v))| (tx, vec![vec![(zkbin.namespace.clone(), v.clone())]]))
+        .collect();
+    assert!(Transaction::batch_verify_zkps(&set, &vks).is_empty());
+
+    // The culprit is found when the batch fails
+    set[1].1[0][0].1 = bad_inputs.clone();
+    assert_eq!(Transaction::batch_verify_zkps(&set, &vks), vec![1]);
+
+    // Proofs of unknown circuits are rejected
+    set[2].1[0][0].0 = String::from("Unknown");
+    assert_eq!(Transaction::batch_verify_zkps(&set, &vks), vec![1, 2]);
+
+    Ok(())
+}
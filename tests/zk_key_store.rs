@@ -0,0 +1,74 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use halo2_proofs::{circuit::Value, pasta::pallas};
+use rand::rngs::OsRng;
+
+use darkfi::{
+    zk::{vm::ZkCircuit, vm_heap::Witness, KeyStore, Proof},
+    zkas::ZkBinary,
+    Result,
+};
+
+#[test]
+fn zk_key_store() -> Result<()> {
+    let bincode = include_bytes!("../proof/arithmetic.zk.bin");
+    let zkbin = ZkBinary::decode(bincode)?;
+
+    let path = std::env::temp_dir().join(format!("darkfi_key_store_{}", std::process::id()));
+    let key_store = KeyStore::new(&path)?;
+
+    // Only the verifying key is stored, and the proving key is built from it
+    key_store.proving_key(bincode, 13)?;
+    key_store.verifying_key(bincode, 13)?;
+    assert_eq!(std::fs::read_dir(&path)?.count(), 1);
+
+    let pk = key_store.proving_key(bincode, 13)?;
+    let vk = key_store.verifying_key(bincode, 13)?;
+
+    let a = pallas::Base::from(42);
+    let b = pallas::Base::from(69);
+    let witnesses = vec![Witness::Base(Value::known(a)), Witness::Base(Value::known(b))];
+    let public_inputs = vec![a + b, a * b, a - b];
+
+    let circuit = ZkCircuit::new(witnesses, zkbin);
+    let proof = Proof::create(&pk, &[circuit], &public_inputs, &mut OsRng)?;
+    proof.verify(&vk, &public_inputs)?;
+
+    // A corrupted key fails its checksum and gets rebuilt
+    let vk_path = std::fs::read_dir(&path)?.next().unwrap()?.path();
+    let mut data = std::fs::read(&vk_path)?;
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    std::fs::write(&vk_path, &data)?;
+    let vk = key_store.verifying_key(bincode, 13)?;
+    proof.verify(&vk, &public_inputs)?;
+    assert_ne!(std::fs::read(&vk_path)?, data);
+
+    // A different k is stored separately
+    key_store.verifying_key(bincode, 14)?;
+    assert_eq!(std::fs::read_dir(&path)?.count(), 2);
+
+    // Built proving keys are kept in memory, so the store isn't read again
+    std::fs::remove_file(&vk_path)?;
+    key_store.clone().proving_key(bincode, 13)?;
+    assert_eq!(std::fs::read_dir(&path)?.count(), 1);
+
+    std::fs::remove_dir_all(path)?;
+    Ok(())
+}
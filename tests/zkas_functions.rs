@@ -0,0 +1,349 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs::{create_dir_all, write};
+
+use darkfi::zkas::{capture_diagnostics, Analyzer, Compiler, Lexer, Parser};
+
+/// Run the zkas stages over `source` like the `zkas` binary does, without
+/// debug info. Returns the bincode, or the message of the first error.
+fn compile(filename: &str, source: &str) -> Result<Vec<u8>, String> {
+    let source = source.replace('\t', "    ").replace("\r\n", "\n");
+
+    let (bincode, diagnostics) = capture_diagnostics(|| {
+        let tokens = Lexer::new(filename, source.chars()).lex();
+        let parser = Parser::new(filename, source.chars(), tokens);
+        let (namespace, constants, witnesses, statements) = parser.parse();
+
+        let mut analyzer =
+            Analyzer::new(filename, source.chars(), constants, witnesses, statements);
+        analyzer.analyze_types();
+
+        Compiler::new(
+            filename,
+            source.chars(),
+            namespace,
+            analyzer.constants,
+            analyzer.witnesses,
+            analyzer.statements,
+            analyzer.literals,
+            false,
+        )
+        .compile()
+    });
+
+    match bincode {
+        Some(bincode) => Ok(bincode),
+        None => Err(diagnostics.into_iter().find(|x| x.is_error).unwrap().message),
+    }
+}
+
+/// Wrap a circuit body with the sections used by the tests below
+fn circuit(imports: &str, body: &str) -> String {
+    format!(
+        r#"{}
+constant "Test" {{
+	EcFixedPointShort VALUE_COMMIT_VALUE,
+	EcFixedPoint VALUE_COMMIT_RANDOM,
+	EcFixedPointBase NULLIFIER_K,
+}}
+
+witness "Test" {{
+	Base secret,
+	Base serial,
+	Base value,
+	Scalar value_blind,
+	Uint32 leaf_pos,
+	MerklePath path,
+}}
+
+circuit "Test" {{
+{}
+}}
+"#,
+        imports, body
+    )
+}
+
+const VALUE_COMMIT: &str = r#"
+function value_commit(Base value, Scalar blind) {
+	vcv = ec_mul_short(value, VALUE_COMMIT_VALUE);
+	vcr = ec_mul(blind, VALUE_COMMIT_RANDOM);
+	return ec_add(vcv, vcr);
+}
+"#;
+
+#[test]
+fn zkas_stdlib_matches_money_circuits() {
+    // The money circuits written with the stdlib helpers must compile to
+    // the exact same bincode, and so the same verifying keys.
+    let mint_std = r#"import "std/money.zk";
+
+constant "Mint_V1" {
+	EcFixedPointShort VALUE_COMMIT_VALUE,
+	EcFixedPoint VALUE_COMMIT_RANDOM,
+	EcFixedPointBase NULLIFIER_K,
+}
+
+witness "Mint_V1" {
+	Base pub_x,
+	Base pub_y,
+	Base value,
+	Base token,
+	Base serial,
+	Base spend_hook,
+	Base user_data,
+	Scalar value_blind,
+	Scalar token_blind,
+}
+
+circuit "Mint_V1" {
+	C = coin(pub_x, pub_y, value, token, serial, spend_hook, user_data);
+	constrain_instance(C);
+	constrain_point(value_commit(value, value_blind));
+	constrain_point(token_commit(token, token_blind));
+}
+"#;
+
+    let burn_std = r#"import "std/money.zk";
+
+constant "Burn_V1" {
+	EcFixedPointShort VALUE_COMMIT_VALUE,
+	EcFixedPoint VALUE_COMMIT_RANDOM,
+	EcFixedPointBase NULLIFIER_K,
+}
+
+witness "Burn_V1" {
+	Base value,
+	Base token,
+	Scalar value_blind,
+	Scalar token_blind,
+	Base serial,
+	Base spend_hook,
+	Base user_data,
+	Base user_data_blind,
+	Base secret,
+	Uint32 leaf_pos,
+	MerklePath path,
+	Base signature_secret,
+}
+
+circuit "Burn_V1" {
+	nullifier = nullifier(secret, serial);
+	constrain_instance(nullifier);
+	constrain_point(value_commit(value, value_blind));
+	constrain_point(token_commit(token, token_blind));
+	C = coin_from_secret(secret, value, token, serial, spend_hook, user_data);
+	merkle_inclusion(leaf_pos, path, C);
+	user_data_enc = poseidon_hash(user_data, user_data_blind);
+	constrain_instance(user_data_enc);
+	constrain_instance(spend_hook);
+	signature_public = ec_mul_base(signature_secret, NULLIFIER_K);
+	signature_x = ec_get_x(signature_public);
+	signature_y = ec_get_y(signature_public);
+	constrain_instance(signature_x);
+	constrain_instance(signature_y);
+}
+"#;
+
+    for (path, source) in [
+        ("src/contract/money/proof/mint_v1.zk", mint_std),
+        ("src/contract/money/proof/burn_v1.zk", burn_std),
+    ] {
+        let baseline = std::fs::read_to_string(path).unwrap();
+        assert_eq!(compile(path, source).unwrap(), compile(path, &baseline).unwrap(), "{}", path);
+    }
+}
+
+#[test]
+fn zkas_function_inlining() {
+    // A function call compiles to the same code as its body written out
+    let called = circuit(
+        VALUE_COMMIT,
+        r#"
+	vc = value_commit(value, value_blind);
+	constrain_instance(ec_get_x(vc));
+"#,
+    );
+
+    let inlined = circuit(
+        "",
+        r#"
+	vcv = ec_mul_short(value, VALUE_COMMIT_VALUE);
+	vcr = ec_mul(value_blind, VALUE_COMMIT_RANDOM);
+	vc = ec_add(vcv, vcr);
+	constrain_instance(ec_get_x(vc));
+"#,
+    );
+
+    assert_eq!(compile("test.zk", &called).unwrap(), compile("test.zk", &inlined).unwrap());
+
+    // Calls nested in opcodes, functions calling functions, and two calls
+    // of the same function, whose locals must not clash
+    let nested = circuit(
+        &format!(
+            "{}\nfunction both(Base a, Base b, Scalar blind) {{\n\tx = value_commit(a, blind);\n\ty = value_commit(b, blind);\n\treturn ec_add(x, y);\n}}\n",
+            VALUE_COMMIT
+        ),
+        r#"
+	constrain_instance(ec_get_y(both(value, serial, value_blind)));
+	constrain_instance(ec_get_x(value_commit(secret, value_blind)));
+"#,
+    );
+    assert!(compile("test.zk", &nested).is_ok());
+
+    // Functions without a return value are called as statements
+    let no_return = circuit(
+        "function check(Base a) {\n\tconstrain_instance(a);\n}\n",
+        "\tcheck(poseidon_hash(secret, serial));\n",
+    );
+    let written_out =
+        circuit("", "\th = poseidon_hash(secret, serial);\n\tconstrain_instance(h);\n");
+    assert_eq!(compile("test.zk", &no_return).unwrap(), compile("test.zk", &written_out).unwrap());
+}
+
+#[test]
+fn zkas_import() {
+    let dir = std::env::temp_dir().join("darkfi_zkas_import");
+    create_dir_all(dir.join("lib")).unwrap();
+    // `commit.zk` also imports `util.zk`, whose functions must still be
+    // visible to the circuit importing both
+    write(dir.join("lib/commit.zk"), format!("import \"../util.zk\";\n{}", VALUE_COMMIT)).unwrap();
+    write(
+        dir.join("util.zk"),
+        "function hash(Base a, Base b) {\n\treturn poseidon_hash(a, b);\n}\n",
+    )
+    .unwrap();
+    write(dir.join("cycle_a.zk"), "import \"cycle_b.zk\";\n").unwrap();
+    write(dir.join("cycle_b.zk"), "import \"cycle_a.zk\";\n").unwrap();
+
+    let source = circuit(
+        "import \"lib/commit.zk\";\nimport \"util.zk\";\nimport \"util.zk\";\nimport \"std/money.zk\";",
+        r#"
+	constrain_instance(hash(secret, serial));
+	constrain_instance(ec_get_x(value_commit(value, value_blind)));
+"#,
+    );
+    let filename = dir.join("test.zk");
+    // Two different modules defining `value_commit`
+    assert_eq!(
+        compile(filename.to_str().unwrap(), &source).unwrap_err(),
+        "Function `value_commit` is already defined."
+    );
+
+    let source = source.replace("import \"std/money.zk\";", "");
+    compile(filename.to_str().unwrap(), &source).unwrap();
+
+    let source = circuit("import \"missing.zk\";", "\tconstrain_instance(secret);\n");
+    assert!(compile(filename.to_str().unwrap(), &source)
+        .unwrap_err()
+        .starts_with("Failed reading import `missing.zk`"));
+
+    let source = circuit("import \"std/dao.zk\";", "\tconstrain_instance(secret);\n");
+    assert_eq!(
+        compile("test.zk", &source).unwrap_err(),
+        "Unknown standard library module `std/dao.zk`."
+    );
+
+    let source = circuit("import std;", "\tconstrain_instance(secret);\n");
+    assert_eq!(compile("test.zk", &source).unwrap_err(), "Import path must be a string.");
+
+    let source = circuit("import \"cycle_a.zk\";", "\tconstrain_instance(secret);\n");
+    assert_eq!(
+        compile(filename.to_str().unwrap(), &source).unwrap_err(),
+        "Circular import of `cycle_a.zk`."
+    );
+
+    write(dir.join("bad.zk"), "constant \"Bad\" {}\n").unwrap();
+    let source = circuit("import \"bad.zk\";", "\tconstrain_instance(secret);\n");
+    assert!(compile(filename.to_str().unwrap(), &source)
+        .unwrap_err()
+        .starts_with("Modules can only contain `import` and `function` declarations"));
+}
+
+#[test]
+fn zkas_function_errors() {
+    let cases = [
+        // Argument types are checked against the declared parameter types
+        (
+            VALUE_COMMIT,
+            "\tconstrain_instance(ec_get_x(value_commit(value, serial)));\n",
+            "Incorrect argument type. Expected `Scalar`, got `Base`.",
+        ),
+        (
+            VALUE_COMMIT,
+            "\tconstrain_instance(ec_get_x(value_commit(value_blind, value_blind)));\n",
+            "Incorrect argument type. Expected `Base`, got `Scalar`.",
+        ),
+        (
+            VALUE_COMMIT,
+            "\tconstrain_instance(ec_get_x(value_commit(value)));\n",
+            "Incorrect number of arguments for function `value_commit`. Expected 2, got 1.",
+        ),
+        (
+            "function check(Base a) {\n\tconstrain_instance(a);\n}\n",
+            "\tx = check(secret);\n",
+            "Function `check` has no return value.",
+        ),
+        (
+            "function hash(Base a) {\n\treturn poseidon_hash(a, SECRET);\n}\n",
+            "\tconstrain_instance(hash(secret));\n",
+            "Function `hash` uses `SECRET`, which is neither a parameter nor a constant of the circuit.",
+        ),
+        (
+            "function hash(Base a) {\n\treturn a;\n}\n",
+            "\tconstrain_instance(hash(secret));\n",
+            "Functions can only return variables assigned in their body.",
+        ),
+        (
+            "function hash(Base a) {\n\treturn poseidon_hash(a, a);\n\tconstrain_instance(a);\n}\n",
+            "\tconstrain_instance(hash(secret));\n",
+            "`return` must be the last statement of a function.",
+        ),
+        (
+            "function hash(Base a) {\n\ta = poseidon_hash(a, a);\n}\n",
+            "\tconstrain_instance(secret);\n",
+            "Parameter `a` is assigned in the function body.",
+        ),
+        (
+            "function hash(Base a, Base a) {\n\treturn poseidon_hash(a, a);\n}\n",
+            "\tconstrain_instance(secret);\n",
+            "Duplicate parameter `a`.",
+        ),
+        (
+            "function hash(Field a) {\n\treturn poseidon_hash(a, a);\n}\n",
+            "\tconstrain_instance(secret);\n",
+            "`Field` is an unsupported parameter type.",
+        ),
+        (
+            "function poseidon_hash(Base a) {\n\treturn poseidon_hash(a, a);\n}\n",
+            "\tconstrain_instance(secret);\n",
+            "Function `poseidon_hash` has the same name as an opcode.",
+        ),
+        (
+            // Functions only see the ones defined before them
+            "function a(Base x) {\n\treturn b(x);\n}\nfunction b(Base x) {\n\treturn poseidon_hash(x, x);\n}\n",
+            "\tconstrain_instance(a(secret));\n",
+            "Unimplemented opcode `b`.",
+        ),
+    ];
+
+    for (functions, body, error) in cases {
+        assert_eq!(compile("test.zk", &circuit(functions, body)).unwrap_err(), error, "{}", body);
+    }
+}
@@ -16,8 +16,14 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::collections::BTreeMap;
+
 use darkfi_sdk::crypto::{
-    pedersen::pedersen_commitment_u64, util::mod_r_p, MerkleNode, MerkleTree, PublicKey, SecretKey,
+    constants::SPARSE_MERKLE_DEPTH,
+    pedersen::pedersen_commitment_u64,
+    smt::{Poseidon, SparseMerkleTree},
+    util::mod_r_p,
+    MerkleNode, MerkleTree, PublicKey, SecretKey,
 };
 use halo2_gadgets::poseidon::{
     primitives as poseidon,
@@ -38,10 +44,34 @@ use darkfi::{
         vm_heap::{empty_witnesses, Witness},
         Proof,
     },
-    zkas::ZkBinary,
+    zkas::{Analyzer, Compiler, Lexer, Parser, ZkBinary},
     Result,
 };
 
+/// Compile a circuit with debug info, like `zkas -g` does
+fn compile_debug(filename: &str, source: &str) -> Vec<u8> {
+    let source = source.replace('\t', "    ").replace("\r\n", "\n");
+
+    let tokens = Lexer::new(filename, source.chars()).lex();
+    let parser = Parser::new(filename, source.chars(), tokens);
+    let (namespace, constants, witnesses, statements) = parser.parse();
+
+    let mut analyzer = Analyzer::new(filename, source.chars(), constants, witnesses, statements);
+    analyzer.analyze_types();
+
+    Compiler::new(
+        filename,
+        source.chars(),
+        namespace,
+        analyzer.constants,
+        analyzer.witnesses,
+        analyzer.statements,
+        analyzer.literals,
+        true,
+    )
+    .compile()
+}
+
 #[test]
 fn zkvm_opcodes() -> Result<()> {
     let bincode = include_bytes!("../proof/opcodes.zk.bin");
@@ -80,6 +110,17 @@ fn zkvm_opcodes() -> Result<()> {
     let pubkey = PublicKey::from_secret(ephem_secret).inner();
     let (ephem_x, ephem_y) = PublicKey::from(pubkey * mod_r_p(ephem_secret.inner())).xy();
 
+    // Sparse Merkle tree without a leaf at key `a`
+    let smt_leaves: BTreeMap<u32, pallas::Base> =
+        [(1, c0), (69, c1), (1337, c3)].into_iter().collect();
+    let smt = SparseMerkleTree::<_, _, SPARSE_MERKLE_DEPTH>::new(
+        &smt_leaves,
+        &Poseidon::<pallas::Base, 2>::new(),
+        &[0; 64],
+    )
+    .unwrap();
+    let smt_path = smt.generate_membership_proof(42).path;
+
     let prover_witnesses = vec![
         Witness::Base(Value::known(pallas::Base::from(value))),
         Witness::Scalar(Value::known(value_blind)),
@@ -92,6 +133,7 @@ fn zkvm_opcodes() -> Result<()> {
         Witness::Uint32(Value::known(leaf_pos.try_into().unwrap())),
         Witness::MerklePath(Value::known(merkle_path.try_into().unwrap())),
         Witness::Base(Value::known(pallas::Base::ONE)),
+        Witness::SparseMerklePath(Value::known(smt_path)),
     ];
 
     let value_commit = pedersen_commitment_u64(value, value_blind);
@@ -114,6 +156,10 @@ fn zkvm_opcodes() -> Result<()> {
         ephem_x,
         ephem_y,
         a,
+        pallas::Base::ONE,
+        pallas::Base::ONE,
+        a.invert().unwrap(),
+        smt.root(),
     ];
 
     let circuit = ZkCircuit::new(prover_witnesses, zkbin.clone());
@@ -131,3 +177,152 @@ fn zkvm_opcodes() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn zkvm_field_opcodes() -> Result<()> {
+    let bincode = include_bytes!("../proof/field.zk.bin");
+    let zkbin = ZkBinary::decode(bincode)?;
+
+    // The public inputs are what the opcodes output for the witnesses,
+    // so only the constraints of the opcodes themselves can fail
+    let prove = |values: [u64; 6]| {
+        let [a, b, c, d, e, f] = values.map(pallas::Base::from);
+        let public_inputs = vec![
+            a.invert().unwrap_or(pallas::Base::ZERO),
+            b * c.invert().unwrap_or(pallas::Base::ZERO),
+            d * e,
+            d + e - d * e,
+            pallas::Base::ONE - f,
+        ];
+
+        let witnesses =
+            [a, b, c, d, e, f].into_iter().map(|x| Witness::Base(Value::known(x))).collect();
+        let circuit = ZkCircuit::new(witnesses, zkbin.clone());
+        MockProver::run(13, &circuit, vec![public_inputs]).unwrap().verify()
+    };
+
+    assert!(prove([5, 10, 2, 1, 0, 1]).is_ok());
+    assert!(prove([1, 0, 7, 1, 1, 0]).is_ok());
+
+    // Zero has no inverse, and nothing can be divided by it
+    assert!(prove([0, 10, 2, 1, 0, 1]).is_err());
+    assert!(prove([5, 10, 0, 1, 0, 1]).is_err());
+    assert!(prove([5, 0, 0, 1, 0, 1]).is_err());
+
+    // The boolean opcodes only take 0 or 1
+    assert!(prove([5, 10, 2, 2, 0, 1]).is_err());
+    assert!(prove([5, 10, 2, 1, 2, 1]).is_err());
+    assert!(prove([5, 10, 2, 1, 0, 2]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn zkvm_u64_opcodes() -> Result<()> {
+    let bincode = include_bytes!("../proof/u64.zk.bin");
+    let zkbin = ZkBinary::decode(bincode)?;
+
+    let prove = |a: u64, b: u64, public_inputs: Vec<pallas::Base>| {
+        let witnesses = vec![
+            Witness::Base(Value::known(pallas::Base::from(a))),
+            Witness::Base(Value::known(pallas::Base::from(b))),
+        ];
+        let circuit = ZkCircuit::new(witnesses, zkbin.clone());
+        MockProver::run(13, &circuit, vec![public_inputs]).unwrap().verify()
+    };
+
+    let a = 0xdead_beef_u64;
+    let b = 0x1234_5678_u64;
+    let public_inputs = vec![
+        pallas::Base::from(a + b),
+        pallas::Base::from(a - b),
+        pallas::Base::from(a * b),
+        pallas::Base::from(a & b),
+        pallas::Base::from(a ^ b),
+    ];
+    assert!(prove(a, b, public_inputs).is_ok());
+
+    // Underflowing subtraction wraps around the field and fails the range check
+    let public_inputs = vec![
+        pallas::Base::from(a + b),
+        pallas::Base::from(b) - pallas::Base::from(a),
+        pallas::Base::from(a * b),
+        pallas::Base::from(a & b),
+        pallas::Base::from(a ^ b),
+    ];
+    assert!(prove(b, a, public_inputs).is_err());
+
+    // Overflowing addition and multiplication fail the range check
+    let (a, b) = (u64::MAX, 2);
+    let public_inputs = vec![
+        pallas::Base::from(a) + pallas::Base::from(b),
+        pallas::Base::from(a - b),
+        pallas::Base::from(a) * pallas::Base::from(b),
+        pallas::Base::from(a & b),
+        pallas::Base::from(a ^ b),
+    ];
+    assert!(prove(a, b, public_inputs).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn zkvm_debug_section() -> Result<()> {
+    let bincode = compile_debug("u64.zk", include_str!("../proof/u64.zk"));
+    assert!(ZkBinary::decode(&bincode)?.debug_info.is_some());
+
+    // Binaries are compiled without debug info by default
+    assert!(ZkBinary::decode(include_bytes!("../proof/u64.zk.bin"))?.debug_info.is_none());
+
+    // A malformed debug section is skipped, since it's not needed for proving
+    let mut trailing = bincode.clone();
+    trailing.push(0);
+    let zkbin = ZkBinary::decode(&trailing)?;
+    assert!(zkbin.debug_info.is_none());
+    assert_eq!(zkbin.opcodes.len(), ZkBinary::decode(&bincode)?.opcodes.len());
+
+    let truncated = &bincode[..bincode.len() - 1];
+    assert!(ZkBinary::decode(truncated)?.debug_info.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn zkvm_trace() -> Result<()> {
+    let bincode = compile_debug("u64.zk", include_str!("../proof/u64.zk"));
+    let zkbin = ZkBinary::decode(&bincode)?;
+    assert!(zkbin.debug_info.is_some());
+
+    let (a, b) = (0x1234_5678_u64, 0xdead_beef_u64);
+    let witnesses = vec![
+        Witness::Base(Value::known(pallas::Base::from(a))),
+        Witness::Base(Value::known(pallas::Base::from(b))),
+    ];
+    let public_inputs = vec![
+        pallas::Base::from(a + b),
+        pallas::Base::from(a) - pallas::Base::from(b),
+        pallas::Base::from(a * b),
+        pallas::Base::from(a & b),
+        pallas::Base::from(a ^ b),
+    ];
+
+    let mut circuit = ZkCircuit::new(witnesses, zkbin.clone());
+    let tracer = circuit.enable_trace();
+    let prover = MockProver::run(13, &circuit, vec![public_inputs]).unwrap();
+
+    // Every opcode is recorded with its source line and values
+    let steps = tracer.steps();
+    assert_eq!(steps.len(), zkbin.opcodes.len());
+    assert_eq!(steps[0].line, Some(9));
+    assert_eq!(steps[0].inputs[0].name.as_deref(), Some("a"));
+    assert_eq!(steps[0].outputs[0].value, Some(format!("{:?}", pallas::Base::from(a + b))));
+
+    // The underflowing subtraction fails its range check
+    let failures = prover.verify().unwrap_err();
+    let failure = tracer.locate(&failures).unwrap();
+    let step = failure.step.unwrap();
+    assert_eq!(step.opcode.name(), "u64_sub");
+    assert_eq!(step.line, Some(10));
+
+    Ok(())
+}